@@ -0,0 +1,77 @@
+// the CPU cost of a delivery before any handler runs: verifying the signature and
+// parsing the payload. `cargo bench --bench delivery`
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use nexus::{
+    blocking::Delivery,
+    models::WebhookPayload,
+    signature::verify_signature,
+    testing::{Event, PullRequestEventBuilder, PushEventBuilder, sign_request},
+};
+
+const SECRET: &str = "benchmark-secret";
+
+// sample bodies, smallest first
+fn bodies() -> Vec<(&'static str, &'static str, Vec<u8>)> {
+    let pull_request = PullRequestEventBuilder::opened()
+        .body("Adds a benchmark for the delivery path.")
+        .label("enhancement");
+    let push = (0..20).fold(PushEventBuilder::branch("main"), |push, i| {
+        push.commit(format!("Commit {} of a large push", i))
+    });
+    vec![
+        (
+            "pull_request",
+            pull_request.event_type(),
+            pull_request.to_json(),
+        ),
+        ("push_20_commits", push.event_type(), push.to_json()),
+    ]
+    .into_iter()
+    .map(|(name, event, json)| (name, event, json.to_string().into_bytes()))
+    .collect()
+}
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+    // GitHub caps deliveries at 25 MB
+    for size in [1 << 10, 64 << 10, 1 << 20, 25 << 20] {
+        let body = vec![b'x'; size];
+        let signature = sign_request(SECRET, &body);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &body, |b, body| {
+            b.iter(|| verify_signature(SECRET, black_box(body), &signature))
+        });
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, _, body) in bodies() {
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &body, |b, body| {
+            b.iter(|| serde_json::from_slice::<WebhookPayload>(black_box(body)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn verify_and_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_and_parse");
+    for (name, event, body) in bodies() {
+        let signature = sign_request(SECRET, &body);
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &body, |b, body| {
+            b.iter(|| {
+                Delivery::from_parts(Some(SECRET), event, None, Some(&signature), black_box(body))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify, parse, verify_and_parse);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+// Generates the gRPC admin service from proto/ when the `grpc` feature is on, with the
+// vendored protoc so no system install is needed.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc");
+        let mut config = tonic_prost_build::Config::new();
+        config.protoc_executable(protoc);
+        tonic_prost_build::configure()
+            .compile_with_config(config, &["proto/nexus/admin/v1/admin.proto"], &["proto"])
+            .expect("failed to compile the admin protocol");
+    }
+}
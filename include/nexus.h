@@ -0,0 +1,107 @@
+#ifndef NEXUS_H
+#define NEXUS_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum NexusStatus {
+  NEXUS_STATUS_OK = 0,
+  NEXUS_STATUS_INVALID_ARGUMENT = 1,
+  NEXUS_STATUS_WRONG_PREFIX = 2,
+  NEXUS_STATUS_INVALID_HEX = 3,
+  NEXUS_STATUS_MISMATCH = 4,
+  NEXUS_STATUS_INVALID_PAYLOAD = 5,
+} NexusStatus;
+
+// A parsed delivery, created by `nexus_parse_event`.
+typedef struct NexusEvent NexusEvent;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// A static description of `status`, e.g. for logs.
+const char *nexus_status_message(enum NexusStatus status);
+
+// Checks an `x-hub-signature-256` value against `body_len` bytes of `body`.
+//
+// # Safety
+//
+// `secret` and `signature` must be NUL-terminated strings and `body` must point to at
+// least `body_len` readable bytes (it may be NULL when `body_len` is 0).
+enum NexusStatus nexus_verify_signature(const char *secret,
+                                        const uint8_t *body,
+                                        size_t body_len,
+                                        const char *signature);
+
+// Parses a delivery's body; on success `*out` holds an event to release with
+// `nexus_event_free`. The signature is not checked, see `nexus_verify_signature`.
+//
+// # Safety
+//
+// `event_type` (the `x-github-event` header) must be a NUL-terminated string, `body`
+// must point to at least `body_len` readable bytes and `out` must be writable.
+enum NexusStatus nexus_parse_event(const char *event_type,
+                                   const uint8_t *body,
+                                   size_t body_len,
+                                   struct NexusEvent **out);
+
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed.
+const char *nexus_event_type(const struct NexusEvent *event);
+
+// NULL when the event has no action.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed.
+const char *nexus_event_action(const struct NexusEvent *event);
+
+// The repository's `owner/name`, NULL when the event has none.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed.
+const char *nexus_event_repository(const struct NexusEvent *event);
+
+// The sender's login, NULL when the event has none.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed.
+const char *nexus_event_sender(const struct NexusEvent *event);
+
+// The payload as JSON, holding the fields nexus models: unknown fields are dropped and
+// missing ones are null.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed.
+const char *nexus_event_payload_json(const struct NexusEvent *event);
+
+// Whether the event matches `event` or `event.action`, e.g. `pull_request.opened`.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed, and `pattern` must
+// be a NUL-terminated string.
+bool nexus_event_matches(const struct NexusEvent *event, const char *pattern);
+
+// Releases an event; NULL is ignored.
+//
+// # Safety
+//
+// `event` must come from `nexus_parse_event` and not have been freed already.
+void nexus_event_free(struct NexusEvent *event);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NEXUS_H */
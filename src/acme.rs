@@ -0,0 +1,953 @@
+// certificates from an ACME directory (Let's Encrypt by default) via the HTTP-01 challenge,
+// renewed in the background and handed to rustls through a certificate resolver
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    routing::get,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use http::header;
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{
+        ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair,
+    },
+};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use simple_asn1::{ASN1Block, ASN1Class, BigInt, BigUint, from_der, oid, to_der};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::IntoFuture,
+    io::{self, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+use tracing::{debug, error, info, warn};
+
+use crate::{config::AcmeConfig, error::NexusError};
+
+// how often the remaining lifetime of the certificate is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+// orders and authorizations are polled for up to two minutes
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 60;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum AcmeError {
+    Io(io::Error),
+    Http(reqwest::Error),
+    Key(String),
+    Protocol(String),
+}
+
+impl fmt::Display for AcmeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AcmeError::Io(e) => write!(f, "{}", e),
+            AcmeError::Http(e) => write!(f, "{}", e),
+            AcmeError::Key(e) => write!(f, "invalid key: {}", e),
+            AcmeError::Protocol(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AcmeError {}
+
+impl From<io::Error> for AcmeError {
+    fn from(e: io::Error) -> Self {
+        AcmeError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for AcmeError {
+    fn from(e: reqwest::Error) -> Self {
+        AcmeError::Http(e)
+    }
+}
+
+struct Certificate {
+    key: Arc<CertifiedKey>,
+    // unix seconds
+    not_after: i64,
+}
+
+/// An ACME account that keeps a certificate for the configured domains, serves the
+/// HTTP-01 challenges for it and resolves it for incoming TLS handshakes.
+pub struct Acme {
+    config: AcmeConfig,
+    http: reqwest::Client,
+    rng: SystemRandom,
+    account: EcdsaKeyPair,
+    // token -> key authorization, while an authorization is pending
+    challenges: Mutex<HashMap<String, String>>,
+    certificate: RwLock<Option<Certificate>>,
+}
+
+impl fmt::Debug for Acme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acme")
+            .field("domains", &self.config.domains)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Acme {
+    pub fn new(config: &AcmeConfig, http: reqwest::Client) -> Result<Self, AcmeError> {
+        if config.domains.is_empty() {
+            return Err(AcmeError::Protocol("no domains configured".to_string()));
+        }
+        std::fs::create_dir_all(&config.cache_dir)?;
+
+        let rng = SystemRandom::new();
+        let pkcs8 = load_or_generate_key(&config.cache_dir.join("account.pk8"), &rng)?;
+        let account = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, &rng)
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+
+        let acme = Acme {
+            config: config.clone(),
+            http,
+            rng,
+            account,
+            challenges: Mutex::new(HashMap::new()),
+            certificate: RwLock::new(None),
+        };
+        match acme.load_cached() {
+            Ok(Some(certificate)) => *acme.certificate.write().unwrap() = Some(certificate),
+            Ok(None) => {}
+            Err(e) => warn!("Ignoring the cached certificate: {}", e),
+        }
+        Ok(acme)
+    }
+
+    // obtains a certificate unless a cached one is still good, then renews in the background
+    pub async fn start(self: Arc<Self>) -> Result<(), AcmeError> {
+        let listener = TcpListener::bind(self.config.challenge_address).await?;
+        info!(
+            "Answering ACME challenges on {}",
+            self.config.challenge_address
+        );
+        let challenges = Router::new()
+            .route("/.well-known/acme-challenge/{token}", get(answer_challenge))
+            .with_state(self.clone());
+        tokio::spawn(axum::serve(listener, challenges).into_future());
+
+        if self.needs_renewal() {
+            self.renew().await?;
+        }
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(CHECK_INTERVAL).await;
+                if self.needs_renewal()
+                    && let Err(e) = self.renew().await
+                {
+                    error!("Failed to renew the certificate: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn tls_config(self: &Arc<Self>) -> Arc<rustls::ServerConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Arc::new(config)
+    }
+
+    fn needs_renewal(&self) -> bool {
+        let renew_at =
+            chrono::Utc::now().timestamp() + (self.config.renew_before_days * 24 * 60 * 60) as i64;
+        self.certificate
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|c| c.not_after <= renew_at)
+    }
+
+    fn load_cached(&self) -> Result<Option<Certificate>, AcmeError> {
+        let dir = &self.config.cache_dir;
+        let (Ok(chain), Ok(key)) = (
+            std::fs::read(dir.join("cert.pem")),
+            std::fs::read(dir.join("cert.pk8")),
+        ) else {
+            return Ok(None);
+        };
+        certified(&chain, key).map(Some)
+    }
+
+    async fn renew(&self) -> Result<(), AcmeError> {
+        info!(
+            "Requesting a certificate for {}",
+            self.config.domains.join(", ")
+        );
+        let directory: Directory = self
+            .http
+            .get(&self.config.directory_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut session = Session {
+            acme: self,
+            directory,
+            nonce: None,
+            kid: None,
+        };
+        session.register().await?;
+
+        let identifiers: Vec<Value> = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect();
+        let response = session
+            .post(
+                &session.directory.new_order.clone(),
+                Some(&json!({ "identifiers": identifiers })),
+            )
+            .await?;
+        let order_url = location(&response)?;
+        let order: Order = response.json().await?;
+        for authorization in &order.authorizations {
+            session.authorize(authorization).await?;
+        }
+
+        let key = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &self.rng)
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+        let csr = self.csr(key.as_ref())?;
+        session
+            .post(&order.finalize, Some(&json!({ "csr": b64(&csr) })))
+            .await?;
+        let order: Order = session
+            .poll(&order_url, |o: &Order| {
+                o.status != "pending" && o.status != "ready" && o.status != "processing"
+            })
+            .await?;
+        let Some(certificate_url) = order.certificate.filter(|_| order.status == "valid") else {
+            return Err(AcmeError::Protocol(format!("order is {}", order.status)));
+        };
+        let chain = session.post(&certificate_url, None).await?.bytes().await?;
+
+        let certificate = certified(&chain, key.as_ref().to_vec())?;
+        write_private(&self.config.cache_dir.join("cert.pem"), &chain)?;
+        write_private(&self.config.cache_dir.join("cert.pk8"), key.as_ref())?;
+        info!(
+            "Obtained a certificate valid until {}",
+            chrono::DateTime::from_timestamp(certificate.not_after, 0).unwrap_or_default()
+        );
+        *self.certificate.write().unwrap() = Some(certificate);
+        Ok(())
+    }
+
+    // a PKCS#10 request for all configured domains, signed with the new certificate key
+    fn csr(&self, pkcs8: &[u8]) -> Result<Vec<u8>, AcmeError> {
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8, &self.rng)
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+        let public = key.public_key().as_ref();
+
+        // dNSName is [2] IMPLICIT IA5String
+        let names = ASN1Block::Sequence(
+            0,
+            self.config
+                .domains
+                .iter()
+                .map(|domain| {
+                    ASN1Block::Unknown(
+                        ASN1Class::ContextSpecific,
+                        false,
+                        0,
+                        BigUint::from(2u32),
+                        domain.as_bytes().to_vec(),
+                    )
+                })
+                .collect(),
+        );
+        let extension_request = ASN1Block::Sequence(
+            0,
+            vec![
+                ASN1Block::ObjectIdentifier(0, oid!(1, 2, 840, 113549, 1, 9, 14)),
+                ASN1Block::Set(
+                    0,
+                    vec![ASN1Block::Sequence(
+                        0,
+                        vec![ASN1Block::Sequence(
+                            0,
+                            vec![
+                                ASN1Block::ObjectIdentifier(0, oid!(2, 5, 29, 17)),
+                                ASN1Block::OctetString(0, der(&names)?),
+                            ],
+                        )],
+                    )],
+                ),
+            ],
+        );
+        let info = ASN1Block::Sequence(
+            0,
+            vec![
+                ASN1Block::Integer(0, BigInt::from(0)),
+                ASN1Block::Sequence(
+                    0,
+                    vec![ASN1Block::Set(
+                        0,
+                        vec![ASN1Block::Sequence(
+                            0,
+                            vec![
+                                ASN1Block::ObjectIdentifier(0, oid!(2, 5, 4, 3)),
+                                ASN1Block::UTF8String(0, self.config.domains[0].clone()),
+                            ],
+                        )],
+                    )],
+                ),
+                ASN1Block::Sequence(
+                    0,
+                    vec![
+                        ASN1Block::Sequence(
+                            0,
+                            vec![
+                                ASN1Block::ObjectIdentifier(0, oid!(1, 2, 840, 10045, 2, 1)),
+                                ASN1Block::ObjectIdentifier(0, oid!(1, 2, 840, 10045, 3, 1, 7)),
+                            ],
+                        ),
+                        ASN1Block::BitString(0, public.len() * 8, public.to_vec()),
+                    ],
+                ),
+                // attributes are [0] IMPLICIT SET OF Attribute
+                ASN1Block::Unknown(
+                    ASN1Class::ContextSpecific,
+                    true,
+                    0,
+                    BigUint::from(0u32),
+                    der(&extension_request)?,
+                ),
+            ],
+        );
+        let info = der(&info)?;
+        let signature = key
+            .sign(&self.rng, &info)
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+
+        let mut request = info;
+        request.extend(der(&ASN1Block::Sequence(
+            0,
+            vec![ASN1Block::ObjectIdentifier(
+                0,
+                oid!(1, 2, 840, 10045, 4, 3, 2),
+            )],
+        ))?);
+        request.extend(der(&ASN1Block::BitString(
+            0,
+            signature.as_ref().len() * 8,
+            signature.as_ref().to_vec(),
+        ))?);
+        // wrap the three parts in the outer SEQUENCE
+        let mut csr = vec![0x30];
+        csr.extend(der_length(request.len()));
+        csr.extend(request);
+        Ok(csr)
+    }
+
+    fn jwk(&self) -> String {
+        // the uncompressed point is 0x04 || x || y; RFC 7638 wants the members sorted
+        let point = self.account.public_key().as_ref();
+        format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            b64(&point[1..33]),
+            b64(&point[33..65])
+        )
+    }
+
+    fn thumbprint(&self) -> String {
+        b64(digest::digest(&digest::SHA256, self.jwk().as_bytes()).as_ref())
+    }
+
+    fn sign(
+        &self,
+        url: &str,
+        nonce: &str,
+        kid: Option<&str>,
+        payload: Option<&Value>,
+    ) -> Result<String, AcmeError> {
+        let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+        match kid {
+            Some(kid) => protected["kid"] = json!(kid),
+            None => {
+                protected["jwk"] = serde_json::from_str(&self.jwk()).expect("the JWK is valid JSON")
+            }
+        }
+        let protected = b64(protected.to_string().as_bytes());
+        // POST-as-GET requests carry an empty payload
+        let payload = payload
+            .map(|p| b64(p.to_string().as_bytes()))
+            .unwrap_or_default();
+        let signature = self
+            .account
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|e| AcmeError::Key(e.to_string()))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": b64(signature.as_ref()),
+        })
+        .to_string())
+    }
+}
+
+impl ResolvesServerCert for Acme {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.certificate
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|c| c.key.clone())
+    }
+}
+
+async fn answer_challenge(
+    State(acme): State<Arc<Acme>>,
+    Path(token): Path<String>,
+) -> Result<String, NexusError> {
+    acme.challenges
+        .lock()
+        .unwrap()
+        .get(&token)
+        .cloned()
+        .ok_or_else(|| NexusError::NotFound("no such ACME challenge".to_string()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    #[serde(default)]
+    token: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Problem {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    detail: String,
+}
+
+// one pass through the order flow; every request consumes the nonce the previous one returned
+struct Session<'a> {
+    acme: &'a Acme,
+    directory: Directory,
+    nonce: Option<String>,
+    kid: Option<String>,
+}
+
+impl Session<'_> {
+    // creating an account that already exists returns the existing one
+    async fn register(&mut self) -> Result<(), AcmeError> {
+        let contact: Vec<String> = self
+            .acme
+            .config
+            .contact
+            .iter()
+            .map(|c| match c.starts_with("mailto:") {
+                true => c.clone(),
+                false => format!("mailto:{}", c),
+            })
+            .collect();
+        let response = self
+            .post(
+                &self.directory.new_account.clone(),
+                Some(&json!({ "termsOfServiceAgreed": true, "contact": contact })),
+            )
+            .await?;
+        self.kid = Some(location(&response)?);
+        Ok(())
+    }
+
+    async fn authorize(&mut self, url: &str) -> Result<(), AcmeError> {
+        let authorization: Authorization = self.post(url, None).await?.json().await?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+        let domain = authorization.identifier.value;
+        let Some(challenge) = authorization
+            .challenges
+            .into_iter()
+            .find(|c| c.kind == "http-01")
+        else {
+            return Err(AcmeError::Protocol(format!(
+                "no http-01 challenge offered for {}",
+                domain
+            )));
+        };
+
+        let key_authorization = format!("{}.{}", challenge.token, self.acme.thumbprint());
+        self.acme
+            .challenges
+            .lock()
+            .unwrap()
+            .insert(challenge.token.clone(), key_authorization);
+        let result = async {
+            self.post(&challenge.url, Some(&json!({}))).await?;
+            self.poll(url, |a: &Authorization| a.status != "pending")
+                .await
+        }
+        .await;
+        self.acme
+            .challenges
+            .lock()
+            .unwrap()
+            .remove(&challenge.token);
+
+        match result?.status.as_str() {
+            "valid" => Ok(()),
+            status => Err(AcmeError::Protocol(format!(
+                "authorization for {} is {}",
+                domain, status
+            ))),
+        }
+    }
+
+    async fn poll<T: DeserializeOwned>(
+        &mut self,
+        url: &str,
+        done: impl Fn(&T) -> bool,
+    ) -> Result<T, AcmeError> {
+        for _ in 0..POLL_ATTEMPTS {
+            let value: T = self.post(url, None).await?.json().await?;
+            if done(&value) {
+                return Ok(value);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Err(AcmeError::Protocol(format!("timed out waiting on {}", url)))
+    }
+
+    // a stale nonce is refused with badNonce; that is retried once with the fresh one
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<reqwest::Response, AcmeError> {
+        let mut retried = false;
+        loop {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce().await?,
+            };
+            let body = self.acme.sign(url, &nonce, self.kid.as_deref(), payload)?;
+            let response = self
+                .acme
+                .http
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(body)
+                .send()
+                .await?;
+            self.nonce = replay_nonce(&response);
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            let problem: Problem = response.json().await.unwrap_or_default();
+            if problem.kind.ends_with(":badNonce") && !retried {
+                retried = true;
+                continue;
+            }
+            return Err(AcmeError::Protocol(format!(
+                "{} returned {}: {}",
+                url, status, problem.detail
+            )));
+        }
+    }
+
+    async fn new_nonce(&self) -> Result<String, AcmeError> {
+        let response = self
+            .acme
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await?;
+        replay_nonce(&response)
+            .ok_or_else(|| AcmeError::Protocol("no Replay-Nonce in response".to_string()))
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+fn location(response: &reqwest::Response) -> Result<String, AcmeError> {
+    response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .ok_or_else(|| AcmeError::Protocol(format!("no Location from {}", response.url())))
+}
+
+fn load_or_generate_key(path: &PathBuf, rng: &SystemRandom) -> Result<Vec<u8>, AcmeError> {
+    if let Ok(pkcs8) = std::fs::read(path) {
+        return Ok(pkcs8);
+    }
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, rng)
+        .map_err(|e| AcmeError::Key(e.to_string()))?;
+    write_private(path, pkcs8.as_ref())?;
+    Ok(pkcs8.as_ref().to_vec())
+}
+
+// readable by the owner only; files written by earlier versions are tightened too
+fn write_private(path: &std::path::Path, data: &[u8]) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(data)
+}
+
+// when the cached certificate expires, or None when there is no readable one
+pub fn cached_expiry(config: &AcmeConfig) -> Option<chrono::DateTime<chrono::Utc>> {
+    let chain = std::fs::read(config.cache_dir.join("cert.pem")).ok()?;
+    let leaf = pem::parse_many(chain)
+        .ok()?
+        .into_iter()
+        .find(|p| p.tag() == "CERTIFICATE")?;
+    chrono::DateTime::from_timestamp(not_after(leaf.contents())?, 0)
+}
+
+fn certified(chain: &[u8], pkcs8: Vec<u8>) -> Result<Certificate, AcmeError> {
+    let chain: Vec<CertificateDer<'static>> = pem::parse_many(chain)
+        .map_err(|e| AcmeError::Protocol(format!("invalid certificate chain: {}", e)))?
+        .into_iter()
+        .filter(|p| p.tag() == "CERTIFICATE")
+        .map(|p| CertificateDer::from(p.into_contents()))
+        .collect();
+    let not_after = chain
+        .first()
+        .and_then(|leaf| not_after(leaf))
+        .ok_or_else(|| AcmeError::Protocol("unreadable certificate".to_string()))?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(pkcs8));
+    let key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| AcmeError::Key(e.to_string()))?;
+    Ok(Certificate {
+        key: Arc::new(CertifiedKey::new(chain, key)),
+        not_after,
+    })
+}
+
+// Certificate ::= SEQUENCE { tbsCertificate SEQUENCE { ..., validity SEQUENCE { notBefore, notAfter }, ... } }
+fn not_after(certificate: &[u8]) -> Option<i64> {
+    let blocks = from_der(certificate).ok()?;
+    let ASN1Block::Sequence(_, certificate) = blocks.first()? else {
+        return None;
+    };
+    let ASN1Block::Sequence(_, tbs) = certificate.first()? else {
+        return None;
+    };
+    tbs.iter().find_map(|field| match field {
+        ASN1Block::Sequence(_, validity) => match validity.as_slice() {
+            [
+                ASN1Block::UTCTime(..) | ASN1Block::GeneralizedTime(..),
+                ASN1Block::UTCTime(_, time) | ASN1Block::GeneralizedTime(_, time),
+            ] => Some(time.assume_utc().unix_timestamp()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn der(block: &ASN1Block) -> Result<Vec<u8>, AcmeError> {
+    to_der(block).map_err(|e| AcmeError::Protocol(format!("failed to encode the CSR: {:?}", e)))
+}
+
+fn der_length(len: usize) -> Vec<u8> {
+    if len < 0x80 {
+        return vec![len as u8];
+    }
+    let bytes: Vec<u8> = len
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|b| *b == 0)
+        .collect();
+    let mut encoded = vec![0x80 | bytes.len() as u8];
+    encoded.extend(bytes);
+    encoded
+}
+
+fn b64(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Accepts TCP connections and completes TLS handshakes off the accept loop, so a slow
+/// client cannot hold up others. Stops accepting once the server is dropped.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: Arc<rustls::ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (sender, incoming) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    _ = sender.closed() => break,
+                    accepted = listener.accept() => accepted,
+                };
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept a connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, peer)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", peer, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+
+        Ok(TlsListener {
+            local_addr,
+            incoming,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(connection) => connection,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_FIXED, UnparsedPublicKey};
+
+    fn acme(name: &str) -> Acme {
+        let cache_dir =
+            std::env::temp_dir().join(format!("nexus-acme-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        let config = AcmeConfig {
+            domains: vec!["example.com".to_string(), "www.example.com".to_string()],
+            cache_dir,
+            ..Default::default()
+        };
+        Acme::new(&config, reqwest::Client::new()).unwrap()
+    }
+
+    // the header and content lengths of the DER element at the start of `data`
+    fn tlv_lengths(data: &[u8]) -> (usize, usize) {
+        match data[1] {
+            len @ 0..0x80 => (2, len as usize),
+            long => {
+                let n = (long & 0x7f) as usize;
+                let len = data[2..2 + n]
+                    .iter()
+                    .fold(0, |len, b| len << 8 | *b as usize);
+                (2 + n, len)
+            }
+        }
+    }
+
+    // the first DER element in `data` and what follows it
+    fn split_tlv(data: &[u8]) -> (&[u8], &[u8]) {
+        let (header, len) = tlv_lengths(data);
+        data.split_at(header + len)
+    }
+
+    #[test]
+    fn account_key_is_private_and_reused() {
+        let first = acme("account");
+        let path = first.config.cache_dir.join("account.pk8");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let again = Acme::new(&first.config, reqwest::Client::new()).unwrap();
+        assert_eq!(first.thumbprint(), again.thumbprint());
+        assert!(first.needs_renewal());
+        std::fs::remove_dir_all(&first.config.cache_dir).unwrap();
+    }
+
+    #[test]
+    fn csr_is_signed_by_the_certificate_key() {
+        let acme = acme("csr");
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &acme.rng).unwrap();
+        let key =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &acme.rng)
+                .unwrap();
+        let csr = acme.csr(pkcs8.as_ref()).unwrap();
+
+        let (outer, rest) = split_tlv(&csr);
+        assert!(rest.is_empty());
+        let (header, _) = tlv_lengths(outer);
+        let (info, rest) = split_tlv(&outer[header..]);
+        let (_algorithm, signature) = split_tlv(rest);
+        let Ok(blocks) = from_der(signature) else {
+            panic!("the signature is not a BIT STRING");
+        };
+        let [ASN1Block::BitString(_, _, signature)] = blocks.as_slice() else {
+            panic!("the signature is not a BIT STRING");
+        };
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key.public_key().as_ref())
+            .verify(info, signature)
+            .unwrap();
+
+        let info = String::from_utf8_lossy(info);
+        assert!(info.contains("example.com") && info.contains("www.example.com"));
+        std::fs::remove_dir_all(&acme.config.cache_dir).unwrap();
+    }
+
+    #[test]
+    fn requests_are_signed_with_the_account_key() {
+        let acme = acme("jws");
+        let jws: Value = serde_json::from_str(
+            &acme
+                .sign(
+                    "https://ca/new-order",
+                    "nonce",
+                    None,
+                    Some(&json!({"a": 1})),
+                )
+                .unwrap(),
+        )
+        .unwrap();
+        let field = |name: &str| jws[name].as_str().unwrap().to_string();
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).unwrap();
+
+        let protected: Value = serde_json::from_slice(&decode(&field("protected"))).unwrap();
+        assert_eq!(protected["alg"], "ES256");
+        assert_eq!(protected["nonce"], "nonce");
+        assert_eq!(protected["url"], "https://ca/new-order");
+        assert_eq!(protected["jwk"]["crv"], "P-256");
+        assert_eq!(decode(&field("payload")), br#"{"a":1}"#);
+
+        let signed = format!("{}.{}", field("protected"), field("payload"));
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, acme.account.public_key().as_ref())
+            .verify(signed.as_bytes(), &decode(&field("signature")))
+            .unwrap();
+
+        // once registered, the account is named by its URL instead
+        let jws: Value = serde_json::from_str(
+            &acme
+                .sign("https://ca/x", "n", Some("https://ca/acct/1"), None)
+                .unwrap(),
+        )
+        .unwrap();
+        let protected: Value =
+            serde_json::from_slice(&decode(jws["protected"].as_str().unwrap())).unwrap();
+        assert_eq!(protected["kid"], "https://ca/acct/1");
+        assert!(protected.get("jwk").is_none());
+        assert_eq!(jws["payload"], "");
+        std::fs::remove_dir_all(&acme.config.cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn challenges_are_answered_while_pending() {
+        let acme = Arc::new(acme("challenge"));
+        acme.challenges
+            .lock()
+            .unwrap()
+            .insert("token".to_string(), "token.thumbprint".to_string());
+
+        let answer = answer_challenge(State(acme.clone()), Path("token".to_string())).await;
+        assert_eq!(answer.unwrap(), "token.thumbprint");
+        let unknown = answer_challenge(State(acme.clone()), Path("other".to_string())).await;
+        assert_eq!(unknown.unwrap_err().code(), "not_found");
+        std::fs::remove_dir_all(&acme.config.cache_dir).unwrap();
+    }
+
+    #[test]
+    fn long_lengths_are_encoded_big_endian() {
+        assert_eq!(der_length(0x7f), [0x7f]);
+        assert_eq!(der_length(0x80), [0x81, 0x80]);
+        assert_eq!(der_length(0x1234), [0x82, 0x12, 0x34]);
+    }
+}
@@ -0,0 +1,400 @@
+// What the admin APIs can do besides reading state: list and redeliver archived
+// deliveries, reload the configuration, and pause or resume processing. The HTTP routes
+// under /admin and the gRPC service (the `grpc` feature) both go through here, so they
+// behave the same.
+
+use chrono::{DateTime, Utc};
+use http::StatusCode;
+use serde::Serialize;
+use std::io;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    archive,
+    automations::rerun::{self, Rerun},
+    error::NexusError,
+    queue::{EnqueueError, Enqueued, ProcessingStatus},
+    service::AppState,
+    webhook,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+    // `archive.dir` is not set, so there is nothing to list or redeliver
+    #[error("deliveries are not archived; set archive.dir")]
+    NoArchive,
+    #[error("no archived delivery {0}")]
+    NotFound(String),
+    // the delivery queue is not running, e.g. in `nexus replay`
+    #[error("the delivery queue is not running")]
+    NoQueue,
+    #[error("failed to read the archive: {0}")]
+    Archive(#[source] io::Error),
+    #[error("the delivery was not queued: {0}")]
+    Queue(#[source] EnqueueError),
+    // processed at once without a queue, and a handler failed
+    #[error("redelivery failed: {0}")]
+    Handler(#[source] Box<NexusError>),
+    // the new configuration is invalid, or the server cannot be reloaded
+    #[error("{0}")]
+    Reload(String),
+}
+
+impl AdminError {
+    // stable across releases, like NexusError's
+    pub fn code(&self) -> &'static str {
+        match self {
+            AdminError::NoArchive => "not_archived",
+            AdminError::NotFound(_) => "not_found",
+            AdminError::NoQueue => "no_queue",
+            AdminError::Archive(_) => "archive_failed",
+            AdminError::Queue(_) => "queue_unavailable",
+            AdminError::Handler(e) => e.code(),
+            AdminError::Reload(_) => "reload_failed",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AdminError::NoArchive | AdminError::NotFound(_) | AdminError::NoQueue => {
+                StatusCode::NOT_FOUND
+            }
+            AdminError::Reload(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AdminError::Queue(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AdminError::Archive(_) | AdminError::Handler(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// compares in constant time, so the token cannot be guessed byte by byte
+pub fn token_matches(expected: &str, provided: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// An archived delivery as the admin APIs list it.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliverySummary {
+    pub delivery_id: Option<String>,
+    pub event_type: String,
+    pub action: Option<String>,
+    pub repository: Option<String>,
+    pub received_at: DateTime<Utc>,
+    // the re-run nexus requested for the failed workflow run the delivery reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerun: Option<Rerun>,
+}
+
+// newest first, at most `limit`, only of `event_type` when given
+pub async fn deliveries(
+    state: &AppState,
+    event_type: Option<&str>,
+    limit: usize,
+) -> Result<Vec<DeliverySummary>, AdminError> {
+    let dir = state
+        .config
+        .archive
+        .dir
+        .as_ref()
+        .ok_or(AdminError::NoArchive)?;
+    let archived = archive::load(dir).await.map_err(AdminError::Archive)?;
+    let jobs = archived
+        .into_iter()
+        .rev()
+        .map(|(_, delivery)| delivery.job)
+        .filter(|job| event_type.is_none_or(|e| job.event_type == e))
+        .take(limit);
+
+    let mut summaries = Vec::new();
+    for job in jobs {
+        let rerun = match (&job.payload.repository, &job.payload.workflow_run) {
+            (Some(repo), Some(run)) => rerun::recorded(state, &repo.full_name, run).await,
+            _ => None,
+        };
+        summaries.push(DeliverySummary {
+            delivery_id: job.delivery_id,
+            event_type: job.event_type,
+            action: job.payload.action,
+            repository: job.payload.repository.map(|r| r.full_name),
+            received_at: job.received_at,
+            rerun,
+        });
+    }
+    Ok(summaries)
+}
+
+// handles an archived delivery again: queued like a new one, so it waits while processing
+// is paused, or processed at once without a queue
+pub async fn redeliver(state: &AppState, delivery_id: &str) -> Result<Enqueued, AdminError> {
+    let dir = state
+        .config
+        .archive
+        .dir
+        .as_ref()
+        .ok_or(AdminError::NoArchive)?;
+    let archived = archive::load(dir).await.map_err(AdminError::Archive)?;
+    let Some((_, delivery)) = archived
+        .into_iter()
+        .rev()
+        .find(|(_, delivery)| delivery.job.delivery_id.as_deref() == Some(delivery_id))
+    else {
+        return Err(AdminError::NotFound(delivery_id.to_string()));
+    };
+
+    let mut job = delivery.job;
+    job.received_at = Utc::now();
+    match &state.queue {
+        Some(queue) => queue.enqueue(job).await.map_err(AdminError::Queue),
+        None => {
+            webhook::dispatch(
+                state,
+                &job.event_type,
+                job.delivery_id.as_deref(),
+                &job.payload,
+                &job.json,
+            )
+            .await
+            .map_err(|e| AdminError::Handler(Box::new(e)))?;
+            Ok(Enqueued::Queued)
+        }
+    }
+}
+
+pub fn pause(state: &AppState, reason: Option<String>) -> Result<ProcessingStatus, AdminError> {
+    let queue = state.queue.as_ref().ok_or(AdminError::NoQueue)?;
+    Ok(queue.pause(reason))
+}
+
+pub fn resume(state: &AppState) -> Result<ProcessingStatus, AdminError> {
+    let queue = state.queue.as_ref().ok_or(AdminError::NoQueue)?;
+    Ok(queue.resume())
+}
+
+pub fn processing(state: &AppState) -> Result<ProcessingStatus, AdminError> {
+    let queue = state.queue.as_ref().ok_or(AdminError::NoQueue)?;
+    Ok(queue.status())
+}
+
+type ReloadReply = oneshot::Sender<Result<(), String>>;
+
+/// Asks the server to load its configuration again. The server checks the new one and
+/// answers before it drains and restarts with it; without a server nothing can reload.
+#[derive(Clone, Default)]
+pub struct Reloader {
+    requests: Option<mpsc::Sender<ReloadReply>>,
+}
+
+impl Reloader {
+    pub fn channel() -> (Self, ReloadRequests) {
+        let (sender, receiver) = mpsc::channel(1);
+        (
+            Self {
+                requests: Some(sender),
+            },
+            ReloadRequests { receiver },
+        )
+    }
+
+    pub async fn reload(&self) -> Result<(), AdminError> {
+        let unavailable = || AdminError::Reload("this process cannot reload".to_string());
+        let requests = self.requests.as_ref().ok_or_else(unavailable)?;
+        let (reply, answer) = oneshot::channel();
+        requests.send(reply).await.map_err(|_| unavailable())?;
+        answer
+            .await
+            .map_err(|_| unavailable())?
+            .map_err(AdminError::Reload)
+    }
+}
+
+/// The server's end of a [`Reloader`].
+pub struct ReloadRequests {
+    receiver: mpsc::Receiver<ReloadReply>,
+}
+
+/// A reload the server has yet to answer.
+pub struct ReloadRequest {
+    reply: ReloadReply,
+}
+
+impl ReloadRequests {
+    // pending forever once every reloader is gone
+    pub async fn next(&mut self) -> ReloadRequest {
+        match self.receiver.recv().await {
+            Some(reply) => ReloadRequest { reply },
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl ReloadRequest {
+    pub fn accept(self) {
+        let _ = self.reply.send(Ok(()));
+    }
+
+    pub fn reject(self, reason: String) {
+        let _ = self.reply.send(Err(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, QueueConfig},
+        queue::{DeliveryQueue, Processing},
+        redact::Redactor,
+        webhook::RawBody,
+    };
+    use bytes::Bytes;
+    use http::HeaderMap;
+    use std::path::{Path, PathBuf};
+
+    const ISSUE: &str = r#"{"action":"opened","repository":{"name":"nexus","full_name":"octo/nexus","html_url":"https://github.com/octo/nexus"},"extra":1}"#;
+    const PUSH: &str = r#"{"ref":"refs/heads/main"}"#;
+    const WORKFLOW_RUN: &str = r#"{"action":"completed","repository":{"name":"nexus","full_name":"octo/nexus","html_url":"https://github.com/octo/nexus"},"workflow_run":{"id":42,"name":"CI","head_sha":"abc","conclusion":"failure","html_url":"https://github.com/octo/nexus/actions/runs/42","run_attempt":1}}"#;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nexus-admin-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    async fn archive(dir: &Path, id: &str, event_type: &str, body: &'static str) {
+        let body = RawBody {
+            received: Bytes::from_static(body.as_bytes()),
+            json: Bytes::from_static(body.as_bytes()),
+        };
+        archive::record(
+            dir,
+            &Redactor::default(),
+            &HeaderMap::new(),
+            Some(id),
+            event_type,
+            &body,
+        )
+        .await
+        .unwrap();
+    }
+
+    fn state(dir: &Path) -> AppState {
+        let mut config = Config::default();
+        config.archive.dir = Some(dir.to_path_buf());
+        AppState::new(None).with_config(config)
+    }
+
+    #[test]
+    fn tokens_match_only_in_full() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+
+    #[tokio::test]
+    async fn lists_archived_deliveries_newest_first() {
+        let dir = temp_dir("list");
+        archive(&dir, "1", "issues", ISSUE).await;
+        archive(&dir, "2", "push", PUSH).await;
+        archive(&dir, "3", "issues", ISSUE).await;
+        let state = state(&dir);
+
+        let all = deliveries(&state, None, 100).await.unwrap();
+        let ids: Vec<_> = all.iter().map(|d| d.delivery_id.as_deref()).collect();
+        assert_eq!(ids, [Some("3"), Some("2"), Some("1")]);
+        assert_eq!(all[0].action.as_deref(), Some("opened"));
+        assert_eq!(all[0].repository.as_deref(), Some("octo/nexus"));
+
+        let issues = deliveries(&state, Some("issues"), 1).await.unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].delivery_id.as_deref(), Some("3"));
+
+        let unarchived = AppState::new(None);
+        assert!(matches!(
+            deliveries(&unarchived, None, 100).await,
+            Err(AdminError::NoArchive)
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lists_the_rerun_a_failed_run_caused() {
+        let dir = temp_dir("rerun");
+        archive(&dir, "1", "workflow_run", WORKFLOW_RUN).await;
+        archive(&dir, "2", "issues", ISSUE).await;
+        let state = state(&dir);
+        let rerun = Rerun {
+            run_id: 42,
+            attempt: 2,
+            job: Some("test".to_string()),
+            pattern: Some("ECONNRESET".to_string()),
+            requested_at: Utc::now(),
+        };
+        rerun::record(&state, "octo/nexus", 1, &rerun).await;
+
+        let all = deliveries(&state, None, 100).await.unwrap();
+        assert_eq!(all[0].rerun, None);
+        assert_eq!(all[1].rerun.as_ref(), Some(&rerun));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn redelivers_the_archived_json_through_the_queue() {
+        let dir = temp_dir("redeliver");
+        archive(&dir, "1", "issues", ISSUE).await;
+        let (queue, mut receiver) = DeliveryQueue::new(&QueueConfig::default());
+        let state = state(&dir).with_queue(queue);
+
+        assert_eq!(redeliver(&state, "1").await.unwrap(), Enqueued::Queued);
+        let job = receiver.recv().await.unwrap();
+        assert_eq!(job.delivery_id.as_deref(), Some("1"));
+        assert_eq!(job.event_type, "issues");
+        assert_eq!(job.json, ISSUE.as_bytes());
+
+        assert!(matches!(
+            redeliver(&state, "2").await,
+            Err(AdminError::NotFound(id)) if id == "2"
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pauses_and_resumes_the_queue() {
+        assert!(matches!(
+            pause(&AppState::new(None), None),
+            Err(AdminError::NoQueue)
+        ));
+
+        let (queue, _receiver) = DeliveryQueue::new(&QueueConfig::default());
+        let state = AppState::new(None).with_queue(queue);
+        let paused = pause(&state, Some("maintenance".to_string())).unwrap();
+        assert_eq!(paused.state, Processing::Paused);
+        assert_eq!(paused.reason.as_deref(), Some("maintenance"));
+        assert_eq!(processing(&state).unwrap().state, Processing::Paused);
+        assert_ne!(resume(&state).unwrap().state, Processing::Paused);
+    }
+
+    #[tokio::test]
+    async fn reloads_are_answered_by_the_server() {
+        assert!(matches!(
+            Reloader::default().reload().await,
+            Err(AdminError::Reload(_))
+        ));
+
+        let (reloader, mut requests) = Reloader::channel();
+        let server = tokio::spawn(async move {
+            requests.next().await.reject("invalid config".to_string());
+            requests.next().await.accept();
+        });
+        assert!(matches!(
+            reloader.reload().await,
+            Err(AdminError::Reload(e)) if e == "invalid config"
+        ));
+        reloader.reload().await.unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+// Counts a few signals per window and alerts when one jumps compared to the window
+// before, then once more when it settles down again.
+
+use serde_json::json;
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+
+use crate::{
+    config::AnomalyThreshold,
+    outbound::{self, Delivery, Request},
+    service::AppState,
+};
+
+// outbound failures of this target are not counted, so a broken alert sink cannot feed itself
+pub const ALERT_TARGET: &str = "anomaly:alert";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    SignatureFailures,
+    SinkErrors,
+    Deliveries,
+}
+
+impl Signal {
+    const ALL: [Signal; 3] = [
+        Signal::SignatureFailures,
+        Signal::SinkErrors,
+        Signal::Deliveries,
+    ];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Signal::SignatureFailures => "signature failures",
+            Signal::SinkErrors => "outbound 5xx and network errors",
+            Signal::Deliveries => "deliveries",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    current: AtomicU64,
+    // the last closed window, and whether it was alerted on
+    previous: Mutex<(u64, bool)>,
+}
+
+/// Per-window counts of each signal.
+#[derive(Clone, Default)]
+pub struct Anomalies {
+    counters: Arc<[Counter; 3]>,
+}
+
+impl Anomalies {
+    pub fn record(&self, signal: Signal) {
+        self.counter(signal).current.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn counter(&self, signal: Signal) -> &Counter {
+        &self.counters[signal as usize]
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) -> Option<JoinHandle<()>> {
+    let config = &state.config.anomaly;
+    if !config.enabled {
+        return None;
+    }
+    let window = Duration::from_secs(config.window_secs.max(1));
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(window);
+        // the first tick fires immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            for signal in Signal::ALL {
+                check(&state, signal).await;
+            }
+        }
+    }))
+}
+
+// closes the window for one signal and alerts on a change of state
+async fn check(state: &AppState, signal: Signal) {
+    let config = &state.config.anomaly;
+    let threshold = match signal {
+        Signal::SignatureFailures => &config.signature_failures,
+        Signal::SinkErrors => &config.sink_errors,
+        Signal::Deliveries => &config.deliveries,
+    };
+    let counter = state.anomalies.counter(signal);
+    let count = counter.current.swap(0, Ordering::Relaxed);
+
+    let message = {
+        let mut previous = counter.previous.lock().unwrap();
+        let (before, alerting) = *previous;
+        let anomalous = is_anomalous(threshold, before, count);
+        // while alerting, the spike is compared with itself, so recovery is judged by the threshold
+        let still = alerting && threshold.min_count > 0 && count >= threshold.min_count;
+        let message = match (alerting, anomalous || still) {
+            (false, true) => Some(format!(
+                "{} {} in the last {}s, up from {}",
+                count,
+                signal.describe(),
+                config.window_secs,
+                before
+            )),
+            (true, false) => Some(format!(
+                "{} are back to normal: {} in the last {}s",
+                signal.describe(),
+                count,
+                config.window_secs
+            )),
+            _ => None,
+        };
+        *previous = (count, anomalous || still);
+        message
+    };
+
+    if let Some(message) = message {
+        warn!("{}", message);
+        alert(state, &message).await;
+    }
+}
+
+fn is_anomalous(threshold: &AnomalyThreshold, before: u64, count: u64) -> bool {
+    threshold.min_count > 0
+        && count >= threshold.min_count
+        && count as f64 >= before.max(1) as f64 * threshold.factor
+}
+
+async fn alert(state: &AppState, message: &str) {
+    let config = &state.config.anomaly;
+    let Some(url) = &config.alert_url else {
+        return;
+    };
+    let request = Request::Http {
+        url: url.clone(),
+        headers: config.alert_headers.clone(),
+        body: json!({ "text": message }),
+    };
+    if let Err(e) = outbound::deliver(state, Delivery::new(ALERT_TARGET, request)).await {
+        error!("Failed to send the anomaly alert: {}", e);
+    }
+}
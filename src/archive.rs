@@ -0,0 +1,344 @@
+// every verified delivery as it was received, with GitHub's headers, kept in `archive.dir`
+// for `nexus replay`; one JSON file per delivery. `payload` is the JSON document, as in a
+// queue journal entry, and `body` the bytes GitHub sent when they differ from it (a
+// compressed or form-encoded body). With redaction configured only the redacted document
+// is kept, without the signature that no longer matches it
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::{config::ArchiveConfig, queue::Job, redact::Redactor, webhook::RawBody};
+
+// how often entries past the retention period are looked for
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    delivery_id: Option<&'a str>,
+    event_type: &'a str,
+    payload: Box<RawValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    headers: BTreeMap<&'a str, &'a str>,
+    received_at: DateTime<Utc>,
+}
+
+// what an entry has besides the job; entries written by earlier versions have neither
+#[derive(Deserialize)]
+struct Received {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+}
+
+/// An archived delivery.
+pub struct Archived {
+    pub job: Job,
+    // the headers GitHub sent, e.g. `x-github-event` and `x-hub-signature-256`
+    pub headers: BTreeMap<String, String>,
+    // the body as GitHub sent it
+    pub body: Bytes,
+}
+
+// the headers GitHub sets on a delivery and those needed to read the body again
+fn archived(name: &str) -> bool {
+    name.starts_with("x-github-")
+        || name.starts_with("x-hub-")
+        || matches!(name, "content-type" | "content-encoding" | "user-agent")
+}
+
+pub async fn record(
+    dir: &Path,
+    redactor: &Redactor,
+    headers: &HeaderMap,
+    delivery_id: Option<&str>,
+    event_type: &str,
+    body: &RawBody,
+) -> io::Result<()> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let mut headers: BTreeMap<&str, &str> = headers
+        .iter()
+        .filter(|(name, _)| archived(name.as_str()))
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect();
+    let json = redactor.json(&body.json)?;
+    let received = match &json {
+        Cow::Owned(_) => {
+            headers.retain(|name, _| !name.starts_with("x-hub-signature"));
+            headers.remove("content-encoding");
+            headers.insert("content-type", "application/json");
+            None
+        }
+        Cow::Borrowed(_) if body.received != body.json => Some(STANDARD.encode(&body.received)),
+        Cow::Borrowed(_) => None,
+    };
+    let received_at = Utc::now();
+    let data = serde_json::to_vec(&Entry {
+        delivery_id,
+        event_type,
+        payload: RawValue::from_string(String::from_utf8_lossy(&json).into_owned())?,
+        body: received,
+        headers,
+        received_at,
+    })?;
+    tokio::fs::create_dir_all(dir).await?;
+    // the timestamp first so that sorting the names sorts by arrival
+    let name = format!(
+        "{:020}-{:06}",
+        received_at.timestamp_micros(),
+        NEXT.fetch_add(1, Ordering::Relaxed) % 1_000_000
+    );
+    let tmp = dir.join(format!("{}.tmp", name));
+    // readable by the owner only, like the queue journal
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, &data).await?;
+    drop(file);
+    tokio::fs::rename(&tmp, dir.join(format!("{}.json", name))).await
+}
+
+fn read(data: &[u8]) -> Result<Archived, String> {
+    let job = Job::from_entry(data).map_err(|e| e.to_string())?;
+    let received: Received = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+    let body = match received.body {
+        Some(body) => STANDARD.decode(body).map_err(|e| e.to_string())?.into(),
+        None => job.json.clone(),
+    };
+    Ok(Archived {
+        job,
+        headers: received.headers,
+        body,
+    })
+}
+
+// archived deliveries, oldest first, with the file each came from
+pub async fn load(dir: &Path) -> io::Result<Vec<(PathBuf, Archived)>> {
+    let mut paths = entries(dir).await?;
+    paths.sort();
+    let mut deliveries = Vec::with_capacity(paths.len());
+    for path in paths {
+        let data = tokio::fs::read(&path).await?;
+        match read(&data) {
+            Ok(delivery) => deliveries.push((path, delivery)),
+            Err(e) => error!(
+                "Skipping unreadable archive entry {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(deliveries)
+}
+
+// deletes entries older than the retention period and returns how many
+pub async fn prune(config: &ArchiveConfig) -> io::Result<usize> {
+    let (Some(dir), days @ 1..) = (&config.dir, config.retention_days) else {
+        return Ok(0);
+    };
+    let cutoff = (Utc::now() - chrono::Duration::days(days as i64)).timestamp_micros();
+    let mut removed = 0;
+    for path in entries(dir).await? {
+        let received = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split('-').next())
+            .and_then(|micros| micros.parse::<i64>().ok());
+        if received.is_some_and(|micros| micros < cutoff) {
+            tokio::fs::remove_file(&path).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn spawn_pruning(config: ArchiveConfig) -> Option<JoinHandle<()>> {
+    if config.dir.is_none() || config.retention_days == 0 {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match prune(&config).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Pruned {} archived deliveries", removed),
+                Err(e) => error!("Failed to prune the delivery archive: {}", e),
+            }
+        }
+    }))
+}
+
+async fn entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+
+    const BODY: &str = r#"{"action":"opened","pusher":{"email":"octocat@github.com"}}"#;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nexus-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    async fn only_entry(dir: &Path) -> Archived {
+        let mut entries = load(dir).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        entries.pop().unwrap().1
+    }
+
+    #[tokio::test]
+    async fn keeps_the_body_and_headers_as_received() {
+        let dir = temp_dir("raw");
+        let headers = headers(&[
+            ("x-github-event", "issues"),
+            ("x-hub-signature-256", "sha256=abc"),
+            ("content-type", "application/json"),
+            ("authorization", "Bearer nope"),
+        ]);
+        let body = RawBody {
+            received: Bytes::from_static(BODY.as_bytes()),
+            json: Bytes::from_static(BODY.as_bytes()),
+        };
+        record(
+            &dir,
+            &Redactor::default(),
+            &headers,
+            Some("1"),
+            "issues",
+            &body,
+        )
+        .await
+        .unwrap();
+
+        let archived = only_entry(&dir).await;
+        assert_eq!(archived.body, BODY.as_bytes());
+        assert_eq!(archived.job.json, BODY.as_bytes());
+        assert_eq!(archived.job.payload.action.as_deref(), Some("opened"));
+        assert_eq!(archived.headers["x-hub-signature-256"], "sha256=abc");
+        assert!(!archived.headers.contains_key("authorization"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = &load(&dir).await.unwrap()[0].0;
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_form_bodies_next_to_their_json() {
+        let dir = temp_dir("form");
+        let form = format!(
+            "payload={}",
+            form_urlencoded::byte_serialize(BODY.as_bytes()).collect::<String>()
+        );
+        let headers = headers(&[("content-type", "application/x-www-form-urlencoded")]);
+        let body = RawBody {
+            received: Bytes::from(form.clone()),
+            json: Bytes::from_static(BODY.as_bytes()),
+        };
+        record(&dir, &Redactor::default(), &headers, None, "issues", &body)
+            .await
+            .unwrap();
+
+        let archived = only_entry(&dir).await;
+        assert_eq!(archived.body, form.as_bytes());
+        assert_eq!(archived.job.json, BODY.as_bytes());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn redacted_entries_drop_the_signature() {
+        let dir = temp_dir("redacted");
+        let redactor = Redactor::from_config(&RedactionConfig {
+            enabled: true,
+            fields: vec!["/pusher/email".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let headers = headers(&[
+            ("x-github-event", "issues"),
+            ("x-hub-signature-256", "sha256=abc"),
+            ("content-encoding", "gzip"),
+        ]);
+        let body = RawBody {
+            received: Bytes::from_static(b"\x1f\x8b..."),
+            json: Bytes::from_static(BODY.as_bytes()),
+        };
+        record(&dir, &redactor, &headers, None, "issues", &body)
+            .await
+            .unwrap();
+
+        let archived = only_entry(&dir).await;
+        assert!(!String::from_utf8_lossy(&archived.body).contains("octocat@github.com"));
+        assert_eq!(archived.body, archived.job.json);
+        assert_eq!(archived.headers["x-github-event"], "issues");
+        assert_eq!(archived.headers["content-type"], "application/json");
+        assert!(!archived.headers.contains_key("x-hub-signature-256"));
+        assert!(!archived.headers.contains_key("content-encoding"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_entries_without_headers() {
+        let dir = temp_dir("old");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("00000000000000000001-000000.json"),
+            format!(
+                r#"{{"delivery_id":"1","event_type":"issues","payload":{},"received_at":"2024-05-01T12:00:00Z"}}"#,
+                BODY
+            ),
+        )
+        .unwrap();
+
+        let archived = only_entry(&dir).await;
+        assert!(archived.headers.is_empty());
+        assert_eq!(archived.body, BODY.as_bytes());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
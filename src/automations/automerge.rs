@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use tracing::{debug, error, info};
+
+use crate::{
+    config::{AutomergeConfig, PolicyAction},
+    error::NexusError,
+    github::{
+        self,
+        checks::{self, CheckConclusion, CheckRunSummary, CheckStatus, StatusState, StatusSummary},
+    },
+    policy::{self, Subject},
+    service::AppState,
+    webhook::{Repository, WebhookPayload},
+};
+
+pub async fn on_checks_completed(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.automerge;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+    let (head_sha, pull_requests) = match (&payload.check_suite, &payload.workflow_run) {
+        (Some(suite), _) => (&suite.head_sha, &suite.pull_requests),
+        (None, Some(run)) => (&run.head_sha, &run.pull_requests),
+        (None, None) => return Ok(()),
+    };
+    if pull_requests.is_empty() {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Auto-merge is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    for pr in pull_requests {
+        try_merge(state, config, &token, repo, pr.number, head_sha)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Auto-merge failed for {}#{}: {}",
+                    repo.full_name, pr.number, e
+                );
+                NexusError::from(e)
+            })?;
+    }
+
+    Ok(())
+}
+
+async fn try_merge(
+    state: &AppState,
+    config: &AutomergeConfig,
+    token: &str,
+    repo: &Repository,
+    number: u64,
+    head_sha: &str,
+) -> Result<(), github::GithubError> {
+    let client = &state.github;
+    let pr = github::get_pull_request(client, token, &repo.full_name, number).await?;
+
+    if pr.state != "open" || pr.draft || pr.merged == Some(true) {
+        return Ok(());
+    }
+    if !pr.labels.iter().any(|l| l.name == config.label) {
+        debug!(
+            "{}#{} has no {} label",
+            repo.full_name, number, config.label
+        );
+        return Ok(());
+    }
+    // a newer commit was pushed after this check suite started
+    if pr.head.as_ref().map(|h| h.sha.as_str()) != Some(head_sha) {
+        debug!("{}#{} head moved past {}", repo.full_name, number, head_sha);
+        return Ok(());
+    }
+
+    if let Some(reason) = checks_blocker(state, config, token, repo, head_sha).await? {
+        info!("Not merging {}#{}: {}", repo.full_name, number, reason);
+        return Ok(());
+    }
+    if let Some(reason) = reviews_blocker(state, config, token, repo, number).await? {
+        info!("Not merging {}#{}: {}", repo.full_name, number, reason);
+        return Ok(());
+    }
+
+    let subject = Subject::pull_request(&repo.full_name, &pr);
+    if !policy::allows(state, token, &[PolicyAction::Merge], &subject).await {
+        return Ok(());
+    }
+
+    github::merge_pull_request(
+        client,
+        token,
+        &repo.full_name,
+        number,
+        head_sha,
+        config.merge_method,
+    )
+    .await?;
+
+    info!(
+        "Auto-merged {}#{} ({:?})",
+        repo.full_name, number, config.merge_method
+    );
+    Ok(())
+}
+
+async fn checks_blocker(
+    state: &AppState,
+    config: &AutomergeConfig,
+    token: &str,
+    repo: &Repository,
+    sha: &str,
+) -> Result<Option<String>, github::GithubError> {
+    let client = &state.github;
+    let runs = checks::list_check_runs(client, token, &repo.full_name, sha).await?;
+    let combined = checks::get_combined_status(client, token, &repo.full_name, sha).await?;
+    Ok(checks_outcome(config, &runs, &combined.statuses))
+}
+
+fn checks_outcome(
+    config: &AutomergeConfig,
+    runs: &[CheckRunSummary],
+    statuses: &[StatusSummary],
+) -> Option<String> {
+    let mut results: HashMap<&str, bool> = HashMap::new();
+    for run in runs {
+        let green = run.status == CheckStatus::Completed
+            && matches!(
+                run.conclusion,
+                Some(
+                    CheckConclusion::Success | CheckConclusion::Neutral | CheckConclusion::Skipped
+                )
+            );
+        results.insert(&run.name, green);
+    }
+    for status in statuses {
+        results.insert(&status.context, status.state == StatusState::Success);
+    }
+
+    if config.required_checks.is_empty() {
+        // checks may not have been created yet when the suite that triggered this completes
+        if results.is_empty() && config.require_checks {
+            return Some("no checks have reported".to_string());
+        }
+        if let Some((name, _)) = results.iter().find(|(_, green)| !**green) {
+            return Some(format!("check {} is not green", name));
+        }
+    } else {
+        for required in &config.required_checks {
+            match results.get(required.as_str()) {
+                Some(true) => {}
+                Some(false) => return Some(format!("check {} is not green", required)),
+                None => return Some(format!("check {} has not reported", required)),
+            }
+        }
+    }
+
+    None
+}
+
+async fn reviews_blocker(
+    state: &AppState,
+    config: &AutomergeConfig,
+    token: &str,
+    repo: &Repository,
+    number: u64,
+) -> Result<Option<String>, github::GithubError> {
+    let reviews = github::list_reviews(&state.github, token, &repo.full_name, number).await?;
+
+    // only each reviewer's latest decisive review counts
+    let mut latest: HashMap<&str, &str> = HashMap::new();
+    for review in &reviews {
+        let Some(user) = &review.user else { continue };
+        if matches!(
+            review.state.as_str(),
+            "APPROVED" | "CHANGES_REQUESTED" | "DISMISSED"
+        ) {
+            latest.insert(&user.login, &review.state);
+        }
+    }
+
+    if latest.values().any(|s| *s == "CHANGES_REQUESTED") {
+        return Ok(Some("changes were requested".to_string()));
+    }
+
+    let approvals = latest.values().filter(|s| **s == "APPROVED").count();
+    if approvals < config.required_approvals {
+        return Ok(Some(format!(
+            "{} of {} required approvals",
+            approvals, config.required_approvals
+        )));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::github::{MockGithub, RecordedRequest};
+    use http::{Method, StatusCode};
+    use serde_json::{Value, json};
+
+    fn run(
+        name: &str,
+        status: CheckStatus,
+        conclusion: Option<CheckConclusion>,
+    ) -> CheckRunSummary {
+        CheckRunSummary {
+            id: 1,
+            name: name.to_string(),
+            status,
+            conclusion,
+        }
+    }
+
+    fn status(context: &str, state: StatusState) -> StatusSummary {
+        StatusSummary {
+            context: context.to_string(),
+            state,
+        }
+    }
+
+    #[test]
+    fn nothing_reported_is_pending() {
+        let config = AutomergeConfig::default();
+        assert_eq!(
+            checks_outcome(&config, &[], &[]).as_deref(),
+            Some("no checks have reported")
+        );
+    }
+
+    #[test]
+    fn nothing_reported_merges_when_checks_are_not_required() {
+        let config = AutomergeConfig {
+            require_checks: false,
+            ..Default::default()
+        };
+        assert_eq!(checks_outcome(&config, &[], &[]), None);
+    }
+
+    #[test]
+    fn every_check_must_be_green() {
+        let config = AutomergeConfig::default();
+        let runs = [
+            run(
+                "build",
+                CheckStatus::Completed,
+                Some(CheckConclusion::Success),
+            ),
+            run(
+                "lint",
+                CheckStatus::Completed,
+                Some(CheckConclusion::Skipped),
+            ),
+        ];
+        assert_eq!(checks_outcome(&config, &runs, &[]), None);
+
+        let statuses = [status("ci/deploy", StatusState::Pending)];
+        assert_eq!(
+            checks_outcome(&config, &runs, &statuses).as_deref(),
+            Some("check ci/deploy is not green")
+        );
+    }
+
+    #[test]
+    fn required_checks_must_report_and_pass() {
+        let config = AutomergeConfig {
+            required_checks: vec!["build".to_string(), "test".to_string()],
+            ..Default::default()
+        };
+        let mut runs = vec![
+            run(
+                "build",
+                CheckStatus::Completed,
+                Some(CheckConclusion::Success),
+            ),
+            run(
+                "flaky",
+                CheckStatus::Completed,
+                Some(CheckConclusion::Failure),
+            ),
+        ];
+        assert_eq!(
+            checks_outcome(&config, &runs, &[]).as_deref(),
+            Some("check test has not reported")
+        );
+
+        runs.push(run("test", CheckStatus::InProgress, None));
+        assert_eq!(
+            checks_outcome(&config, &runs, &[]).as_deref(),
+            Some("check test is not green")
+        );
+
+        runs.pop();
+        let statuses = [status("test", StatusState::Success)];
+        assert_eq!(checks_outcome(&config, &runs, &statuses), None);
+    }
+
+    // pages of `per_page=100` out of `items`, as GitHub serves them
+    fn page(request: &RecordedRequest, items: &[Value]) -> Vec<Value> {
+        let page: usize = request
+            .query
+            .as_deref()
+            .and_then(|q| q.split('&').find_map(|p| p.strip_prefix("page=")))
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(1);
+        items
+            .iter()
+            .skip((page - 1) * 100)
+            .take(100)
+            .cloned()
+            .collect()
+    }
+
+    fn repository() -> Repository {
+        serde_json::from_value(json!({
+            "name": "example",
+            "full_name": "octo-org/example",
+            "html_url": "https://github.com/octo-org/example"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn changes_requested_after_the_first_page_block_the_merge() {
+        let github = MockGithub::start().await.unwrap();
+        let mut reviews: Vec<Value> = (0..100)
+            .map(|i| {
+                json!({
+                    "user": { "login": format!("r{}", i), "html_url": "https://github.com/r" },
+                    "state": "APPROVED"
+                })
+            })
+            .collect();
+        reviews.push(json!({
+            "user": { "login": "r0", "html_url": "https://github.com/r" },
+            "state": "CHANGES_REQUESTED"
+        }));
+        github.respond_with(Method::GET, "/repos/*/*/pulls/*/reviews", move |r| {
+            (StatusCode::OK, Value::Array(page(r, &reviews)))
+        });
+        let state = AppState::new(None).with_github(github.client());
+
+        let blocker = reviews_blocker(
+            &state,
+            &AutomergeConfig::default(),
+            "token",
+            &repository(),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(blocker.as_deref(), Some("changes were requested"));
+        assert_eq!(
+            github
+                .requests_to(Method::GET, "/repos/*/*/pulls/*/reviews")
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_runs_after_the_first_page_block_the_merge() {
+        let github = MockGithub::start().await.unwrap();
+        let mut runs: Vec<Value> = (0..100)
+            .map(|i| {
+                json!({
+                    "id": i,
+                    "name": format!("check {}", i),
+                    "status": "completed",
+                    "conclusion": "success"
+                })
+            })
+            .collect();
+        runs.push(json!({
+            "id": 100,
+            "name": "flaky",
+            "status": "completed",
+            "conclusion": "failure"
+        }));
+        github.respond_with(Method::GET, "/repos/*/*/commits/*/check-runs", move |r| {
+            let body = json!({ "total_count": runs.len(), "check_runs": page(r, &runs) });
+            (StatusCode::OK, body)
+        });
+        github.respond(
+            Method::GET,
+            "/repos/*/*/commits/*/status",
+            StatusCode::OK,
+            json!({ "state": "success", "statuses": [] }),
+        );
+        let state = AppState::new(None).with_github(github.client());
+
+        let blocker = checks_blocker(
+            &state,
+            &AutomergeConfig::default(),
+            "token",
+            &repository(),
+            "abc",
+        )
+        .await
+        .unwrap();
+        assert_eq!(blocker.as_deref(), Some("check flaky is not green"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::PolicyAction,
+    error::NexusError,
+    github::{
+        self, GithubError, RepoCommit, contents,
+        git::{self, Picked},
+    },
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    template,
+    webhook::{PullRequest, Repository, WebhookPayload},
+};
+
+// a merge brings labels added earlier; a label added afterwards backports right away
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.backport;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if pr.merged != Some(true)
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let labels: Vec<&str> = match action.as_str() {
+        "closed" => pr.labels.iter().map(|l| l.name.as_str()).collect(),
+        "labeled" => payload.label.iter().map(|l| l.name.as_str()).collect(),
+        _ => return Ok(()),
+    };
+    let branches: Vec<&str> = labels
+        .into_iter()
+        .filter_map(|name| name.strip_prefix(config.label_prefix.as_str()))
+        .map(str::trim)
+        .filter(|branch| !branch.is_empty())
+        .collect();
+    if branches.is_empty() {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Backports are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let commits: Vec<RepoCommit> =
+        github::list_pull_request_commits(&state.github, &token, &repo.full_name, pr.number)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to list the commits of {}#{}: {}",
+                    repo.full_name, pr.number, e
+                );
+                NexusError::from(e)
+            })?
+            .into_iter()
+            // merges from the base branch are not part of the change
+            .filter(|c| c.parents.len() <= 1)
+            .collect();
+
+    for branch in branches {
+        backport(state, &token, repo, pr, &commits, branch)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to backport {}#{} to {}: {}",
+                    repo.full_name, pr.number, branch, e
+                );
+                NexusError::from(e)
+            })?;
+    }
+    Ok(())
+}
+
+async fn backport(
+    state: &AppState,
+    token: &str,
+    repo: &Repository,
+    pr: &PullRequest,
+    commits: &[RepoCommit],
+    branch: &str,
+) -> Result<(), GithubError> {
+    let config = &state.config.backport;
+    let client = &state.github;
+    let mut vars = HashMap::from([
+        ("number", pr.number.to_string()),
+        ("title", pr.title.clone()),
+        ("branch", branch.to_string()),
+        ("url", pr.html_url.clone()),
+        ("author", pr.user.login.clone()),
+    ]);
+    let backport_branch = template::render(&config.branch_template, &vars);
+    vars.insert("backport_branch", backport_branch.clone());
+
+    let base = match contents::get_branch_sha(client, token, &repo.full_name, branch).await {
+        Ok(sha) => sha,
+        Err(e) if e.is_not_found() => {
+            let body = format!(
+                "Cannot backport to `{}`: the branch does not exist.",
+                branch
+            );
+            return comment(state, token, repo, pr, &body).await;
+        }
+        Err(e) => return Err(e),
+    };
+    match contents::create_branch(client, token, &repo.full_name, &backport_branch, &base).await {
+        Ok(()) => {}
+        // a redelivery, or the label was added twice
+        Err(GithubError::Api { status, .. }) if status.as_u16() == 422 => {
+            info!(
+                "{} already exists in {}, not backporting #{} again",
+                backport_branch, repo.full_name, pr.number
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+
+    let mut head = base.clone();
+    for commit in commits {
+        match git::cherry_pick(
+            client,
+            token,
+            &repo.full_name,
+            &backport_branch,
+            &head,
+            &commit.sha,
+        )
+        .await?
+        {
+            Picked::Applied(sha) => head = sha,
+            Picked::Empty => {}
+            Picked::Conflict => {
+                warn!(
+                    "Backporting {}#{} to {} conflicts at {}",
+                    repo.full_name, pr.number, branch, commit.sha
+                );
+                contents::delete_branch(client, token, &repo.full_name, &backport_branch).await?;
+                let shas: Vec<&str> = commits.iter().map(|c| c.sha.as_str()).collect();
+                vars.insert("commits", shas.join(" "));
+                let body = template::render(&config.conflict_template, &vars);
+                return comment(state, token, repo, pr, &body).await;
+            }
+        }
+    }
+    if head == base {
+        contents::delete_branch(client, token, &repo.full_name, &backport_branch).await?;
+        info!(
+            "Nothing to backport from {}#{} to {}",
+            repo.full_name, pr.number, branch
+        );
+        return Ok(());
+    }
+
+    let backport = github::create_pull_request(
+        client,
+        token,
+        &repo.full_name,
+        &backport_branch,
+        branch,
+        &template::render(&config.title_template, &vars),
+        &template::render(&config.body_template, &vars),
+    )
+    .await?;
+    info!(
+        "Opened {}#{} backporting #{} to {}",
+        repo.full_name, backport.number, pr.number, branch
+    );
+    let body = format!("Backported to `{}` in #{}.", branch, backport.number);
+    comment(state, token, repo, pr, &body).await
+}
+
+async fn comment(
+    state: &AppState,
+    token: &str,
+    repo: &Repository,
+    pr: &PullRequest,
+    body: &str,
+) -> Result<(), GithubError> {
+    let subject = Subject::pull_request(&repo.full_name, pr);
+    if !policy::allows(state, token, &[PolicyAction::Comment], &subject).await {
+        return Ok(());
+    }
+    github::create_issue_comment(&state.github, token, &repo.full_name, pr.number, body).await
+}
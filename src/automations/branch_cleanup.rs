@@ -0,0 +1,166 @@
+use globset::Glob;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::PolicyAction,
+    error::NexusError,
+    github::{self, GithubError, contents},
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+pub async fn on_pull_request_closed(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.branch_cleanup;
+    if !config.enabled || payload.action.as_deref() != Some("closed") {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo)) = (&payload.pull_request, &payload.repository) else {
+        return Ok(());
+    };
+    if pr.merged != Some(true)
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let Some(head) = &pr.head else {
+        return Ok(());
+    };
+    let branch = head.ref_name.as_str();
+
+    // a fork's branch is not ours to delete
+    let same_repo = head
+        .repo
+        .as_ref()
+        .is_some_and(|r| r.full_name.eq_ignore_ascii_case(&repo.full_name));
+    if !same_repo || repo.default_branch.as_deref() == Some(branch) {
+        return Ok(());
+    }
+    for pattern in &config.keep {
+        match Glob::new(pattern) {
+            Ok(glob) if glob.compile_matcher().is_match(branch) => {
+                debug!("Keeping {} in {}", branch, repo.full_name);
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Invalid branch_cleanup keep pattern {:?}: {}", pattern, e),
+        }
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Branch cleanup is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!("Failed to delete {} in {}: {}", branch, repo.full_name, e);
+        NexusError::from(e)
+    };
+
+    match contents::get_branch(client, &token, &repo.full_name, branch)
+        .await
+        .map_err(api_error)?
+    {
+        None => return Ok(()),
+        Some(b) if b.protected => {
+            debug!("Keeping protected branch {} in {}", branch, repo.full_name);
+            return Ok(());
+        }
+        // pushed to after the merge, so the new commits would be lost
+        Some(b) if b.commit.sha != head.sha => {
+            info!(
+                "Keeping {} in {}: it has moved on since #{} was merged",
+                branch, repo.full_name, pr.number
+            );
+            return Ok(());
+        }
+        Some(_) => {}
+    }
+    // deleting the base of an open pull request would close it
+    let dependents = github::list_pull_requests_into(client, &token, &repo.full_name, branch)
+        .await
+        .map_err(api_error)?;
+    if !dependents.is_empty() {
+        info!(
+            "Keeping {} in {}: {} open pull requests merge into it",
+            branch,
+            repo.full_name,
+            dependents.len()
+        );
+        return Ok(());
+    }
+
+    let subject = Subject::pull_request(&repo.full_name, pr);
+    if !policy::allows(state, &token, &[PolicyAction::DeleteBranch], &subject).await {
+        return Ok(());
+    }
+    contents::delete_branch(client, &token, &repo.full_name, branch)
+        .await
+        .map_err(api_error)?;
+    info!(
+        "Deleted {} in {} after #{} was merged",
+        branch, repo.full_name, pr.number
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        testing::{Event, PullRequestEventBuilder, github::MockGithub},
+    };
+    use http::{Method, StatusCode};
+    use serde_json::json;
+
+    async fn cleanup(tip: impl Fn(&str) -> String) -> usize {
+        let github = MockGithub::start().await.unwrap();
+        let payload = PullRequestEventBuilder::merged().head("feature").payload();
+        let head = payload
+            .pull_request
+            .as_ref()
+            .unwrap()
+            .head
+            .as_ref()
+            .unwrap();
+        github.respond(
+            Method::GET,
+            "/repos/*/*/branches/*",
+            StatusCode::OK,
+            json!({ "name": "feature", "protected": false, "commit": { "sha": tip(&head.sha) } }),
+        );
+        github.respond(Method::GET, "/repos/*/*/pulls", StatusCode::OK, json!([]));
+        github.respond(
+            Method::DELETE,
+            "/repos/*/*/git/refs/heads/*",
+            StatusCode::NO_CONTENT,
+            json!(null),
+        );
+        let mut config = Config::default();
+        config.branch_cleanup.enabled = true;
+        config.github.token = Some("token".to_string());
+        let state = AppState::new(None)
+            .with_config(config)
+            .with_github(github.client());
+
+        on_pull_request_closed(&state, &payload).await.unwrap();
+        github
+            .requests_to(Method::DELETE, "/repos/*/*/git/refs/heads/*")
+            .len()
+    }
+
+    #[tokio::test]
+    async fn deletes_the_merged_head() {
+        assert_eq!(cleanup(|merged| merged.to_string()).await, 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_a_branch_pushed_to_after_the_merge() {
+        assert_eq!(cleanup(|_| "0".repeat(40)).await, 0);
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use tracing::{debug, error, info};
+
+use crate::{
+    config::{ChangelogConfig, ChangelogMode},
+    conventional,
+    error::NexusError,
+    github::{
+        self, GithubError,
+        contents::{self, FileUpdate},
+        releases,
+    },
+    service::AppState,
+    template,
+    webhook::{Issue, WebhookPayload},
+};
+
+pub async fn on_release_published(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.changelog;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(release), Some(repo)) = (&payload.release, &payload.repository) else {
+        return Ok(());
+    };
+    if release.prerelease && !config.include_prereleases {
+        debug!(
+            "Not adding prerelease {} to the changelog",
+            release.tag_name
+        );
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("The changelog is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to update the changelog for {} in {}: {}",
+            release.tag_name, repo.full_name, e
+        );
+        NexusError::from(e)
+    };
+
+    let previous = releases::list_releases(&state.github, &token, &repo.full_name)
+        .await
+        .map_err(api_error)?
+        .into_iter()
+        .filter(|r| {
+            !r.draft
+                && (config.include_prereleases || !r.prerelease)
+                && r.tag_name != release.tag_name
+                && r.created_at < release.created_at
+        })
+        .max_by_key(|r| r.created_at);
+    let pull_requests = releases::list_merged_pull_requests(
+        &state.github,
+        &token,
+        &repo.full_name,
+        previous.as_ref().map(|r| r.created_at),
+        release.created_at,
+    )
+    .await
+    .map_err(api_error)?;
+
+    let vars = HashMap::from([
+        ("tag", release.tag_name.clone()),
+        ("url", release.html_url.clone()),
+        ("date", Utc::now().format("%Y-%m-%d").to_string()),
+        ("repo", repo.full_name.clone()),
+    ]);
+    let section = format!(
+        "{}\n\n{}",
+        template::render(&config.heading_template, &vars),
+        render_changes(config, &pull_requests)
+    );
+
+    let base = config
+        .branch
+        .clone()
+        .or_else(|| repo.default_branch.clone())
+        .unwrap_or_else(|| "main".to_string());
+    let title = template::render(&config.title, &vars);
+    let github = &state.github;
+    let (branch, created) = match config.mode {
+        ChangelogMode::Commit => (base.clone(), false),
+        ChangelogMode::PullRequest => {
+            let branch = template::render(&config.pr_branch, &vars);
+            let sha = contents::get_branch_sha(github, &token, &repo.full_name, &base)
+                .await
+                .map_err(api_error)?;
+            // a redelivery finds the branch from the first attempt
+            match contents::create_branch(github, &token, &repo.full_name, &branch, &sha).await {
+                Ok(()) => (branch, true),
+                Err(GithubError::Api { status, .. }) if status.as_u16() == 422 => (branch, false),
+                Err(e) => return Err(api_error(e)),
+            }
+        }
+    };
+
+    let file = contents::get_file(github, &token, &repo.full_name, &config.path, &branch)
+        .await
+        .map_err(api_error)?;
+    let current = file
+        .as_ref()
+        .map(|f| f.content.as_str())
+        .unwrap_or_default();
+    if has_release(current, &release.tag_name) {
+        info!(
+            "{} in {} already lists {}",
+            config.path, repo.full_name, release.tag_name
+        );
+        return Ok(());
+    }
+    let update = FileUpdate {
+        branch: &branch,
+        message: &title,
+        content: &insert(current, &section),
+        sha: file.as_ref().map(|f| f.sha.as_str()),
+    };
+    contents::put_file(github, &token, &repo.full_name, &config.path, &update)
+        .await
+        .map_err(api_error)?;
+
+    if config.mode == ChangelogMode::Commit {
+        info!(
+            "Added {} to {} in {}",
+            release.tag_name, config.path, repo.full_name
+        );
+        return Ok(());
+    }
+    if created {
+        let body = format!(
+            "Adds {} to `{}`.\n\n{}",
+            release.tag_name, config.path, section
+        );
+        let pr = github::create_pull_request(
+            github,
+            &token,
+            &repo.full_name,
+            &branch,
+            &base,
+            &title,
+            &body,
+        )
+        .await
+        .map_err(api_error)?;
+        info!(
+            "Opened #{} adding {} to {} in {}",
+            pr.number, release.tag_name, config.path, repo.full_name
+        );
+    }
+    Ok(())
+}
+
+fn render_changes(config: &ChangelogConfig, pull_requests: &[Issue]) -> String {
+    let mut breaking = Vec::new();
+    let mut sections: Vec<(&str, Vec<String>)> = config
+        .sections
+        .iter()
+        .map(|s| (s.title.as_str(), Vec::new()))
+        .collect();
+    let mut other = Vec::new();
+
+    for pr in pull_requests {
+        if config.exclude_labels.iter().any(|l| pr.has_label(l)) {
+            continue;
+        }
+        let change = conventional::parse(&pr.title);
+        let kind = change.as_ref().map(|c| c.kind.as_str()).unwrap_or_default();
+        let is_breaking = change.as_ref().is_some_and(|c| c.breaking);
+        let scope = change.as_ref().and_then(|c| c.scope);
+        let description = change.as_ref().map_or(pr.title.as_str(), |c| c.description);
+        if !is_breaking && config.exclude_types.iter().any(|t| t == kind) {
+            continue;
+        }
+
+        let vars = HashMap::from([
+            ("title", pr.title.clone()),
+            ("description", description.to_string()),
+            (
+                "scope",
+                scope.map(|s| format!("**{}:** ", s)).unwrap_or_default(),
+            ),
+            ("type", kind.to_string()),
+            ("number", pr.number.to_string()),
+            ("author", pr.user.login.clone()),
+            ("url", pr.html_url.clone()),
+        ]);
+        let line = template::render(&config.item_template, &vars);
+
+        if is_breaking {
+            breaking.push(line);
+            continue;
+        }
+        match config
+            .sections
+            .iter()
+            .position(|s| s.types.iter().any(|t| t == kind))
+        {
+            Some(index) => sections[index].1.push(line),
+            None => other.push(line),
+        }
+    }
+    sections.insert(0, (&config.breaking_title, breaking));
+    sections.push((&config.other_title, other));
+
+    let mut output = String::new();
+    for (title, lines) in sections.iter().filter(|(_, lines)| !lines.is_empty()) {
+        output.push_str(&format!("### {}\n\n{}\n\n", title, lines.join("\n")));
+    }
+    if output.is_empty() {
+        output.push_str("No notable changes.\n\n");
+    }
+    output
+}
+
+// a heading naming the tag, so a redelivered release is not added twice
+fn has_release(changelog: &str, tag: &str) -> bool {
+    changelog.lines().any(|line| {
+        line.starts_with('#')
+            && line
+                .split(|c: char| c.is_whitespace() || "[]()".contains(c))
+                .any(|word| word == tag)
+    })
+}
+
+// above the newest release, below the title and any introduction
+fn insert(changelog: &str, section: &str) -> String {
+    if changelog.trim().is_empty() {
+        return format!("# Changelog\n\n{}", section);
+    }
+    let position = changelog
+        .match_indices("\n## ")
+        .next()
+        .map(|(i, _)| i + 1)
+        .or_else(|| changelog.starts_with("## ").then_some(0));
+    match position {
+        Some(i) => format!("{}{}{}", &changelog[..i], section, &changelog[i..]),
+        None => format!("{}\n\n{}", changelog.trim_end(), section.trim_end()) + "\n",
+    }
+}
@@ -0,0 +1,414 @@
+use std::{collections::HashMap, fmt, future::Future, pin::Pin};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{Permission, PolicyAction},
+    error::NexusError,
+    github::{self, GithubError, actions},
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::{Issue, WebhookPayload},
+};
+
+/// A `/command` found in a comment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Invocation {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// What a command handler gets to work with.
+pub struct Context<'a> {
+    pub state: &'a AppState,
+    pub token: &'a str,
+    pub repo: &'a str,
+    pub issue: &'a Issue,
+    // whoever wrote the comment
+    pub sender: &'a str,
+    pub args: &'a [String],
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    // shown to the commenter as a reply
+    Usage(String),
+    Github(GithubError),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Usage(message) => f.write_str(message),
+            CommandError::Github(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<GithubError> for CommandError {
+    fn from(e: GithubError) -> Self {
+        CommandError::Github(e)
+    }
+}
+
+pub type CommandFuture<'a> = Pin<Box<dyn Future<Output = Result<(), CommandError>> + Send + 'a>>;
+pub type Handler = for<'a> fn(&'a Context<'a>) -> CommandFuture<'a>;
+
+struct Command {
+    permission: Permission,
+    handler: Handler,
+}
+
+/// Command names and the handlers they dispatch to.
+#[derive(Default)]
+pub struct Registry {
+    commands: HashMap<String, Command>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `permission` is the least access the commenter needs; the config can raise or lower it
+    pub fn add(&mut self, name: impl Into<String>, permission: Permission, handler: Handler) {
+        self.commands.insert(
+            name.into(),
+            Command {
+                permission,
+                handler,
+            },
+        );
+    }
+
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.add("label", Permission::Triage, label);
+        registry.add("unlabel", Permission::Triage, unlabel);
+        registry.add("assign", Permission::Triage, assign);
+        registry.add("retest", Permission::Write, retest);
+        registry
+    }
+}
+
+pub async fn on_issue_comment(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.chatops;
+    if !config.enabled || payload.action.as_deref() != Some("created") {
+        return Ok(());
+    }
+    let (Some(issue), Some(comment), Some(repo)) =
+        (&payload.issue, &payload.comment, &payload.repository)
+    else {
+        return Ok(());
+    };
+    if (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+        || comment.user.login.ends_with("[bot]")
+    {
+        return Ok(());
+    }
+    let registry = Registry::builtin();
+    let invocations: Vec<_> = parse(&comment.body)
+        .into_iter()
+        .filter(|i| registry.commands.contains_key(&i.name) && !config.disabled.contains(&i.name))
+        .collect();
+    if invocations.is_empty() {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("ChatOps is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let sender = comment.user.login.as_str();
+    let role = github::collaborator_permission(client, &token, &repo.full_name, sender)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to look up the permission of {} in {}: {}",
+                sender, repo.full_name, e
+            );
+            NexusError::from(e)
+        })?;
+    let granted = permission(&role);
+
+    // commands are not idempotent, so a failure is reported rather than retried
+    for invocation in &invocations {
+        let command = &registry.commands[&invocation.name];
+        let needed = config
+            .permissions
+            .get(&invocation.name)
+            .copied()
+            .unwrap_or(command.permission);
+        let result = if granted < needed {
+            info!(
+                "{} may not run /{} in {}: has {} access",
+                sender, invocation.name, repo.full_name, role
+            );
+            Err(CommandError::Usage(format!(
+                "@{} `/{}` needs {} access to this repository.",
+                sender,
+                invocation.name,
+                role_name(needed)
+            )))
+        } else {
+            let context = Context {
+                state,
+                token: &token,
+                repo: &repo.full_name,
+                issue,
+                sender,
+                args: &invocation.args,
+            };
+            (command.handler)(&context).await
+        };
+
+        let reaction = match &result {
+            Ok(()) => {
+                info!(
+                    "Ran /{} from {} on {}#{}",
+                    invocation.name, sender, repo.full_name, issue.number
+                );
+                "+1"
+            }
+            Err(CommandError::Usage(message)) => {
+                if let Err(e) = github::create_issue_comment(
+                    client,
+                    &token,
+                    &repo.full_name,
+                    issue.number,
+                    message,
+                )
+                .await
+                {
+                    warn!("Failed to reply to /{}: {}", invocation.name, e);
+                }
+                "-1"
+            }
+            Err(CommandError::Github(e)) => {
+                error!(
+                    "/{} on {}#{} failed: {}",
+                    invocation.name, repo.full_name, issue.number, e
+                );
+                "confused"
+            }
+        };
+        if config.reactions
+            && let Err(e) =
+                github::add_comment_reaction(client, &token, &repo.full_name, comment.id, reaction)
+                    .await
+        {
+            debug!("Failed to react to comment {}: {}", comment.id, e);
+        }
+    }
+    Ok(())
+}
+
+// a line starting with `/name`; quoted text and fenced code are not commands
+pub fn parse(body: &str) -> Vec<Invocation> {
+    let mut invocations = Vec::new();
+    let mut fenced = false;
+    for line in body.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            fenced = !fenced;
+            continue;
+        }
+        let Some(rest) = line.strip_prefix('/') else {
+            continue;
+        };
+        if fenced {
+            continue;
+        }
+        let mut words = split(rest).into_iter();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            continue;
+        }
+        invocations.push(Invocation {
+            name: name.to_lowercase(),
+            args: words.collect(),
+        });
+    }
+    invocations
+}
+
+// whitespace-separated words; double quotes keep spaces, e.g. `/label "good first issue"`
+fn split(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        words.push(word);
+    }
+    words
+}
+
+fn permission(role: &str) -> Permission {
+    match role {
+        "admin" => Permission::Admin,
+        "maintain" => Permission::Maintain,
+        "write" => Permission::Write,
+        "triage" => Permission::Triage,
+        "read" => Permission::Read,
+        _ => Permission::None,
+    }
+}
+
+fn role_name(permission: Permission) -> &'static str {
+    match permission {
+        Permission::Admin => "admin",
+        Permission::Maintain => "maintain",
+        Permission::Write => "write",
+        Permission::Triage => "triage",
+        Permission::Read => "read",
+        Permission::None => "no",
+    }
+}
+
+async fn allowed(context: &Context<'_>, action: PolicyAction) -> Result<(), CommandError> {
+    let subject = Subject::issue(context.repo, context.issue);
+    if policy::allows(context.state, context.token, &[action], &subject).await {
+        Ok(())
+    } else {
+        Err(CommandError::Usage(format!(
+            "`{}` is not allowed here by policy.",
+            action
+        )))
+    }
+}
+
+fn label<'a>(context: &'a Context<'a>) -> CommandFuture<'a> {
+    Box::pin(async move {
+        if context.args.is_empty() {
+            return Err(CommandError::Usage(
+                "Usage: `/label <name> [<name>...]`".to_string(),
+            ));
+        }
+        allowed(context, PolicyAction::Label).await?;
+        github::add_labels(
+            &context.state.github,
+            context.token,
+            context.repo,
+            context.issue.number,
+            context.args,
+        )
+        .await?;
+        Ok(())
+    })
+}
+
+fn unlabel<'a>(context: &'a Context<'a>) -> CommandFuture<'a> {
+    Box::pin(async move {
+        if context.args.is_empty() {
+            return Err(CommandError::Usage(
+                "Usage: `/unlabel <name> [<name>...]`".to_string(),
+            ));
+        }
+        allowed(context, PolicyAction::Unlabel).await?;
+        for name in context.args {
+            github::remove_label(
+                &context.state.github,
+                context.token,
+                context.repo,
+                context.issue.number,
+                name,
+            )
+            .await?;
+        }
+        Ok(())
+    })
+}
+
+// `@me` is the commenter; with no arguments as well
+fn assign<'a>(context: &'a Context<'a>) -> CommandFuture<'a> {
+    Box::pin(async move {
+        let mut assignees: Vec<String> = context
+            .args
+            .iter()
+            .map(|arg| match arg.trim_start_matches('@') {
+                "me" => context.sender.to_string(),
+                login => login.to_string(),
+            })
+            .collect();
+        if assignees.is_empty() {
+            assignees.push(context.sender.to_string());
+        }
+        allowed(context, PolicyAction::Assign).await?;
+        github::add_assignees(
+            &context.state.github,
+            context.token,
+            context.repo,
+            context.issue.number,
+            &assignees,
+        )
+        .await?;
+        Ok(())
+    })
+}
+
+// re-runs the failed jobs of every failed workflow run on the pull request's head
+fn retest<'a>(context: &'a Context<'a>) -> CommandFuture<'a> {
+    Box::pin(async move {
+        if !context.issue.is_pull_request() {
+            return Err(CommandError::Usage(
+                "`/retest` only works on pull requests.".to_string(),
+            ));
+        }
+        let client = &context.state.github;
+        let pr =
+            github::get_pull_request(client, context.token, context.repo, context.issue.number)
+                .await?;
+        let Some(head) = &pr.head else {
+            return Ok(());
+        };
+        let runs =
+            actions::list_runs_for_commit(client, context.token, context.repo, &head.sha).await?;
+        let failed: Vec<_> = runs
+            .iter()
+            .filter(|run| {
+                matches!(
+                    run.conclusion.as_deref(),
+                    Some("failure" | "timed_out" | "cancelled")
+                )
+            })
+            .collect();
+        if failed.is_empty() {
+            return Err(CommandError::Usage(format!(
+                "No failed workflow runs on {} to re-run.",
+                &head.sha[..head.sha.len().min(7)]
+            )));
+        }
+        for run in failed {
+            actions::rerun_failed_jobs(client, context.token, context.repo, run.id).await?;
+        }
+        Ok(())
+    })
+}
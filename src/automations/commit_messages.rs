@@ -0,0 +1,301 @@
+use globset::Glob;
+use regex::RegexSet;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{CommitMessagesConfig, PolicyAction},
+    conventional,
+    error::NexusError,
+    github::{
+        self, GithubError, RepoCommit,
+        checks::{self, Verdict},
+    },
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::{PullRequest, Repository, WebhookPayload},
+};
+
+const ACTIONS: &[&str] = &["opened", "reopened", "synchronize"];
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+// finds the comment to edit on later pushes
+const MARKER: &str = "<!-- nexus:commit-messages -->";
+
+pub async fn on_push(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.commit_messages;
+    if !config.enabled || payload.deleted {
+        return Ok(());
+    }
+    let (Some(repo), Some(git_ref), Some(after)) =
+        (&payload.repository, &payload.ref_name, &payload.after)
+    else {
+        return Ok(());
+    };
+    let Some(branch) = git_ref.strip_prefix("refs/heads/") else {
+        return Ok(());
+    };
+    if (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+        || !watched(config, repo, branch)
+    {
+        return Ok(());
+    }
+    let Some(ignore) = ignore_set(config) else {
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Commit message checks are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to check commit messages on {} in {}: {}",
+            branch, repo.full_name, e
+        );
+        NexusError::from(e)
+    };
+
+    let base = match payload.before.as_deref() {
+        Some(before) if before != NULL_SHA => before,
+        _ => match repo.default_branch.as_deref() {
+            Some(default) if default != branch => default,
+            _ => return Ok(()),
+        },
+    };
+    let commits = github::compare_commits(client, &token, &repo.full_name, base, after)
+        .await
+        .map_err(api_error)?;
+    let commits: Vec<&RepoCommit> = commits.iter().filter(|c| c.parents.len() <= 1).collect();
+    if commits.is_empty() {
+        return Ok(());
+    }
+    let failures = problems(config, &ignore, &commits);
+
+    checks::report(
+        client,
+        &token,
+        &repo.full_name,
+        after,
+        &config.name,
+        config.report,
+        verdict(config, commits.len(), &failures),
+    )
+    .await
+    .map_err(api_error)?;
+    info!(
+        "Commit messages pushed to {} in {}: {} of {} fail",
+        branch,
+        repo.full_name,
+        failures.len(),
+        commits.len()
+    );
+    Ok(())
+}
+
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.commit_messages;
+    if !config.enabled || !config.pull_requests {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if !ACTIONS.contains(&action.as_str())
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let Some(head) = &pr.head else {
+        return Ok(());
+    };
+    let Some(ignore) = ignore_set(config) else {
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Commit message checks are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to check commit messages of {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    };
+
+    let commits = github::list_pull_request_commits(client, &token, &repo.full_name, pr.number)
+        .await
+        .map_err(api_error)?;
+    let commits: Vec<&RepoCommit> = commits.iter().filter(|c| c.parents.len() <= 1).collect();
+    let failures = problems(config, &ignore, &commits);
+    let verdict = verdict(config, commits.len(), &failures);
+
+    checks::report(
+        client,
+        &token,
+        &repo.full_name,
+        &head.sha,
+        &config.name,
+        config.report,
+        verdict.clone(),
+    )
+    .await
+    .map_err(api_error)?;
+    if config.comment {
+        comment(state, &token, repo, pr, &verdict)
+            .await
+            .map_err(api_error)?;
+    }
+    info!(
+        "Commit messages of {}#{}: {} of {} fail",
+        repo.full_name,
+        pr.number,
+        failures.len(),
+        commits.len()
+    );
+    Ok(())
+}
+
+fn watched(config: &CommitMessagesConfig, repo: &Repository, branch: &str) -> bool {
+    if config.branches.is_empty() {
+        return repo.default_branch.as_deref() == Some(branch);
+    }
+    config
+        .branches
+        .iter()
+        .any(|pattern| match Glob::new(pattern) {
+            Ok(glob) => glob.compile_matcher().is_match(branch),
+            Err(e) => {
+                warn!(
+                    "Invalid commit_messages branch pattern {:?}: {}",
+                    pattern, e
+                );
+                false
+            }
+        })
+}
+
+fn ignore_set(config: &CommitMessagesConfig) -> Option<RegexSet> {
+    RegexSet::new(&config.ignore)
+        .map_err(|e| warn!("Invalid commit_messages ignore pattern: {}", e))
+        .ok()
+}
+
+fn problems<'a>(
+    config: &CommitMessagesConfig,
+    ignore: &RegexSet,
+    commits: &[&'a RepoCommit],
+) -> Vec<(&'a RepoCommit, String)> {
+    commits
+        .iter()
+        .filter(|c| !ignore.is_match(&c.commit.message))
+        .filter_map(|c| {
+            conventional::violation(
+                &c.commit.message,
+                &config.types,
+                &config.scopes,
+                config.require_scope,
+            )
+            .map(|problem| (*c, problem))
+        })
+        .collect()
+}
+
+fn verdict(
+    config: &CommitMessagesConfig,
+    total: usize,
+    failures: &[(&RepoCommit, String)],
+) -> Verdict {
+    if failures.is_empty() {
+        return Verdict {
+            passed: true,
+            title: "Commit messages follow the convention".to_string(),
+            summary: "Every commit message follows Conventional Commits.".to_string(),
+            text: None,
+            details_url: config.details_url.clone(),
+        };
+    }
+    let summary = failures
+        .iter()
+        .map(|(c, problem)| {
+            format!(
+                "- `{}` `{}`: {}",
+                &c.sha[..c.sha.len().min(7)],
+                c.commit.message.lines().next().unwrap_or_default(),
+                problem
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut text = format!(
+        "Commit messages follow [Conventional Commits](https://www.conventionalcommits.org/): \
+         `type(scope): description`, with `!` before the colon for breaking changes.\n\n\
+         Types: {}",
+        config.types.join(", ")
+    );
+    if !config.scopes.is_empty() {
+        text.push_str(&format!("\n\nScopes: {}", config.scopes.join(", ")));
+    }
+    text.push_str(
+        "\n\nReword the commits with `git rebase -i` and `reword`, then force-push, \
+         or squash them into one commit with a conforming message.",
+    );
+    Verdict {
+        passed: false,
+        title: format!(
+            "{} of {} commit messages do not follow the convention",
+            failures.len(),
+            total
+        ),
+        summary,
+        text: Some(text),
+        details_url: config.details_url.clone(),
+    }
+}
+
+// one comment per pull request, edited as commits are pushed
+async fn comment(
+    state: &AppState,
+    token: &str,
+    repo: &Repository,
+    pr: &PullRequest,
+    verdict: &Verdict,
+) -> Result<(), GithubError> {
+    let client = &state.github;
+    let existing = github::list_issue_comments(client, token, &repo.full_name, pr.number)
+        .await?
+        .into_iter()
+        .find(|c| c.body.contains(MARKER));
+    let body = if verdict.passed {
+        if existing.is_none() {
+            return Ok(());
+        }
+        format!("{}\n{}", MARKER, verdict.summary)
+    } else {
+        format!(
+            "{}\n**{}**\n\n{}\n\n{}",
+            MARKER,
+            verdict.title,
+            verdict.summary,
+            verdict.text.as_deref().unwrap_or_default()
+        )
+    };
+    match existing {
+        Some(existing) if existing.body == body => Ok(()),
+        Some(existing) => {
+            github::update_issue_comment(client, token, &repo.full_name, existing.id, &body).await
+        }
+        None => {
+            let subject = Subject::pull_request(&repo.full_name, pr);
+            if !policy::allows(state, token, &[PolicyAction::Comment], &subject).await {
+                return Ok(());
+            }
+            github::create_issue_comment(client, token, &repo.full_name, pr.number, &body).await
+        }
+    }
+}
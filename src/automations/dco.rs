@@ -0,0 +1,189 @@
+use tracing::{error, info};
+
+use crate::{
+    config::DcoConfig,
+    error::NexusError,
+    github::{
+        self, GitPerson, GithubError, RepoCommit,
+        checks::{self, Verdict},
+    },
+    repos,
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+const ACTIONS: &[&str] = &["opened", "reopened", "synchronize"];
+
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.dco;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if !ACTIONS.contains(&action.as_str())
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let Some(head) = &pr.head else {
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("DCO checks are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to check the sign-offs of {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    };
+
+    // merges from the base branch are made by GitHub or by `git merge`, neither signs off
+    let commits: Vec<RepoCommit> =
+        github::list_pull_request_commits(client, &token, &repo.full_name, pr.number)
+            .await
+            .map_err(api_error)?
+            .into_iter()
+            .filter(|c| c.parents.len() <= 1)
+            .collect();
+    let failures: Vec<(&RepoCommit, String)> = commits
+        .iter()
+        .filter(|c| {
+            !c.commit
+                .author
+                .as_ref()
+                .is_some_and(|a| exempt(&config.exempt_authors, &a.email))
+        })
+        .filter_map(|c| check(&c.commit.message, c.commit.author.as_ref()).map(|p| (c, p)))
+        .collect();
+
+    checks::report(
+        client,
+        &token,
+        &repo.full_name,
+        &head.sha,
+        &config.name,
+        config.report,
+        verdict(config, commits.len(), &failures),
+    )
+    .await
+    .map_err(api_error)?;
+    info!(
+        "DCO for {}#{}: {} of {} commits not signed off",
+        repo.full_name,
+        pr.number,
+        failures.len(),
+        commits.len()
+    );
+    Ok(())
+}
+
+// `*` matches anything; bot emails contain brackets, so these are not globs
+fn exempt(patterns: &[String], email: &str) -> bool {
+    let email = email.to_lowercase();
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.to_lowercase();
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+        let Some(mut rest) = email.strip_prefix(first) else {
+            return false;
+        };
+        let parts: Vec<&str> = parts.collect();
+        let Some((last, middle)) = parts.split_last() else {
+            return rest.is_empty();
+        };
+        for part in middle {
+            match rest.find(part) {
+                Some(at) => rest = &rest[at + part.len()..],
+                None => return false,
+            }
+        }
+        rest.ends_with(last)
+    })
+}
+
+// what is wrong with the commit's sign-off, if anything
+fn check(message: &str, author: Option<&GitPerson>) -> Option<String> {
+    let Some(author) = author else {
+        return Some("the commit has no author".to_string());
+    };
+    let sign_offs: Vec<(&str, &str)> = message
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Signed-off-by:"))
+        .filter_map(|value| {
+            let (name, email) = value.trim().strip_suffix('>')?.rsplit_once('<')?;
+            Some((name.trim(), email.trim()))
+        })
+        .collect();
+    if sign_offs.is_empty() {
+        return Some("no `Signed-off-by:` line".to_string());
+    }
+    if sign_offs.iter().any(|(name, email)| {
+        email.eq_ignore_ascii_case(&author.email) && *name == author.name.trim()
+    }) {
+        return None;
+    }
+    let signed: Vec<String> = sign_offs
+        .iter()
+        .map(|(name, email)| format!("{} <{}>", name, email))
+        .collect();
+    Some(format!(
+        "signed off by {}, but authored by {} <{}>",
+        signed.join(", "),
+        author.name,
+        author.email
+    ))
+}
+
+fn verdict(config: &DcoConfig, total: usize, failures: &[(&RepoCommit, String)]) -> Verdict {
+    if failures.is_empty() {
+        return Verdict {
+            passed: true,
+            title: "All commits are signed off".to_string(),
+            summary: format!(
+                "All {} commits have a matching `Signed-off-by:` line.",
+                total
+            ),
+            text: None,
+            details_url: config.details_url.clone(),
+        };
+    }
+    let summary = failures
+        .iter()
+        .map(|(c, problem)| {
+            format!(
+                "- `{}` {}: {}",
+                &c.sha[..c.sha.len().min(7)],
+                c.commit.message.lines().next().unwrap_or_default(),
+                problem
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Verdict {
+        passed: false,
+        title: format!("{} of {} commits are not signed off", failures.len(), total),
+        summary,
+        text: Some(format!(
+            "Every commit must certify the [Developer Certificate of Origin](https://developercertificate.org/) \
+             with a `Signed-off-by:` line naming its author.\n\n\
+             To sign off the commits already in this pull request, rebase and force-push:\n\n\
+             ```\n\
+             git rebase --signoff HEAD~{}\n\
+             git push --force-with-lease\n\
+             ```\n\n\
+             Use `git commit -s` for new commits. The sign-off uses `user.name` and `user.email` \
+             from your git config, which must match the commit author.",
+            total
+        )),
+        details_url: config.details_url.clone(),
+    }
+}
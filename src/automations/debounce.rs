@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, time::Duration};
+use tracing::{error, info};
+
+use crate::{
+    config::DebounceRule,
+    repos,
+    service::AppState,
+    webhook::{self, WebhookPayload},
+};
+
+// held deliveries live under `debounce-items:{rule}:{event}:{repo}:{subject}` in the
+// shared store, so a burst spread across replicas is still handled once
+// they are stored unredacted, since the handlers that run on flush need the payload as sent
+const ITEMS: &str = "debounce-items:";
+
+#[derive(Serialize, Deserialize)]
+struct Held {
+    event: String,
+    delivery_id: Option<String>,
+    payload: WebhookPayload,
+    // the payload as received, until coalescing changes it
+    #[serde(default)]
+    json: Option<String>,
+}
+
+// true when the delivery was taken into a window and must not be handled now
+pub async fn hold(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+    json: &[u8],
+) -> bool {
+    let config = &state.config.debounce;
+    if !config.enabled {
+        return false;
+    }
+    let Some(repo) = &payload.repository else {
+        return false;
+    };
+    let Some((index, rule)) = config
+        .rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| matches(rule, event_type, payload, &repo.full_name))
+    else {
+        return false;
+    };
+
+    let held = Held {
+        event: event_type.to_string(),
+        delivery_id: delivery_id.map(String::from),
+        payload: match serde_json::to_value(payload).and_then(serde_json::from_value) {
+            Ok(payload) => payload,
+            Err(_) => return false,
+        },
+        json: std::str::from_utf8(json).ok().map(String::from),
+    };
+    let Ok(item) = serde_json::to_string(&held) else {
+        return false;
+    };
+    let key = format!(
+        "{}:{}:{}:{}",
+        index,
+        event_type,
+        repo.full_name,
+        subject(payload)
+    );
+    let opened = match state.store.push(&format!("{}{}", ITEMS, key), &item).await {
+        Ok(len) => len == 1,
+        Err(e) => {
+            // handled right away rather than lost
+            error!("Failed to debounce {} event: {}", event_type, e);
+            return false;
+        }
+    };
+
+    // the first event of a window schedules its flush
+    if opened {
+        let state = state.clone();
+        let window = Duration::from_secs(rule.window_secs);
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            flush(&state, &key).await;
+        });
+    }
+    true
+}
+
+fn matches(rule: &DebounceRule, event_type: &str, payload: &WebhookPayload, repo: &str) -> bool {
+    rule.event == event_type
+        && rule
+            .action
+            .as_ref()
+            .is_none_or(|a| payload.action.as_ref() == Some(a))
+        && (rule.repos.is_empty() || repos::matches(&rule.repos, repo))
+}
+
+// what repeated events are about: a branch, a pull request or an issue
+fn subject(payload: &WebhookPayload) -> String {
+    if let Some(git_ref) = &payload.ref_name {
+        return git_ref.clone();
+    }
+    if let Some(pr) = &payload.pull_request {
+        return format!("#{}", pr.number);
+    }
+    if let Some(issue) = &payload.issue {
+        return format!("#{}", issue.number);
+    }
+    String::new()
+}
+
+// handles whatever is being held, e.g. on shutdown
+pub async fn flush_all(state: &AppState) {
+    match state.store.keys(ITEMS).await {
+        Ok(keys) => {
+            for key in keys {
+                flush(state, &key[ITEMS.len()..]).await;
+            }
+        }
+        Err(e) => error!("Failed to list debounced events: {}", e),
+    }
+}
+
+async fn flush(state: &AppState, key: &str) {
+    let items = match state.store.take(&format!("{}{}", ITEMS, key)).await {
+        Ok(items) => items,
+        Err(e) => {
+            error!("Failed to collect debounced events for {}: {}", key, e);
+            return;
+        }
+    };
+    let held: Vec<Held> = items
+        .iter()
+        .filter_map(|item| serde_json::from_str(item).ok())
+        .collect();
+    let count = held.len();
+    let Some(held) = coalesce(held) else {
+        return;
+    };
+    if count > 1 {
+        info!(
+            "Coalesced {} {} events for {}",
+            count,
+            held.event,
+            key.split_once(':').map_or(key, |(_, rest)| rest)
+        );
+    }
+    let json = match held.json {
+        Some(json) => json.into_bytes(),
+        None => serde_json::to_vec(&held.payload).unwrap_or_default(),
+    };
+    if let Err(e) = webhook::handle(
+        state,
+        &held.event,
+        held.delivery_id.as_deref(),
+        &held.payload,
+        &json,
+    )
+    .await
+    {
+        error!("Failed to handle debounced {} event: {}", held.event, e);
+    }
+}
+
+// the latest event stands for the window; a push also carries the commits of the
+// earlier ones, so `compare` spans from the first push to the last
+fn coalesce(held: Vec<Held>) -> Option<Held> {
+    let mut held = held.into_iter();
+    let first = held.next()?;
+    let first_compare = first.payload.compare.clone();
+    let mut commits = Vec::new();
+    let mut last = first;
+    for next in held {
+        commits.append(&mut last.payload.commits);
+        last = next;
+    }
+    if last.event != "push" || last.payload.deleted || commits.is_empty() {
+        return Some(last);
+    }
+
+    // a force push repeats the commits it kept, so each is listed once
+    commits.append(&mut last.payload.commits);
+    let mut seen = HashSet::new();
+    commits.retain(|c| seen.insert(c.id.clone()));
+    last.payload.commits = commits;
+    last.json = None;
+    if let (Some(first), Some(latest)) = (first_compare, &last.payload.compare)
+        && let (Some((base, _)), Some((_, head))) =
+            (first.rsplit_once("..."), latest.rsplit_once("..."))
+    {
+        last.payload.compare = Some(format!("{}...{}", base, head));
+    }
+    Some(last)
+}
@@ -0,0 +1,187 @@
+use serde_json::json;
+use std::{collections::HashMap, time::Duration};
+use tracing::{debug, error, info};
+
+use crate::{
+    command::CommandError,
+    config::DeployAction,
+    error::NexusError,
+    github::deployments::{self, DeploymentState, DeploymentStatus},
+    outbound::{self, Delivery, DeliveryError, Request},
+    service::AppState,
+    template,
+    webhook::{Deployment, WebhookPayload},
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeployError {
+    #[error("no command or url configured for {0}")]
+    NoAction(String),
+    #[error("{0}")]
+    Command(#[from] CommandError),
+    #[error("{0}")]
+    Delivery(#[from] DeliveryError),
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+pub async fn on_deployment_created(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.deploy;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let (Some(deployment), Some(repo)) = (&payload.deployment, &payload.repository) else {
+        return Ok(());
+    };
+    let Some(action) = config.environments.get(&deployment.environment) else {
+        debug!(
+            "No deploy action for environment {} in {}",
+            deployment.environment, repo.full_name
+        );
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Deploys are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    deployments::create_deployment_status(
+        &state.github,
+        &token,
+        &repo.full_name,
+        deployment.id,
+        &DeploymentStatus::new(DeploymentState::InProgress).description("Deploying with nexus"),
+    )
+    .await
+    .map_err(|e| {
+        error!(
+            "Failed to start deployment {} in {}: {}",
+            deployment.id, repo.full_name, e
+        );
+        NexusError::from(e)
+    })?;
+
+    // deploys outlive GitHub's ten second webhook timeout, so they finish in the background
+    let state = state.clone();
+    let repo = repo.full_name.clone();
+    let deployment = deployment.clone();
+    let action = action.clone();
+    tokio::spawn(async move { run(&state, &repo, &deployment, &action).await });
+
+    Ok(())
+}
+
+async fn run(state: &AppState, repo: &str, deployment: &Deployment, action: &DeployAction) {
+    let vars = HashMap::from([
+        ("repo", repo.to_string()),
+        ("environment", deployment.environment.clone()),
+        ("sha", deployment.sha.clone()),
+        ("ref", deployment.ref_name.clone()),
+        ("id", deployment.id.to_string()),
+        (
+            "task",
+            deployment.task.clone().unwrap_or_else(|| "deploy".into()),
+        ),
+    ]);
+
+    info!(
+        "Deploying {}@{} to {}",
+        repo, deployment.sha, deployment.environment
+    );
+    let timeout_secs = state.config.deploy.timeout_secs;
+    let result = tokio::time::timeout(
+        Duration::from_secs(timeout_secs),
+        execute(state, repo, deployment, action, &vars),
+    )
+    .await
+    .unwrap_or(Err(DeployError::TimedOut(timeout_secs)));
+
+    let status = match &result {
+        Ok(()) => {
+            info!("Deployment {} in {} succeeded", deployment.id, repo);
+            let status =
+                DeploymentStatus::new(DeploymentState::Success).description("Deployed by nexus");
+            match &action.environment_url {
+                Some(url) => status.environment_url(template::render(url, &vars)),
+                None => status,
+            }
+        }
+        Err(reason) => {
+            error!(
+                "Deployment {} in {} failed: {}",
+                deployment.id, repo, reason
+            );
+            DeploymentStatus::new(DeploymentState::Failure)
+                .description(format!("Deploy failed: {}", reason))
+        }
+    };
+
+    // fetched again since an installation token may have expired during a long deploy
+    let Some(token) = state.github_token(repo).await else {
+        return;
+    };
+    if let Err(e) =
+        deployments::create_deployment_status(&state.github, &token, repo, deployment.id, &status)
+            .await
+    {
+        error!(
+            "Failed to report deployment {} in {}: {}",
+            deployment.id, repo, e
+        );
+    }
+}
+
+async fn execute(
+    state: &AppState,
+    repo: &str,
+    deployment: &Deployment,
+    action: &DeployAction,
+    vars: &HashMap<&str, String>,
+) -> Result<(), DeployError> {
+    if let Some(url) = &action.url {
+        let request = Request::Http {
+            url: template::render(url, vars),
+            headers: action.headers.clone(),
+            body: json!({
+                "repository": repo,
+                "deployment_id": deployment.id,
+                "environment": deployment.environment,
+                "sha": deployment.sha,
+                "ref": deployment.ref_name,
+                "task": deployment.task,
+                "payload": deployment.payload,
+            }),
+        };
+        let target = format!("deploy:{}", deployment.environment);
+        let scope = format!("deployment:{}", deployment.id);
+        let delivery = Delivery::new(target, request).idempotent(Some(&scope));
+        return Ok(outbound::deliver(state, delivery).await?);
+    }
+
+    let Some((program, args)) = action.command.split_first() else {
+        return Err(DeployError::NoAction(deployment.environment.clone()));
+    };
+
+    let output = tokio::process::Command::new(template::render(program, vars))
+        .args(args.iter().map(|arg| template::render(arg, vars)))
+        .env("NEXUS_REPOSITORY", repo)
+        .env("NEXUS_DEPLOYMENT_ID", deployment.id.to_string())
+        .env("NEXUS_ENVIRONMENT", &deployment.environment)
+        .env("NEXUS_SHA", &deployment.sha)
+        .env("NEXUS_REF", &deployment.ref_name)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| CommandError::spawn(program, e))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    Err(CommandError::exited(program, &output).into())
+}
@@ -0,0 +1,215 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::{collections::HashMap, time::Duration};
+use tracing::{error, info, warn};
+
+use crate::{
+    environments,
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    repos,
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+// a window's items live under `digest-items:{rule}:{repo}` and its start under
+// `digest-since:{rule}:{repo}` in the shared store, so replicas add to the same window
+const ITEMS: &str = "digest-items:";
+const SINCE: &str = "digest-since:";
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.digest;
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+
+    for (index, rule) in config.rules.iter().enumerate() {
+        let matches = rule.event == event_type
+            && rule
+                .action
+                .as_ref()
+                .is_none_or(|a| payload.action.as_ref() == Some(a))
+            && (rule.repos.is_empty() || repos::matches(&rule.repos, &repo.full_name));
+        if !matches {
+            continue;
+        }
+
+        let item = template::render(&rule.item_template, &item_vars(state, event_type, payload));
+        let key = format!("{}:{}", index, repo.full_name);
+        let opened = match state.store.push(&format!("{}{}", ITEMS, key), &item).await {
+            Ok(len) => len == 1,
+            Err(e) => {
+                error!("Failed to add to the {} digest: {}", rule.event, e);
+                continue;
+            }
+        };
+        if !opened {
+            continue;
+        }
+
+        // the first event of a window schedules its flush
+        let since = Utc::now().to_rfc3339();
+        if let Err(e) = state
+            .store
+            .set_if_absent(&format!("{}{}", SINCE, key), &since)
+            .await
+        {
+            warn!(
+                "Failed to record when the {} digest started: {}",
+                rule.event, e
+            );
+        }
+        let state = state.clone();
+        let window = Duration::from_secs(rule.window_secs);
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            flush(&state, &key).await;
+        });
+    }
+
+    Ok(())
+}
+
+// sends whatever has been collected so far, e.g. on shutdown
+pub async fn flush_all(state: &AppState) {
+    match state.store.keys(ITEMS).await {
+        Ok(keys) => {
+            for key in keys {
+                flush(state, &key[ITEMS.len()..]).await;
+            }
+        }
+        Err(e) => error!("Failed to list open digests: {}", e),
+    }
+}
+
+// the start is taken first so an event arriving in between opens a fresh window
+async fn flush(state: &AppState, key: &str) {
+    let Some((index, repo)) = key.split_once(':') else {
+        return;
+    };
+    let Some(rule) = index
+        .parse::<usize>()
+        .ok()
+        .and_then(|i| state.config.digest.rules.get(i))
+    else {
+        return;
+    };
+
+    let since = match state.store.take_value(&format!("{}{}", SINCE, key)).await {
+        Ok(since) => since
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|s| s.with_timezone(&Utc)),
+        Err(e) => {
+            warn!(
+                "Failed to read when the {} digest started: {}",
+                rule.event, e
+            );
+            None
+        }
+    };
+    let items = match state.store.take(&format!("{}{}", ITEMS, key)).await {
+        Ok(items) if items.is_empty() => return,
+        Ok(items) => items,
+        Err(e) => {
+            error!(
+                "Failed to collect the {} digest for {}: {}",
+                rule.event, repo, e
+            );
+            return;
+        }
+    };
+
+    let vars = HashMap::from([
+        ("repo", repo.to_string()),
+        ("event", rule.event.clone()),
+        ("count", items.len().to_string()),
+        (
+            "since",
+            since
+                .unwrap_or_else(Utc::now)
+                .format("%H:%M UTC")
+                .to_string(),
+        ),
+        ("items", items.join("\n")),
+    ]);
+    let text = template::render(&rule.template, &vars);
+
+    let request = Request::Http {
+        url: rule.url.clone(),
+        headers: rule.headers.clone(),
+        body: json!({ "text": text }),
+    };
+    let target = format!("digest:{}", rule.event);
+    // a window is identified by the repository and when it opened
+    let scope = since.map(|since| format!("{}:{}", repo, since.to_rfc3339()));
+    let delivery = Delivery::new(target, request).idempotent(scope.as_deref());
+    match outbound::deliver(state, delivery).await {
+        Ok(()) => info!(
+            "Sent digest of {} {} events in {}",
+            items.len(),
+            rule.event,
+            repo
+        ),
+        Err(e) => error!("Failed to send {} digest for {}: {}", rule.event, repo, e),
+    }
+}
+
+fn item_vars(
+    state: &AppState,
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> HashMap<&'static str, String> {
+    let action = payload.action.clone().unwrap_or_default();
+    let ref_name = payload
+        .ref_name
+        .as_deref()
+        .map(|r| r.trim_start_matches("refs/heads/").to_string())
+        .unwrap_or_default();
+
+    let summary = match event_type {
+        "push" => format!(
+            "pushed {} commit{} to {}",
+            payload.commits.len(),
+            if payload.commits.len() == 1 { "" } else { "s" },
+            ref_name
+        ),
+        _ if action.is_empty() => event_type.to_string(),
+        _ => format!("{} {}", action, event_type),
+    };
+    let message = payload
+        .commits
+        .last()
+        .and_then(|c| c.message.lines().next())
+        .unwrap_or_default()
+        .to_string();
+
+    HashMap::from([
+        ("event", event_type.to_string()),
+        ("action", action),
+        ("ref", ref_name),
+        (
+            "sender",
+            payload
+                .sender
+                .as_ref()
+                .map(|s| s.login.clone())
+                .unwrap_or_default(),
+        ),
+        ("summary", summary),
+        ("commits", payload.commits.len().to_string()),
+        ("message", message),
+        ("url", payload.compare.clone().unwrap_or_default()),
+        (
+            "environment",
+            environments::for_payload(&state.config.environments, payload).unwrap_or_default(),
+        ),
+    ])
+}
@@ -0,0 +1,255 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    process::Stdio,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncWriteExt, sync::Semaphore};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ExecRule, environments, error::NexusError, repos, scripting, service::AppState,
+    template, transform, webhook::WebhookPayload,
+};
+
+// how many finished runs are kept for /admin/exec
+const RECENT_RUNS: usize = 100;
+// per stream; the rest of a chatty command's output is dropped
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecRun {
+    pub delivery_id: Option<String>,
+    pub event_type: String,
+    pub command: Vec<String>,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    // None when the command could not be started, timed out or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Recent command runs with their output, and the slots that cap how many run at once.
+#[derive(Clone, Default)]
+pub struct ExecRuns {
+    recent: Arc<Mutex<VecDeque<ExecRun>>>,
+    // sized from `exec.max_concurrent` on first use
+    slots: Arc<OnceLock<Arc<Semaphore>>>,
+}
+
+impl ExecRuns {
+    // newest last; only those for `delivery_id` when given
+    pub fn recent(&self, delivery_id: Option<&str>) -> Vec<ExecRun> {
+        self.recent
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|run| delivery_id.is_none_or(|id| run.delivery_id.as_deref() == Some(id)))
+            .cloned()
+            .collect()
+    }
+
+    fn record(&self, run: ExecRun) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_RUNS {
+            recent.pop_front();
+        }
+        recent.push_back(run);
+    }
+}
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.exec;
+    if !config.enabled {
+        return Ok(());
+    }
+    let repo = payload.repository.as_ref().map(|r| r.full_name.as_str());
+
+    let rules = config.rules.iter().filter(|rule| {
+        rule.event == event_type
+            && rule
+                .action
+                .as_ref()
+                .is_none_or(|a| payload.action.as_ref() == Some(a))
+            && (rule.repos.is_empty() || repo.is_some_and(|r| repos::matches(&rule.repos, r)))
+    });
+
+    let value = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to serialize the {} payload: {}", event_type, e);
+            return Err(NexusError::Serialize(e));
+        }
+    };
+    let vars = Arc::new(variables(state, event_type, delivery_id, payload));
+
+    // commands outlive GitHub's webhook timeout and the worker, so they run in the background
+    for rule in rules {
+        let value = match &rule.script {
+            Some(script) => {
+                match scripting::apply(state, script, event_type, delivery_id, &value) {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+            None => value.clone(),
+        };
+        let stdin = if rule.transform.is_empty() {
+            value.to_string()
+        } else {
+            match transform::apply(&rule.transform, &value) {
+                Ok(value) => value.to_string(),
+                Err(e) => {
+                    warn!("Transform for {} is invalid: {}", rule.command.join(" "), e);
+                    continue;
+                }
+            }
+        };
+        let state = state.clone();
+        let rule = rule.clone();
+        let vars = vars.clone();
+        tokio::spawn(async move {
+            let slots = state
+                .exec
+                .slots
+                .get_or_init(|| Arc::new(Semaphore::new(state.config.exec.max_concurrent.max(1))))
+                .clone();
+            let Ok(_slot) = slots.acquire_owned().await else {
+                return;
+            };
+            let run = run(&rule, &vars, stdin.as_bytes()).await;
+            match (&run.error, run.exit_code) {
+                (None, Some(0)) => info!(
+                    "{} exited successfully after {}ms",
+                    run.command.join(" "),
+                    run.duration_ms
+                ),
+                (Some(e), _) => error!("{} failed: {}", run.command.join(" "), e),
+                (None, code) => warn!(
+                    "{} exited with {}",
+                    run.command.join(" "),
+                    code.map_or("a signal".to_string(), |c| c.to_string())
+                ),
+            }
+            state.exec.record(run);
+        });
+    }
+
+    Ok(())
+}
+
+fn variables(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::from([
+        ("event", event_type.to_string()),
+        ("action", payload.action.clone().unwrap_or_default()),
+        ("ref", payload.ref_name.clone().unwrap_or_default()),
+        ("delivery", delivery_id.unwrap_or_default().to_string()),
+    ]);
+    if let Some(repo) = &payload.repository {
+        vars.insert("repo", repo.full_name.clone());
+    }
+    if let Some(sender) = &payload.sender {
+        vars.insert("sender", sender.login.clone());
+    }
+    if let Some(key) = state.correlation.key(payload) {
+        vars.insert("correlation", key);
+    }
+    if let Some(environment) = environments::for_payload(&state.config.environments, payload) {
+        vars.insert("environment", environment);
+    }
+    if let Some(number) = payload
+        .pull_request
+        .as_ref()
+        .map(|pr| pr.number)
+        .or(payload.issue.as_ref().map(|issue| issue.number))
+    {
+        vars.insert("number", number.to_string());
+    }
+    vars
+}
+
+async fn run(rule: &ExecRule, vars: &HashMap<&'static str, String>, stdin: &[u8]) -> ExecRun {
+    let command: Vec<String> = rule
+        .command
+        .iter()
+        .map(|arg| template::render(arg, vars))
+        .collect();
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let mut run = ExecRun {
+        delivery_id: vars.get("delivery").filter(|d| !d.is_empty()).cloned(),
+        event_type: vars["event"].clone(),
+        command: command.clone(),
+        started_at,
+        duration_ms: 0,
+        exit_code: None,
+        error: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+
+    let Some((program, args)) = command.split_first() else {
+        run.error = Some("no command configured".to_string());
+        return run;
+    };
+    let mut process = tokio::process::Command::new(program);
+    process
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // placeholders become NEXUS_EVENT, NEXUS_REPO and so on
+    for (name, value) in vars {
+        process.env(format!("NEXUS_{}", name.to_uppercase()), value);
+    }
+    if let Some(dir) = &rule.working_dir {
+        process.current_dir(dir);
+    }
+
+    let result = tokio::time::timeout(Duration::from_secs(rule.timeout_secs), async {
+        let mut child = process.spawn()?;
+        let pipe = child.stdin.take();
+        // written while the output is read so neither side can fill a pipe and block; a
+        // command that ignores stdin closes it early, which is not an error
+        let write = async move {
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.write_all(stdin).await;
+            }
+        };
+        let ((), output) = tokio::join!(write, child.wait_with_output());
+        output
+    })
+    .await;
+
+    run.duration_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(Ok(output)) => {
+            run.exit_code = output.status.code();
+            run.stdout = truncate(&output.stdout);
+            run.stderr = truncate(&output.stderr);
+        }
+        Ok(Err(e)) => run.error = Some(format!("could not run {}: {}", program, e)),
+        Err(_) => run.error = Some(format!("timed out after {}s", rule.timeout_secs)),
+    }
+    run
+}
+
+fn truncate(output: &[u8]) -> String {
+    let output = String::from_utf8_lossy(&output[..output.len().min(MAX_OUTPUT_BYTES)]);
+    output.into_owned()
+}
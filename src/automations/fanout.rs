@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::FanoutRule,
+    environments,
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    repos,
+    scripting::{self, Outcome},
+    service::AppState,
+    template, transform,
+    webhook::WebhookPayload,
+};
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.fanout;
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+
+    let rules = config.rules.iter().filter(|rule| {
+        rule.event == event_type
+            && rule
+                .action
+                .as_ref()
+                .is_none_or(|a| payload.action.as_ref() == Some(a))
+            && (rule.repos.is_empty() || repos::matches(&rule.repos, &repo.full_name))
+    });
+
+    let environment = environments::for_payload(&state.config.environments, payload);
+    for rule in rules {
+        let Some(client_payload) = render_payload(
+            state,
+            rule,
+            event_type,
+            delivery_id,
+            payload,
+            environment.as_deref(),
+        ) else {
+            continue;
+        };
+
+        // each target retries in the background so one slow downstream does not hold up the others
+        for target in &rule.targets {
+            let delivery = Delivery::new(
+                format!("dispatch:{}", target),
+                Request::Dispatch {
+                    repo: target.clone(),
+                    event_type: rule.event_type.clone(),
+                    client_payload: client_payload.clone(),
+                },
+            )
+            .idempotent(delivery_id);
+            let state = state.clone();
+            let target = target.clone();
+            let dispatched = rule.event_type.clone();
+            let source = format!("{} in {}", event_type, repo.full_name);
+            tokio::spawn(async move {
+                match outbound::deliver(&state, delivery).await {
+                    Ok(()) => info!("Dispatched {} to {} after {}", dispatched, target, source),
+                    Err(e) => error!("Failed to dispatch {} to {}: {}", dispatched, target, e),
+                }
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// a map returned by the rule's script is the payload, unless `transform` reshapes it. Values
+// are JSON-escaped before substitution so the rendered template stays valid JSON
+fn render_payload(
+    state: &AppState,
+    rule: &FanoutRule,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+    environment: Option<&str>,
+) -> Option<serde_json::Value> {
+    let mut scripted = None;
+    if let Some(script) = &rule.script {
+        let value = serde_json::to_value(payload).unwrap_or_default();
+        match scripting::run(state, script, event_type, delivery_id, &value) {
+            Ok(Outcome::Skip) => return None,
+            Ok(Outcome::Keep) => {}
+            Ok(Outcome::Replace(value)) => scripted = Some(value),
+            Err(e) => {
+                warn!("Script {} {}", script.display(), e);
+                return None;
+            }
+        }
+    }
+    if !rule.transform.is_empty() {
+        let value = scripted.unwrap_or_else(|| serde_json::to_value(payload).unwrap_or_default());
+        return transform::apply(&rule.transform, &value)
+            .inspect_err(|e| {
+                warn!(
+                    "Fan-out transform for {} is invalid: {}",
+                    rule.event_type, e
+                )
+            })
+            .ok();
+    }
+    if scripted.is_some() {
+        return scripted;
+    }
+
+    let escape = |value: &str| {
+        let quoted = serde_json::to_string(value).unwrap_or_default();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+
+    let mut vars = HashMap::from([
+        ("event", escape(event_type)),
+        (
+            "action",
+            escape(payload.action.as_deref().unwrap_or_default()),
+        ),
+        (
+            "ref",
+            escape(payload.ref_name.as_deref().unwrap_or_default()),
+        ),
+    ]);
+    if let Some(repo) = &payload.repository {
+        vars.insert("repo", escape(&repo.full_name));
+    }
+    if let Some(sender) = &payload.sender {
+        vars.insert("sender", escape(&sender.login));
+    }
+    if let Some(release) = &payload.release {
+        vars.insert("tag", escape(&release.tag_name));
+        vars.insert("release_url", escape(&release.html_url));
+    }
+    if let Some(pr) = &payload.pull_request {
+        vars.insert("number", pr.number.to_string());
+    }
+    if let Some(environment) = environment {
+        vars.insert("environment", escape(environment));
+    }
+
+    let rendered = template::render(&rule.client_payload, &vars);
+    match serde_json::from_str::<serde_json::Value>(&rendered) {
+        Ok(value) if value.is_object() => Some(value),
+        Ok(_) => {
+            warn!(
+                "Fan-out client_payload for {} is not a JSON object",
+                rule.event_type
+            );
+            None
+        }
+        Err(e) => {
+            warn!(
+                "Fan-out client_payload for {} is not valid JSON: {}",
+                rule.event_type, e
+            );
+            None
+        }
+    }
+}
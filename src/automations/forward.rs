@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ForwardTarget,
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    repos, scripting,
+    service::AppState,
+    transform,
+    webhook::{SignatureVerifier, WebhookPayload},
+};
+
+// `json` is the payload as received; scripts and transforms work on it rather than on
+// the parsed model, which leaves out fields nexus does not know
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+    json: &[u8],
+) -> Result<(), NexusError> {
+    let config = &state.config.forward;
+    if !config.enabled {
+        return Ok(());
+    }
+    let repo = payload.repository.as_ref().map(|r| r.full_name.as_str());
+
+    let targets = config.targets.iter().filter(|target| {
+        (target.events.is_empty() || target.events.iter().any(|e| e == event_type))
+            && (target.repos.is_empty() || repo.is_some_and(|r| repos::matches(&target.repos, r)))
+    });
+
+    // GitHub sends JSON, which is UTF-8
+    let received = std::str::from_utf8(json).map_err(|e| {
+        error!(
+            "The {} payload is not UTF-8 and cannot be forwarded",
+            event_type
+        );
+        NexusError::NotUtf8(e)
+    })?;
+
+    // each target retries in the background so one slow receiver does not hold up the others
+    for target in targets {
+        let Some(body) = body(state, target, event_type, delivery_id, received) else {
+            continue;
+        };
+        let headers = headers(target, event_type, delivery_id, body.as_bytes());
+        let request = Request::Forward {
+            url: target.url.clone(),
+            headers,
+            body,
+        };
+        let delivery =
+            Delivery::new(format!("forward:{}", target.url), request).idempotent(delivery_id);
+        let state = state.clone();
+        let url = target.url.clone();
+        let event_type = event_type.to_string();
+        tokio::spawn(async move {
+            match outbound::deliver(&state, delivery).await {
+                Ok(()) => info!("Forwarded {} event to {}", event_type, url),
+                Err(e) => error!("Failed to forward {} event to {}: {}", event_type, url, e),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// the delivery as received unless the target's script or transform makes a new body;
+// None when it is not forwarded to this target
+fn body(
+    state: &AppState,
+    target: &ForwardTarget,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    received: &str,
+) -> Option<String> {
+    if target.script.is_none() && target.transform.is_empty() {
+        return Some(received.to_string());
+    }
+    let mut value = serde_json::from_str(received)
+        .inspect_err(|e| warn!("The {} payload is not valid JSON: {}", event_type, e))
+        .ok()?;
+    if let Some(script) = &target.script {
+        value = scripting::apply(state, script, event_type, delivery_id, &value)?;
+    }
+    if !target.transform.is_empty() {
+        value = transform::apply(&target.transform, &value)
+            .inspect_err(|e| warn!("Forward transform for {} is invalid: {}", target.url, e))
+            .ok()?;
+    }
+    Some(value.to_string())
+}
+
+// GitHub's delivery headers, then the target's overrides. The signature covers the body
+// exactly as it is sent, so retries stay verifiable
+fn headers(
+    target: &ForwardTarget,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    body: &[u8],
+) -> HashMap<String, String> {
+    let mut headers = HashMap::from([
+        ("x-github-event".to_string(), event_type.to_string()),
+        (
+            "user-agent".to_string(),
+            concat!("nexus/", env!("CARGO_PKG_VERSION")).to_string(),
+        ),
+    ]);
+    if let Some(delivery_id) = delivery_id {
+        headers.insert("x-github-delivery".to_string(), delivery_id.to_string());
+    }
+    if let Some(mut verifier) = target.secret.as_deref().and_then(SignatureVerifier::new) {
+        verifier.update(body);
+        headers.insert("x-hub-signature-256".to_string(), verifier.signature());
+    }
+
+    headers.retain(|name, _| {
+        !target
+            .strip_headers
+            .iter()
+            .any(|strip| strip.eq_ignore_ascii_case(name))
+    });
+    for (name, value) in &target.headers {
+        headers.insert(name.to_lowercase(), value.clone());
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Config, ForwardConfig},
+        signature,
+    };
+    use axum::{Router, body::Bytes, extract::State, http::HeaderMap, routing::post};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    // spacing, key order and a field nexus does not model, as GitHub might send them
+    const JSON: &str = r#"{"ref":"refs/heads/main",  "zen": "Keep it logically awesome.", "repository": {"name":"repo","full_name":"org/repo","html_url":"https://github.com/org/repo","custom_properties":{"team":"web"}}}"#;
+
+    // forwards JSON to `target`, pointed at a local receiver, and returns what it got
+    async fn forward(mut target: ForwardTarget) -> (HeaderMap, Bytes) {
+        let received = Received::default();
+        let app = Router::new()
+            .route(
+                "/",
+                post(
+                    |State(received): State<Received>, headers: HeaderMap, body: Bytes| async move {
+                        received.lock().unwrap().push((headers, body));
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        target.url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let state = AppState::new(None).with_config(Config {
+            forward: ForwardConfig {
+                enabled: true,
+                targets: vec![target],
+            },
+            ..Config::default()
+        });
+        let payload = serde_json::from_str(JSON).unwrap();
+        on_event(&state, "push", Some("d1"), &payload, JSON.as_bytes())
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            if let Some(received) = received.lock().unwrap().pop() {
+                return received;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("nothing was forwarded");
+    }
+
+    fn signed(headers: &HeaderMap, secret: &str, body: &[u8]) -> bool {
+        let signature = headers["x-hub-signature-256"].to_str().unwrap();
+        signature::verify_signature(secret, body, signature)
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_forwarded_byte_for_byte() {
+        let (headers, body) = forward(ForwardTarget {
+            secret: Some("downstream".to_string()),
+            ..ForwardTarget::default()
+        })
+        .await;
+        assert_eq!(body, JSON.as_bytes());
+        assert!(signed(&headers, "downstream", &body));
+        assert_eq!(headers["x-github-event"], "push");
+        assert_eq!(headers["x-github-delivery"], "d1");
+        assert_eq!(headers.get_all("content-type").iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn transforms_see_fields_nexus_does_not_model() {
+        let (headers, body) = forward(ForwardTarget {
+            secret: Some("downstream".to_string()),
+            transform: HashMap::from([
+                ("zen".to_string(), "/zen".to_string()),
+                (
+                    "team".to_string(),
+                    "/repository/custom_properties/team".to_string(),
+                ),
+            ]),
+            ..ForwardTarget::default()
+        })
+        .await;
+        let forwarded: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            forwarded,
+            serde_json::json!({"zen": "Keep it logically awesome.", "team": "web"})
+        );
+        assert!(signed(&headers, "downstream", &body));
+    }
+
+    #[tokio::test]
+    async fn overridden_and_stripped_headers() {
+        let (headers, _) = forward(ForwardTarget {
+            headers: HashMap::from([("X-Source".to_string(), "nexus".to_string())]),
+            strip_headers: vec!["X-GitHub-Delivery".to_string()],
+            ..ForwardTarget::default()
+        })
+        .await;
+        assert_eq!(headers["x-source"], "nexus");
+        assert!(headers.get("x-github-delivery").is_none());
+        assert!(headers.get("x-hub-signature-256").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    cloud::{self, FunctionAuth},
+    config::{FunctionAuthKind, FunctionTarget, Invocation},
+    environments,
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    repos, scripting,
+    service::AppState,
+    transform,
+    webhook::WebhookPayload,
+};
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.functions;
+    if !config.enabled {
+        return Ok(());
+    }
+    let repo = payload.repository.as_ref().map(|r| r.full_name.as_str());
+
+    let targets = config.targets.iter().filter(|target| {
+        (target.events.is_empty() || target.events.iter().any(|e| e == event_type))
+            && (target.repos.is_empty() || repo.is_some_and(|r| repos::matches(&target.repos, r)))
+    });
+
+    // functions get no delivery headers through the Lambda API, so the event travels in the body
+    let envelope = json!({
+        "event": event_type,
+        "delivery_id": delivery_id,
+        "environment": environments::for_payload(&state.config.environments, payload),
+        "payload": payload,
+    });
+
+    for target in targets {
+        let mut envelope = envelope.clone();
+        if let Some(script) = &target.script {
+            let payload = &envelope["payload"];
+            let Some(payload) = scripting::apply(state, script, event_type, delivery_id, payload)
+            else {
+                continue;
+            };
+            envelope["payload"] = payload;
+        }
+        let Some((name, request)) = request(target, event_type, delivery_id, &envelope) else {
+            continue;
+        };
+        let delivery = Delivery::new(format!("function:{}", name), request).idempotent(delivery_id);
+        let state = state.clone();
+        let event_type = event_type.to_string();
+        tokio::spawn(async move {
+            match outbound::deliver(&state, delivery).await {
+                Ok(()) => info!("Invoked {} for {} event", name, event_type),
+                Err(e) => error!("Failed to invoke {} for {} event: {}", name, event_type, e),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// the target's name for logs and its request, or None when it is misconfigured
+fn request(
+    target: &FunctionTarget,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    envelope: &serde_json::Value,
+) -> Option<(String, Request)> {
+    let region = target
+        .region
+        .clone()
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .unwrap_or_default();
+
+    let mut headers = HashMap::from([("x-github-event".to_string(), event_type.to_string())]);
+    if let Some(delivery_id) = delivery_id {
+        headers.insert("x-github-delivery".to_string(), delivery_id.to_string());
+    }
+    let (name, url, auth) = match (&target.function, &target.url) {
+        (Some(function), None) => {
+            if region.is_empty() {
+                warn!("No region is set for function {}", function);
+                return None;
+            }
+            let invocation = match target.invocation {
+                Invocation::Event => "Event",
+                Invocation::RequestResponse => "RequestResponse",
+            };
+            headers.insert("x-amz-invocation-type".to_string(), invocation.to_string());
+            // AWS_ENDPOINT_URL_LAMBDA points elsewhere, e.g. LocalStack, as in the AWS SDKs
+            let endpoint = std::env::var("AWS_ENDPOINT_URL_LAMBDA")
+                .or_else(|_| std::env::var("AWS_ENDPOINT_URL"))
+                .unwrap_or_else(|_| format!("https://lambda.{}.amazonaws.com", region));
+            let url = format!(
+                "{}/2015-03-31/functions/{}/invocations",
+                endpoint.trim_end_matches('/'),
+                cloud::uri_encode(function)
+            );
+            let auth = FunctionAuth::Sigv4 {
+                region,
+                service: "lambda".to_string(),
+            };
+            (function.clone(), url, auth)
+        }
+        (None, Some(url)) => {
+            let auth = match target.auth {
+                FunctionAuthKind::None => FunctionAuth::None,
+                FunctionAuthKind::Sigv4 if region.is_empty() => {
+                    warn!("No region is set for function {}", url);
+                    return None;
+                }
+                FunctionAuthKind::Sigv4 => FunctionAuth::Sigv4 {
+                    region,
+                    service: target.service.clone(),
+                },
+                FunctionAuthKind::Oidc => FunctionAuth::Oidc {
+                    audience: target.audience.clone().unwrap_or_else(|| url.clone()),
+                    token_file: target.token_file.clone(),
+                },
+            };
+            (url.clone(), url.clone(), auth)
+        }
+        _ => {
+            warn!("A function target needs exactly one of `function` and `url`");
+            return None;
+        }
+    };
+    for (name, value) in &target.headers {
+        headers.insert(name.to_lowercase(), value.clone());
+    }
+
+    let body = if target.transform.is_empty() {
+        envelope.clone()
+    } else {
+        match transform::apply(&target.transform, envelope) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Function transform for {} is invalid: {}", name, e);
+                return None;
+            }
+        }
+    };
+    Some((
+        name,
+        Request::Function {
+            url,
+            auth,
+            headers,
+            body,
+        },
+    ))
+}
@@ -0,0 +1,146 @@
+use chrono::Utc;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::{error, info};
+
+use crate::{
+    config::GrafanaConfig,
+    environments,
+    error::NexusError,
+    github::deployments::DeploymentState,
+    outbound::{self, Delivery, Request},
+    repos,
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+// one annotation per finished deployment, rollback or published release
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.grafana;
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+    if !config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name) {
+        return Ok(());
+    }
+    let Some((kind, text)) = describe(config, event_type, payload) else {
+        return Ok(());
+    };
+    if !config.annotate.iter().any(|k| k == kind) {
+        return Ok(());
+    }
+    let Some(token) = &config.api_token else {
+        error!("Grafana annotations are enabled but no api_token is configured");
+        return Ok(());
+    };
+
+    let environment = environments::for_payload(&state.config.environments, payload);
+    let mut tags = vec![kind.to_string(), format!("repo:{}", repo.full_name)];
+    if let Some(environment) = &environment {
+        tags.push(format!("env:{}", environment));
+    }
+    tags.extend(config.tags.iter().cloned());
+    let mut body = json!({
+        "time": Utc::now().timestamp_millis(),
+        "tags": tags,
+        "text": text,
+    });
+    if let Some(uid) = &config.dashboard_uid {
+        body["dashboardUID"] = json!(uid);
+    }
+    if let Some(panel_id) = config.panel_id {
+        body["panelId"] = json!(panel_id);
+    }
+
+    let request = Request::Http {
+        url: format!("{}/api/annotations", config.url.trim_end_matches('/')),
+        headers: HashMap::from([("authorization".to_string(), format!("Bearer {}", token))]),
+        body,
+    };
+    let delivery = Delivery::new("grafana", request).idempotent(delivery_id);
+    let state = state.clone();
+    let repo = repo.full_name.clone();
+    tokio::spawn(async move {
+        match outbound::deliver(&state, delivery).await {
+            Ok(()) => info!("Annotated a {} of {} in Grafana", kind, repo),
+            Err(e) => error!(
+                "Failed to annotate a {} of {} in Grafana: {}",
+                kind, repo, e
+            ),
+        }
+    });
+
+    Ok(())
+}
+
+// the kind of change and the annotation text, or None when the event is not annotated
+fn describe(
+    config: &GrafanaConfig,
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> Option<(&'static str, String)> {
+    let repo = &payload.repository.as_ref()?.full_name;
+    match event_type {
+        "deployment_status" => {
+            let (deployment, status) = (
+                payload.deployment.as_ref()?,
+                payload.deployment_status.as_ref()?,
+            );
+            let state = match status.state {
+                DeploymentState::Success => "success",
+                DeploymentState::Failure => "failure",
+                DeploymentState::Error => "error",
+                _ => return None,
+            };
+            if !config.deployment_states.iter().any(|s| s == state) {
+                return None;
+            }
+            let task = deployment.task.as_deref().unwrap_or("deploy");
+            let kind = if config.rollback_tasks.iter().any(|t| t == task) {
+                "rollback"
+            } else {
+                "deploy"
+            };
+            let sha = &deployment.sha[..deployment.sha.len().min(7)];
+            let mut text = match kind {
+                "rollback" => format!(
+                    "Rolled back {} in {} to {} ({})",
+                    repo, deployment.environment, deployment.ref_name, sha
+                ),
+                _ => format!(
+                    "Deployed {} {} ({}) to {}",
+                    repo, deployment.ref_name, sha, deployment.environment
+                ),
+            };
+            if state != "success" {
+                text.push_str(&format!(": {}", state));
+            }
+            if let Some(description) = status.description.as_deref().filter(|d| !d.is_empty()) {
+                text.push_str(&format!("\n{}", description));
+            }
+            Some((kind, text))
+        }
+        "release" if payload.action.as_deref() == Some("published") => {
+            let release = payload.release.as_ref()?;
+            let name = release.name.as_deref().filter(|n| !n.is_empty());
+            Some((
+                "release",
+                format!(
+                    "Released {} <a href=\"{}\">{}</a>",
+                    repo,
+                    release.html_url,
+                    name.unwrap_or(&release.tag_name)
+                ),
+            ))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,257 @@
+use http::StatusCode;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::JiraConfig,
+    error::NexusError,
+    repos,
+    service::AppState,
+    template,
+    webhook::{PullRequest, WebhookPayload},
+};
+
+static KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b([A-Z][A-Z0-9_]+)-[1-9][0-9]*\b").unwrap());
+
+#[derive(Debug, thiserror::Error)]
+pub enum JiraError {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    // Jira explains the problem in `errorMessages`, e.g. a field the transition requires
+    #[error("Jira returned {status}: {message}")]
+    Api { status: StatusCode, message: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transition {
+    id: String,
+    name: String,
+    to: Option<Status>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Status {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comments {
+    comments: Vec<JiraComment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraComment {
+    body: String,
+}
+
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.jira;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    let event = match action.as_str() {
+        "opened" | "reopened" => action.as_str(),
+        "closed" if pr.merged == Some(true) => "merged",
+        "closed" => "closed",
+        _ => return Ok(()),
+    };
+    let transition = config.transitions.get(event).filter(|t| !t.is_empty());
+    let comment = config.comment_on.iter().any(|e| e == event);
+    if (transition.is_none() && !comment)
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let keys = keys(config, pr);
+    if keys.is_empty() {
+        return Ok(());
+    }
+    if config.base_url.is_empty() || config.api_token.is_none() {
+        error!("Jira sync is enabled but base_url or api_token is not configured");
+        return Ok(());
+    }
+
+    let vars = HashMap::from([
+        ("event", event.to_string()),
+        ("repo", repo.full_name.clone()),
+        ("number", pr.number.to_string()),
+        ("title", pr.title.clone()),
+        ("url", pr.html_url.clone()),
+        ("author", pr.user.login.clone()),
+        (
+            "branch",
+            pr.head
+                .as_ref()
+                .map_or_else(String::new, |h| h.ref_name.clone()),
+        ),
+    ]);
+    let body = template::render(&config.comment_template, &vars);
+
+    // transitions that are no longer offered and comments already posted are skipped,
+    // so a redelivery after a failure picks up where this one stopped
+    for key in &keys {
+        let jira_error = |e: JiraError| {
+            error!(
+                "Failed to update {} for {}#{}: {}",
+                key, repo.full_name, pr.number, e
+            );
+            NexusError::sink("Jira", e)
+        };
+        if let Some(name) = transition {
+            match apply_transition(state, config, key, name)
+                .await
+                .map_err(jira_error)?
+            {
+                Some(true) => info!(
+                    "Moved {} with {:?} for {}#{} ({})",
+                    key, name, repo.full_name, pr.number, event
+                ),
+                Some(false) => info!(
+                    "{} offers no {:?} transition from its current status",
+                    key, name
+                ),
+                None => {
+                    warn!(
+                        "{} from {}#{} is not a Jira issue",
+                        key, repo.full_name, pr.number
+                    );
+                    continue;
+                }
+            }
+        }
+        if comment
+            && add_comment(state, config, key, &body)
+                .await
+                .map_err(jira_error)?
+        {
+            info!(
+                "Commented on {} for {}#{} ({})",
+                key, repo.full_name, pr.number, event
+            );
+        }
+    }
+    Ok(())
+}
+
+// issue keys in the head branch and the title, e.g. `feature/ABC-123-login` or `ABC-123: Fix login`
+fn keys(config: &JiraConfig, pr: &PullRequest) -> Vec<String> {
+    let branch = pr.head.as_ref().map_or("", |h| h.ref_name.as_str());
+    let mut keys: Vec<String> = Vec::new();
+    for text in [branch, pr.title.as_str()] {
+        for captures in KEY.captures_iter(text) {
+            let key = &captures[0];
+            if (config.projects.is_empty() || config.projects.iter().any(|p| p == &captures[1]))
+                && !keys.iter().any(|k| k == key)
+            {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+// Some(false) when the issue's status has no transition by that name; None when the issue
+// does not exist
+async fn apply_transition(
+    state: &AppState,
+    config: &JiraConfig,
+    key: &str,
+    name: &str,
+) -> Result<Option<bool>, JiraError> {
+    let path = format!("/rest/api/2/issue/{}/transitions", key);
+    let Some(response) = send(config, state.http_client.get(url(config, &path))).await? else {
+        return Ok(None);
+    };
+    let transitions: Transitions = response.json().await?;
+    // either the transition's own name or the status it leads to
+    let Some(transition) = transitions.transitions.iter().find(|t| {
+        t.name.eq_ignore_ascii_case(name)
+            || t.to
+                .as_ref()
+                .is_some_and(|s| s.name.eq_ignore_ascii_case(name))
+    }) else {
+        return Ok(Some(false));
+    };
+    let request = state
+        .http_client
+        .post(url(config, &path))
+        .json(&json!({ "transition": { "id": transition.id } }));
+    Ok(send(config, request).await?.map(|_| true))
+}
+
+// false when the same comment is already on the issue
+async fn add_comment(
+    state: &AppState,
+    config: &JiraConfig,
+    key: &str,
+    body: &str,
+) -> Result<bool, JiraError> {
+    let path = format!("/rest/api/2/issue/{}/comment", key);
+    let request = state
+        .http_client
+        .get(url(config, &path))
+        .query(&[("orderBy", "-created"), ("maxResults", "100")]);
+    let Some(response) = send(config, request).await? else {
+        return Ok(false);
+    };
+    let existing: Comments = response.json().await?;
+    if existing
+        .comments
+        .iter()
+        .any(|c| c.body.trim() == body.trim())
+    {
+        return Ok(false);
+    }
+    let request = state
+        .http_client
+        .post(url(config, &path))
+        .json(&json!({ "body": body }));
+    Ok(send(config, request).await?.is_some())
+}
+
+fn url(config: &JiraConfig, path: &str) -> String {
+    format!("{}{}", config.base_url.trim_end_matches('/'), path)
+}
+
+// None on a 404
+async fn send(
+    config: &JiraConfig,
+    request: reqwest::RequestBuilder,
+) -> Result<Option<reqwest::Response>, JiraError> {
+    let token = config.api_token.as_deref().unwrap_or_default();
+    let request = match &config.email {
+        Some(email) => request.basic_auth(email, Some(token)),
+        None => request.bearer_auth(token),
+    };
+    let response = request
+        .header("Accept", "application/json")
+        .timeout(Duration::from_secs(30))
+        .send()
+        .await?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if status.is_success() {
+        return Ok(Some(response));
+    }
+    let message = response.text().await.unwrap_or_default();
+    Err(JiraError::Api {
+        status,
+        message: message.trim().to_string(),
+    })
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::{
+    config::{LabelSyncConfig, SyncedLabel},
+    error::NexusError,
+    github::{self, GithubError},
+    repos,
+    scheduler::Scheduler,
+    service::AppState,
+    webhook::{Label, WebhookPayload},
+};
+
+pub fn schedule(
+    scheduler: &mut Scheduler,
+    config: &LabelSyncConfig,
+) -> Result<(), cron::error::Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    scheduler.add(
+        "label_sync",
+        &config.schedule,
+        |state: Arc<AppState>| async move {
+            run(&state).await;
+        },
+    )
+}
+
+pub async fn run(state: &AppState) {
+    for repo in &repos::expand(state, &state.config.label_sync.repos).await {
+        sync(state, repo).await;
+    }
+}
+
+// someone created, edited or deleted a label in one of the repositories
+pub async fn on_label(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.label_sync;
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+    if repos::matches(&config.repos, &repo.full_name) {
+        sync(state, &repo.full_name).await;
+    }
+    Ok(())
+}
+
+async fn sync(state: &AppState, repo: &str) {
+    let Some(token) = state.github_token(repo).await else {
+        error!(
+            "Label sync is enabled but no GitHub token is available for {}",
+            repo
+        );
+        return;
+    };
+    match reconcile(state, &token, repo).await {
+        Ok(0) => {}
+        Ok(changes) => info!("Synced labels in {}: {} changes", repo, changes),
+        Err(e) => error!("Label sync failed for {}: {}", repo, e),
+    }
+}
+
+// returns how many labels were created, updated or deleted
+async fn reconcile(state: &AppState, token: &str, repo: &str) -> Result<usize, GithubError> {
+    let config = &state.config.label_sync;
+    let client = &state.github;
+    let existing = github::list_labels(client, token, repo).await?;
+    let mut changes = 0;
+
+    for wanted in config.labels.iter().map(canonical) {
+        // GitHub treats label names case-insensitively
+        let current = existing
+            .iter()
+            .find(|l| l.name.eq_ignore_ascii_case(&wanted.name));
+        match current {
+            None => github::create_label(client, token, repo, &wanted).await?,
+            Some(current) if differs(current, &wanted) => {
+                github::update_label(client, token, repo, &current.name, &wanted).await?
+            }
+            Some(_) => continue,
+        }
+        changes += 1;
+    }
+
+    if config.prune {
+        for label in &existing {
+            let wanted = config
+                .labels
+                .iter()
+                .any(|l| l.name.eq_ignore_ascii_case(&label.name));
+            if !wanted {
+                github::delete_label(client, token, repo, &label.name).await?;
+                changes += 1;
+            }
+        }
+    }
+    Ok(changes)
+}
+
+fn canonical(label: &SyncedLabel) -> Label {
+    Label {
+        name: label.name.clone(),
+        color: Some(label.color.trim_start_matches('#').to_lowercase()),
+        description: Some(label.description.clone()),
+    }
+}
+
+fn differs(current: &Label, wanted: &Label) -> bool {
+    let color = current.color.as_deref().map(str::to_lowercase);
+    current.name != wanted.name
+        || color != wanted.color
+        || current.description.as_deref().unwrap_or_default()
+            != wanted.description.as_deref().unwrap_or_default()
+}
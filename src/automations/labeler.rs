@@ -0,0 +1,89 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::PolicyAction,
+    error::NexusError,
+    github,
+    policy::{self, Subject},
+    service::AppState,
+    webhook::{PullRequest, Repository},
+};
+
+pub async fn on_pull_request_changed(
+    state: &AppState,
+    pr: &PullRequest,
+    repo: &Repository,
+) -> Result<(), NexusError> {
+    let config = &state.config.labeler;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let rules: Vec<(&String, GlobSet)> = config
+        .rules_for_repo(&repo.full_name)
+        .filter_map(|(label, patterns)| build_globset(label, patterns).map(|set| (label, set)))
+        .collect();
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Labeler is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    let files = github::list_pull_request_files(&state.github, &token, &repo.full_name, pr.number)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to list files for {}#{}: {}",
+                repo.full_name, pr.number, e
+            );
+            NexusError::from(e)
+        })?;
+
+    let mut labels: Vec<String> = rules
+        .iter()
+        .filter(|(_, set)| files.iter().any(|f| set.is_match(&f.filename)))
+        .map(|(label, _)| label.to_string())
+        .collect();
+    labels.sort();
+    labels.dedup();
+
+    if labels.is_empty() {
+        return Ok(());
+    }
+    let subject = Subject::pull_request(&repo.full_name, pr);
+    if !policy::allows(state, &token, &[PolicyAction::Label], &subject).await {
+        return Ok(());
+    }
+
+    github::add_labels(&state.github, &token, &repo.full_name, pr.number, &labels)
+        .await
+        .map_err(|e| {
+            error!("Failed to label {}#{}: {}", repo.full_name, pr.number, e);
+            NexusError::from(e)
+        })?;
+
+    info!(
+        "Applied labels [{}] to {}#{}",
+        labels.join(", "),
+        repo.full_name,
+        pr.number
+    );
+    Ok(())
+}
+
+fn build_globset(label: &str, patterns: &[String]) -> Option<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Invalid glob {:?} for label {}: {}", pattern, label, e),
+        }
+    }
+    builder.build().ok()
+}
@@ -0,0 +1,496 @@
+use axum::{body::Bytes, extract::State};
+use http::{HeaderMap, StatusCode};
+use regex::Regex;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{LinearConfig, PolicyAction},
+    error::NexusError,
+    github::{self, graphql::Query},
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::{self, PullRequest, WebhookPayload},
+};
+
+// Linear's magic words: the closing ones move the issue along with the pull request,
+// the others only link it
+static MAGIC_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:(close[sd]?|closing|fix(?:e[sd]|ing)?|resolv(?:e[sd]?|ing)|complet(?:e[sd]?|ing))|refs?|references|part of|related to|contributes to|towards?)\b:?",
+    )
+    .unwrap()
+});
+// an identifier, or a link to the issue in Linear
+static ISSUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:https://linear\.app/[\w-]+/issue/)?([A-Z][A-Z0-9]*-[1-9][0-9]*)\b[^\s,]*")
+        .unwrap()
+});
+static IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b[A-Z][A-Z0-9]*-[1-9][0-9]*\b").unwrap());
+// Linear names branches after the lowercased identifier, e.g. `jane/eng-123-fix-login`
+static BRANCH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b[a-z][a-z0-9]*-[1-9][0-9]*\b").unwrap());
+static PULL_REQUEST_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^https?://[^/]+/([^/]+/[^/]+)/pull/([0-9]+)/?$").unwrap());
+
+const ISSUE_QUERY: &str = "query($id: String!) {
+  issue(id: $id) {
+    id identifier url
+    state { name }
+    team { key states { nodes { id name } } }
+    attachments { nodes { url } }
+  }
+}";
+const ATTACH: &str = "mutation($input: AttachmentCreateInput!) {
+  attachmentCreate(input: $input) { success }
+}";
+const MOVE: &str = "mutation($id: String!, $stateId: String!) {
+  issueUpdate(id: $id, input: { stateId: $stateId }) { success }
+}";
+
+#[derive(Debug, thiserror::Error)]
+pub enum LinearError {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    // an answer that is not GraphQL, e.g. from a proxy in between
+    #[error("Linear returned {status}: {message}")]
+    Api { status: StatusCode, message: String },
+    // Linear answers a failed query with a 400 and an `errors` array
+    #[error("{}", graphql_errors(.0))]
+    GraphQl(Vec<String>),
+}
+
+fn graphql_errors(messages: &[String]) -> String {
+    match messages.is_empty() {
+        true => "Linear returned no data".to_string(),
+        false => format!("Linear GraphQL errors: {}", messages.join("; ")),
+    }
+}
+
+/// An issue mentioned by a pull request.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub identifier: String,
+    // mentioned with a closing magic word, in the title or in the branch name
+    pub closes: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueData {
+    issue: Option<LinearIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LinearIssue {
+    id: String,
+    identifier: String,
+    url: String,
+    state: Named,
+    team: Team,
+    attachments: Nodes<Attachment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Team {
+    key: String,
+    states: Nodes<WorkflowState>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowState {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Attachment {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+// what Linear POSTs to /linear
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    action: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: Value,
+    #[serde(default)]
+    updated_from: Option<Value>,
+    // milliseconds
+    webhook_timestamp: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueEvent {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentEvent {
+    body: String,
+    issue_id: String,
+    user: Option<Named>,
+}
+
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.linear;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    let event = match action.as_str() {
+        "opened" | "reopened" | "edited" => action.as_str(),
+        "closed" if pr.merged == Some(true) => "merged",
+        "closed" => "closed",
+        _ => return Ok(()),
+    };
+    if !config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name) {
+        return Ok(());
+    }
+    let references: Vec<Reference> = references(pr)
+        .into_iter()
+        .filter(|r| {
+            config.teams.is_empty()
+                || r.identifier
+                    .split_once('-')
+                    .is_some_and(|(team, _)| config.teams.iter().any(|t| t == team))
+        })
+        .collect();
+    if references.is_empty() {
+        return Ok(());
+    }
+    if config.api_key.is_none() {
+        error!("Linear sync is enabled but no api_key is configured");
+        return Ok(());
+    }
+    let target = config.states.get(event).filter(|s| !s.is_empty());
+
+    // linking is an upsert on the URL and issues already in the target state are left
+    // alone, so redeliveries are harmless
+    for reference in &references {
+        let linear_error = |e: LinearError| {
+            error!(
+                "Failed to update {} for {}#{}: {}",
+                reference.identifier, repo.full_name, pr.number, e
+            );
+            NexusError::sink("Linear", e)
+        };
+        let Some(issue) = issue(state, config, &reference.identifier)
+            .await
+            .map_err(linear_error)?
+        else {
+            debug!(
+                "{} from {}#{} is not a Linear issue",
+                reference.identifier, repo.full_name, pr.number
+            );
+            continue;
+        };
+        if config.link {
+            let status = match event {
+                "merged" => "Merged",
+                "closed" => "Closed",
+                _ => "Open",
+            };
+            let query = Query::new(ATTACH).variable(
+                "input",
+                json!({
+                    "issueId": issue.id,
+                    "url": pr.html_url,
+                    "title": format!("{}#{} {}", repo.full_name, pr.number, pr.title),
+                    "subtitle": status,
+                }),
+            );
+            graphql::<Value>(state, config, &query)
+                .await
+                .map_err(linear_error)?;
+        }
+        let Some(target) = target.filter(|_| reference.closes) else {
+            continue;
+        };
+        if issue.state.name.eq_ignore_ascii_case(target) {
+            continue;
+        }
+        let Some(workflow_state) = issue
+            .team
+            .states
+            .nodes
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(target))
+        else {
+            warn!(
+                "Team {} in Linear has no {:?} state for {}",
+                issue.team.key, target, issue.identifier
+            );
+            continue;
+        };
+        let query = Query::new(MOVE)
+            .variable("id", &issue.id)
+            .variable("stateId", &workflow_state.id);
+        graphql::<Value>(state, config, &query)
+            .await
+            .map_err(linear_error)?;
+        info!(
+            "Moved {} from {} to {} for {}#{} ({})",
+            issue.identifier,
+            issue.state.name,
+            workflow_state.name,
+            repo.full_name,
+            pr.number,
+            event
+        );
+    }
+    Ok(())
+}
+
+// the branch and the title close what they name; the description uses magic words
+pub fn references(pr: &PullRequest) -> Vec<Reference> {
+    let mut references: Vec<Reference> = Vec::new();
+    let mut add = |identifier: String, closes: bool| match references
+        .iter_mut()
+        .find(|r| r.identifier == identifier)
+    {
+        Some(existing) => existing.closes |= closes,
+        None => references.push(Reference { identifier, closes }),
+    };
+    if let Some(head) = &pr.head {
+        for found in BRANCH.find_iter(&head.ref_name) {
+            add(found.as_str().to_uppercase(), true);
+        }
+    }
+    for found in IDENTIFIER.find_iter(&pr.title) {
+        add(found.as_str().to_string(), true);
+    }
+    let body = pr.body.as_deref().unwrap_or_default();
+    for captures in MAGIC_WORD.captures_iter(body) {
+        let closes = captures.get(1).is_some();
+        // a list may follow: `Fixes ENG-1, ENG-2 and ENG-3`
+        let mut rest = &body[captures.get(0).unwrap().end()..];
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+            if let Some(after) = rest.strip_prefix("and ") {
+                rest = after;
+                continue;
+            }
+            let Some(issue) = ISSUE.captures(rest) else {
+                break;
+            };
+            add(issue[1].to_string(), closes);
+            rest = &rest[issue.get(0).unwrap().end()..];
+        }
+    }
+    references
+}
+
+/// POST /linear: state changes and comments on linked issues, posted to their pull requests.
+pub async fn webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, NexusError> {
+    let config = &state.config.linear;
+    if !config.enabled {
+        return Err(NexusError::NotFound(
+            "Linear sync is not enabled".to_string(),
+        ));
+    }
+    let Some(secret) = &config.webhook_secret else {
+        warn!("Refusing a Linear webhook: no webhook_secret is configured");
+        return Err(NexusError::Unverified(
+            "no Linear webhook_secret is configured",
+        ));
+    };
+    let signature = headers
+        .get("linear-signature")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !webhook::verify_signature(secret, &body, &format!("sha256={}", signature)) {
+        warn!("Refusing a Linear webhook with an invalid signature");
+        return Err(NexusError::Unverified("invalid linear-signature"));
+    }
+    let event: Event = serde_json::from_slice(&body).inspect_err(|e| {
+        warn!("Invalid Linear webhook payload: {}", e);
+    })?;
+    // a signed payload is only accepted for a minute, against replays
+    if let Some(sent) = event.webhook_timestamp
+        && (chrono::Utc::now().timestamp_millis() - sent).abs() > 60_000
+    {
+        warn!("Refusing a Linear webhook sent at {}", sent);
+        return Err(NexusError::Unverified(
+            "the Linear webhook was sent more than a minute ago",
+        ));
+    }
+
+    let (issue_id, text) = match (event.kind.as_str(), event.action.as_str()) {
+        ("Issue", "update") if config.post_state_changes => {
+            let state_changed = event
+                .updated_from
+                .as_ref()
+                .is_some_and(|from| from.get("stateId").is_some());
+            let data = serde_json::from_value::<IssueEvent>(event.data)?;
+            if !state_changed {
+                return Ok(StatusCode::OK);
+            }
+            (data.id, None)
+        }
+        ("Comment", "create") if config.post_comments => {
+            let data = serde_json::from_value::<CommentEvent>(event.data)?;
+            let quoted = data
+                .body
+                .lines()
+                .map(|line| format!("> {}", line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let author = data.user.map_or_else(|| "Someone".to_string(), |u| u.name);
+            (data.issue_id, Some((author, quoted)))
+        }
+        _ => return Ok(StatusCode::OK),
+    };
+
+    let issue = match issue(&state, config, &issue_id).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return Ok(StatusCode::OK),
+        Err(e) => {
+            error!("Failed to look up Linear issue {}: {}", issue_id, e);
+            return Err(NexusError::sink("Linear", e));
+        }
+    };
+    let comment = match text {
+        Some((author, quoted)) => format!(
+            "{} commented on [{}]({}) in Linear:\n\n{}",
+            author, issue.identifier, issue.url, quoted
+        ),
+        None => format!(
+            "[{}]({}) moved to **{}** in Linear.",
+            issue.identifier, issue.url, issue.state.name
+        ),
+    };
+
+    let mut results = Vec::new();
+    for attachment in &issue.attachments.nodes {
+        let Some(captures) = PULL_REQUEST_URL.captures(&attachment.url) else {
+            continue;
+        };
+        let repo = &captures[1];
+        let Ok(number) = captures[2].parse::<u64>() else {
+            continue;
+        };
+        if !config.repos.is_empty() && !repos::matches(&config.repos, repo) {
+            continue;
+        }
+        if let Err(e) = post(&state, repo, number, &comment).await {
+            error!(
+                "Failed to post {} from Linear to {}#{}: {}",
+                issue.identifier, repo, number, e
+            );
+            results.push(Err(NexusError::from(e)));
+        }
+    }
+    NexusError::collect(results).map(|()| StatusCode::OK)
+}
+
+async fn post(
+    state: &AppState,
+    repo: &str,
+    number: u64,
+    comment: &str,
+) -> Result<(), github::GithubError> {
+    let Some(token) = state.github_token(repo).await else {
+        error!("Linear sync is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let pr = github::get_pull_request(client, &token, repo, number).await?;
+    let subject = Subject::pull_request(repo, &pr);
+    if !policy::allows(state, &token, &[PolicyAction::Comment], &subject).await {
+        return Ok(());
+    }
+    github::create_issue_comment(client, &token, repo, number, comment).await?;
+    info!("Posted a Linear update to {}#{}", repo, number);
+    Ok(())
+}
+
+// None when there is no such issue, e.g. `UTF-8` read from a title
+async fn issue(
+    state: &AppState,
+    config: &LinearConfig,
+    id: &str,
+) -> Result<Option<LinearIssue>, LinearError> {
+    let query = Query::new(ISSUE_QUERY).variable("id", id);
+    match graphql::<IssueData>(state, config, &query).await {
+        Ok(data) => Ok(data.issue),
+        Err(LinearError::GraphQl(messages))
+            if messages
+                .iter()
+                .any(|m| m.to_lowercase().contains("not found")) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn graphql<T: DeserializeOwned>(
+    state: &AppState,
+    config: &LinearConfig,
+    query: &Query,
+) -> Result<T, LinearError> {
+    let response = state
+        .http_client
+        .post(&config.api_url)
+        // personal API keys are sent as they are, without `Bearer`
+        .header(
+            "Authorization",
+            config.api_key.as_deref().unwrap_or_default(),
+        )
+        .timeout(Duration::from_secs(30))
+        .json(query)
+        .send()
+        .await?;
+    let status = response.status();
+    let text = response.text().await?;
+    let response: Response<T> = serde_json::from_str(&text).map_err(|_| LinearError::Api {
+        status,
+        message: text.trim().to_string(),
+    })?;
+    match response.data {
+        Some(data) if response.errors.is_empty() => Ok(data),
+        _ => Err(LinearError::GraphQl(
+            response.errors.into_iter().map(|e| e.message).collect(),
+        )),
+    }
+}
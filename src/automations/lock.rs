@@ -0,0 +1,110 @@
+use chrono::{Duration, Utc};
+use std::{collections::HashSet, sync::Arc};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{LockConfig, PolicyAction},
+    github::{self, GithubError},
+    policy::{self, Subject},
+    repos,
+    scheduler::Scheduler,
+    service::AppState,
+};
+
+pub fn schedule(scheduler: &mut Scheduler, config: &LockConfig) -> Result<(), cron::error::Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    scheduler.add(
+        "lock",
+        &config.schedule,
+        |state: Arc<AppState>| async move {
+            run(&state).await;
+        },
+    )
+}
+
+pub async fn run(state: &AppState) {
+    let config = &state.config.lock;
+
+    for repo in &repos::expand(state, &config.repos).await {
+        let Some(token) = state.github_token(repo).await else {
+            error!(
+                "Locking is enabled but no GitHub token is available for {}",
+                repo
+            );
+            continue;
+        };
+        match sweep_repo(state, config, &token, repo).await {
+            Ok(()) => {}
+            Err(GithubError::RateLimited { reset }) => {
+                // the rest waits for the next run
+                warn!(
+                    "Stopped locking in {}: rate limited until {}",
+                    repo,
+                    reset.map_or("unknown".to_string(), |r| r.to_rfc3339())
+                );
+                return;
+            }
+            Err(e) => error!("Locking failed for {}: {}", repo, e),
+        }
+    }
+}
+
+// the first page is searched again after each batch, since locked items drop out of it
+async fn sweep_repo(
+    state: &AppState,
+    config: &LockConfig,
+    token: &str,
+    repo: &str,
+) -> Result<(), GithubError> {
+    let client = &state.github;
+    let cutoff = Utc::now() - Duration::days(config.days_since_close);
+    let mut query = format!(
+        "repo:{} is:closed is:unlocked closed:<{}",
+        repo,
+        cutoff.format("%Y-%m-%d")
+    );
+    if !config.include_pull_requests {
+        query.push_str(" is:issue");
+    }
+    for label in &config.exempt_labels {
+        query.push_str(&format!(" -label:\"{}\"", label));
+    }
+    let mut actions = vec![PolicyAction::Lock];
+    if !config.comment.is_empty() {
+        actions.push(PolicyAction::Comment);
+    }
+    let reason = Some(config.lock_reason.as_str()).filter(|r| !r.is_empty());
+
+    let mut seen = HashSet::new();
+    let mut locked = 0;
+    'pages: loop {
+        let batch = github::search_issues(client, token, &query, 100).await?;
+        // refused by policy, or still listed while the search index catches up
+        let fresh: Vec<_> = batch
+            .into_iter()
+            .filter(|issue| seen.insert(issue.number))
+            .collect();
+        if fresh.is_empty() {
+            break;
+        }
+        for issue in &fresh {
+            if locked >= config.limit {
+                break 'pages;
+            }
+            if !policy::allows(state, token, &actions, &Subject::issue(repo, issue)).await {
+                continue;
+            }
+            if !config.comment.is_empty() {
+                github::create_issue_comment(client, token, repo, issue.number, &config.comment)
+                    .await?;
+            }
+            github::lock_issue(client, token, repo, issue.number, reason).await?;
+            locked += 1;
+        }
+    }
+
+    info!("Lock sweep for {}: {} locked", repo, locked);
+    Ok(())
+}
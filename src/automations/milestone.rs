@@ -0,0 +1,143 @@
+use regex::Regex;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::PolicyAction,
+    error::NexusError,
+    github::{self, GithubError, graphql},
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::{Milestone, WebhookPayload},
+};
+
+pub async fn on_pull_request_closed(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.milestone;
+    if !config.enabled || payload.action.as_deref() != Some("closed") {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo)) = (&payload.pull_request, &payload.repository) else {
+        return Ok(());
+    };
+    if pr.merged != Some(true)
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let pattern = match Regex::new(&config.pattern) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            warn!("Invalid milestone pattern: {}", e);
+            return Ok(());
+        }
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Milestones are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to set the milestone of {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    };
+
+    let milestones = github::list_open_milestones(client, &token, &repo.full_name)
+        .await
+        .map_err(api_error)?;
+    let Some(milestone) = next(&milestones, &pattern) else {
+        debug!(
+            "No open milestone in {} matches {}",
+            repo.full_name, config.pattern
+        );
+        return Ok(());
+    };
+
+    let keep = pr.milestone.is_some() && !config.overwrite;
+    if !keep
+        && pr.milestone.as_ref().map(|m| m.number) != Some(milestone.number)
+        && policy::allows(
+            state,
+            &token,
+            &[PolicyAction::Milestone],
+            &Subject::pull_request(&repo.full_name, pr),
+        )
+        .await
+    {
+        github::set_milestone(client, &token, &repo.full_name, pr.number, milestone.number)
+            .await
+            .map_err(api_error)?;
+        info!(
+            "Added {}#{} to milestone {}",
+            repo.full_name, pr.number, milestone.title
+        );
+    }
+    if !config.issues {
+        return Ok(());
+    }
+
+    let issues = graphql::closing_issues(client, &token, &repo.full_name, pr.number)
+        .await
+        .map_err(api_error)?;
+    for issue in &issues {
+        // milestones belong to one repository
+        if !issue
+            .repository
+            .name_with_owner
+            .eq_ignore_ascii_case(&repo.full_name)
+        {
+            continue;
+        }
+        match &issue.milestone {
+            Some(m) if m.number == milestone.number => continue,
+            Some(_) if !config.overwrite => continue,
+            _ => {}
+        }
+        let subject = Subject {
+            repo: &repo.full_name,
+            number: issue.number,
+            author: issue.author.as_ref().map_or("ghost", |a| a.login.as_str()),
+            labels: issue.labels.nodes.iter().map(|l| l.name.as_str()).collect(),
+            pull_request: false,
+        };
+        if !policy::allows(state, &token, &[PolicyAction::Milestone], &subject).await {
+            continue;
+        }
+        github::set_milestone(
+            client,
+            &token,
+            &repo.full_name,
+            issue.number,
+            milestone.number,
+        )
+        .await
+        .map_err(api_error)?;
+        info!(
+            "Added {}#{}, closed by #{}, to milestone {}",
+            repo.full_name, issue.number, pr.number, milestone.title
+        );
+    }
+    Ok(())
+}
+
+// the matching milestone due soonest; undated ones come after, lowest version first
+fn next<'a>(milestones: &'a [Milestone], pattern: &Regex) -> Option<&'a Milestone> {
+    milestones
+        .iter()
+        .filter(|m| pattern.is_match(&m.title))
+        .min_by_key(|m| (m.due_on.is_none(), m.due_on, version(&m.title)))
+}
+
+// the numbers in a title, so `1.10` sorts after `1.9`
+fn version(title: &str) -> Vec<u64> {
+    title
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
@@ -0,0 +1,254 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{error, info};
+
+use crate::{
+    command::CommandError,
+    config::{MirrorMode, MirrorRepo, redacted_url},
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+// pull request refs are left out: GitHub refuses pushes to them on the mirror side
+const REFSPECS: &[&str] = &["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+// remotes are kept redacted, as these end up in logs and alerts
+#[derive(Debug, thiserror::Error)]
+pub enum MirrorError {
+    #[error("could not create {}: {source}", .path.display())]
+    CacheDir {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{0}")]
+    Init(#[source] CommandError),
+    #[error("fetching {remote}: {source}")]
+    Fetch {
+        remote: String,
+        #[source]
+        source: CommandError,
+    },
+    #[error("pushing to {remote}: {source}")]
+    Push {
+        remote: String,
+        #[source]
+        source: CommandError,
+    },
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+/// One sync at a time per mirrored repository, since they share a cached clone.
+#[derive(Clone, Default)]
+pub struct Mirrors {
+    locks: Arc<Mutex<HashMap<usize, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl Mirrors {
+    fn lock(&self, index: usize) -> Arc<tokio::sync::Mutex<()>> {
+        self.locks.lock().unwrap().entry(index).or_default().clone()
+    }
+}
+
+pub async fn on_push(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.mirror;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(repo), Some(git_ref)) = (&payload.repository, &payload.ref_name) else {
+        return Ok(());
+    };
+
+    // pushes to slow remotes outlive GitHub's webhook timeout, so they run in the background
+    for (index, rule) in config.repos.iter().enumerate() {
+        if rule.repo != repo.full_name {
+            continue;
+        }
+        let state = state.clone();
+        let rule = rule.clone();
+        let git_ref = git_ref.clone();
+        let deleted = payload.deleted;
+        tokio::spawn(async move {
+            let lock = state.mirrors.lock(index);
+            let _syncing = lock.lock().await;
+
+            let timeout_secs = state.config.mirror.timeout_secs;
+            let failures = tokio::time::timeout(
+                Duration::from_secs(timeout_secs),
+                sync(&state, &rule, &git_ref, deleted),
+            )
+            .await
+            .unwrap_or_else(|_| vec![MirrorError::TimedOut(timeout_secs)]);
+
+            if failures.is_empty() {
+                info!(
+                    "Mirrored {} of {} to {} remotes",
+                    git_ref,
+                    rule.repo,
+                    rule.remotes.len()
+                );
+            }
+            for failure in failures {
+                error!("Mirroring {} failed: {}", rule.repo, failure);
+                alert(&state, &rule.repo, &failure.to_string()).await;
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// returns what went wrong, one entry per failed step or remote
+async fn sync(
+    state: &AppState,
+    rule: &MirrorRepo,
+    git_ref: &str,
+    deleted: bool,
+) -> Vec<MirrorError> {
+    let cache = cache_path(&state.config.mirror.cache_dir, &rule.repo);
+    let source = rule
+        .source
+        .clone()
+        .unwrap_or_else(|| format!("{}/{}.git", state.config.github.web_url(), rule.repo));
+    let token = state.github_token(&rule.repo).await;
+
+    if !cache.exists() {
+        if let Some(parent) = cache.parent()
+            && let Err(e) = std::fs::create_dir_all(parent)
+        {
+            return vec![MirrorError::CacheDir {
+                path: parent.to_path_buf(),
+                source: e,
+            }];
+        }
+        let cache = cache.display().to_string();
+        if let Err(e) = git(&["init", "--bare", "--quiet", &cache], None, Vec::new()).await {
+            return vec![MirrorError::Init(e)];
+        }
+    }
+    let mut fetch = vec!["fetch", "--prune", "--quiet", source.as_str()];
+    fetch.extend_from_slice(REFSPECS);
+    let env = token.map(|t| https_auth(&t)).unwrap_or_default();
+    if let Err(e) = git(&fetch, Some(&cache), env).await {
+        let redacted = redacted_url(&source);
+        return vec![MirrorError::Fetch {
+            source: e.redact(&source, &redacted),
+            remote: redacted,
+        }];
+    }
+
+    let mut failures = Vec::new();
+    for remote in &rule.remotes {
+        let mut args = vec!["push".to_string(), "--quiet".to_string()];
+        match rule.mode {
+            MirrorMode::Mirror => args.extend(["--mirror".to_string(), remote.clone()]),
+            MirrorMode::Refs => {
+                let refspec = match deleted {
+                    true => format!(":{}", git_ref),
+                    false => format!("{}:{}", git_ref, git_ref),
+                };
+                args.extend(["--force".to_string(), remote.clone(), refspec]);
+            }
+        }
+        if let Err(e) = git(&args, Some(&cache), remote_auth(rule, remote)).await {
+            // git quotes the URL in its complaint too
+            let redacted = redacted_url(remote);
+            failures.push(MirrorError::Push {
+                source: e.redact(remote, &redacted),
+                remote: redacted,
+            });
+        }
+    }
+    failures
+}
+
+// `cache_dir/owner/name.git`
+fn cache_path(cache_dir: &Path, repo: &str) -> PathBuf {
+    let (owner, name) = repo.split_once('/').unwrap_or(("", repo));
+    cache_dir.join(owner).join(format!("{}.git", name))
+}
+
+// passed through the environment so tokens stay out of the process list
+fn https_auth(token: &str) -> Vec<(&'static str, String)> {
+    let credentials = STANDARD.encode(format!("x-access-token:{}", token));
+    vec![
+        ("GIT_CONFIG_COUNT", "1".to_string()),
+        ("GIT_CONFIG_KEY_0", "http.extraHeader".to_string()),
+        (
+            "GIT_CONFIG_VALUE_0",
+            format!("Authorization: Basic {}", credentials),
+        ),
+    ]
+}
+
+fn remote_auth(rule: &MirrorRepo, remote: &str) -> Vec<(&'static str, String)> {
+    if remote.starts_with("https://") {
+        return rule.token.as_deref().map(https_auth).unwrap_or_default();
+    }
+    match &rule.ssh_key {
+        Some(key) => vec![(
+            "GIT_SSH_COMMAND",
+            format!(
+                "ssh -i '{}' -o IdentitiesOnly=yes -o StrictHostKeyChecking=accept-new",
+                key.display()
+            ),
+        )],
+        None => Vec::new(),
+    }
+}
+
+async fn git<S: AsRef<str>>(
+    args: &[S],
+    dir: Option<&Path>,
+    env: Vec<(&'static str, String)>,
+) -> Result<(), CommandError> {
+    let mut command = tokio::process::Command::new("git");
+    command
+        .args(args.iter().map(|arg| arg.as_ref()))
+        .envs(env)
+        // fail instead of waiting for a password nobody will type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|e| CommandError::spawn("git", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // git ends with advice about access rights, so its first complaint is more telling
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let complaint = stderr
+        .lines()
+        .find(|l| l.starts_with("fatal:") || l.starts_with("error:"))
+        .or_else(|| stderr.lines().rev().find(|l| !l.trim().is_empty()));
+    Err(CommandError::complained("git", output.status, complaint))
+}
+
+async fn alert(state: &AppState, repo: &str, failure: &str) {
+    let config = &state.config.mirror;
+    let Some(url) = &config.alert_url else {
+        return;
+    };
+    let request = Request::Http {
+        url: url.clone(),
+        headers: config.alert_headers.clone(),
+        body: json!({ "text": format!("Mirroring {} failed: {}", repo, failure) }),
+    };
+    if let Err(e) = outbound::deliver(state, Delivery::new("mirror:alert", request)).await {
+        error!("Failed to send the mirror alert for {}: {}", repo, e);
+    }
+}
@@ -0,0 +1,37 @@
+pub mod automerge;
+pub mod backport;
+pub mod branch_cleanup;
+pub mod changelog;
+pub mod chatops;
+pub mod commit_messages;
+pub mod dco;
+pub mod debounce;
+pub mod deploy;
+pub mod digest;
+pub mod exec;
+pub mod fanout;
+pub mod forward;
+pub mod functions;
+pub mod grafana;
+pub mod jira;
+pub mod label_sync;
+pub mod labeler;
+pub mod linear;
+pub mod lock;
+pub mod milestone;
+pub mod mirror;
+pub mod notify;
+pub mod pr_size;
+pub mod pr_title;
+pub mod push_deploy;
+pub mod rebuild;
+pub mod release_notes;
+pub mod reports;
+pub mod rerun;
+pub mod reviewers;
+pub mod scripts;
+pub mod signatures;
+pub mod stale;
+pub mod statuspage;
+pub mod triage;
+pub mod welcome;
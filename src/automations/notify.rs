@@ -0,0 +1,165 @@
+use regex::Regex;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::LazyLock,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    codeowners,
+    config::{ChannelFormat, NotifyChannel},
+    error::NexusError,
+    github::{self, GithubError},
+    outbound::{self, Delivery, Request},
+    repos,
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+// Slack's `<url|label>` links, rewritten as Markdown for Teams
+static SLACK_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([^|>\s]+)\|([^>]*)>").expect("valid regex"));
+
+// pull_request and pull_request_review events; each channel mapped to an owner of the
+// changed paths gets one message naming its owners
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.notify;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if !config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name) {
+        return Ok(());
+    }
+
+    let mut vars = HashMap::from([
+        ("repo", repo.full_name.clone()),
+        ("number", pr.number.to_string()),
+        ("title", state.redactor.text(&pr.title).into_owned()),
+        ("url", pr.html_url.clone()),
+        ("author", pr.user.login.clone()),
+        ("action", action.replace('_', " ")),
+    ]);
+    let template = match event_type {
+        "pull_request" if config.actions.contains(action) && !pr.draft => {
+            &config.pull_request_template
+        }
+        "pull_request_review" if action == "submitted" => {
+            let Some(review) = &payload.review else {
+                return Ok(());
+            };
+            let review_state = review.state.to_lowercase();
+            if !config.review_states.contains(&review_state) {
+                return Ok(());
+            }
+            vars.insert("reviewer", review.user.login.clone());
+            vars.insert("state", review_state.replace('_', " "));
+            &config.review_template
+        }
+        _ => return Ok(()),
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Notifications are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to find the owners of {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    };
+    let base_ref = pr.base.as_ref().map(|b| b.ref_name.as_str());
+    let owners: BTreeSet<String> =
+        match codeowners::fetch(&state.github, &token, &repo.full_name, base_ref)
+            .await
+            .map_err(api_error)?
+        {
+            Some(codeowners) => {
+                github::list_pull_request_files(&state.github, &token, &repo.full_name, pr.number)
+                    .await
+                    .map_err(api_error)?
+                    .iter()
+                    .flat_map(|f| codeowners.owners_for(&f.filename))
+                    .cloned()
+                    .collect()
+            }
+            None => {
+                debug!("No CODEOWNERS file in {}", repo.full_name);
+                BTreeSet::new()
+            }
+        };
+
+    let mut channels: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for owner in &owners {
+        if let Some(channel) = config.owners.get(owner) {
+            channels.entry(channel).or_default().push(owner);
+        }
+    }
+    if channels.is_empty()
+        && let Some(channel) = &config.default_channel
+    {
+        channels.insert(channel, owners.iter().map(String::as_str).collect());
+    }
+
+    for (name, owners) in channels {
+        let Some(channel) = config.channels.get(name) else {
+            warn!("Notification channel {} is not configured", name);
+            continue;
+        };
+        let mut vars = vars.clone();
+        vars.insert(
+            "owners",
+            if owners.is_empty() {
+                repo.full_name.clone()
+            } else {
+                owners.join(", ")
+            },
+        );
+        let text = template::render(template, &vars);
+        let delivery = Delivery::new(format!("notify:{}", name), request(channel, &text))
+            .idempotent(delivery_id);
+        let state = state.clone();
+        let name = name.to_string();
+        let subject = format!("{}#{}", repo.full_name, pr.number);
+        tokio::spawn(async move {
+            match outbound::deliver(&state, delivery).await {
+                Ok(()) => info!("Notified {} about {}", name, subject),
+                Err(e) => error!("Failed to notify {} about {}: {}", name, subject, e),
+            }
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn request(channel: &NotifyChannel, text: &str) -> Request {
+    let body = match channel.format {
+        ChannelFormat::Slack => json!({ "text": text }),
+        ChannelFormat::Teams => {
+            let text = SLACK_LINK.replace_all(text, "[$2]($1)");
+            json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": text,
+                "text": text,
+            })
+        }
+    };
+    Request::Http {
+        url: channel.url.clone(),
+        headers: channel.headers.clone(),
+        body,
+    }
+}
@@ -0,0 +1,132 @@
+use globset::{Glob, GlobSetBuilder};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{PolicyAction, PrSizeConfig},
+    error::NexusError,
+    github::{self, GithubError},
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+const ACTIONS: &[&str] = &["opened", "reopened", "synchronize"];
+
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.pr_size;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if !ACTIONS.contains(&action.as_str())
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Size labels are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!("Failed to size {}#{}: {}", repo.full_name, pr.number, e);
+        NexusError::from(e)
+    };
+
+    let mut ignored = GlobSetBuilder::new();
+    for pattern in &config.ignore_paths {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                ignored.add(glob);
+            }
+            Err(e) => warn!("Invalid pr_size ignore path {:?}: {}", pattern, e),
+        }
+    }
+    let ignored = ignored.build().unwrap_or_default();
+    let files: Vec<_> = github::list_pull_request_files(client, &token, &repo.full_name, pr.number)
+        .await
+        .map_err(api_error)?
+        .into_iter()
+        .filter(|f| !ignored.is_match(&f.filename))
+        .collect();
+    let lines: u64 = files.iter().map(|f| f.additions + f.deletions).sum();
+
+    let Some(size) = label(config, lines) else {
+        return Ok(());
+    };
+    let stale: Vec<&str> = pr
+        .labels
+        .iter()
+        .map(|l| l.name.as_str())
+        .filter(|name| name.starts_with(&config.label_prefix) && !name.eq_ignore_ascii_case(&size))
+        .collect();
+    if pr.has_label(&size) && stale.is_empty() {
+        return Ok(());
+    }
+
+    let subject = Subject::pull_request(&repo.full_name, pr);
+    if !stale.is_empty() && policy::allows(state, &token, &[PolicyAction::Unlabel], &subject).await
+    {
+        for name in &stale {
+            github::remove_label(client, &token, &repo.full_name, pr.number, name)
+                .await
+                .map_err(api_error)?;
+        }
+    }
+    if pr.has_label(&size) || !policy::allows(state, &token, &[PolicyAction::Label], &subject).await
+    {
+        return Ok(());
+    }
+    github::add_labels(
+        client,
+        &token,
+        &repo.full_name,
+        pr.number,
+        std::slice::from_ref(&size),
+    )
+    .await
+    .map_err(api_error)?;
+    info!(
+        "Labelled {}#{} {} ({} lines in {} files)",
+        repo.full_name,
+        pr.number,
+        size,
+        lines,
+        files.len()
+    );
+
+    // only when the size changes, so each push does not add another comment
+    let too_large = config.warn_lines.is_some_and(|max| lines >= max)
+        || config.warn_files.is_some_and(|max| files.len() >= max);
+    if too_large && policy::allows(state, &token, &[PolicyAction::Comment], &subject).await {
+        let vars = HashMap::from([
+            ("lines", lines.to_string()),
+            ("files", files.len().to_string()),
+            ("size", size),
+            ("author", pr.user.login.clone()),
+        ]);
+        let body = template::render(&config.warning_template, &vars);
+        github::create_issue_comment(client, &token, &repo.full_name, pr.number, &body)
+            .await
+            .map_err(api_error)?;
+    }
+    Ok(())
+}
+
+// the largest size the pull request reaches
+fn label(config: &PrSizeConfig, lines: u64) -> Option<String> {
+    config
+        .sizes
+        .iter()
+        .filter(|size| lines >= size.lines)
+        .max_by_key(|size| size.lines)
+        .map(|size| format!("{}{}", config.label_prefix, size.name))
+}
@@ -0,0 +1,150 @@
+use regex::Regex;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{PrTitleConfig, ReportKind},
+    conventional,
+    error::NexusError,
+    github::{
+        GithubError,
+        checks::{self, CheckConclusion, CheckOutput, CheckRun, CommitStatus, StatusState},
+    },
+    repos,
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+const ACTIONS: &[&str] = &["opened", "edited", "reopened", "synchronize"];
+
+// statuses belong to a commit, so new pushes are checked again as well as title edits
+pub async fn on_pull_request(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.pr_title;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(pr), Some(repo), Some(action)) =
+        (&payload.pull_request, &payload.repository, &payload.action)
+    else {
+        return Ok(());
+    };
+    if !ACTIONS.contains(&action.as_str())
+        || (!config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name))
+    {
+        return Ok(());
+    }
+    let Some(head) = &pr.head else {
+        return Ok(());
+    };
+
+    let problem = match check(config, &pr.title) {
+        Ok(problem) => problem,
+        Err(e) => {
+            warn!("Invalid pr_title pattern: {}", e);
+            return Ok(());
+        }
+    };
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Title checks are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    let summary = problem
+        .clone()
+        .unwrap_or_else(|| "The title follows the convention".to_string());
+    let result = match config.report {
+        ReportKind::Status => {
+            let outcome = if problem.is_some() {
+                StatusState::Failure
+            } else {
+                StatusState::Success
+            };
+            let mut status = CommitStatus::new(&config.name, outcome).description(&summary);
+            if let Some(url) = &config.details_url {
+                status = status.target_url(url);
+            }
+            checks::create_commit_status(&state.github, &token, &repo.full_name, &head.sha, &status)
+                .await
+        }
+        ReportKind::CheckRun => {
+            let conclusion = if problem.is_some() {
+                CheckConclusion::Failure
+            } else {
+                CheckConclusion::Success
+            };
+            let output = CheckOutput {
+                title: if problem.is_some() {
+                    "Title does not follow the convention".to_string()
+                } else {
+                    summary.clone()
+                },
+                summary: format!("{}\n\nTitle: `{}`", summary, pr.title),
+                text: Some(help(config)),
+                ..Default::default()
+            };
+            let mut run = CheckRun::completed(&config.name, &head.sha, conclusion, output);
+            run.details_url = config.details_url.clone();
+            checks::create_check_run(&state.github, &token, &repo.full_name, &run)
+                .await
+                .map(|_| ())
+        }
+    };
+    result.map_err(|e: GithubError| {
+        error!(
+            "Failed to report the title check on {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    })?;
+
+    info!(
+        "Title of {}#{} {}",
+        repo.full_name,
+        pr.number,
+        if problem.is_some() {
+            "failed"
+        } else {
+            "passed"
+        }
+    );
+    Ok(())
+}
+
+// what is wrong with the title, if anything
+fn check(config: &PrTitleConfig, title: &str) -> Result<Option<String>, regex::Error> {
+    if let Some(pattern) = &config.pattern {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+        return Ok((!regex.is_match(title)).then(|| format!("Title must match `{}`", pattern)));
+    }
+
+    if conventional::parse(title).is_none() {
+        return Ok(Some(
+            "Title must look like `type(scope): description`".to_string(),
+        ));
+    }
+    Ok(conventional::violation(
+        title,
+        &config.types,
+        &config.scopes,
+        config.require_scope,
+    ))
+}
+
+// spelled out in the check run's details
+fn help(config: &PrTitleConfig) -> String {
+    if let Some(pattern) = &config.pattern {
+        return format!("Titles must match the regular expression `{}`.", pattern);
+    }
+    let mut text = format!(
+        "Titles follow [Conventional Commits](https://www.conventionalcommits.org/): \
+         `type(scope): description`, with `!` before the colon for breaking changes.\n\n\
+         Types: {}",
+        config.types.join(", ")
+    );
+    if !config.scopes.is_empty() {
+        text.push_str(&format!("\n\nScopes: {}", config.scopes.join(", ")));
+    }
+    if config.require_scope {
+        text.push_str("\n\nA scope is required.");
+    }
+    text
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    command::CommandError,
+    config::{PushDeployTarget, RestartMethod},
+    environments,
+    error::NexusError,
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushDeployError {
+    #[error("restart = \"systemd\" needs a unit")]
+    NoUnit,
+    #[error("{0}")]
+    Command(#[from] CommandError),
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+/// Keeps deploys of the same target from overlapping: one runs at a time, and pushes that
+/// arrive meanwhile collapse into a single follow-up deploy of the newest commit.
+#[derive(Clone, Default)]
+pub struct PushDeploys {
+    targets: Arc<Mutex<HashMap<usize, Arc<TargetLock>>>>,
+}
+
+#[derive(Default)]
+struct TargetLock {
+    running: tokio::sync::Mutex<()>,
+    queued: AtomicBool,
+}
+
+impl PushDeploys {
+    fn lock(&self, target: usize) -> Arc<TargetLock> {
+        self.targets
+            .lock()
+            .unwrap()
+            .entry(target)
+            .or_default()
+            .clone()
+    }
+}
+
+pub async fn on_push(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.push_deploy;
+    if !config.enabled || payload.deleted {
+        return Ok(());
+    }
+    let (Some(repo), Some(git_ref)) = (&payload.repository, &payload.ref_name) else {
+        return Ok(());
+    };
+    let Some(branch) = git_ref.strip_prefix("refs/heads/") else {
+        return Ok(());
+    };
+    let sha = payload.after.as_deref().unwrap_or_default();
+    let short_sha = sha[..sha.len().min(7)].to_string();
+
+    // deploys outlive GitHub's webhook timeout and the worker, so they run in the background
+    for (index, target) in config.targets.iter().enumerate() {
+        let deploys = match &target.environment {
+            Some(environment) => {
+                environments::for_branch(&state.config.environments, branch).as_ref()
+                    == Some(environment)
+            }
+            None => target.branch == branch,
+        };
+        if target.repo != repo.full_name || !deploys {
+            continue;
+        }
+        let state = state.clone();
+        let mut target = target.clone();
+        target.branch = branch.to_string();
+        let short_sha = short_sha.clone();
+        tokio::spawn(async move {
+            let lock = state.push_deploys.lock(index);
+            // the deploy already waiting will pick up this commit too
+            if lock.queued.swap(true, Ordering::SeqCst) {
+                debug!(
+                    "A deploy of {}@{} is already queued, skipping {}",
+                    target.repo, target.branch, short_sha
+                );
+                return;
+            }
+            let _running = lock.running.lock().await;
+            lock.queued.store(false, Ordering::SeqCst);
+
+            info!(
+                "Deploying {}@{} after push of {}",
+                target.repo, target.branch, short_sha
+            );
+            let timeout_secs = state.config.push_deploy.timeout_secs;
+            let result = tokio::time::timeout(Duration::from_secs(timeout_secs), deploy(&target))
+                .await
+                .unwrap_or(Err(PushDeployError::TimedOut(timeout_secs)));
+            match result {
+                Ok(()) => info!("Deployed {}@{}", target.repo, target.branch),
+                Err(e) => error!("Deploy of {}@{} failed: {}", target.repo, target.branch, e),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn deploy(target: &PushDeployTarget) -> Result<(), PushDeployError> {
+    let dir = target.dir.as_deref();
+    // the newest commit on the branch, which may be past the one that was pushed
+    if let Some(dir) = dir {
+        run(
+            "git",
+            &["fetch", "--quiet", "origin", &target.branch],
+            Some(dir),
+        )
+        .await?;
+        run(
+            "git",
+            &["reset", "--hard", "--quiet", "FETCH_HEAD"],
+            Some(dir),
+        )
+        .await?;
+    }
+
+    match target.restart {
+        RestartMethod::Compose => {
+            let mut compose = vec!["compose".to_string()];
+            if let Some(file) = &target.compose_file {
+                compose.extend(["-f".to_string(), file.display().to_string()]);
+            }
+            if let Some(project) = &target.compose_project {
+                compose.extend(["-p".to_string(), project.clone()]);
+            }
+            let args = |extra: &[&str]| -> Vec<String> {
+                compose
+                    .iter()
+                    .cloned()
+                    .chain(extra.iter().map(|s| s.to_string()))
+                    .collect()
+            };
+            if target.pull_images {
+                run("docker", &args(&["pull", "--quiet"]), dir).await?;
+            }
+            Ok(run("docker", &args(&["up", "-d", "--remove-orphans"]), dir).await?)
+        }
+        RestartMethod::Systemd => {
+            let Some(unit) = &target.unit else {
+                return Err(PushDeployError::NoUnit);
+            };
+            Ok(run("systemctl", &["restart", unit.as_str()], None).await?)
+        }
+        RestartMethod::None => Ok(()),
+    }
+}
+
+async fn run<S: AsRef<str>>(
+    program: &str,
+    args: &[S],
+    dir: Option<&Path>,
+) -> Result<(), CommandError> {
+    let mut command = tokio::process::Command::new(program);
+    command
+        .args(args.iter().map(|arg| arg.as_ref()))
+        .kill_on_drop(true);
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|e| CommandError::spawn(program, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let invocation = format!(
+        "{} {}",
+        program,
+        args.first().map(AsRef::as_ref).unwrap_or_default()
+    );
+    Err(CommandError::exited(invocation, &output))
+}
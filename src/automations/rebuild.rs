@@ -0,0 +1,178 @@
+use globset::{Glob, GlobSetBuilder};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    command::CommandError,
+    config::RebuildSite,
+    environments,
+    error::NexusError,
+    outbound::{self, Delivery, DeliveryError, Request},
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RebuildError {
+    #[error("no command or url configured for {0}")]
+    NoAction(String),
+    #[error("{0}")]
+    Command(#[from] CommandError),
+    #[error("{0}")]
+    Delivery(#[from] DeliveryError),
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+/// Debounces rebuilds per site: every matching push restarts the wait, only the last one
+/// builds, and builds of the same site never overlap.
+#[derive(Clone, Default)]
+pub struct Rebuilds {
+    sites: Arc<Mutex<HashMap<usize, Arc<SiteState>>>>,
+}
+
+#[derive(Default)]
+struct SiteState {
+    // bumped by every matching push; a waiting build gives way when it moved on
+    generation: AtomicU64,
+    building: tokio::sync::Mutex<()>,
+}
+
+impl Rebuilds {
+    fn site(&self, index: usize) -> Arc<SiteState> {
+        self.sites.lock().unwrap().entry(index).or_default().clone()
+    }
+}
+
+pub async fn on_push(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.rebuild;
+    if !config.enabled || payload.deleted {
+        return Ok(());
+    }
+    let (Some(repo), Some(git_ref)) = (&payload.repository, &payload.ref_name) else {
+        return Ok(());
+    };
+    let Some(branch) = git_ref.strip_prefix("refs/heads/") else {
+        return Ok(());
+    };
+    let files: Vec<&String> = payload
+        .commits
+        .iter()
+        .flat_map(|c| c.added.iter().chain(&c.removed).chain(&c.modified))
+        .collect();
+
+    for (index, site) in config.sites.iter().enumerate() {
+        if site.repo != repo.full_name || site.branch != branch || !touches(site, &files) {
+            continue;
+        }
+        let site_state = state.rebuilds.site(index);
+        let generation = site_state.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let state = state.clone();
+        let site = site.clone();
+        let sha = payload.after.clone().unwrap_or_default();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(site.debounce_secs)).await;
+            if site_state.generation.load(Ordering::SeqCst) != generation {
+                debug!(
+                    "A newer push to {}@{} superseded {}",
+                    site.repo, site.branch, sha
+                );
+                return;
+            }
+            let _building = site_state.building.lock().await;
+
+            info!("Rebuilding {}@{} at {}", site.repo, site.branch, sha);
+            let result = tokio::time::timeout(
+                Duration::from_secs(site.timeout_secs),
+                build(&state, &site, &sha),
+            )
+            .await
+            .unwrap_or(Err(RebuildError::TimedOut(site.timeout_secs)));
+            match result {
+                Ok(()) => info!("Rebuilt {}@{}", site.repo, site.branch),
+                Err(e) => error!("Rebuild of {}@{} failed: {}", site.repo, site.branch, e),
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// pushes without file lists, such as a branch reset to an older commit, always match
+fn touches(site: &RebuildSite, files: &[&String]) -> bool {
+    if site.paths.is_empty() || files.is_empty() {
+        return true;
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &site.paths {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Invalid rebuild glob {:?}: {}", pattern, e),
+        }
+    }
+    match builder.build() {
+        Ok(set) => files.iter().any(|f| set.is_match(f)),
+        Err(_) => false,
+    }
+}
+
+async fn build(state: &AppState, site: &RebuildSite, sha: &str) -> Result<(), RebuildError> {
+    let mut vars = HashMap::from([
+        ("repo", site.repo.clone()),
+        ("branch", site.branch.clone()),
+        ("sha", sha.to_string()),
+    ]);
+    if let Some(environment) = environments::for_branch(&state.config.environments, &site.branch) {
+        vars.insert("environment", environment);
+    }
+
+    if let Some(url) = &site.url {
+        let request = Request::Http {
+            url: template::render(url, &vars),
+            headers: site.headers.clone(),
+            body: json!({ "repository": site.repo, "branch": site.branch, "sha": sha }),
+        };
+        let target = format!("rebuild:{}", site.repo);
+        let scope = format!("rebuild:{}", sha);
+        let delivery = Delivery::new(target, request)
+            .idempotent(Some(scope.as_str()).filter(|_| !sha.is_empty()));
+        return Ok(outbound::deliver(state, delivery).await?);
+    }
+
+    let Some((program, args)) = site.command.split_first() else {
+        return Err(RebuildError::NoAction(site.repo.clone()));
+    };
+    let mut command = tokio::process::Command::new(template::render(program, &vars));
+    command
+        .args(args.iter().map(|arg| template::render(arg, &vars)))
+        .env("NEXUS_REPO", &site.repo)
+        .env("NEXUS_BRANCH", &site.branch)
+        .env("NEXUS_SHA", sha)
+        .kill_on_drop(true);
+    if let Some(environment) = vars.get("environment") {
+        command.env("NEXUS_ENVIRONMENT", environment);
+    }
+    if let Some(dir) = &site.working_dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .output()
+        .await
+        .map_err(|e| CommandError::spawn(program, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(CommandError::exited(program, &output).into())
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tracing::{debug, error, info};
+
+use crate::{
+    config::ReleaseNotesConfig,
+    error::NexusError,
+    github::{self, releases},
+    service::AppState,
+    template,
+    webhook::{Issue, Repository, WebhookPayload},
+};
+
+pub async fn on_release_created(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.release_notes;
+    if !config.enabled {
+        return Ok(());
+    }
+    let (Some(release), Some(repo)) = (&payload.release, &payload.repository) else {
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Release notes are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: github::GithubError| {
+        error!(
+            "Failed to write release notes for {} in {}: {}",
+            release.tag_name, repo.full_name, e
+        );
+        NexusError::from(e)
+    };
+
+    let notes = generate(
+        state,
+        config,
+        &token,
+        repo,
+        &release.tag_name,
+        release.created_at,
+    )
+    .await
+    .map_err(api_error)?;
+    releases::update_release_body(&state.github, &token, &repo.full_name, release.id, &notes)
+        .await
+        .map_err(api_error)?;
+
+    info!(
+        "Wrote release notes for {} in {}",
+        release.tag_name, repo.full_name
+    );
+    Ok(())
+}
+
+pub async fn on_tag_created(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.release_notes;
+    if !config.enabled || !config.create_on_tag {
+        return Ok(());
+    }
+    let (Some(tag), Some(repo)) = (&payload.ref_name, &payload.repository) else {
+        return Ok(());
+    };
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Release notes are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: github::GithubError| {
+        error!(
+            "Failed to draft a release for {} in {}: {}",
+            tag, repo.full_name, e
+        );
+        NexusError::from(e)
+    };
+
+    // tags pushed alongside a release are handled by the release event
+    if releases::get_release_by_tag(&state.github, &token, &repo.full_name, tag)
+        .await
+        .map_err(api_error)?
+        .is_some()
+    {
+        debug!("{} in {} already has a release", tag, repo.full_name);
+        return Ok(());
+    }
+
+    let notes = generate(state, config, &token, repo, tag, Utc::now())
+        .await
+        .map_err(api_error)?;
+    let release =
+        releases::create_release(&state.github, &token, &repo.full_name, tag, &notes, true)
+            .await
+            .map_err(api_error)?;
+
+    info!(
+        "Drafted release {} for {} in {}",
+        release.html_url, tag, repo.full_name
+    );
+    Ok(())
+}
+
+async fn generate(
+    state: &AppState,
+    config: &ReleaseNotesConfig,
+    token: &str,
+    repo: &Repository,
+    tag: &str,
+    until: DateTime<Utc>,
+) -> Result<String, github::GithubError> {
+    let previous = releases::list_releases(&state.github, token, &repo.full_name)
+        .await?
+        .into_iter()
+        .filter(|r| !r.draft && r.tag_name != tag && r.created_at < until)
+        .max_by_key(|r| r.created_at);
+
+    let pull_requests = releases::list_merged_pull_requests(
+        &state.github,
+        token,
+        &repo.full_name,
+        previous.as_ref().map(|r| r.created_at),
+        until,
+    )
+    .await?;
+
+    let compare_url = match &previous {
+        Some(previous) => format!("{}/compare/{}...{}", repo.html_url, previous.tag_name, tag),
+        None => format!("{}/commits/{}", repo.html_url, tag),
+    };
+    let vars = HashMap::from([
+        ("changes", render_changes(config, &pull_requests)),
+        ("compare_url", compare_url),
+        ("tag", tag.to_string()),
+        (
+            "previous_tag",
+            previous.map(|r| r.tag_name).unwrap_or_default(),
+        ),
+        ("repo", repo.full_name.clone()),
+    ]);
+    Ok(template::render(&config.template, &vars))
+}
+
+fn render_changes(config: &ReleaseNotesConfig, pull_requests: &[Issue]) -> String {
+    let mut sections: Vec<(&str, Vec<String>)> = config
+        .sections
+        .iter()
+        .map(|s| (s.title.as_str(), Vec::new()))
+        .collect();
+    let mut other = Vec::new();
+
+    for pr in pull_requests {
+        if config.exclude_labels.iter().any(|l| pr.has_label(l)) {
+            continue;
+        }
+
+        let vars = HashMap::from([
+            ("title", pr.title.clone()),
+            ("number", pr.number.to_string()),
+            ("author", pr.user.login.clone()),
+            ("url", pr.html_url.clone()),
+        ]);
+        let line = template::render(&config.item_template, &vars);
+
+        match config
+            .sections
+            .iter()
+            .position(|s| s.labels.iter().any(|l| pr.has_label(l)))
+        {
+            Some(index) => sections[index].1.push(line),
+            None => other.push(line),
+        }
+    }
+    sections.push((&config.other_title, other));
+
+    let mut output = String::new();
+    for (title, lines) in sections.iter().filter(|(_, lines)| !lines.is_empty()) {
+        output.push_str(&format!("### {}\n\n{}\n\n", title, lines.join("\n")));
+    }
+    if output.is_empty() {
+        output.push_str("No notable changes.\n\n");
+    }
+    output
+}
@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::{collections::HashMap, sync::Arc};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ReportsConfig,
+    error::NexusError,
+    outbound::{self, Delivery, Request},
+    repos,
+    scheduler::Scheduler,
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+// a report's items live under `report-items:{report}:{section}:{repo}` and the start of its
+// period under `report-since:{report}:{repo}` in the shared store
+const ITEMS: &str = "report-items:";
+const SINCE: &str = "report-since:";
+// each section's placeholder and the placeholder with its count
+const SECTIONS: &[(&str, &str)] = &[
+    ("merged", "merged_count"),
+    ("issues", "issues_count"),
+    ("failures", "failures_count"),
+];
+
+pub fn schedule(
+    scheduler: &mut Scheduler,
+    config: &ReportsConfig,
+) -> Result<(), cron::error::Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    for (index, report) in config.reports.iter().enumerate() {
+        scheduler.add(
+            format!("report {}", report.name),
+            &report.schedule,
+            move |state: Arc<AppState>| async move {
+                send(&state, index).await;
+            },
+        )?;
+    }
+    Ok(())
+}
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.reports;
+    if !config.enabled {
+        return Ok(());
+    }
+    let Some(repo) = &payload.repository else {
+        return Ok(());
+    };
+    let Some((section, vars)) = item(event_type, payload) else {
+        return Ok(());
+    };
+
+    for (index, report) in config.reports.iter().enumerate() {
+        if !report.repos.is_empty() && !repos::matches(&report.repos, &repo.full_name) {
+            continue;
+        }
+        let item = template::render(&report.item_template, &vars);
+        let key = format!("{}:{}:{}", index, section, repo.full_name);
+        if let Err(e) = state.store.push(&format!("{}{}", ITEMS, key), &item).await {
+            error!("Failed to add to the {} report: {}", report.name, e);
+            continue;
+        }
+        let since = format!("{}{}:{}", SINCE, index, repo.full_name);
+        if let Err(e) = state
+            .store
+            .set_if_absent(&since, &Utc::now().to_rfc3339())
+            .await
+        {
+            warn!(
+                "Failed to record when the {} report started: {}",
+                report.name, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// the section an event belongs in, with the placeholders for its line
+fn item(
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> Option<(&'static str, HashMap<&'static str, String>)> {
+    let action = payload.action.as_deref();
+    let (section, number, title, url) = match event_type {
+        "pull_request" if action == Some("closed") => {
+            let pr = payload.pull_request.as_ref()?;
+            if pr.merged != Some(true) {
+                return None;
+            }
+            ("merged", pr.number, pr.title.clone(), pr.html_url.clone())
+        }
+        "issues" if action == Some("opened") => {
+            let issue = payload.issue.as_ref()?;
+            (
+                "issues",
+                issue.number,
+                issue.title.clone(),
+                issue.html_url.clone(),
+            )
+        }
+        "workflow_run" if action == Some("completed") => {
+            let run = payload.workflow_run.as_ref()?;
+            if run.conclusion.as_deref() != Some("failure") {
+                return None;
+            }
+            let name = run.name.clone().unwrap_or_else(|| "workflow".to_string());
+            let branch = run.head_branch.as_deref().unwrap_or("?");
+            (
+                "failures",
+                run.id,
+                format!("{} on {}", name, branch),
+                run.html_url.clone(),
+            )
+        }
+        _ => return None,
+    };
+
+    let sender = payload
+        .sender
+        .as_ref()
+        .map(|s| s.login.clone())
+        .unwrap_or_default();
+    Some((
+        section,
+        HashMap::from([
+            ("number", number.to_string()),
+            ("title", title),
+            ("url", url),
+            ("sender", sender),
+        ]),
+    ))
+}
+
+// one message per repository that saw anything since the previous report
+async fn send(state: &AppState, index: usize) {
+    let Some(report) = state.config.reports.reports.get(index) else {
+        return;
+    };
+    let prefix = format!("{}{}:", SINCE, index);
+    let repos = match state.store.keys(&prefix).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            error!(
+                "Failed to list repositories for the {} report: {}",
+                report.name, e
+            );
+            return;
+        }
+    };
+
+    for key in repos {
+        let repo = &key[prefix.len()..];
+        // the start is taken first so an event arriving in between opens the next period
+        let since = match state.store.take_value(&key).await {
+            Ok(since) => since
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|s| s.with_timezone(&Utc)),
+            Err(e) => {
+                warn!(
+                    "Failed to read when the {} report started: {}",
+                    report.name, e
+                );
+                None
+            }
+        };
+
+        let mut vars = HashMap::from([
+            ("repo", repo.to_string()),
+            ("name", report.name.clone()),
+            (
+                "since",
+                since
+                    .unwrap_or_else(Utc::now)
+                    .format("%Y-%m-%d %H:%M UTC")
+                    .to_string(),
+            ),
+        ]);
+        let mut total = 0;
+        for &(section, count) in SECTIONS {
+            let items_key = format!("{}{}:{}:{}", ITEMS, index, section, repo);
+            let items = match state.store.take(&items_key).await {
+                Ok(items) => items,
+                Err(e) => {
+                    error!(
+                        "Failed to collect the {} report for {}: {}",
+                        report.name, repo, e
+                    );
+                    Vec::new()
+                }
+            };
+            total += items.len();
+            vars.insert(count, items.len().to_string());
+            let lines = if items.is_empty() {
+                "_none_".to_string()
+            } else {
+                items.join("\n")
+            };
+            vars.insert(section, lines);
+        }
+        // another replica already sent this period's report
+        if total == 0 {
+            continue;
+        }
+
+        let request = Request::Http {
+            url: report.url.clone(),
+            headers: report.headers.clone(),
+            body: json!({ "text": template::render(&report.template, &vars) }),
+        };
+        let target = format!("report:{}", report.name);
+        // a period is identified by the repository and when it started
+        let scope = since.map(|since| format!("{}:{}", repo, since.to_rfc3339()));
+        let delivery = Delivery::new(target, request).idempotent(scope.as_deref());
+        match outbound::deliver(state, delivery).await {
+            Ok(()) => info!("Sent the {} report for {}", report.name, repo),
+            Err(e) => error!(
+                "Failed to send the {} report for {}: {}",
+                report.name, repo, e
+            ),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::RerunConfig,
+    error::NexusError,
+    github::{self, actions},
+    service::AppState,
+    webhook::{Repository, WebhookPayload, WorkflowRun},
+};
+
+// `rerun:{repo}:{run id}:{attempt}`, under the attempt that failed, so the delivery
+// reporting that failure finds the re-run it caused
+const RERUNS: &str = "rerun:";
+// about as long as anyone looks back at an archived delivery
+const RETENTION_SECS: u64 = 30 * 86400;
+
+/// A re-run nexus requested, as the admin APIs show it next to the delivery behind it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rerun {
+    pub run_id: u64,
+    // the attempt the re-run starts
+    pub attempt: u32,
+    // the failed job whose logs matched, and the pattern they matched; unset without
+    // `fetch_logs`
+    pub job: Option<String>,
+    pub pattern: Option<String>,
+    pub requested_at: DateTime<Utc>,
+}
+
+fn key(repo: &str, run_id: u64, attempt: u32) -> String {
+    format!("{}{}:{}:{}", RERUNS, repo, run_id, attempt)
+}
+
+pub(crate) async fn record(state: &AppState, repo: &str, failed_attempt: u32, rerun: &Rerun) {
+    let key = key(repo, rerun.run_id, failed_attempt);
+    let recorded = match serde_json::to_string(rerun) {
+        Ok(value) => match state.store.set_if_absent(&key, &value).await {
+            Ok(_) => state.store.expire(&key, RETENTION_SECS).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => {
+            error!("Failed to encode re-run of run {}: {}", rerun.run_id, e);
+            return;
+        }
+    };
+    // the re-run itself went through, so this is only logged
+    if let Err(e) = recorded {
+        error!(
+            "Failed to record re-run of run {} in {}: {}",
+            rerun.run_id, repo, e
+        );
+    }
+}
+
+// the re-run requested for a failed attempt of a run, if there was one
+pub async fn recorded(state: &AppState, repo: &str, run: &WorkflowRun) -> Option<Rerun> {
+    match state.store.value(&key(repo, run.id, run.run_attempt)).await {
+        Ok(value) => value.and_then(|v| serde_json::from_str(&v).ok()),
+        Err(e) => {
+            warn!(
+                "Failed to look up re-runs of run {} in {}: {}",
+                run.id, repo, e
+            );
+            None
+        }
+    }
+}
+
+pub async fn on_workflow_run_completed(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.rerun;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let (Some(run), Some(repo)) = (&payload.workflow_run, &payload.repository) else {
+        return Ok(());
+    };
+    if run.conclusion.as_deref() != Some("failure") {
+        return Ok(());
+    }
+    let name = run.name.as_deref().unwrap_or_default();
+    if !config.workflows.is_empty() && !config.workflows.iter().any(|w| w == name) {
+        return Ok(());
+    }
+    // `run_attempt` is tracked by GitHub, so the limit holds across restarts
+    if run.run_attempt >= config.max_attempts {
+        info!(
+            "Workflow {} run {} in {} failed on attempt {}, not retrying",
+            name, run.id, repo.full_name, run.run_attempt
+        );
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Flaky re-runs are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    try_rerun(state, config, &token, repo, run)
+        .await
+        .map_err(|e| {
+            error!(
+                "Failed to re-run workflow run {} in {}: {}",
+                run.id, repo.full_name, e
+            );
+            NexusError::from(e)
+        })
+}
+
+async fn try_rerun(
+    state: &AppState,
+    config: &RerunConfig,
+    token: &str,
+    repo: &Repository,
+    run: &WorkflowRun,
+) -> Result<(), github::GithubError> {
+    let client = &state.github;
+
+    let mut matched = None;
+    if config.fetch_logs {
+        let Some(patterns) = build_patterns(&config.patterns) else {
+            return Ok(());
+        };
+        let Some((job, pattern)) = find_flaky_job(state, token, repo, run, &patterns).await? else {
+            debug!(
+                "No flaky pattern matched the failed jobs of run {} in {}",
+                run.id, repo.full_name
+            );
+            return Ok(());
+        };
+        info!(
+            "Job {} of run {} in {} failed with a known flake",
+            job, run.id, repo.full_name
+        );
+        matched = Some((job, pattern));
+    }
+
+    actions::rerun_failed_jobs(client, token, &repo.full_name, run.id).await?;
+    let (job, pattern) = matched.unzip();
+    let rerun = Rerun {
+        run_id: run.id,
+        attempt: run.run_attempt + 1,
+        job,
+        pattern,
+        requested_at: Utc::now(),
+    };
+    record(state, &repo.full_name, run.run_attempt, &rerun).await;
+
+    info!(
+        "Re-running failed jobs of {} run {} in {} (attempt {} of {})",
+        run.name.as_deref().unwrap_or_default(),
+        run.id,
+        repo.full_name,
+        run.run_attempt + 1,
+        config.max_attempts
+    );
+    Ok(())
+}
+
+async fn find_flaky_job(
+    state: &AppState,
+    token: &str,
+    repo: &Repository,
+    run: &WorkflowRun,
+    patterns: &RegexSet,
+) -> Result<Option<(String, String)>, github::GithubError> {
+    let client = &state.github;
+    let jobs = actions::list_run_jobs(client, token, &repo.full_name, run.id).await?;
+
+    for job in jobs
+        .iter()
+        .filter(|j| j.conclusion.as_deref() == Some("failure"))
+    {
+        let logs = actions::get_job_logs(client, token, &repo.full_name, job.id).await?;
+        if let Some(index) = patterns.matches(&logs).iter().next() {
+            return Ok(Some((job.name.clone(), patterns.patterns()[index].clone())));
+        }
+    }
+
+    Ok(None)
+}
+
+fn build_patterns(patterns: &[String]) -> Option<RegexSet> {
+    if patterns.is_empty() {
+        warn!("Flaky re-runs fetch logs but no patterns are configured");
+        return None;
+    }
+    RegexSet::new(patterns)
+        .map_err(|e| warn!("Invalid flaky pattern: {}", e))
+        .ok()
+}
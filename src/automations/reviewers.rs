@@ -0,0 +1,136 @@
+use std::collections::BTreeSet;
+use tracing::{debug, error, info};
+
+use crate::{
+    codeowners,
+    config::PolicyAction,
+    error::NexusError,
+    github,
+    policy::{self, Subject},
+    service::AppState,
+    store::StoreError,
+    webhook::{PullRequest, Repository},
+};
+
+// rotates through a configured team, skipping the author; the position is kept in the
+// shared store so replicas take turns together
+async fn next_member<'a>(
+    state: &AppState,
+    team: &str,
+    members: &'a [String],
+    skip: &str,
+) -> Result<Option<&'a String>, StoreError> {
+    let candidates: Vec<&String> = members
+        .iter()
+        .filter(|m| !m.eq_ignore_ascii_case(skip))
+        .collect();
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let position = state.store.incr(&format!("rotation:{}", team)).await?;
+    Ok(Some(candidates[position as usize % candidates.len()]))
+}
+
+pub async fn on_pull_request_opened(
+    state: &AppState,
+    pr: &PullRequest,
+    repo: &Repository,
+) -> Result<(), NexusError> {
+    let config = &state.config.reviewers;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Reviewer assignment is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+
+    let client = &state.github;
+    let base_ref = pr.base.as_ref().map(|b| b.ref_name.as_str());
+    let api_error = |e: github::GithubError| {
+        error!(
+            "Reviewer assignment failed for {}#{}: {}",
+            repo.full_name, pr.number, e
+        );
+        NexusError::from(e)
+    };
+
+    let codeowners = codeowners::fetch(client, &token, &repo.full_name, base_ref)
+        .await
+        .map_err(api_error)?;
+    let Some(codeowners) = codeowners else {
+        debug!("No CODEOWNERS file in {}", repo.full_name);
+        return Ok(());
+    };
+
+    let files = github::list_pull_request_files(client, &token, &repo.full_name, pr.number)
+        .await
+        .map_err(api_error)?;
+
+    let owners: BTreeSet<&String> = files
+        .iter()
+        .flat_map(|f| codeowners.owners_for(&f.filename))
+        .collect();
+
+    let author = pr.user.login.as_str();
+    let mut reviewers = BTreeSet::new();
+    let mut team_reviewers = BTreeSet::new();
+
+    for owner in owners {
+        if let Some(members) = config.teams.get(owner.as_str()) {
+            let member = next_member(state, owner, members, author)
+                .await
+                .map_err(|e| {
+                    error!("Failed to advance the rotation for {}: {}", owner, e);
+                    NexusError::from(e)
+                })?;
+            if let Some(member) = member {
+                reviewers.insert(member.trim_start_matches('@').to_string());
+            }
+        } else if let Some((_, team)) = owner.trim_start_matches('@').split_once('/') {
+            team_reviewers.insert(team.to_string());
+        } else if owner.starts_with('@') {
+            let user = owner.trim_start_matches('@');
+            if !user.eq_ignore_ascii_case(author) {
+                reviewers.insert(user.to_string());
+            }
+        }
+    }
+
+    let mut reviewers: Vec<String> = reviewers.into_iter().collect();
+    let team_reviewers: Vec<String> = team_reviewers.into_iter().collect();
+    if let Some(max) = config.max_reviewers {
+        reviewers.truncate(max);
+    }
+
+    if reviewers.is_empty() && team_reviewers.is_empty() {
+        return Ok(());
+    }
+
+    let subject = Subject::pull_request(&repo.full_name, pr);
+    if !policy::allows(state, &token, &[PolicyAction::RequestReview], &subject).await {
+        return Ok(());
+    }
+
+    github::request_reviewers(
+        client,
+        &token,
+        &repo.full_name,
+        pr.number,
+        &reviewers,
+        &team_reviewers,
+    )
+    .await
+    .map_err(api_error)?;
+
+    info!(
+        "Requested reviews on {}#{} from users [{}] and teams [{}]",
+        repo.full_name,
+        pr.number,
+        reviewers.join(", "),
+        team_reviewers.join(", ")
+    );
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use tracing::{error, info, warn};
+
+use crate::{
+    error::NexusError,
+    repos,
+    scripting::{self, Outcome},
+    service::AppState,
+    webhook::WebhookPayload,
+};
+
+// `[[scripting.rules]]` scripts act only through what they post or dispatch, so what they
+// return is logged and otherwise ignored
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.scripting;
+    if !config.enabled {
+        return Ok(());
+    }
+    let repo = payload.repository.as_ref().map(|r| r.full_name.as_str());
+
+    let mut rules = config
+        .rules
+        .iter()
+        .filter(|rule| {
+            rule.event == event_type
+                && rule
+                    .action
+                    .as_ref()
+                    .is_none_or(|a| payload.action.as_ref() == Some(a))
+                && (rule.repos.is_empty() || repo.is_some_and(|r| repos::matches(&rule.repos, r)))
+        })
+        .peekable();
+    if rules.peek().is_none() {
+        return Ok(());
+    }
+
+    let value = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to serialize the {} payload: {}", event_type, e);
+            return Err(NexusError::Serialize(e));
+        }
+    };
+    for rule in rules {
+        match scripting::run(state, &rule.script, event_type, delivery_id, &value) {
+            Ok(Outcome::Skip) => info!(
+                "Script {} skipped {} event",
+                rule.script.display(),
+                event_type
+            ),
+            Ok(_) => info!(
+                "Script {} ran for {} event",
+                rule.script.display(),
+                event_type
+            ),
+            Err(e) => warn!("Script {} {}", rule.script.display(), e),
+        }
+    }
+
+    Ok(())
+}
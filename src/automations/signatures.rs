@@ -0,0 +1,208 @@
+use globset::Glob;
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::{
+    automations::notify,
+    config::SignaturesConfig,
+    error::NexusError,
+    github::{
+        self, GithubClient, GithubError, RepoCommit,
+        checks::{self, Verdict},
+        contents,
+    },
+    outbound::{self, Delivery},
+    repos,
+    service::AppState,
+    template,
+    webhook::WebhookPayload,
+};
+
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+pub async fn on_push(
+    state: &AppState,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.signatures;
+    if !config.enabled || payload.deleted {
+        return Ok(());
+    }
+    let (Some(repo), Some(git_ref), Some(after)) =
+        (&payload.repository, &payload.ref_name, &payload.after)
+    else {
+        return Ok(());
+    };
+    let Some(branch) = git_ref.strip_prefix("refs/heads/") else {
+        return Ok(());
+    };
+    if !config.repos.is_empty() && !repos::matches(&config.repos, &repo.full_name) {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Signature checks are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let client = &state.github;
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to check signatures on {} in {}: {}",
+            branch, repo.full_name, e
+        );
+        NexusError::from(e)
+    };
+    if !watched(client, config, &token, &repo.full_name, branch)
+        .await
+        .map_err(api_error)?
+    {
+        return Ok(());
+    }
+
+    // a new branch brings the commits that are not on the default branch yet
+    let base = match payload.before.as_deref() {
+        Some(before) if before != NULL_SHA => before,
+        _ => match repo.default_branch.as_deref() {
+            Some(default) if default != branch => default,
+            _ => return Ok(()),
+        },
+    };
+    let commits = github::compare_commits(client, &token, &repo.full_name, base, after)
+        .await
+        .map_err(api_error)?;
+    if commits.is_empty() {
+        return Ok(());
+    }
+    let unsigned: Vec<&RepoCommit> = commits
+        .iter()
+        .filter(|c| !c.commit.verification.as_ref().is_some_and(|v| v.verified))
+        .collect();
+
+    checks::report(
+        client,
+        &token,
+        &repo.full_name,
+        after,
+        &config.name,
+        config.report,
+        verdict(config, commits.len(), &unsigned),
+    )
+    .await
+    .map_err(api_error)?;
+    info!(
+        "{} of {} commits pushed to {} in {} are unsigned",
+        unsigned.len(),
+        commits.len(),
+        branch,
+        repo.full_name
+    );
+
+    if unsigned.is_empty() || config.channel.is_empty() {
+        return Ok(());
+    }
+    let Some(channel) = state.config.notify.channels.get(&config.channel) else {
+        warn!("Notification channel {} is not configured", config.channel);
+        return Ok(());
+    };
+    let vars = HashMap::from([
+        ("count", unsigned.len().to_string()),
+        ("branch", branch.to_string()),
+        ("repo", repo.full_name.clone()),
+        (
+            "pusher",
+            payload
+                .sender
+                .as_ref()
+                .map_or_else(String::new, |s| s.login.clone()),
+        ),
+        (
+            "commits",
+            unsigned
+                .iter()
+                .map(|c| short(&c.sha))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        ("url", payload.compare.clone().unwrap_or_default()),
+    ]);
+    let text = template::render(&config.template, &vars);
+    let delivery = Delivery::new(
+        format!("signatures:{}", config.channel),
+        notify::request(channel, &text),
+    )
+    .idempotent(delivery_id);
+    let state = state.clone();
+    let name = config.channel.clone();
+    tokio::spawn(async move {
+        if let Err(e) = outbound::deliver(&state, delivery).await {
+            error!("Failed to notify {} about unsigned commits: {}", name, e);
+        }
+    });
+    Ok(())
+}
+
+async fn watched(
+    client: &GithubClient,
+    config: &SignaturesConfig,
+    token: &str,
+    repo: &str,
+    branch: &str,
+) -> Result<bool, GithubError> {
+    if config.branches.is_empty() {
+        let branch = contents::get_branch(client, token, repo, branch).await?;
+        return Ok(branch.is_some_and(|b| b.protected));
+    }
+    Ok(config
+        .branches
+        .iter()
+        .any(|pattern| match Glob::new(pattern) {
+            Ok(glob) => glob.compile_matcher().is_match(branch),
+            Err(e) => {
+                warn!("Invalid signatures branch pattern {:?}: {}", pattern, e);
+                false
+            }
+        }))
+}
+
+fn verdict(config: &SignaturesConfig, total: usize, unsigned: &[&RepoCommit]) -> Verdict {
+    if unsigned.is_empty() {
+        return Verdict {
+            passed: true,
+            title: format!("All {} commits are signed", total),
+            summary: "Every commit has a verified signature.".to_string(),
+            text: None,
+            details_url: config.details_url.clone(),
+        };
+    }
+    let summary = unsigned
+        .iter()
+        .map(|c| {
+            format!(
+                "- `{}` {} ({})",
+                short(&c.sha),
+                c.commit.message.lines().next().unwrap_or_default(),
+                c.commit
+                    .verification
+                    .as_ref()
+                    .map_or("unsigned", |v| v.reason.as_str())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Verdict {
+        passed: false,
+        title: format!("{} of {} commits are not signed", unsigned.len(), total),
+        summary,
+        text: Some(
+            "Sign commits with a GPG or SSH key (`git commit -S`) that is added to your GitHub \
+             account. See https://docs.github.com/authentication/managing-commit-signature-verification."
+                .to_string(),
+        ),
+        details_url: config.details_url.clone(),
+    }
+}
+
+fn short(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
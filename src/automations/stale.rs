@@ -0,0 +1,189 @@
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::{
+    config::{PolicyAction, StaleConfig},
+    error::NexusError,
+    github,
+    policy::{self, Subject},
+    repos,
+    scheduler::Scheduler,
+    service::AppState,
+    webhook::{Issue, WebhookPayload},
+};
+
+pub fn schedule(scheduler: &mut Scheduler, config: &StaleConfig) -> Result<(), cron::error::Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    scheduler.add(
+        "stale",
+        &config.schedule,
+        |state: Arc<AppState>| async move {
+            run(&state).await;
+        },
+    )
+}
+
+pub async fn run(state: &AppState) {
+    let config = &state.config.stale;
+
+    for repo in &repos::expand(state, &config.repos).await {
+        let Some(token) = state.github_token(repo).await else {
+            error!(
+                "Stale bot is enabled but no GitHub token is available for {}",
+                repo
+            );
+            continue;
+        };
+        if let Err(e) = sweep_repo(state, config, &token, repo).await {
+            error!("Stale sweep failed for {}: {}", repo, e);
+        }
+    }
+}
+
+async fn sweep_repo(
+    state: &AppState,
+    config: &StaleConfig,
+    token: &str,
+    repo: &str,
+) -> Result<(), github::GithubError> {
+    let client = &state.github;
+    let now = Utc::now();
+    let stale_after = Duration::days(config.days_until_stale);
+    let close_after = Duration::days(config.days_until_close);
+
+    let (mut marked, mut closed) = (0, 0);
+    for issue in github::list_open_issues(client, token, repo).await? {
+        if issue.is_pull_request() && !config.include_pull_requests {
+            continue;
+        }
+        if config.exempt_labels.iter().any(|l| issue.has_label(l)) {
+            continue;
+        }
+        let Some(updated_at) = issue.updated_at else {
+            continue;
+        };
+        let idle = now - updated_at;
+        let subject = Subject::issue(repo, &issue);
+
+        // marking an item stale bumps updated_at, so the close timer starts from the marking
+        if issue.has_label(&config.stale_label) {
+            if idle >= close_after
+                && policy::allows(
+                    state,
+                    token,
+                    &[PolicyAction::Comment, PolicyAction::Close],
+                    &subject,
+                )
+                .await
+            {
+                close(state, config, token, repo, &issue).await?;
+                closed += 1;
+            }
+        } else if idle >= stale_after
+            && policy::allows(
+                state,
+                token,
+                &[PolicyAction::Label, PolicyAction::Comment],
+                &subject,
+            )
+            .await
+        {
+            mark(state, config, token, repo, &issue).await?;
+            marked += 1;
+        }
+    }
+
+    info!(
+        "Stale sweep for {}: {} marked, {} closed",
+        repo, marked, closed
+    );
+    Ok(())
+}
+
+async fn mark(
+    state: &AppState,
+    config: &StaleConfig,
+    token: &str,
+    repo: &str,
+    issue: &Issue,
+) -> Result<(), github::GithubError> {
+    let client = &state.github;
+    github::add_labels(
+        client,
+        token,
+        repo,
+        issue.number,
+        std::slice::from_ref(&config.stale_label),
+    )
+    .await?;
+    github::create_issue_comment(client, token, repo, issue.number, &config.stale_message).await
+}
+
+async fn close(
+    state: &AppState,
+    config: &StaleConfig,
+    token: &str,
+    repo: &str,
+    issue: &Issue,
+) -> Result<(), github::GithubError> {
+    let client = &state.github;
+    github::create_issue_comment(client, token, repo, issue.number, &config.close_message).await?;
+    github::close_issue(client, token, repo, issue.number, "not_planned").await
+}
+
+// any human comment on a stale item un-stales it
+pub async fn on_issue_comment(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.stale;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let (Some(issue), Some(comment), Some(repo)) =
+        (&payload.issue, &payload.comment, &payload.repository)
+    else {
+        return Ok(());
+    };
+    // ignore our own stale notice, which may be posted under a regular user's token
+    if !issue.has_label(&config.stale_label)
+        || comment.user.login.ends_with("[bot]")
+        || comment.body == config.stale_message
+    {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        return Ok(());
+    };
+    let subject = Subject::issue(&repo.full_name, issue);
+    if !policy::allows(state, &token, &[PolicyAction::Unlabel], &subject).await {
+        return Ok(());
+    }
+
+    github::remove_label(
+        &state.github,
+        &token,
+        &repo.full_name,
+        issue.number,
+        &config.stale_label,
+    )
+    .await
+    .map_err(|e| {
+        error!(
+            "Failed to remove stale label from {}#{}: {}",
+            repo.full_name, issue.number, e
+        );
+        NexusError::from(e)
+    })?;
+
+    info!(
+        "Removed stale label from {}#{}",
+        repo.full_name, issue.number
+    );
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use http::StatusCode;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{collections::HashMap, time::Duration};
+use tracing::{debug, error, info};
+
+use crate::{
+    config::{StatuspageConfig, StatuspageRule},
+    environments,
+    error::NexusError,
+    repos,
+    service::AppState,
+    store::StoreError,
+    template,
+    webhook::WebhookPayload,
+};
+
+// `statuspage-incident:{component id}` in the shared store, the incident a rule opened
+const INCIDENT: &str = "statuspage-incident:";
+
+#[derive(Debug, Deserialize)]
+struct Incident {
+    id: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatuspageError {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    // Statuspage explains the problem in `error`, e.g. an unknown component
+    #[error("Statuspage returned {status}: {message}")]
+    Api { status: StatusCode, message: String },
+    #[error("unexpected response: {0}")]
+    Response(#[from] serde_json::Error),
+    // the incident a rule opened is kept in the shared store
+    #[error("storage failed: {0}")]
+    Store(#[from] StoreError),
+}
+
+pub async fn on_event(
+    state: &AppState,
+    event_type: &str,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let config = &state.config.statuspage;
+    if !config.enabled {
+        return Ok(());
+    }
+    let environment = environments::for_payload(&state.config.environments, payload);
+    let deployment_state = payload
+        .deployment_status
+        .as_ref()
+        .and_then(|status| serde_json::to_value(status.state).ok())
+        .and_then(|state| state.as_str().map(str::to_string));
+    let repo = payload.repository.as_ref().map(|r| r.full_name.as_str());
+
+    let rules: Vec<&StatuspageRule> = config
+        .rules
+        .iter()
+        .filter(|rule| {
+            rule.event == event_type
+                && rule
+                    .action
+                    .as_ref()
+                    .is_none_or(|a| payload.action.as_ref() == Some(a))
+                && (rule.repos.is_empty() || repo.is_some_and(|r| repos::matches(&rule.repos, r)))
+                && (rule.environments.is_empty()
+                    || environment
+                        .as_ref()
+                        .is_some_and(|e| rule.environments.contains(e)))
+                && (rule.deployment_states.is_empty()
+                    || deployment_state
+                        .as_ref()
+                        .is_some_and(|s| rule.deployment_states.contains(s)))
+        })
+        .collect();
+    if rules.is_empty() {
+        return Ok(());
+    }
+    if config.api_key.is_none() || config.page_id.is_empty() {
+        error!("Statuspage updates are enabled but api_key or page_id is not configured");
+        return Ok(());
+    }
+
+    let vars = variables(event_type, payload, environment, deployment_state);
+    let mut result = Ok(());
+    for rule in rules {
+        let outcome = if rule.resolve {
+            resolve(state, config, rule, &vars).await
+        } else {
+            open(state, config, rule, &vars).await
+        };
+        if let Err(e) = outcome {
+            error!(
+                "Failed to update Statuspage component {} for {} event: {}",
+                rule.component_id, event_type, e
+            );
+            result = Err(NexusError::sink("Statuspage", e));
+        }
+    }
+    result
+}
+
+fn variables(
+    event_type: &str,
+    payload: &WebhookPayload,
+    environment: Option<String>,
+    deployment_state: Option<String>,
+) -> HashMap<&'static str, String> {
+    let mut vars = HashMap::from([
+        ("event", event_type.to_string()),
+        ("action", payload.action.clone().unwrap_or_default()),
+        ("environment", environment.unwrap_or_default()),
+        ("state", deployment_state.unwrap_or_default()),
+    ]);
+    if let Some(repo) = &payload.repository {
+        vars.insert("repo", repo.full_name.clone());
+    }
+    if let Some(sender) = &payload.sender {
+        vars.insert("sender", sender.login.clone());
+    }
+    if let Some(deployment) = &payload.deployment {
+        vars.insert("ref", deployment.ref_name.clone());
+        vars.insert("sha", deployment.sha.clone());
+    }
+    if let Some(status) = &payload.deployment_status {
+        vars.insert(
+            "description",
+            status.description.clone().unwrap_or_default(),
+        );
+        if let Some(url) = status.log_url.as_ref().or(status.environment_url.as_ref()) {
+            vars.insert("url", url.clone());
+        }
+    }
+    if let Some(advisory) = payload
+        .security_advisory
+        .as_ref()
+        .or(payload.repository_advisory.as_ref())
+    {
+        vars.insert("ghsa", advisory.ghsa_id.clone());
+        vars.insert("summary", advisory.summary.clone());
+        vars.insert("severity", advisory.severity.clone().unwrap_or_default());
+        let url = advisory
+            .html_url
+            .clone()
+            .unwrap_or_else(|| format!("https://github.com/advisories/{}", advisory.ghsa_id));
+        vars.insert("url", url);
+    }
+    vars
+}
+
+// the component's status is set on every match; the incident is opened once, until a
+// resolving rule closes it
+async fn open(
+    state: &AppState,
+    config: &StatuspageConfig,
+    rule: &StatuspageRule,
+    vars: &HashMap<&'static str, String>,
+) -> Result<(), StatuspageError> {
+    let path = format!("/components/{}", rule.component_id);
+    let body = json!({ "component": { "status": rule.component_status } });
+    send(state, config, reqwest::Method::PATCH, &path, body).await?;
+    info!(
+        "Set Statuspage component {} to {}",
+        rule.component_id, rule.component_status
+    );
+
+    let Some(name) = &rule.incident_name else {
+        return Ok(());
+    };
+    let key = format!("{}{}", INCIDENT, rule.component_id);
+    if let Some(id) = state.store.value(&key).await? {
+        debug!(
+            "Statuspage incident {} for component {} is already open",
+            id, rule.component_id
+        );
+        return Ok(());
+    }
+    let body = json!({
+        "incident": {
+            "name": template::render(name, vars),
+            "status": "investigating",
+            "body": template::render(&rule.incident_body, vars),
+            "component_ids": [rule.component_id],
+            "components": { &rule.component_id: rule.component_status },
+        }
+    });
+    let response = send(state, config, reqwest::Method::POST, "/incidents", body).await?;
+    let incident: Incident = serde_json::from_value(response)?;
+    state.store.set_if_absent(&key, &incident.id).await?;
+    info!(
+        "Opened Statuspage incident {} for component {}",
+        incident.id, rule.component_id
+    );
+    Ok(())
+}
+
+async fn resolve(
+    state: &AppState,
+    config: &StatuspageConfig,
+    rule: &StatuspageRule,
+    vars: &HashMap<&'static str, String>,
+) -> Result<(), StatuspageError> {
+    let key = format!("{}{}", INCIDENT, rule.component_id);
+    let Some(id) = state.store.take_value(&key).await? else {
+        let path = format!("/components/{}", rule.component_id);
+        let body = json!({ "component": { "status": "operational" } });
+        send(state, config, reqwest::Method::PATCH, &path, body).await?;
+        info!(
+            "Set Statuspage component {} to operational",
+            rule.component_id
+        );
+        return Ok(());
+    };
+    let mut incident = json!({
+        "status": "resolved",
+        "components": { &rule.component_id: "operational" },
+    });
+    if !rule.incident_body.is_empty() {
+        incident["body"] = json!(template::render(&rule.incident_body, vars));
+    }
+    let path = format!("/incidents/{}", id);
+    let body = json!({ "incident": incident });
+    if let Err(e) = send(state, config, reqwest::Method::PATCH, &path, body).await {
+        // kept for the next resolving event
+        let _ = state.store.set_if_absent(&key, &id).await;
+        return Err(e);
+    }
+    info!(
+        "Resolved Statuspage incident {} for component {}",
+        id, rule.component_id
+    );
+    Ok(())
+}
+
+async fn send(
+    state: &AppState,
+    config: &StatuspageConfig,
+    method: reqwest::Method,
+    path: &str,
+    body: Value,
+) -> Result<Value, StatuspageError> {
+    let url = format!(
+        "{}/pages/{}{}",
+        config.api_url.trim_end_matches('/'),
+        config.page_id,
+        path
+    );
+    let response = state
+        .http_client
+        .request(method, url)
+        .header(
+            "Authorization",
+            format!("OAuth {}", config.api_key.as_deref().unwrap_or_default()),
+        )
+        .timeout(Duration::from_secs(30))
+        .json(&body)
+        .send()
+        .await?;
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(StatuspageError::Api {
+            status,
+            message: text.trim().to_string(),
+        });
+    }
+    Ok(serde_json::from_str(&text)?)
+}
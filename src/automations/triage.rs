@@ -0,0 +1,131 @@
+use regex::RegexSet;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{PolicyAction, TriageRule},
+    error::NexusError,
+    github,
+    policy::{self, Subject},
+    repos,
+    service::AppState,
+    webhook::{Issue, WebhookPayload},
+};
+
+pub async fn on_issue_opened(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    let config = &state.config.triage;
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let (Some(issue), Some(repo)) = (&payload.issue, &payload.repository) else {
+        return Ok(());
+    };
+    // the issues API reports pull requests too, but they are not ours to triage
+    if issue.is_pull_request() {
+        return Ok(());
+    }
+
+    let mut labels = Vec::new();
+    let mut assignees = Vec::new();
+    for rule in config
+        .rules
+        .iter()
+        .filter(|r| r.repos.is_empty() || repos::matches(&r.repos, &repo.full_name))
+    {
+        if matches(rule, issue) {
+            labels.extend(rule.labels.iter().cloned());
+            assignees.extend(rule.assignees.iter().cloned());
+        }
+    }
+    labels.sort();
+    labels.dedup();
+    assignees.sort();
+    assignees.dedup();
+
+    if labels.is_empty() && assignees.is_empty() {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Issue triage is enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: github::GithubError| {
+        error!(
+            "Failed to triage {}#{}: {}",
+            repo.full_name, issue.number, e
+        );
+        NexusError::from(e)
+    };
+
+    let subject = Subject::issue(&repo.full_name, issue);
+    if !labels.is_empty() && !policy::allows(state, &token, &[PolicyAction::Label], &subject).await
+    {
+        labels.clear();
+    }
+    if !assignees.is_empty()
+        && !policy::allows(state, &token, &[PolicyAction::Assign], &subject).await
+    {
+        assignees.clear();
+    }
+
+    if !labels.is_empty() {
+        github::add_labels(
+            &state.github,
+            &token,
+            &repo.full_name,
+            issue.number,
+            &labels,
+        )
+        .await
+        .map_err(api_error)?;
+    }
+    if !assignees.is_empty() {
+        github::add_assignees(
+            &state.github,
+            &token,
+            &repo.full_name,
+            issue.number,
+            &assignees,
+        )
+        .await
+        .map_err(api_error)?;
+    }
+
+    info!(
+        "Triaged {}#{} with labels [{}] and assignees [{}]",
+        repo.full_name,
+        issue.number,
+        labels.join(", "),
+        assignees.join(", ")
+    );
+    Ok(())
+}
+
+fn matches(rule: &TriageRule, issue: &Issue) -> bool {
+    let text = format!(
+        "{}\n{}",
+        issue.title,
+        issue.body.as_deref().unwrap_or_default()
+    );
+
+    let lowered = text.to_lowercase();
+    if rule
+        .keywords
+        .iter()
+        .any(|k| lowered.contains(&k.to_lowercase()))
+    {
+        return true;
+    }
+
+    if rule.patterns.is_empty() {
+        return false;
+    }
+    match RegexSet::new(&rule.patterns) {
+        Ok(set) => set.is_match(&text),
+        Err(e) => {
+            warn!("Invalid triage pattern: {}", e);
+            false
+        }
+    }
+}
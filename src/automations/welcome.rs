@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use tracing::{error, info};
+
+use crate::{
+    config::PolicyAction,
+    error::NexusError,
+    github::{self, GithubError},
+    policy::{self, Subject},
+    service::AppState,
+    template,
+    webhook::{PullRequest, Repository, WebhookPayload},
+};
+
+// associations that settle the question without a search
+const FIRST_TIME_ASSOCIATIONS: &[&str] = &["FIRST_TIME_CONTRIBUTOR", "FIRST_TIMER"];
+const RETURNING_ASSOCIATIONS: &[&str] = &["OWNER", "MEMBER", "COLLABORATOR", "CONTRIBUTOR"];
+
+// what is being greeted, a pull request or an issue
+struct Opened<'a> {
+    number: u64,
+    title: &'a str,
+    url: &'a str,
+    author: &'a str,
+    association: Option<&'a str>,
+    subject: Subject<'a>,
+}
+
+pub async fn on_pull_request_opened(
+    state: &AppState,
+    pr: &PullRequest,
+    repo: &Repository,
+) -> Result<(), NexusError> {
+    let opened = Opened {
+        number: pr.number,
+        title: &pr.title,
+        url: &pr.html_url,
+        author: &pr.user.login,
+        association: pr.author_association.as_deref(),
+        subject: Subject::pull_request(&repo.full_name, pr),
+    };
+    greet(state, repo, opened, false).await
+}
+
+pub async fn on_issue_opened(state: &AppState, payload: &WebhookPayload) -> Result<(), NexusError> {
+    if !state.config.welcome.issues {
+        return Ok(());
+    }
+    let (Some(issue), Some(repo)) = (&payload.issue, &payload.repository) else {
+        return Ok(());
+    };
+    if issue.is_pull_request() {
+        return Ok(());
+    }
+    let opened = Opened {
+        number: issue.number,
+        title: &issue.title,
+        url: &issue.html_url,
+        author: &issue.user.login,
+        association: issue.author_association.as_deref(),
+        subject: Subject::issue(&repo.full_name, issue),
+    };
+    greet(state, repo, opened, true).await
+}
+
+async fn greet(
+    state: &AppState,
+    repo: &Repository,
+    opened: Opened<'_>,
+    issue: bool,
+) -> Result<(), NexusError> {
+    let config = &state.config.welcome;
+    let (enabled, first_time_only, template) = config.for_repo(&repo.full_name, issue);
+    if !enabled || opened.author.ends_with("[bot]") {
+        return Ok(());
+    }
+
+    let Some(token) = state.github_token(&repo.full_name).await else {
+        error!("Welcome comments are enabled but no GitHub token is configured");
+        return Ok(());
+    };
+    let api_error = |e: GithubError| {
+        error!(
+            "Failed to welcome {} on {}#{}: {}",
+            opened.author, repo.full_name, opened.number, e
+        );
+        NexusError::from(e)
+    };
+
+    let first_time = if !first_time_only && config.label.is_empty() {
+        // nothing depends on it, so the search is skipped
+        false
+    } else {
+        is_first_contribution(state, &token, &repo.full_name, &opened)
+            .await
+            .map_err(api_error)?
+    };
+    if first_time_only && !first_time {
+        return Ok(());
+    }
+
+    if first_time
+        && !config.label.is_empty()
+        && policy::allows(state, &token, &[PolicyAction::Label], &opened.subject).await
+    {
+        github::add_labels(
+            &state.github,
+            &token,
+            &repo.full_name,
+            opened.number,
+            std::slice::from_ref(&config.label),
+        )
+        .await
+        .map_err(api_error)?;
+    }
+
+    let vars = HashMap::from([
+        ("author", opened.author.to_string()),
+        ("title", opened.title.to_string()),
+        ("number", opened.number.to_string()),
+        ("repo", repo.full_name.clone()),
+        ("url", opened.url.to_string()),
+    ]);
+    let body = template::render(template, &vars);
+
+    if !policy::allows(state, &token, &[PolicyAction::Comment], &opened.subject).await {
+        return Ok(());
+    }
+
+    github::create_issue_comment(&state.github, &token, &repo.full_name, opened.number, &body)
+        .await
+        .map_err(api_error)?;
+
+    info!(
+        "Posted welcome comment on {}#{}",
+        repo.full_name, opened.number
+    );
+    Ok(())
+}
+
+// no earlier issue or pull request by the author in the repository; the search index
+// may already list the new one, so it does not count
+async fn is_first_contribution(
+    state: &AppState,
+    token: &str,
+    repo: &str,
+    opened: &Opened<'_>,
+) -> Result<bool, GithubError> {
+    match opened.association {
+        Some(a) if FIRST_TIME_ASSOCIATIONS.contains(&a) => return Ok(true),
+        Some(a) if RETURNING_ASSOCIATIONS.contains(&a) => return Ok(false),
+        _ => {}
+    }
+    let query = format!("repo:{} author:{}", repo, opened.author);
+    let earlier = github::search_issues(&state.github, token, &query, 2).await?;
+    Ok(earlier.iter().all(|item| item.number == opened.number))
+}
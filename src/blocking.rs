@@ -0,0 +1,230 @@
+// Verify, parse and match deliveries without an async runtime, for CGI-style handlers and
+// scripts. Needs only the `blocking` feature; the server's runtime and HTTP stack are not
+// built with it.
+//
+//     let delivery = Delivery::from_headers(Some(secret), &headers, &body)?;
+//     if delivery.matches("pull_request.opened") { ... }
+
+use std::{fmt, io::Read};
+
+use crate::{
+    models::WebhookPayload,
+    signature::{SignatureError, SignatureVerifier},
+};
+
+/// Why a delivery was rejected.
+#[derive(Debug)]
+pub enum Error {
+    MissingEvent,
+    // a secret is configured but the delivery is not signed
+    MissingSignature,
+    Signature(SignatureError),
+    Parse(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingEvent => write!(f, "no x-github-event header"),
+            Error::MissingSignature => write!(f, "no x-hub-signature-256 header"),
+            Error::Signature(e) => write!(f, "invalid signature: {}", e),
+            Error::Parse(e) => write!(f, "invalid payload: {}", e),
+            Error::Io(e) => write!(f, "failed to read the body: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Signature(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::MissingEvent | Error::MissingSignature => None,
+        }
+    }
+}
+
+/// A verified and parsed delivery.
+#[derive(Debug)]
+pub struct Delivery {
+    pub event_type: String,
+    pub delivery_id: Option<String>,
+    pub payload: WebhookPayload,
+}
+
+impl Delivery {
+    // with a secret the body must carry a valid `signature`; without one it is not checked
+    pub fn from_parts(
+        secret: Option<&str>,
+        event_type: &str,
+        delivery_id: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<Self, Error> {
+        if let Some(secret) = secret {
+            verify(secret, body, signature.ok_or(Error::MissingSignature)?)?;
+        }
+        Ok(Self {
+            event_type: event_type.to_string(),
+            delivery_id: delivery_id.map(str::to_string),
+            payload: parse(body)?,
+        })
+    }
+
+    // header names are matched ignoring case
+    pub fn from_headers<K, V>(
+        secret: Option<&str>,
+        headers: impl IntoIterator<Item = (K, V)>,
+        body: &[u8],
+    ) -> Result<Self, Error>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let (mut event_type, mut delivery_id, mut signature) = (None, None, None);
+        for (name, value) in headers {
+            let value = value.as_ref().to_string();
+            match name.as_ref().to_ascii_lowercase().as_str() {
+                "x-github-event" => event_type = Some(value),
+                "x-github-delivery" => delivery_id = Some(value),
+                "x-hub-signature-256" => signature = Some(value),
+                _ => {}
+            }
+        }
+        let event_type = event_type.ok_or(Error::MissingEvent)?;
+        Self::from_parts(
+            secret,
+            &event_type,
+            delivery_id.as_deref(),
+            signature.as_deref(),
+            body,
+        )
+    }
+
+    // a CGI request: headers from `HTTP_*` environment variables, the body from stdin
+    pub fn from_cgi(secret: Option<&str>) -> Result<Self, Error> {
+        let mut body = Vec::new();
+        std::io::stdin().read_to_end(&mut body).map_err(Error::Io)?;
+        let var = |name: &str| std::env::var(name).ok();
+        let event_type = var("HTTP_X_GITHUB_EVENT").ok_or(Error::MissingEvent)?;
+        Self::from_parts(
+            secret,
+            &event_type,
+            var("HTTP_X_GITHUB_DELIVERY").as_deref(),
+            var("HTTP_X_HUB_SIGNATURE_256").as_deref(),
+            &body,
+        )
+    }
+
+    pub fn action(&self) -> Option<&str> {
+        self.payload.action.as_deref()
+    }
+
+    pub fn repository(&self) -> Option<&str> {
+        self.payload
+            .repository
+            .as_ref()
+            .map(|r| r.full_name.as_str())
+    }
+
+    pub fn sender(&self) -> Option<&str> {
+        self.payload.sender.as_ref().map(|s| s.login.as_str())
+    }
+
+    // `event` or `event.action`, as in `[queue]` priorities, e.g. `pull_request.opened`
+    pub fn matches(&self, pattern: &str) -> bool {
+        match pattern.split_once('.') {
+            Some((event_type, action)) => {
+                event_type == self.event_type && self.action() == Some(action)
+            }
+            None => pattern == self.event_type,
+        }
+    }
+
+    // any of the patterns; an empty list matches everything
+    pub fn matches_any(&self, patterns: &[&str]) -> bool {
+        patterns.is_empty() || patterns.iter().any(|p| self.matches(p))
+    }
+}
+
+pub fn verify(secret: &str, body: &[u8], signature: &str) -> Result<(), Error> {
+    let mut verifier = SignatureVerifier::new(secret).expect("HMAC accepts any key length");
+    verifier.update(body);
+    verifier.check(signature).map_err(Error::Signature)
+}
+
+pub fn parse(body: &[u8]) -> Result<WebhookPayload, Error> {
+    serde_json::from_slice(body).map_err(Error::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "secret";
+    const BODY: &[u8] = br#"{"action":"opened","repository":{"name":"nexus","full_name":"octo/nexus","html_url":"https://github.com/octo/nexus"},"sender":{"login":"octocat","html_url":"https://github.com/octocat"}}"#;
+
+    fn signature(body: &[u8]) -> String {
+        let mut verifier = SignatureVerifier::new(SECRET).unwrap();
+        verifier.update(body);
+        verifier.signature()
+    }
+
+    #[test]
+    fn accepts_a_signed_delivery_with_headers_in_any_case() {
+        let signature = signature(BODY);
+        let headers = [
+            ("X-GitHub-Event", "pull_request"),
+            ("x-github-delivery", "1"),
+            ("X-Hub-Signature-256", signature.as_str()),
+        ];
+        let delivery = Delivery::from_headers(Some(SECRET), headers, BODY).unwrap();
+        assert_eq!(delivery.event_type, "pull_request");
+        assert_eq!(delivery.delivery_id.as_deref(), Some("1"));
+        assert_eq!(delivery.repository(), Some("octo/nexus"));
+        assert_eq!(delivery.sender(), Some("octocat"));
+        assert!(delivery.matches("pull_request"));
+        assert!(delivery.matches("pull_request.opened"));
+        assert!(!delivery.matches("pull_request.closed"));
+        assert!(delivery.matches_any(&[]));
+        assert!(delivery.matches_any(&["push", "pull_request.opened"]));
+    }
+
+    #[test]
+    fn rejects_unsigned_and_tampered_deliveries() {
+        let unsigned = Delivery::from_parts(Some(SECRET), "pull_request", None, None, BODY);
+        assert!(matches!(unsigned, Err(Error::MissingSignature)));
+
+        let signature = signature(BODY);
+        let tampered = String::from_utf8_lossy(BODY).replace("opened", "closed");
+        let tampered = Delivery::from_parts(
+            Some(SECRET),
+            "pull_request",
+            None,
+            Some(&signature),
+            tampered.as_bytes(),
+        );
+        assert!(matches!(
+            tampered,
+            Err(Error::Signature(SignatureError::Mismatch))
+        ));
+
+        let no_event = Delivery::from_headers(
+            Some(SECRET),
+            [("x-hub-signature-256", signature.as_str())],
+            BODY,
+        );
+        assert!(matches!(no_event, Err(Error::MissingEvent)));
+    }
+
+    #[test]
+    fn checks_nothing_without_a_secret() {
+        let delivery = Delivery::from_parts(None, "pull_request", None, None, BODY).unwrap();
+        assert_eq!(delivery.action(), Some("opened"));
+
+        let invalid = Delivery::from_parts(None, "pull_request", None, None, b"{");
+        assert!(matches!(invalid, Err(Error::Parse(_))));
+    }
+}
@@ -0,0 +1,245 @@
+// Processed deliveries as rows in ClickHouse, for dashboards that outlive the in-memory
+// metrics. Rows are buffered and inserted in batches as JSONEachRow over the HTTP
+// interface; the table is created, and columns added to it, before the first insert.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::ClickHouseConfig, error::NexusError, service::AppState, webhook::WebhookPayload,
+};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+// new columns go at the end; existing tables get them through ADD COLUMN IF NOT EXISTS
+const COLUMNS: &[(&str, &str)] = &[
+    ("delivery_id", "String"),
+    ("event_type", "LowCardinality(String)"),
+    ("action", "LowCardinality(String)"),
+    ("repository", "LowCardinality(String)"),
+    ("sender", "String"),
+    ("received_at", "DateTime64(3, 'UTC')"),
+    ("processed_at", "DateTime64(3, 'UTC')"),
+    ("duration_ms", "UInt64"),
+    ("latency_ms", "UInt64"),
+    ("success", "UInt8"),
+    ("status", "UInt16"),
+];
+
+#[derive(Serialize)]
+struct Row<'a> {
+    delivery_id: &'a str,
+    event_type: &'a str,
+    action: &'a str,
+    repository: &'a str,
+    sender: &'a str,
+    received_at: String,
+    processed_at: String,
+    // spent in the handlers
+    duration_ms: u64,
+    // from receipt to the end of processing, queue wait included
+    latency_ms: u64,
+    success: u8,
+    // 0 on success
+    status: u16,
+}
+
+/// Rows waiting for the next insert.
+#[derive(Clone, Default)]
+pub struct ClickHouse {
+    rows: Arc<Mutex<Vec<String>>>,
+    // one insert at a time keeps batches in order
+    inserting: Arc<tokio::sync::Mutex<()>>,
+    // wakes the flusher when a full batch is waiting
+    full: Arc<Notify>,
+    // set once the table is known to match COLUMNS
+    migrated: Arc<AtomicBool>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Processed<'a> {
+    pub event_type: &'a str,
+    pub delivery_id: Option<&'a str>,
+    pub payload: &'a WebhookPayload,
+    pub received_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    // the status the handlers failed with
+    pub status: Option<u16>,
+}
+
+pub fn record(state: &AppState, processed: Processed<'_>) {
+    let config = &state.config.clickhouse;
+    if !config.enabled {
+        return;
+    }
+    let payload = processed.payload;
+    let now = Utc::now();
+    let row = Row {
+        delivery_id: processed.delivery_id.unwrap_or_default(),
+        event_type: processed.event_type,
+        action: payload.action.as_deref().unwrap_or_default(),
+        repository: payload
+            .repository
+            .as_ref()
+            .map_or("", |r| r.full_name.as_str()),
+        sender: payload.sender.as_ref().map_or("", |s| s.login.as_str()),
+        received_at: processed.received_at.format(TIME_FORMAT).to_string(),
+        processed_at: now.format(TIME_FORMAT).to_string(),
+        duration_ms: processed.duration_ms,
+        latency_ms: (now - processed.received_at).num_milliseconds().max(0) as u64,
+        success: u8::from(processed.status.is_none()),
+        status: processed.status.unwrap_or_default(),
+    };
+    let Ok(row) = serde_json::to_string(&row) else {
+        return;
+    };
+
+    let buffered = {
+        let mut rows = state.clickhouse.rows.lock().unwrap();
+        rows.push(row);
+        rows.len()
+    };
+    if buffered >= config.batch_size {
+        state.clickhouse.full.notify_one();
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) -> Option<JoinHandle<()>> {
+    if !state.config.clickhouse.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let config = &state.config.clickhouse;
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.flush_interval_secs.max(1)));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = state.clickhouse.full.notified() => {}
+            }
+            flush(&state).await;
+        }
+    }))
+}
+
+// sends what is buffered; on failure the rows go back to the front of the buffer
+pub async fn flush(state: &AppState) {
+    let config = &state.config.clickhouse;
+    if !config.enabled {
+        return;
+    }
+    let _inserting = state.clickhouse.inserting.lock().await;
+    // retried before every insert until it works, so ClickHouse may start after nexus
+    if config.create_table && !state.clickhouse.migrated.load(Ordering::Relaxed) {
+        match migrate(state, config).await {
+            Ok(()) => state.clickhouse.migrated.store(true, Ordering::Relaxed),
+            Err(e) => {
+                error!("Failed to prepare the ClickHouse table: {}", e);
+                return;
+            }
+        }
+    }
+    let rows = std::mem::take(&mut *state.clickhouse.rows.lock().unwrap());
+    if rows.is_empty() {
+        return;
+    }
+
+    let insert = format!("INSERT INTO {} FORMAT JSONEachRow", table_name(config));
+    match query(state, config, &insert, rows.join("\n")).await {
+        Ok(()) => info!("Inserted {} rows into ClickHouse", rows.len()),
+        Err(e) => {
+            let mut buffered = state.clickhouse.rows.lock().unwrap();
+            let newer = std::mem::replace(&mut *buffered, rows);
+            buffered.extend(newer);
+            let excess = buffered.len().saturating_sub(config.max_buffered_rows);
+            buffered.drain(..excess);
+            warn!(
+                "Failed to insert into ClickHouse, keeping {} rows for the next attempt{}: {}",
+                buffered.len(),
+                match excess {
+                    0 => String::new(),
+                    n => format!(" and dropping the {} oldest", n),
+                },
+                e
+            );
+        }
+    }
+}
+
+async fn migrate(state: &AppState, config: &ClickHouseConfig) -> Result<(), NexusError> {
+    let columns: Vec<String> = COLUMNS
+        .iter()
+        .map(|(name, kind)| format!("`{}` {}", name, kind))
+        .collect();
+    let create = format!(
+        "CREATE TABLE IF NOT EXISTS {} ({}) ENGINE = MergeTree \
+         PARTITION BY toYYYYMM(received_at) ORDER BY (repository, event_type, received_at)",
+        table_name(config),
+        columns.join(", ")
+    );
+    query(state, config, &create, String::new()).await?;
+    for column in &columns {
+        let alter = format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {}",
+            table_name(config),
+            column
+        );
+        query(state, config, &alter, String::new()).await?;
+    }
+    Ok(())
+}
+
+fn table_name(config: &ClickHouseConfig) -> String {
+    format!(
+        "`{}`.`{}`",
+        config.database.replace('`', ""),
+        config.table.replace('`', "")
+    )
+}
+
+async fn query(
+    state: &AppState,
+    config: &ClickHouseConfig,
+    query: &str,
+    body: String,
+) -> Result<(), NexusError> {
+    let mut request = state
+        .http_client
+        .post(&config.url)
+        .query(&[("query", query)])
+        .timeout(Duration::from_secs(30))
+        .body(body);
+    if let Some(user) = &config.user {
+        request = request.header("X-ClickHouse-User", user);
+    }
+    if let Some(password) = &config.password {
+        request = request.header("X-ClickHouse-Key", password);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|source| NexusError::SinkUnreachable {
+            sink: "ClickHouse",
+            source,
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    // ClickHouse explains the problem in the body, e.g. an unknown column
+    let message = response.text().await.unwrap_or_default();
+    Err(NexusError::SinkRejected {
+        sink: "ClickHouse",
+        status,
+        message: message.trim().to_string(),
+    })
+}
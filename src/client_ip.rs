@@ -0,0 +1,146 @@
+use http::HeaderMap;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+/// The address a request originally came from, resolved through trusted proxies.
+/// Inserted into request extensions by the listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+// proxies append to the chain, so it is walked right to left and the first hop that is
+// not a trusted proxy is the client; anything further left could be spoofed
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let chain = forwarded_chain(headers);
+    let mut client = peer;
+    for hop in chain.iter().rev() {
+        client = *hop;
+        if !is_trusted(hop) {
+            break;
+        }
+    }
+    client
+}
+
+// `Forwarded` (RFC 7239) wins over `X-Forwarded-For` when both are present
+fn forwarded_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    let values = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+    };
+
+    let forwarded: Vec<IpAddr> = values("forwarded")
+        .into_iter()
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                key.eq_ignore_ascii_case("for")
+                    .then(|| parse_node(value))
+                    .flatten()
+            })
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    values("x-forwarded-for")
+        .into_iter()
+        .filter_map(parse_node)
+        .collect()
+}
+
+// accepts `1.2.3.4`, `1.2.3.4:80`, `"[2001:db8::1]:443"` and `2001:db8::1`
+fn parse_node(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+    if let Ok(ip) = value.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    value.rsplit_once(':')?.0.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn spoofed_hops_left_of_the_client_are_ignored() {
+        let headers = headers(&[("x-forwarded-for", "1.1.1.1, 203.0.113.7, 10.0.0.2")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn an_untrusted_peer_is_the_client_whatever_it_claims() {
+        let headers = headers(&[("x-forwarded-for", "1.1.1.1"), ("forwarded", "for=2.2.2.2")]);
+        assert_eq!(
+            resolve(ip("198.51.100.9"), &headers, &trusted()),
+            ip("198.51.100.9")
+        );
+    }
+
+    #[test]
+    fn forwarded_wins_over_x_forwarded_for() {
+        let headers = headers(&[
+            ("x-forwarded-for", "1.1.1.1"),
+            ("forwarded", "for=203.0.113.7;proto=https, for=10.0.0.2"),
+        ]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn hops_may_carry_a_port() {
+        let forwarded = headers(&[("forwarded", "for=\"[2001:db8::1]:4711\", For=\"[fd00::2]\"")]);
+        assert_eq!(
+            resolve(ip("fd00::1"), &forwarded, &trusted()),
+            ip("2001:db8::1")
+        );
+        let x_forwarded_for = headers(&[("x-forwarded-for", "203.0.113.7:51234")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &x_forwarded_for, &trusted()),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_chain_of_trusted_proxies_falls_back_to_the_first_hop() {
+        let headers = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.3")
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use globset::{GlobBuilder, GlobMatcher};
+use tracing::warn;
+
+use crate::github::{self, GithubClient, GithubError};
+
+pub const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug)]
+pub struct Rule {
+    pub pattern: String,
+    pub owners: Vec<String>,
+    matchers: Vec<GlobMatcher>,
+}
+
+#[derive(Debug, Default)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+impl CodeOwners {
+    pub fn parse(contents: &str) -> Self {
+        let rules = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let line = line.split(" #").next().unwrap_or(line);
+                let mut parts = line.split_whitespace();
+                let pattern = parts.next()?.to_string();
+                let owners = parts.map(str::to_string).collect();
+                let matchers = compile(&pattern)?;
+                Some(Rule {
+                    pattern,
+                    owners,
+                    matchers,
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    // the last matching rule wins, as on GitHub
+    pub fn owners_for(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matchers.iter().any(|m| m.is_match(path)))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+// the first CODEOWNERS file found at `git_ref`, in the order GitHub looks for them
+pub async fn fetch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    git_ref: Option<&str>,
+) -> Result<Option<CodeOwners>, GithubError> {
+    for location in LOCATIONS {
+        if let Some(contents) =
+            github::get_file_contents(client, token, repo, location, git_ref).await?
+        {
+            return Ok(Some(CodeOwners::parse(&contents)));
+        }
+    }
+    Ok(None)
+}
+
+// translates gitignore-style CODEOWNERS patterns into globs
+fn compile(pattern: &str) -> Option<Vec<GlobMatcher>> {
+    let anchored = pattern.starts_with('/') || pattern.trim_end_matches('/').contains('/');
+    let directory = pattern.ends_with('/');
+    let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+
+    let base = if anchored || trimmed.starts_with("**") {
+        trimmed.to_string()
+    } else {
+        format!("**/{}", trimmed)
+    };
+
+    // `docs/*` owns the files in docs but not those below it; a plain name may be a
+    // directory and owns everything under it
+    let last = trimmed.rsplit('/').next().unwrap_or(trimmed);
+    let wildcard = last.contains(['*', '?', '[']);
+    let mut globs = Vec::new();
+    if directory || !wildcard {
+        globs.push(format!("{}/**", base));
+    }
+    if !directory {
+        globs.push(base);
+    }
+
+    globs
+        .iter()
+        .map(|glob| {
+            GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .map(|g| g.compile_matcher())
+                .map_err(|e| warn!("Invalid CODEOWNERS pattern {:?}: {}", pattern, e))
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(contents: &str, path: &str) -> Option<String> {
+        CodeOwners::parse(contents)
+            .owners_for(path)
+            .first()
+            .cloned()
+    }
+
+    #[test]
+    fn a_trailing_wildcard_stays_in_its_directory() {
+        let contents = "docs/* @docs";
+        assert_eq!(owner(contents, "docs/README.md").as_deref(), Some("@docs"));
+        assert_eq!(owner(contents, "docs/api/index.md"), None);
+    }
+
+    #[test]
+    fn an_extension_matches_files_anywhere() {
+        let contents = "*.js @frontend";
+        assert_eq!(owner(contents, "app.js").as_deref(), Some("@frontend"));
+        assert_eq!(
+            owner(contents, "src/ui/app.js").as_deref(),
+            Some("@frontend")
+        );
+        assert_eq!(owner(contents, "src/ui/app.ts"), None);
+    }
+
+    #[test]
+    fn a_plain_name_owns_everything_under_it() {
+        let contents = "/build/logs/ @ops\napps/ @apps\nsrc @core";
+        assert_eq!(
+            owner(contents, "build/logs/a/b.log").as_deref(),
+            Some("@ops")
+        );
+        assert_eq!(owner(contents, "build/other.log"), None);
+        assert_eq!(
+            owner(contents, "x/apps/web/main.rs").as_deref(),
+            Some("@apps")
+        );
+        assert_eq!(owner(contents, "src/lib.rs").as_deref(), Some("@core"));
+        assert_eq!(owner(contents, "src").as_deref(), Some("@core"));
+    }
+
+    #[test]
+    fn double_stars_match_at_any_depth() {
+        let contents = "docs/** @docs\n**/logs @ops";
+        assert_eq!(owner(contents, "docs/a/b/c.md").as_deref(), Some("@docs"));
+        assert_eq!(
+            owner(contents, "deep/down/logs/x.log").as_deref(),
+            Some("@ops")
+        );
+    }
+
+    #[test]
+    fn the_last_matching_rule_wins_and_comments_are_skipped() {
+        let contents = "# owners\n* @everyone\n\n/docs/ @docs # the writers\n";
+        let owners = CodeOwners::parse(contents);
+        assert_eq!(owners.rules().len(), 2);
+        assert_eq!(owners.owners_for("docs/a.md"), ["@docs"]);
+        assert_eq!(owners.owners_for("src/a.rs"), ["@everyone"]);
+    }
+}
@@ -0,0 +1,68 @@
+// What running a configured program can fail with: deploy and rebuild commands, the
+// restarts of push deploys, and git for mirrors. The program's own complaint is kept,
+// since its exit status rarely says what went wrong.
+
+use std::process::{ExitStatus, Output};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("could not run {program}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("{}", exited(.program, .status, .complaint))]
+    Exited {
+        program: String,
+        status: ExitStatus,
+        complaint: Option<String>,
+    },
+}
+
+fn exited(program: &str, status: &ExitStatus, complaint: &Option<String>) -> String {
+    match complaint {
+        Some(line) => format!("{}: {} ({})", program, line, status),
+        None => format!("{} exited with {}", program, status),
+    }
+}
+
+impl CommandError {
+    pub fn spawn(program: impl Into<String>, source: std::io::Error) -> Self {
+        CommandError::Spawn {
+            program: program.into(),
+            source,
+        }
+    }
+
+    // the last line of stderr usually says what went wrong
+    pub fn exited(program: impl Into<String>, output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let complaint = stderr.lines().rev().find(|l| !l.trim().is_empty());
+        Self::complained(program, output.status, complaint)
+    }
+
+    // for complaints that quote a URL with credentials in it
+    pub fn redact(self, secret: &str, redacted: &str) -> Self {
+        match self {
+            CommandError::Exited {
+                program,
+                status,
+                complaint,
+            } => CommandError::Exited {
+                program,
+                status,
+                complaint: complaint.map(|c| c.replace(secret, redacted)),
+            },
+            spawn => spawn,
+        }
+    }
+
+    pub fn complained(program: impl Into<String>, status: ExitStatus, line: Option<&str>) -> Self {
+        CommandError::Exited {
+            program: program.into(),
+            status,
+            complaint: line.map(|l| l.trim().to_string()),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use nexus::Config;
+use tracing::error;
+
+// the effective configuration as TOML, with secrets redacted by the serializers
+pub fn show(config: &Config) -> i32 {
+    match toml::to_string_pretty(config) {
+        Ok(config) => {
+            print!("{}", config);
+            0
+        }
+        Err(e) => {
+            error!("Failed to serialize config: {}", e);
+            1
+        }
+    }
+}
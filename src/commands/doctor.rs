@@ -0,0 +1,575 @@
+use chrono::{DateTime, Utc};
+use nexus::{
+    Config, acme,
+    config::{RouteGroup, StateBackend},
+    github::{auth::AppAuth, client},
+    proxy,
+    redact::Redactor,
+    scheduler::Scheduler,
+    schema::Schemas,
+    scripting,
+    store::Store,
+};
+use reqwest::Url;
+use std::{fmt::Display, io, net::SocketAddr, path::Path, time::Duration};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::Args;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+// GitHub App tokens are signed JWTs that GitHub rejects when the clock is a minute off
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn line(&mut self, level: Level, check: &str, detail: impl Display) {
+        let tag = match level {
+            Level::Ok => "  ok",
+            Level::Warn => {
+                self.warnings += 1;
+                "warn"
+            }
+            Level::Fail => {
+                self.failures += 1;
+                "FAIL"
+            }
+        };
+        println!("[{}] {:<10} {}", tag, check, detail);
+    }
+}
+
+// exits 1 when any check failed; warnings alone still exit 0
+pub async fn run(args: &Args) -> i32 {
+    let mut report = Report::default();
+    let config = match super::try_load_config(args) {
+        Ok(config) => {
+            let source = match &args.config {
+                Some(path) => format!("loaded from {}", path.display()),
+                None => "defaults, no --config given".to_string(),
+            };
+            report.line(Level::Ok, "config", source);
+            config
+        }
+        Err(e) => {
+            report.line(Level::Fail, "config", e);
+            return 1;
+        }
+    };
+
+    check_config(&mut report, &config);
+    check_secret(&mut report, args.secret.as_deref());
+    check_ports(&mut report, &config, args.port).await;
+    check_github(&mut report, &config).await;
+    check_sinks(&mut report, &config).await;
+    check_certificate(&mut report, &config);
+    check_scripts(&mut report, &config);
+
+    println!();
+    println!("{} failed, {} warnings", report.failures, report.warnings);
+    if report.failures == 0 { 0 } else { 1 }
+}
+
+// what `serve` would refuse to start with
+fn check_config(report: &mut Report, config: &Config) {
+    match AppAuth::from_config(&config.github) {
+        Ok(Some(app)) => report.line(Level::Ok, "github", format!("GitHub App {}", app.app_id())),
+        Ok(None) if config.github.token.is_some() => {
+            report.line(Level::Ok, "github", "authenticating with a token")
+        }
+        Ok(None) => report.line(
+            Level::Warn,
+            "github",
+            "no token or GitHub App credentials; automations that call the API do nothing",
+        ),
+        Err(e) => report.line(
+            Level::Fail,
+            "github",
+            format!("GitHub App credentials: {}", e),
+        ),
+    }
+    for mapping in &config.environments {
+        let pattern = match (&mapping.branch, &mapping.tag) {
+            (Some(pattern), None) | (None, Some(pattern)) => pattern,
+            _ => {
+                report.line(
+                    Level::Fail,
+                    "environments",
+                    format!(
+                        "{} needs exactly one of branch and tag",
+                        mapping.environment
+                    ),
+                );
+                continue;
+            }
+        };
+        if let Err(e) = globset::Glob::new(pattern) {
+            report.line(
+                Level::Fail,
+                "environments",
+                format!("invalid pattern for {}: {}", mapping.environment, e),
+            );
+        }
+    }
+    if let Err(e) = proxy::client(&config.proxy) {
+        report.line(Level::Fail, "proxy", e);
+    } else if let Err(e) = client::http_client(config) {
+        report.line(Level::Fail, "github", e);
+    }
+    if let Err(e) = nexus::automations::stale::schedule(&mut Scheduler::new(), &config.stale) {
+        report.line(Level::Fail, "stale", format!("invalid schedule: {}", e));
+    }
+    if let Err(e) = nexus::automations::lock::schedule(&mut Scheduler::new(), &config.lock) {
+        report.line(Level::Fail, "lock", format!("invalid schedule: {}", e));
+    }
+    if let Err(e) = nexus::automations::reports::schedule(&mut Scheduler::new(), &config.reports) {
+        report.line(Level::Fail, "reports", format!("invalid schedule: {}", e));
+    }
+    if let Err(e) =
+        nexus::automations::label_sync::schedule(&mut Scheduler::new(), &config.label_sync)
+    {
+        report.line(
+            Level::Fail,
+            "label_sync",
+            format!("invalid schedule: {}", e),
+        );
+    }
+    if let Err(e) = nexus::export::schedule(&mut Scheduler::new(), &config.export) {
+        report.line(Level::Fail, "export", format!("invalid schedule: {}", e));
+    }
+    if config.export.enabled {
+        if !cfg!(feature = "parquet") {
+            report.line(
+                Level::Fail,
+                "export",
+                "enabled, but nexus was built without the `parquet` feature",
+            );
+        }
+        if config.export.dir.is_none() || config.archive.dir.is_none() {
+            report.line(Level::Fail, "export", "needs export.dir and archive.dir");
+        }
+    }
+    for label in &config.label_sync.labels {
+        let color = label.color.trim_start_matches('#');
+        if color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+            report.line(
+                Level::Fail,
+                "label_sync",
+                format!("label `{}` needs a six digit hex color", label.name),
+            );
+        }
+    }
+    let notify = &config.notify;
+    for channel in notify.owners.values().chain(&notify.default_channel) {
+        if !notify.channels.contains_key(channel) {
+            report.line(
+                Level::Fail,
+                "notify",
+                format!("channel `{}` is not in [notify.channels]", channel),
+            );
+        }
+    }
+    if let Some(pattern) = &config.pr_title.pattern
+        && let Err(e) = regex::Regex::new(pattern)
+    {
+        report.line(Level::Fail, "pr_title", format!("invalid pattern: {}", e));
+    }
+    if let Err(e) = regex::RegexSet::new(&config.commit_messages.ignore) {
+        report.line(
+            Level::Fail,
+            "commit_messages",
+            format!("invalid ignore pattern: {}", e),
+        );
+    }
+    if config.jira.enabled && (config.jira.base_url.is_empty() || config.jira.api_token.is_none()) {
+        report.line(Level::Fail, "jira", "base_url and api_token are required");
+    }
+    if config.linear.enabled {
+        if config.linear.api_key.is_none() {
+            report.line(Level::Fail, "linear", "api_key is required");
+        }
+        if config.linear.webhook_secret.is_none()
+            && (config.linear.post_state_changes || config.linear.post_comments)
+        {
+            report.line(
+                Level::Warn,
+                "linear",
+                "no webhook_secret, so updates from Linear are refused",
+            );
+        }
+    }
+    if config.statuspage.enabled
+        && (config.statuspage.api_key.is_none() || config.statuspage.page_id.is_empty())
+    {
+        report.line(
+            Level::Fail,
+            "statuspage",
+            "api_key and page_id are required",
+        );
+    }
+    if config.grafana.enabled && config.grafana.api_token.is_none() {
+        report.line(Level::Fail, "grafana", "api_token is required");
+    }
+    if config.milestone.enabled
+        && let Err(e) = regex::Regex::new(&config.milestone.pattern)
+    {
+        report.line(Level::Fail, "milestone", format!("invalid pattern: {}", e));
+    }
+    if let Err(e) = Redactor::from_config(&config.redaction) {
+        report.line(Level::Fail, "redaction", e);
+    }
+    if let Err(e) = Schemas::from_config(&config.schema) {
+        report.line(Level::Fail, "schema", e);
+    }
+    if config.server.listeners.iter().any(|l| l.tls) && !config.server.acme.enabled {
+        report.line(
+            Level::Fail,
+            "listeners",
+            "a listener has `tls = true` but [server.acme] is not enabled",
+        );
+    }
+    if config.server.acme.enabled && config.server.acme.domains.is_empty() {
+        report.line(Level::Fail, "acme", "enabled without any domains");
+    }
+    let serves_admin = config.server.listeners.is_empty()
+        || config
+            .server
+            .listeners
+            .iter()
+            .any(|l| l.routes.contains(&RouteGroup::Admin));
+    if serves_admin && config.server.admin_token.is_none() {
+        report.line(
+            Level::Warn,
+            "admin",
+            "no admin_token; /admin endpoints and `nexus tail` answer 403",
+        );
+    }
+    if config.server.grpc.enabled {
+        if !cfg!(feature = "grpc") {
+            report.line(
+                Level::Fail,
+                "grpc",
+                "enabled, but nexus was built without the `grpc` feature",
+            );
+        } else if config.server.admin_token.is_none() {
+            report.line(
+                Level::Warn,
+                "grpc",
+                "no admin_token; every admin call is denied",
+            );
+        }
+    }
+}
+
+fn check_secret(report: &mut Report, secret: Option<&str>) {
+    match secret {
+        None => report.line(
+            Level::Warn,
+            "secret",
+            "not set; signatures are not verified. Create one with `nexus gen-secret`",
+        ),
+        Some(secret) if secret.len() < 16 => report.line(
+            Level::Warn,
+            "secret",
+            format!(
+                "only {} characters; use a long random one from `nexus gen-secret`",
+                secret.len()
+            ),
+        ),
+        Some(_) => report.line(Level::Ok, "secret", "set"),
+    }
+}
+
+async fn check_ports(report: &mut Report, config: &Config, port: u16) {
+    let mut addresses: Vec<(SocketAddr, &str)> = if config.server.listeners.is_empty() {
+        vec![(SocketAddr::from(([0, 0, 0, 0], port)), "listener")]
+    } else {
+        config
+            .server
+            .listeners
+            .iter()
+            .map(|l| (l.address, "listener"))
+            .collect()
+    };
+    if config.server.acme.enabled {
+        addresses.push((config.server.acme.challenge_address, "acme"));
+    }
+
+    for (address, check) in addresses {
+        match TcpListener::bind(address).await {
+            Ok(_) => report.line(Level::Ok, check, format!("{} is free", address)),
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => report.line(
+                Level::Fail,
+                check,
+                format!("{} is in use; is nexus already running?", address),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => report.line(
+                Level::Fail,
+                check,
+                format!(
+                    "cannot bind {}: ports below 1024 need root or CAP_NET_BIND_SERVICE",
+                    address
+                ),
+            ),
+            Err(e) => report.line(
+                Level::Fail,
+                check,
+                format!("cannot bind {}: {}", address, e),
+            ),
+        }
+    }
+}
+
+// the API is reachable, the token works, and the clock agrees with GitHub's
+async fn check_github(report: &mut Report, config: &Config) {
+    let api_url = config.github.api_url.trim_end_matches('/');
+    let host = Url::parse(api_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| api_url.to_string());
+    let client = match client::http_client(config) {
+        Ok(client) => client,
+        // already reported by check_config
+        Err(_) => return,
+    };
+    let mut request = client
+        .get(format!("{}/rate_limit", api_url))
+        .timeout(TIMEOUT)
+        .header("user-agent", concat!("nexus/", env!("CARGO_PKG_VERSION")));
+    if let Some(token) = &config.github.token {
+        request = request.bearer_auth(token);
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            report.line(
+                Level::Fail,
+                "api",
+                format!("{} is unreachable: {}", host, e),
+            );
+            return;
+        }
+    };
+
+    let remaining = response
+        .headers()
+        .get("x-ratelimit-remaining")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("?")
+        .to_string();
+    match response.status() {
+        status if status.is_success() => report.line(
+            Level::Ok,
+            "api",
+            format!("{} reachable, {} requests left this hour", host, remaining),
+        ),
+        reqwest::StatusCode::UNAUTHORIZED => report.line(
+            Level::Fail,
+            "api",
+            "GitHub rejected the token; it may be expired or revoked",
+        ),
+        status => report.line(Level::Warn, "api", format!("{} answered {}", host, status)),
+    }
+
+    let date = response
+        .headers()
+        .get("date")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+    let Some(date) = date else {
+        report.line(Level::Warn, "clock", "GitHub sent no usable Date header");
+        return;
+    };
+    // the header has whole seconds, so a second of skew is noise
+    let skew = (Utc::now() - date.with_timezone(&Utc)).num_seconds();
+    let detail = format!("{}s off GitHub's clock", skew);
+    if skew.unsigned_abs() >= MAX_CLOCK_SKEW.as_secs() {
+        report.line(
+            Level::Fail,
+            "clock",
+            format!(
+                "{}; GitHub App tokens will be rejected, sync with NTP",
+                detail
+            ),
+        );
+    } else if skew.abs() > 5 {
+        report.line(Level::Warn, "clock", detail);
+    } else {
+        report.line(Level::Ok, "clock", detail);
+    }
+}
+
+// only connects, so nothing is delivered to the sinks
+async fn check_sinks(report: &mut Report, config: &Config) {
+    let mut urls: Vec<(String, &str)> = Vec::new();
+    if config.deploy.enabled {
+        for (environment, action) in &config.deploy.environments {
+            if let Some(url) = &action.url {
+                urls.push((url.clone(), environment));
+            }
+        }
+    }
+    if config.digest.enabled {
+        for rule in &config.digest.rules {
+            urls.push((rule.url.clone(), &rule.event));
+        }
+    }
+    // sinks are reached through the proxy, so only the proxy itself can be checked
+    if let Some(proxy) = &config.proxy.url {
+        urls = vec![(proxy.clone(), "proxy")];
+    }
+    for (url, used_by) in urls {
+        let check = "sink";
+        let target = Url::parse(&url).ok().and_then(|url| {
+            let host = url.host_str()?.to_string();
+            Some((host, url.port_or_known_default()?))
+        });
+        let Some((host, port)) = target else {
+            report.line(
+                Level::Fail,
+                check,
+                format!("{} ({}): invalid url", url, used_by),
+            );
+            continue;
+        };
+        // placeholders are filled in per delivery
+        if host.contains('{') {
+            report.line(
+                Level::Ok,
+                check,
+                format!("{} ({}): templated host, skipped", url, used_by),
+            );
+            continue;
+        }
+        match tokio::time::timeout(TIMEOUT, TcpStream::connect((host.as_str(), port))).await {
+            Ok(Ok(_)) => report.line(
+                Level::Ok,
+                check,
+                format!("{}:{} ({}) reachable", host, port, used_by),
+            ),
+            Ok(Err(e)) => report.line(
+                Level::Fail,
+                check,
+                format!("{}:{} ({}) unreachable: {}", host, port, used_by, e),
+            ),
+            Err(_) => report.line(
+                Level::Fail,
+                check,
+                format!("{}:{} ({}) timed out", host, port, used_by),
+            ),
+        }
+    }
+
+    if config.state.backend == StateBackend::Redis {
+        let result = match Store::from_config(&config.state) {
+            Ok(Store::Redis(redis)) => redis.command(&["PING"]).await.map(|_| ()),
+            Ok(Store::Memory(_)) => Ok(()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => report.line(Level::Ok, "redis", "answers PING"),
+            Err(e) => report.line(Level::Fail, "redis", e),
+        }
+    }
+}
+
+fn check_certificate(report: &mut Report, config: &Config) {
+    let acme = &config.server.acme;
+    if !acme.enabled {
+        return;
+    }
+    let Some(expiry) = acme::cached_expiry(acme) else {
+        report.line(
+            Level::Warn,
+            "tls",
+            format!(
+                "no certificate in {} yet; one is requested on start, which needs {} reachable from the internet",
+                acme.cache_dir.display(),
+                acme.challenge_address
+            ),
+        );
+        return;
+    };
+    let days = (expiry - Utc::now()).num_days();
+    if expiry <= Utc::now() {
+        report.line(
+            Level::Fail,
+            "tls",
+            format!("the certificate expired on {}", expiry),
+        );
+    } else if days < acme.renew_before_days as i64 {
+        report.line(
+            Level::Warn,
+            "tls",
+            format!(
+                "the certificate expires in {} days and is due for renewal; check that renewals succeed",
+                days
+            ),
+        );
+    } else {
+        report.line(
+            Level::Ok,
+            "tls",
+            format!("certificate valid until {}", expiry),
+        );
+    }
+}
+
+// every script a rule refers to must exist and compile; at runtime a broken one is skipped
+fn check_scripts(report: &mut Report, config: &Config) {
+    let mut scripts: Vec<&Path> = config
+        .scripting
+        .rules
+        .iter()
+        .map(|rule| rule.script.as_path())
+        .chain(
+            config
+                .forward
+                .targets
+                .iter()
+                .filter_map(|t| t.script.as_deref()),
+        )
+        .chain(
+            config
+                .functions
+                .targets
+                .iter()
+                .filter_map(|t| t.script.as_deref()),
+        )
+        .chain(config.exec.rules.iter().filter_map(|r| r.script.as_deref()))
+        .chain(
+            config
+                .fanout
+                .rules
+                .iter()
+                .filter_map(|r| r.script.as_deref()),
+        )
+        .collect();
+    scripts.sort();
+    scripts.dedup();
+
+    for script in scripts {
+        let compiled = std::fs::read_to_string(script)
+            .map_err(scripting::ScriptError::Read)
+            .and_then(|source| scripting::compile(&source));
+        match compiled {
+            Ok(_) => report.line(
+                Level::Ok,
+                "script",
+                format!("{} compiles", script.display()),
+            ),
+            Err(e) => report.line(Level::Fail, "script", format!("{} {}", script.display(), e)),
+        }
+    }
+}
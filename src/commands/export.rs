@@ -0,0 +1,89 @@
+use nexus::{
+    Config,
+    archive::{self, Archived},
+    export::{self, Column, ExportFormat, Options, Partition},
+};
+use std::path::PathBuf;
+
+use super::replay::Selection;
+
+// what the command line sets; anything left unset comes from `[export]`
+pub struct Request {
+    pub format: ExportFormat,
+    pub output: Option<PathBuf>,
+    pub columns: Vec<Column>,
+    pub exclude_columns: Vec<Column>,
+    pub partition_by: Vec<Partition>,
+    pub no_partitions: bool,
+}
+
+impl Request {
+    fn options(&self, config: &Config) -> Options {
+        let export = &config.export;
+        let pick = |given: &[Column], configured: &[Column]| {
+            if given.is_empty() {
+                configured.to_vec()
+            } else {
+                given.to_vec()
+            }
+        };
+        let partition_by = if self.no_partitions {
+            Vec::new()
+        } else if self.partition_by.is_empty() {
+            export.partition_by.clone()
+        } else {
+            self.partition_by.clone()
+        };
+        Options {
+            format: self.format,
+            partition_by,
+            columns: export::columns(
+                &pick(&self.columns, &export.columns),
+                &pick(&self.exclude_columns, &export.exclude_columns),
+            ),
+        }
+    }
+}
+
+// writes every selected archived delivery, not only those since the scheduled export's
+// last run; exits 0 when the files were written
+pub async fn run(config: Config, selection: &Selection, request: &Request) -> i32 {
+    let Some(dir) = &config.archive.dir else {
+        eprintln!("Nothing to export: set archive.dir so deliveries are kept");
+        return 1;
+    };
+    let Some(output) = request.output.as_ref().or(config.export.dir.as_ref()) else {
+        eprintln!("Pass --output or set export.dir");
+        return 1;
+    };
+    let deliveries: Vec<Archived> = match archive::load(dir).await {
+        Ok(deliveries) => deliveries
+            .into_iter()
+            .map(|(_, delivery)| delivery)
+            .filter(|delivery| selection.matches(&delivery.job))
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", dir.display(), e);
+            return 1;
+        }
+    };
+    if deliveries.is_empty() {
+        eprintln!("No archived deliveries match");
+        return 1;
+    }
+
+    let count = deliveries.len();
+    match export::export(deliveries, output, &request.options(&config)).await {
+        Ok(written) => {
+            for path in &written {
+                println!("{}", path.display());
+            }
+            eprintln!("Exported {} deliveries to {} files", count, written.len());
+            0
+        }
+        Err(e) => {
+            eprintln!("Export failed: {}", e);
+            1
+        }
+    }
+}
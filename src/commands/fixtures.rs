@@ -0,0 +1,331 @@
+// sample payloads shaped like GitHub's, for `nexus fixtures` and `nexus send`
+use serde_json::{Value, json};
+use std::path::Path;
+
+pub const EVENTS: &[&str] = &[
+    "ping",
+    "push",
+    "pull_request",
+    "pull_request_review",
+    "issues",
+    "issue_comment",
+    "check_suite",
+    "workflow_run",
+    "deployment",
+    "release",
+    "create",
+];
+
+// the values substituted into every fixture
+#[derive(clap::Args)]
+pub struct Template {
+    /// Repository the sample event comes from
+    #[arg(long, default_value = "octo-org/example")]
+    pub repo: String,
+
+    /// Pull request or issue number
+    #[arg(long, default_value = "1")]
+    pub number: u64,
+
+    /// Login of the user who triggered the event
+    #[arg(long, default_value = "octocat")]
+    pub sender: String,
+
+    /// Defaults to the event's most common action, e.g. opened
+    #[arg(long)]
+    pub action: Option<String>,
+}
+
+const HEAD_SHA: &str = "6dcb09b5b57875f334f61aebed695e2e4193db5e";
+const BASE_SHA: &str = "9049f1265b7d61be4a8904a9a27120d2064dab3b";
+const TIMESTAMP: &str = "2024-05-01T12:00:00Z";
+
+pub fn fixture(event: &str, template: &Template) -> Option<Value> {
+    let repo = &template.repo;
+    let number = template.number;
+    let (owner, name) = repo.split_once('/').unwrap_or((repo, repo));
+    let action = |default: &str| template.action.clone().unwrap_or(default.to_string());
+    let repo_url = format!("https://github.com/{}", repo);
+
+    let user = |login: &str| {
+        json!({
+            "login": login,
+            "id": 583231,
+            "type": "User",
+            "site_admin": false,
+            "html_url": format!("https://github.com/{}", login),
+        })
+    };
+    let sender = user(&template.sender);
+    let repository = json!({
+        "id": 1296269,
+        "name": name,
+        "full_name": repo,
+        "private": false,
+        "owner": user(owner),
+        "html_url": repo_url,
+        "default_branch": "main",
+        "created_at": TIMESTAMP,
+        "pushed_at": TIMESTAMP,
+    });
+    let label = json!({
+        "name": "bug",
+        "color": "d73a4a",
+        "description": "Something isn't working",
+    });
+
+    let payload = match event {
+        "ping" => json!({
+            "zen": "Keep it logically awesome.",
+            "hook_id": 1,
+            "hook": { "type": "Repository", "id": 1, "active": true, "events": ["*"] },
+            "repository": repository,
+            "sender": sender,
+        }),
+        "push" => json!({
+            "ref": "refs/heads/main",
+            "before": BASE_SHA,
+            "after": HEAD_SHA,
+            "created": false,
+            "deleted": false,
+            "forced": false,
+            "repository": repository,
+            "pusher": { "name": template.sender, "email": format!("{}@users.noreply.github.com", template.sender) },
+            "sender": sender,
+            "commits": [{
+                "id": HEAD_SHA,
+                "message": "Fix all the bugs",
+                "timestamp": TIMESTAMP,
+                "url": format!("{}/commit/{}", repo_url, HEAD_SHA),
+                "author": { "name": "Monalisa Octocat", "email": "octocat@github.com", "username": template.sender },
+                "added": [],
+                "removed": [],
+                "modified": ["README.md"],
+            }],
+            "head_commit": { "id": HEAD_SHA, "message": "Fix all the bugs", "timestamp": TIMESTAMP },
+            "compare": format!("{}/compare/{}...{}", repo_url, &BASE_SHA[..12], &HEAD_SHA[..12]),
+        }),
+        "pull_request" => json!({
+            "action": action("opened"),
+            "number": number,
+            "repository": repository,
+            "sender": sender,
+            "pull_request": {
+                "id": 279147437,
+                "node_id": "MDExOlB1bGxSZXF1ZXN0Mjc5MTQ3NDM3",
+                "number": number,
+                "title": "Update the README with new information",
+                "body": "This is a pretty simple change that we need to pull into main.",
+                "html_url": format!("{}/pull/{}", repo_url, number),
+                "state": "open",
+                "locked": false,
+                "user": sender,
+                "author_association": "CONTRIBUTOR",
+                "head": { "ref": "update-readme", "sha": HEAD_SHA, "repo": repository },
+                "base": { "ref": "main", "sha": BASE_SHA, "repo": repository },
+                "labels": [],
+                "draft": false,
+                "merged": false,
+                "merged_at": null,
+                "commits": 1,
+                "additions": 12,
+                "deletions": 3,
+                "changed_files": 1,
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+        }),
+        "pull_request_review" => json!({
+            "action": action("submitted"),
+            "repository": repository,
+            "sender": sender,
+            "review": {
+                "id": 80,
+                "node_id": "MDE3OlB1bGxSZXF1ZXN0UmV2aWV3ODA=",
+                "user": sender,
+                "body": "Looks great!",
+                "state": "approved",
+                "commit_id": HEAD_SHA,
+                "html_url": format!("{}/pull/{}#pullrequestreview-80", repo_url, number),
+                "submitted_at": TIMESTAMP,
+            },
+            "pull_request": {
+                "id": 279147437,
+                "node_id": "MDExOlB1bGxSZXF1ZXN0Mjc5MTQ3NDM3",
+                "number": number,
+                "title": "Update the README with new information",
+                "html_url": format!("{}/pull/{}", repo_url, number),
+                "state": "open",
+                "user": user(owner),
+                "author_association": "CONTRIBUTOR",
+                "head": { "ref": "update-readme", "sha": HEAD_SHA, "repo": repository },
+                "base": { "ref": "main", "sha": BASE_SHA, "repo": repository },
+                "labels": [],
+                "draft": false,
+                "merged_at": null,
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+        }),
+        "issues" => json!({
+            "action": action("opened"),
+            "repository": repository,
+            "sender": sender,
+            "issue": {
+                "id": 1,
+                "node_id": "MDU6SXNzdWUx",
+                "number": number,
+                "title": "Found a bug",
+                "body": "I'm having a problem with this.",
+                "html_url": format!("{}/issues/{}", repo_url, number),
+                "state": "open",
+                "locked": false,
+                "user": sender,
+                "labels": [label],
+                "comments": 0,
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+        }),
+        "issue_comment" => json!({
+            "action": action("created"),
+            "repository": repository,
+            "sender": sender,
+            "issue": {
+                "id": 1,
+                "number": number,
+                "title": "Found a bug",
+                "body": "I'm having a problem with this.",
+                "html_url": format!("{}/issues/{}", repo_url, number),
+                "state": "open",
+                "user": user(owner),
+                "labels": [label],
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+            "comment": {
+                "id": 1,
+                "body": "Me too",
+                "user": sender,
+                "html_url": format!("{}/issues/{}#issuecomment-1", repo_url, number),
+                "author_association": "CONTRIBUTOR",
+                "created_at": TIMESTAMP,
+            },
+        }),
+        "check_suite" => json!({
+            "action": action("completed"),
+            "repository": repository,
+            "sender": sender,
+            "check_suite": {
+                "id": 118578147,
+                "head_sha": HEAD_SHA,
+                "head_branch": "update-readme",
+                "status": "completed",
+                "conclusion": "failure",
+                "pull_requests": [{ "number": number }],
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+        }),
+        "workflow_run" => json!({
+            "action": action("completed"),
+            "repository": repository,
+            "sender": sender,
+            "workflow_run": {
+                "id": 30433642,
+                "name": "CI",
+                "head_sha": HEAD_SHA,
+                "head_branch": "update-readme",
+                "event": "pull_request",
+                "status": "completed",
+                "conclusion": "failure",
+                "html_url": format!("{}/actions/runs/30433642", repo_url),
+                "run_attempt": 1,
+                "pull_requests": [{ "number": number }],
+                "created_at": TIMESTAMP,
+                "updated_at": TIMESTAMP,
+            },
+        }),
+        "deployment" => json!({
+            "action": action("created"),
+            "repository": repository,
+            "sender": sender,
+            "deployment": {
+                "id": 145988746,
+                "sha": HEAD_SHA,
+                "ref": "main",
+                "task": "deploy",
+                "environment": "production",
+                "description": null,
+                "payload": {},
+                "creator": sender,
+                "created_at": TIMESTAMP,
+            },
+        }),
+        "release" => json!({
+            "action": action("published"),
+            "repository": repository,
+            "sender": sender,
+            "release": {
+                "id": 11248810,
+                "tag_name": "v1.0.0",
+                "target_commitish": "main",
+                "name": "v1.0.0",
+                "body": "The first release",
+                "draft": false,
+                "prerelease": false,
+                "author": sender,
+                "html_url": format!("{}/releases/tag/v1.0.0", repo_url),
+                "created_at": TIMESTAMP,
+                "published_at": TIMESTAMP,
+            },
+        }),
+        "create" => json!({
+            "ref": "v1.0.0",
+            "ref_type": "tag",
+            "master_branch": "main",
+            "pusher_type": "user",
+            "repository": repository,
+            "sender": sender,
+        }),
+        _ => return None,
+    };
+    Some(payload)
+}
+
+// prints one fixture, or writes every fixture to `output` as `{event}.json`
+pub fn run(event: Option<&str>, template: &Template, output: Option<&Path>) -> i32 {
+    let events: Vec<&str> = match event {
+        Some(event) if EVENTS.contains(&event) => vec![event],
+        Some(event) => {
+            eprintln!(
+                "There is no sample {} payload; choose one of {}",
+                event,
+                EVENTS.join(", ")
+            );
+            return 1;
+        }
+        None if output.is_some() => EVENTS.to_vec(),
+        None => {
+            println!("{}", EVENTS.join("\n"));
+            return 0;
+        }
+    };
+
+    for event in events {
+        let payload = fixture(event, template).expect("every listed event has a fixture");
+        let json = serde_json::to_string_pretty(&payload).unwrap();
+        let Some(dir) = output else {
+            println!("{}", json);
+            continue;
+        };
+        let path = dir.join(format!("{}.json", event));
+        let written = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, json + "\n"));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            return 1;
+        }
+        println!("{}", path.display());
+    }
+    0
+}
@@ -0,0 +1,54 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use clap::ValueEnum;
+use rand::{RngCore, rngs::OsRng};
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Encoding {
+    Hex,
+    Base64,
+}
+
+pub fn run(bytes: usize, encoding: Encoding, output: Option<&Path>) -> i32 {
+    if bytes < 16 {
+        eprintln!("Use at least 16 bytes; GitHub recommends a long random secret");
+        return 1;
+    }
+    let mut secret = vec![0; bytes];
+    OsRng.fill_bytes(&mut secret);
+    let secret = match encoding {
+        Encoding::Hex => hex::encode(secret),
+        Encoding::Base64 => STANDARD.encode(secret),
+    };
+
+    // with a file the secret stays off the terminal and out of shell history
+    let Some(path) = output else {
+        println!("{}", secret);
+        eprintln!();
+        eprintln!("Set it as the webhook secret on GitHub and start nexus with:");
+        eprintln!("  GITHUB_WEBHOOK_SECRET='{}' nexus", secret);
+        return 0;
+    };
+    if let Err(e) = write_private(path, &secret) {
+        eprintln!("Failed to write {}: {}", path.display(), e);
+        return 1;
+    }
+    println!("Wrote a {}-byte secret to {}", bytes, path.display());
+    println!();
+    println!("Set it as the webhook secret on GitHub and start nexus with:");
+    println!(
+        "  GITHUB_WEBHOOK_SECRET=\"$(cat {})\" nexus",
+        path.display()
+    );
+    0
+}
+
+// readable by the owner only; an existing file is never overwritten
+fn write_private(path: &Path, secret: &str) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    writeln!(file, "{}", secret)
+}
@@ -0,0 +1,27 @@
+use nexus::{Config, config::RouteGroup};
+use std::time::Duration;
+
+// exits 0 when the instance is healthy, 1 otherwise
+pub async fn run(config: &Config, port: u16, url: Option<String>, timeout: Duration) -> i32 {
+    let url = url.unwrap_or_else(|| super::local_url(config, port, RouteGroup::Health, "/health"));
+    healthcheck(&url, timeout).await
+}
+
+async fn healthcheck(url: &str, timeout: Duration) -> i32 {
+    let response = reqwest::Client::new()
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => 0,
+        Ok(response) => {
+            eprintln!("{} returned {}", url, response.status());
+            1
+        }
+        Err(e) => {
+            eprintln!("{} is unreachable: {}", url, e);
+            1
+        }
+    }
+}
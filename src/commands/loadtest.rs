@@ -0,0 +1,187 @@
+// `nexus loadtest`: signed sample deliveries at a fixed rate, then the latency
+// percentiles and error rates, for capacity planning without external tools
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::Semaphore,
+    time::{self, MissedTickBehavior},
+};
+
+use super::{
+    fixtures::{EVENTS, Template, fixture},
+    send::{deliver, random_delivery_id},
+};
+
+pub struct Load {
+    pub rps: u32,
+    pub duration: Duration,
+    // `mixed` for every event with a sample payload, or a comma-separated list
+    pub events: String,
+    // requests in flight at most; ticks beyond it are counted as dropped
+    pub concurrency: usize,
+}
+
+#[derive(Default)]
+struct Results {
+    // milliseconds, of the deliveries that got an answer
+    latencies: Vec<f64>,
+    statuses: BTreeMap<u16, u64>,
+    errors: BTreeMap<String, u64>,
+    dropped: u64,
+}
+
+// exits 0 when every delivery was accepted, 1 otherwise
+pub async fn run(url: &str, load: &Load, template: &Template, secret: Option<&str>) -> i32 {
+    if load.rps == 0 || load.concurrency == 0 {
+        eprintln!("--rps and --concurrency must be at least 1");
+        return 1;
+    }
+    let bodies = match bodies(&load.events, template) {
+        Ok(bodies) => bodies,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    println!(
+        "Sending {} deliveries/s to {} for {}s ({})",
+        load.rps,
+        url,
+        load.duration.as_secs(),
+        bodies
+            .iter()
+            .map(|(event, _)| *event)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let client = reqwest::Client::new();
+    let results = Arc::new(Mutex::new(Results::default()));
+    let in_flight = Arc::new(Semaphore::new(load.concurrency));
+    let mut ticks = time::interval(Duration::from_secs_f64(1.0 / load.rps as f64));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let started = Instant::now();
+    let mut sent = 0usize;
+
+    while started.elapsed() < load.duration {
+        ticks.tick().await;
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            results.lock().unwrap().dropped += 1;
+            continue;
+        };
+        let (event, body) = &bodies[sent % bodies.len()];
+        sent += 1;
+        let (client, results) = (client.clone(), results.clone());
+        let (url, event, body) = (url.to_string(), *event, body.clone());
+        let secret = secret.map(str::to_string);
+        tokio::spawn(async move {
+            let request_started = Instant::now();
+            let outcome = deliver(
+                &client,
+                &url,
+                event,
+                &random_delivery_id(),
+                body,
+                secret.as_deref(),
+            )
+            .await;
+            let elapsed = request_started.elapsed().as_secs_f64() * 1000.0;
+            let mut results = results.lock().unwrap();
+            match outcome {
+                Ok((status, _)) => {
+                    results.latencies.push(elapsed);
+                    *results.statuses.entry(status.as_u16()).or_default() += 1;
+                }
+                Err(e) => *results.errors.entry(e).or_default() += 1,
+            }
+            drop(permit);
+        });
+    }
+    // waits for the deliveries still in flight
+    let _ = in_flight.acquire_many(load.concurrency as u32).await;
+    let elapsed = started.elapsed();
+
+    let results = results.lock().unwrap();
+    report(&results, load, sent, elapsed);
+    let accepted: u64 = results
+        .statuses
+        .iter()
+        .filter(|(status, _)| (200..300).contains(*status))
+        .map(|(_, count)| count)
+        .sum();
+    if accepted == sent as u64 && results.dropped == 0 {
+        0
+    } else {
+        1
+    }
+}
+
+// each event's sample payload, in the order they are sent
+fn bodies(events: &str, template: &Template) -> Result<Vec<(&'static str, Vec<u8>)>, String> {
+    let names: Vec<&str> = match events.trim() {
+        "mixed" => EVENTS.iter().filter(|e| **e != "ping").copied().collect(),
+        list => list.split(',').map(str::trim).collect(),
+    };
+    names
+        .into_iter()
+        .map(|name| {
+            let event = EVENTS.iter().find(|e| **e == name).ok_or_else(|| {
+                format!(
+                    "There is no sample payload for {} events; use mixed or some of {}",
+                    name,
+                    EVENTS.join(", ")
+                )
+            })?;
+            let payload = fixture(event, template).expect("every event in EVENTS has a fixture");
+            Ok((*event, payload.to_string().into_bytes()))
+        })
+        .collect()
+}
+
+fn report(results: &Results, load: &Load, sent: usize, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "\n{} sent in {:.1}s, {:.1}/s achieved",
+        sent,
+        secs,
+        sent as f64 / secs
+    );
+    if results.dropped > 0 {
+        println!(
+            "{} not sent: all {} connections were busy; raise --concurrency or lower --rps",
+            results.dropped, load.concurrency
+        );
+    }
+
+    let mut latencies = results.latencies.clone();
+    latencies.sort_by(f64::total_cmp);
+    if !latencies.is_empty() {
+        let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p).round() as usize];
+        println!(
+            "latency ms: p50 {:.1}  p90 {:.1}  p99 {:.1}  max {:.1}",
+            percentile(0.5),
+            percentile(0.9),
+            percentile(0.99),
+            latencies[latencies.len() - 1]
+        );
+    }
+
+    let rate = |count: u64| count as f64 * 100.0 / sent.max(1) as f64;
+    for (status, count) in &results.statuses {
+        println!("  {}: {} ({:.2}%)", status, count, rate(*count));
+    }
+    for (error, count) in &results.errors {
+        println!("  error: {} ({:.2}%): {}", count, rate(*count), error);
+    }
+    let failed: u64 = results
+        .statuses
+        .iter()
+        .filter(|(status, _)| !(200..300).contains(*status))
+        .map(|(_, count)| count)
+        .sum::<u64>()
+        + results.errors.values().sum::<u64>();
+    println!("error rate: {:.2}%", rate(failed));
+}
@@ -0,0 +1,540 @@
+// the command line: `serve` (the default) runs the webhook server, the rest are tools
+// that work against its configuration
+
+mod config;
+mod doctor;
+mod export;
+mod fixtures;
+mod gen_secret;
+mod healthcheck;
+mod loadtest;
+mod pause;
+mod register;
+mod replay;
+mod send;
+mod serve;
+#[cfg(windows)]
+mod service;
+mod tail;
+mod verify;
+
+#[cfg(windows)]
+use service::ServiceCommand;
+
+use clap::{Parser, Subcommand};
+use nexus::{Config, config::RouteGroup};
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+use tracing::error;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    #[arg(short, long, default_value = "6666", global = true)]
+    port: u16,
+
+    #[arg(short, long, env = "GITHUB_WEBHOOK_SECRET", global = true)]
+    secret: Option<String>,
+
+    #[arg(short, long, env = "NEXUS_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true, global = true)]
+    github_token: Option<String>,
+
+    #[arg(long, env = "GITHUB_APP_ID", global = true)]
+    github_app_id: Option<u64>,
+
+    #[arg(long, env = "GITHUB_APP_PRIVATE_KEY_PATH", global = true)]
+    github_app_private_key: Option<PathBuf>,
+
+    #[arg(long, env = "NEXUS_ADMIN_TOKEN", hide_env_values = true, global = true)]
+    admin_token: Option<String>,
+
+    // flags are global so they work before or after the subcommand
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the webhook server; the default when no subcommand is given
+    Serve,
+    /// Check that the local instance answers /health; exits 0 when healthy, 1 otherwise
+    Healthcheck {
+        /// Defaults to the first listener serving health routes, or --port on localhost
+        #[arg(long)]
+        url: Option<String>,
+
+        #[arg(long, default_value = "5")]
+        timeout_secs: u64,
+    },
+    /// POST a signed delivery to a running instance, as GitHub would
+    Send {
+        /// Event name sent as X-GitHub-Event, e.g. push or pull_request
+        event: String,
+
+        /// JSON body to send, or - for stdin; defaults to the sample payload for the event
+        #[arg(long)]
+        payload: Option<PathBuf>,
+
+        #[command(flatten)]
+        template: fixtures::Template,
+
+        /// Defaults to the first listener serving the webhook route, or --port on localhost
+        #[arg(long)]
+        url: Option<String>,
+
+        /// X-GitHub-Delivery id; a random one by default
+        #[arg(long)]
+        delivery: Option<String>,
+    },
+    /// Send signed sample deliveries at a fixed rate and report latency and error rates
+    Loadtest {
+        /// Defaults to the first listener serving the webhook route, or --port on localhost
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Deliveries per second
+        #[arg(long, default_value = "50")]
+        rps: u32,
+
+        #[arg(long, default_value = "30")]
+        duration_secs: u64,
+
+        /// `mixed` for every event with a sample payload, or a list such as push,pull_request
+        #[arg(long, default_value = "mixed")]
+        events: String,
+
+        /// Deliveries in flight at most; beyond it they are skipped and reported
+        #[arg(long, default_value = "256")]
+        concurrency: usize,
+
+        #[command(flatten)]
+        template: fixtures::Template,
+    },
+    /// Check a saved payload against an X-Hub-Signature-256 value using --secret
+    Verify {
+        /// The request body exactly as received
+        payload: PathBuf,
+
+        /// The header value, e.g. sha256=6f1e...
+        signature: String,
+    },
+    /// Generate a random webhook secret
+    GenSecret {
+        /// Random bytes before encoding
+        #[arg(long, default_value = "32")]
+        bytes: usize,
+
+        #[arg(long, value_enum, default_value = "hex")]
+        encoding: gen_secret::Encoding,
+
+        /// Write the secret to a new file readable only by its owner instead of printing it
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Print deliveries as a running instance receives them; needs the admin token
+    Tail {
+        /// Defaults to the first listener serving the admin routes, or --port on localhost
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Only deliveries for this repository, e.g. octo-org/example
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Only deliveries of this event type, e.g. pull_request
+        #[arg(long)]
+        event: Option<String>,
+
+        /// One JSON object per line, for piping into jq
+        #[arg(long)]
+        json: bool,
+    },
+    /// Hold processing: deliveries are still accepted and queued, but no handler runs
+    Pause {
+        /// Defaults to the first listener serving the admin routes, or --port on localhost
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Shown in the status and logs, e.g. "database migration"
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Process the held deliveries in the order they arrived and continue as normal
+    Resume {
+        /// Defaults to the first listener serving the admin routes, or --port on localhost
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Run archived deliveries through the automations again, or post them to --url
+    Replay {
+        /// Only these delivery ids; repeat for several
+        #[arg(long = "id")]
+        ids: Vec<String>,
+
+        /// Only deliveries for this repository, e.g. octo-org/example
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Only deliveries of this event type, e.g. pull_request
+        #[arg(long)]
+        event: Option<String>,
+
+        /// Only deliveries received at or after this time (RFC 3339)
+        #[arg(long)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Only deliveries received before this time (RFC 3339)
+        #[arg(long)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Post the deliveries here, signed with --secret, instead of running them locally
+        #[arg(long)]
+        url: Option<String>,
+
+        /// List the selected deliveries without replaying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write archived deliveries as partitioned Parquet files, e.g. for a data lake
+    Export {
+        #[arg(long, default_value = "parquet")]
+        format: nexus::export::ExportFormat,
+
+        /// Directory to write to; defaults to export.dir
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Only deliveries for this repository, e.g. octo-org/example
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// Only deliveries of this event type, e.g. pull_request
+        #[arg(long)]
+        event: Option<String>,
+
+        /// Only deliveries received at or after this time (RFC 3339)
+        #[arg(long)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Only deliveries received before this time (RFC 3339)
+        #[arg(long)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Only these columns, e.g. delivery_id,event_type,received_at; defaults to export.columns
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<nexus::export::Column>,
+
+        /// Leave these columns out, e.g. payload,headers; defaults to export.exclude_columns
+        #[arg(long, value_delimiter = ',')]
+        exclude_columns: Vec<nexus::export::Column>,
+
+        /// Directory levels, from date and event_type; defaults to export.partition_by
+        #[arg(long, value_delimiter = ',')]
+        partition_by: Vec<nexus::export::Partition>,
+
+        /// Write a single file instead of partitioning
+        #[arg(long, conflicts_with = "partition_by")]
+        no_partitions: bool,
+    },
+    /// Create or update the GitHub webhooks that deliver to this instance
+    Register {
+        /// Repository as owner/repo; repeat for several. Replaces webhook.repos
+        #[arg(long = "repo")]
+        repos: Vec<String>,
+
+        /// Organization; repeat for several. Replaces webhook.orgs
+        #[arg(long = "org")]
+        orgs: Vec<String>,
+
+        /// Public address of /webhook; defaults to webhook.url
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Show what would change without touching any hooks
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print sample payloads, or list the events that have one
+    Fixtures {
+        /// Event to print; all of them are written when --output is given without one
+        event: Option<String>,
+
+        #[command(flatten)]
+        template: fixtures::Template,
+
+        /// Directory to write {event}.json files to instead of printing
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check the configuration and the environment nexus runs in and report problems
+    Doctor,
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage the Windows service
+    #[cfg(windows)]
+    #[command(subcommand)]
+    Service(ServiceCommand),
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration (file, defaults and overrides) with secrets redacted
+    Show,
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the tokio runtime")
+}
+
+fn load_config(args: &Args) -> Config {
+    try_load_config(args).unwrap_or_else(|e| {
+        error!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+// the config file with the command line and environment overrides applied
+fn try_load_config(args: &Args) -> Result<Config, String> {
+    let mut config = match &args.config {
+        Some(path) => Config::load(path)
+            .map_err(|e| format!("Failed to load config from {}: {}", path.display(), e))?,
+        None => Config::default(),
+    };
+    if args.github_token.is_some() {
+        config.github.token = args.github_token.clone();
+    }
+    if args.github_app_id.is_some() {
+        config.github.app_id = args.github_app_id;
+    }
+    if args.github_app_private_key.is_some() {
+        config.github.private_key_path = args.github_app_private_key.clone();
+    }
+
+    if args.admin_token.is_some() {
+        config.server.admin_token = args.admin_token.clone();
+    }
+    Ok(config)
+}
+
+// `path` on the first plain HTTP listener serving `group`; unspecified addresses are
+// reached over loopback and TLS listeners are skipped since their certificate does not
+// name localhost
+fn local_url(config: &Config, port: u16, group: RouteGroup, path: &str) -> String {
+    let listener = config
+        .server
+        .listeners
+        .iter()
+        .find(|l| l.routes.contains(&group) && !l.tls);
+    let mut address = match listener {
+        Some(listener) => listener.address,
+        None => SocketAddr::from(([127, 0, 0, 1], port)),
+    };
+    if address.ip().is_unspecified() {
+        address.set_ip(match address {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    format!("http://{}{}", address, path)
+}
+
+pub fn main(args: Args) {
+    // the Windows service manager drives its own runtime and logging
+    #[cfg(windows)]
+    if let Some(Command::Service(_)) = &args.command {
+        std::process::exit(service::run(args));
+    }
+
+    tracing_subscriber::fmt::init();
+    runtime().block_on(run(args));
+}
+
+async fn run(args: Args) {
+    // reports a broken config file instead of exiting on it
+    if let Some(Command::Doctor) = &args.command {
+        std::process::exit(doctor::run(&args).await);
+    }
+    let config = load_config(&args);
+
+    let code = match &args.command {
+        None | Some(Command::Serve) => {
+            serve::serve(&args, config, serve::shutdown_signal()).await;
+            return;
+        }
+        Some(Command::Healthcheck { url, timeout_secs }) => {
+            healthcheck::run(
+                &config,
+                args.port,
+                url.clone(),
+                Duration::from_secs(*timeout_secs),
+            )
+            .await
+        }
+        Some(Command::Send {
+            event,
+            payload,
+            template,
+            url,
+            delivery,
+        }) => {
+            let url = url
+                .clone()
+                .unwrap_or_else(|| local_url(&config, args.port, RouteGroup::Webhook, "/webhook"));
+            send::run(
+                &url,
+                event,
+                payload.as_deref(),
+                template,
+                delivery.clone(),
+                args.secret.as_deref(),
+            )
+            .await
+        }
+        Some(Command::Loadtest {
+            target,
+            rps,
+            duration_secs,
+            events,
+            concurrency,
+            template,
+        }) => {
+            let url = target
+                .clone()
+                .unwrap_or_else(|| local_url(&config, args.port, RouteGroup::Webhook, "/webhook"));
+            let load = loadtest::Load {
+                rps: *rps,
+                duration: Duration::from_secs(*duration_secs),
+                events: events.clone(),
+                concurrency: *concurrency,
+            };
+            loadtest::run(&url, &load, template, args.secret.as_deref()).await
+        }
+        Some(Command::Verify { payload, signature }) => {
+            verify::run(payload, signature, args.secret.as_deref())
+        }
+        Some(Command::GenSecret {
+            bytes,
+            encoding,
+            output,
+        }) => gen_secret::run(*bytes, *encoding, output.as_deref()),
+        Some(Command::Tail {
+            url,
+            repo,
+            event,
+            json,
+        }) => {
+            let url = url.clone().unwrap_or_else(|| {
+                local_url(&config, args.port, RouteGroup::Admin, "/admin/events")
+            });
+            let filter = tail::Filter {
+                repo: repo.clone(),
+                event: event.clone(),
+            };
+            tail::run(&url, config.server.admin_token.as_deref(), &filter, *json).await
+        }
+        Some(Command::Pause { url, reason }) => {
+            let url = url.clone().unwrap_or_else(|| {
+                local_url(&config, args.port, RouteGroup::Admin, "/admin/pause")
+            });
+            pause::run(
+                &url,
+                config.server.admin_token.as_deref(),
+                reason.as_deref(),
+            )
+            .await
+        }
+        Some(Command::Resume { url }) => {
+            let url = url.clone().unwrap_or_else(|| {
+                local_url(&config, args.port, RouteGroup::Admin, "/admin/resume")
+            });
+            pause::run(&url, config.server.admin_token.as_deref(), None).await
+        }
+        Some(Command::Replay {
+            ids,
+            repo,
+            event,
+            since,
+            until,
+            url,
+            dry_run,
+        }) => {
+            let selection = replay::Selection {
+                ids: ids.clone(),
+                repo: repo.clone(),
+                event: event.clone(),
+                since: *since,
+                until: *until,
+            };
+            replay::run(
+                config,
+                &selection,
+                url.as_deref(),
+                args.secret.clone(),
+                *dry_run,
+            )
+            .await
+        }
+        Some(Command::Export {
+            format,
+            output,
+            repo,
+            event,
+            since,
+            until,
+            columns,
+            exclude_columns,
+            partition_by,
+            no_partitions,
+        }) => {
+            let selection = replay::Selection {
+                ids: Vec::new(),
+                repo: repo.clone(),
+                event: event.clone(),
+                since: *since,
+                until: *until,
+            };
+            let request = export::Request {
+                format: *format,
+                output: output.clone(),
+                columns: columns.clone(),
+                exclude_columns: exclude_columns.clone(),
+                partition_by: partition_by.clone(),
+                no_partitions: *no_partitions,
+            };
+            export::run(config, &selection, &request).await
+        }
+        Some(Command::Register {
+            repos,
+            orgs,
+            url,
+            dry_run,
+        }) => {
+            let targets = register::Targets {
+                repos: repos.clone(),
+                orgs: orgs.clone(),
+                url: url.clone(),
+            };
+            register::run(&config, targets, args.secret.as_deref(), *dry_run).await
+        }
+        Some(Command::Fixtures {
+            event,
+            template,
+            output,
+        }) => fixtures::run(event.as_deref(), template, output.as_deref()),
+        Some(Command::Doctor) => unreachable!("handled before the config is loaded"),
+        Some(Command::Config(ConfigCommand::Show)) => config::show(&config),
+        #[cfg(windows)]
+        Some(Command::Service(_)) => unreachable!("handled before the runtime starts"),
+    };
+    std::process::exit(code);
+}
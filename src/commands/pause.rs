@@ -0,0 +1,58 @@
+use nexus::queue::Processing;
+use serde::Deserialize;
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct Status {
+    state: Processing,
+    held: usize,
+    reason: Option<String>,
+}
+
+// POSTs to /admin/pause or /admin/resume and reports the state the server ends up in
+pub async fn run(url: &str, admin_token: Option<&str>, reason: Option<&str>) -> i32 {
+    let mut request = reqwest::Client::new()
+        .post(url)
+        .timeout(Duration::from_secs(10));
+    if let Some(reason) = reason {
+        request = request.query(&[("reason", reason)]);
+    }
+    if let Some(token) = admin_token {
+        request = request.bearer_auth(token);
+    }
+
+    let status: Status = match request.send().await {
+        Ok(response) if response.status().is_success() => match response.json().await {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("{} returned an unexpected response: {}", url, e);
+                return 1;
+            }
+        },
+        Ok(response) => {
+            eprintln!("{} returned {}", url, response.status());
+            if admin_token.is_none() {
+                eprintln!("Pass the admin token with --admin-token or NEXUS_ADMIN_TOKEN");
+            }
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("{} is unreachable: {}", url, e);
+            return 1;
+        }
+    };
+
+    match status.state {
+        Processing::Paused => println!(
+            "Paused{}, {} deliveries held",
+            status
+                .reason
+                .map(|r| format!(" ({})", r))
+                .unwrap_or_default(),
+            status.held
+        ),
+        Processing::Draining => println!("Resumed, queueing {} held deliveries", status.held),
+        Processing::Running => println!("Running"),
+    }
+    0
+}
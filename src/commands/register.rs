@@ -0,0 +1,110 @@
+use nexus::{
+    Config,
+    github::{
+        GithubClient, GithubError,
+        hooks::{self, HookConfig, HookOwner, HookSettings},
+    },
+};
+
+pub struct Targets {
+    pub repos: Vec<String>,
+    pub orgs: Vec<String>,
+    pub url: Option<String>,
+}
+
+// points a hook on every repository and organization at nexus, creating it or updating
+// the one that already delivers to the same url; exits 0 when all of them succeeded
+pub async fn run(config: &Config, targets: Targets, secret: Option<&str>, dry_run: bool) -> i32 {
+    let Some(token) = &config.github.token else {
+        eprintln!("Pass a token that can manage webhooks with --github-token or GITHUB_TOKEN");
+        return 1;
+    };
+    let Some(url) = targets.url.or_else(|| config.webhook.url.clone()) else {
+        eprintln!("Set webhook.url or pass --url with the public address of /webhook");
+        return 1;
+    };
+    // GitHub never reveals a hook's secret, so it cannot be left as it is on update
+    let Some(secret) = secret else {
+        eprintln!("Pass the webhook secret with --secret or GITHUB_WEBHOOK_SECRET");
+        return 1;
+    };
+
+    let pick = |cli: Vec<String>, configured: &Vec<String>| {
+        if cli.is_empty() {
+            configured.clone()
+        } else {
+            cli
+        }
+    };
+    let owners: Vec<HookOwner> = pick(targets.repos, &config.webhook.repos)
+        .into_iter()
+        .map(HookOwner::Repo)
+        .chain(
+            pick(targets.orgs, &config.webhook.orgs)
+                .into_iter()
+                .map(HookOwner::Org),
+        )
+        .collect();
+    if owners.is_empty() {
+        eprintln!(
+            "Nothing to register: set webhook.repos or webhook.orgs, or pass --repo or --org"
+        );
+        return 1;
+    }
+
+    let settings = HookSettings {
+        config: HookConfig {
+            url: Some(url.clone()),
+            content_type: Some("json".to_string()),
+            secret: Some(secret.to_string()),
+            insecure_ssl: Some("0".to_string()),
+        },
+        events: config.webhook.events.clone(),
+        active: true,
+    };
+    let client = match GithubClient::from_config(config) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Invalid GitHub settings: {}", e);
+            return 1;
+        }
+    };
+    let mut failed = 0;
+    for owner in &owners {
+        match register(&client, token, owner, &url, &settings, dry_run).await {
+            Ok(outcome) => println!("{}: {}", owner, outcome),
+            Err(e) => {
+                println!("{}: failed, {}", owner, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed == 0 { 0 } else { 1 }
+}
+
+async fn register(
+    client: &GithubClient,
+    token: &str,
+    owner: &HookOwner,
+    url: &str,
+    settings: &HookSettings,
+    dry_run: bool,
+) -> Result<String, GithubError> {
+    let existing = hooks::list_hooks(client, token, owner)
+        .await?
+        .into_iter()
+        .find(|hook| hook.config.url.as_deref() == Some(url));
+
+    Ok(match (existing, dry_run) {
+        (Some(hook), true) => format!("would update hook {}", hook.id),
+        (Some(hook), false) => {
+            hooks::update_hook(client, token, owner, hook.id, settings).await?;
+            format!("updated hook {}", hook.id)
+        }
+        (None, true) => "would create a hook".to_string(),
+        (None, false) => {
+            let hook = hooks::create_hook(client, token, owner, settings).await?;
+            format!("created hook {}", hook.id)
+        }
+    })
+}
@@ -0,0 +1,311 @@
+use chrono::{DateTime, Utc};
+use nexus::{
+    AppState, Config,
+    archive::{self, Archived},
+    automations,
+    feed::FeedEvent,
+    github::{GithubClient, auth::AppAuth},
+    proxy,
+    queue::Job,
+    store::Store,
+    webhook::{self, SignatureVerifier},
+};
+use std::time::Duration;
+
+use super::tail;
+
+pub struct Selection {
+    pub ids: Vec<String>,
+    pub repo: Option<String>,
+    pub event: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Selection {
+    pub fn matches(&self, job: &Job) -> bool {
+        let id = self.ids.is_empty()
+            || job
+                .delivery_id
+                .as_ref()
+                .is_some_and(|id| self.ids.contains(id));
+        let repo = self.repo.as_deref().is_none_or(|repo| {
+            job.payload
+                .repository
+                .as_ref()
+                .is_some_and(|r| r.full_name.eq_ignore_ascii_case(repo))
+        });
+        let event = self.event.as_deref().is_none_or(|e| e == job.event_type);
+        let since = self.since.is_none_or(|since| job.received_at >= since);
+        let until = self.until.is_none_or(|until| job.received_at < until);
+        id && repo && event && since && until
+    }
+}
+
+// runs the selected deliveries through the automations in this process, or posts them
+// to `url`; exits 0 when every one succeeded
+pub async fn run(
+    config: Config,
+    selection: &Selection,
+    url: Option<&str>,
+    secret: Option<String>,
+    dry_run: bool,
+) -> i32 {
+    let Some(dir) = &config.archive.dir else {
+        eprintln!("Nothing to replay: set archive.dir so deliveries are kept");
+        return 1;
+    };
+    let deliveries: Vec<Archived> = match archive::load(dir).await {
+        Ok(deliveries) => deliveries
+            .into_iter()
+            .map(|(_, delivery)| delivery)
+            .filter(|delivery| selection.matches(&delivery.job))
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", dir.display(), e);
+            return 1;
+        }
+    };
+    if deliveries.is_empty() {
+        eprintln!("No archived deliveries match");
+        return 1;
+    }
+
+    if dry_run {
+        for delivery in &deliveries {
+            println!(
+                "{} {}",
+                delivery.job.received_at.format("%Y-%m-%d"),
+                tail::format_event(&feed_event(&delivery.job), false)
+            );
+        }
+        eprintln!("{} deliveries would be replayed", deliveries.len());
+        return 0;
+    }
+
+    let failed = match url {
+        Some(url) => resend(&deliveries, url, secret.as_deref()).await,
+        None => match rerun(config, secret, &deliveries).await {
+            Ok(failed) => failed,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+    };
+    eprintln!(
+        "Replayed {} deliveries, {} failed",
+        deliveries.len(),
+        failed
+    );
+    if failed == 0 { 0 } else { 1 }
+}
+
+// the bytes and headers GitHub sent, so the original signature still holds for a receiver
+// with the same secret; with `secret` the body is signed afresh
+async fn resend(deliveries: &[Archived], url: &str, secret: Option<&str>) -> usize {
+    let client = reqwest::Client::new();
+    let mut failed = 0;
+    for delivery in deliveries {
+        let job = &delivery.job;
+        let id = job.delivery_id.as_deref().unwrap_or("-");
+        match post(&client, url, delivery, secret).await {
+            Ok(status) => {
+                println!("{} {} -> {}", job.event_type, id, status);
+                if !status.is_success() {
+                    failed += 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{} {}: {}", job.event_type, id, e);
+                failed += 1;
+            }
+        }
+    }
+    failed
+}
+
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    delivery: &Archived,
+    secret: Option<&str>,
+) -> Result<reqwest::StatusCode, String> {
+    let mut request = client.post(url).timeout(Duration::from_secs(30));
+    // entries archived before headers were kept
+    if delivery.headers.is_empty() {
+        request = request
+            .header("user-agent", "GitHub-Hookshot/nexus")
+            .header("content-type", "application/json")
+            .header("x-github-event", &delivery.job.event_type);
+        if let Some(id) = &delivery.job.delivery_id {
+            request = request.header("x-github-delivery", id);
+        }
+    }
+    for (name, value) in &delivery.headers {
+        if secret.is_none() || !name.starts_with("x-hub-signature") {
+            request = request.header(name, value);
+        }
+    }
+    if let Some(secret) = secret {
+        let mut signer =
+            SignatureVerifier::new(secret).ok_or("The secret cannot be used as an HMAC key")?;
+        signer.update(&delivery.body);
+        request = request.header("x-hub-signature-256", signer.signature());
+    }
+
+    let response = request
+        .body(delivery.body.clone())
+        .send()
+        .await
+        .map_err(|e| format!("{} is unreachable: {}", url, e))?;
+    Ok(response.status())
+}
+
+// the same handlers a queue worker runs, followed by a drain so digests and outbound
+// retries are not lost when the process exits
+async fn rerun(
+    config: Config,
+    secret: Option<String>,
+    deliveries: &[Archived],
+) -> Result<usize, String> {
+    let github_app = AppAuth::from_config(&config.github)
+        .map_err(|e| format!("Failed to load GitHub App credentials: {}", e))?;
+    let store =
+        Store::from_config(&config.state).map_err(|e| format!("Invalid state backend: {}", e))?;
+    let http_client =
+        proxy::client(&config.proxy).map_err(|e| format!("Invalid proxy settings: {}", e))?;
+    let github = GithubClient::from_config(&config)
+        .map_err(|e| format!("Invalid GitHub settings: {}", e))?;
+    let mut state = AppState::new(secret)
+        .with_config(config)
+        .with_http_client(http_client)
+        .with_github(github)
+        .with_store(store);
+    if let Some(app) = github_app {
+        state = state.with_github_app(app);
+    }
+
+    let mut failed = 0;
+    for Archived { job, .. } in deliveries {
+        let delivery = job.delivery_id.as_deref().unwrap_or("-");
+        match webhook::dispatch(
+            &state,
+            &job.event_type,
+            job.delivery_id.as_deref(),
+            &job.payload,
+            &job.json,
+        )
+        .await
+        {
+            Ok(()) => println!("{} {} -> ok", job.event_type, delivery),
+            Err(e) => {
+                println!("{} {} -> {}", job.event_type, delivery, e);
+                failed += 1;
+            }
+        }
+    }
+    automations::debounce::flush_all(&state).await;
+    automations::digest::flush_all(&state).await;
+    state.outbound.drained().await;
+    Ok(failed)
+}
+
+fn feed_event(job: &Job) -> FeedEvent {
+    FeedEvent {
+        received_at: job.received_at,
+        ..FeedEvent::new(&job.event_type, job.delivery_id.as_deref(), &job.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, http::HeaderMap, routing::post};
+    use bytes::Bytes;
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex},
+    };
+
+    const BODY: &str = r#"{ "action": "opened",  "unmodelled": [1, 2.50] }"#;
+
+    type Received = Arc<Mutex<Option<(HeaderMap, Bytes)>>>;
+
+    async fn receiver() -> (Received, String) {
+        let received = Received::default();
+        let app = Router::new().route(
+            "/",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: Bytes| async move {
+                    *received.lock().unwrap() = Some((headers, body));
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (received, url)
+    }
+
+    fn archived(headers: &[(&str, &str)]) -> Archived {
+        Archived {
+            job: Job {
+                delivery_id: Some("72d3162e".to_string()),
+                event_type: "issues".to_string(),
+                payload: serde_json::from_str(BODY).unwrap(),
+                json: Bytes::from_static(BODY.as_bytes()),
+                received_at: Utc::now(),
+                journal: None,
+            },
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+            body: Bytes::from_static(BODY.as_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn resends_the_archived_bytes_and_signature() {
+        let (received, url) = receiver().await;
+        let delivery = archived(&[
+            ("x-github-event", "issues"),
+            ("x-github-delivery", "72d3162e"),
+            ("x-hub-signature-256", "sha256=original"),
+            ("content-type", "application/json"),
+        ]);
+
+        assert_eq!(resend(&[delivery], &url, None).await, 0);
+        let (headers, body) = received.lock().unwrap().take().unwrap();
+        assert_eq!(body, BODY.as_bytes());
+        assert_eq!(headers["x-hub-signature-256"], "sha256=original");
+        assert_eq!(headers["x-github-delivery"], "72d3162e");
+    }
+
+    #[tokio::test]
+    async fn signs_afresh_with_a_secret() {
+        let (received, url) = receiver().await;
+        let delivery = archived(&[("x-hub-signature-256", "sha256=original")]);
+
+        assert_eq!(resend(&[delivery], &url, Some("secret")).await, 0);
+        let (headers, body) = received.lock().unwrap().take().unwrap();
+        assert_eq!(body, BODY.as_bytes());
+        let mut signer = SignatureVerifier::new("secret").unwrap();
+        signer.update(BODY.as_bytes());
+        assert_eq!(headers["x-hub-signature-256"], signer.signature().as_str());
+        assert_eq!(headers.get_all("x-hub-signature-256").iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn entries_without_headers_get_github_headers() {
+        let (received, url) = receiver().await;
+
+        assert_eq!(resend(&[archived(&[])], &url, None).await, 0);
+        let (headers, _) = received.lock().unwrap().take().unwrap();
+        assert_eq!(headers["x-github-event"], "issues");
+        assert_eq!(headers["x-github-delivery"], "72d3162e");
+        assert_eq!(headers["content-type"], "application/json");
+    }
+}
@@ -0,0 +1,113 @@
+use nexus::webhook::SignatureVerifier;
+use rand::Rng;
+use reqwest::StatusCode;
+use std::{
+    io::{self, Read},
+    path::Path,
+    time::Duration,
+};
+
+use super::fixtures::{Template, fixture};
+
+// exits 0 when the delivery was accepted, 1 otherwise
+pub async fn run(
+    url: &str,
+    event: &str,
+    payload: Option<&Path>,
+    template: &Template,
+    delivery: Option<String>,
+    secret: Option<&str>,
+) -> i32 {
+    let body = match load_payload(event, payload, template) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let delivery = delivery.unwrap_or_else(random_delivery_id);
+
+    match deliver(&reqwest::Client::new(), url, event, &delivery, body, secret).await {
+        Ok((status, text)) => {
+            println!("{} {} -> {}", event, delivery, status);
+            if !text.is_empty() {
+                println!("{}", text);
+            }
+            if status.is_success() { 0 } else { 1 }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+// posts `body` with the headers GitHub sets, signed when there is a secret
+pub async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    event: &str,
+    delivery: &str,
+    body: Vec<u8>,
+    secret: Option<&str>,
+) -> Result<(StatusCode, String), String> {
+    let mut request = client
+        .post(url)
+        .timeout(Duration::from_secs(30))
+        .header("user-agent", "GitHub-Hookshot/nexus")
+        .header("content-type", "application/json")
+        .header("x-github-event", event)
+        .header("x-github-delivery", delivery);
+    // GitHub only signs when the hook has a secret
+    if let Some(secret) = secret {
+        let mut signer =
+            SignatureVerifier::new(secret).ok_or("The secret cannot be used as an HMAC key")?;
+        signer.update(&body);
+        request = request.header("x-hub-signature-256", signer.signature());
+    }
+
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("{} is unreachable: {}", url, e))?;
+    let status = response.status();
+    Ok((status, response.text().await.unwrap_or_default()))
+}
+
+fn load_payload(event: &str, path: Option<&Path>, template: &Template) -> Result<Vec<u8>, String> {
+    match path {
+        Some(path) if path == Path::new("-") => {
+            let mut body = Vec::new();
+            io::stdin()
+                .read_to_end(&mut body)
+                .map_err(|e| format!("Failed to read the payload from stdin: {}", e))?;
+            Ok(body)
+        }
+        Some(path) => {
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        }
+        None => fixture(event, template)
+            .map(|payload| payload.to_string().into_bytes())
+            .ok_or_else(|| {
+                format!(
+                    "There is no built-in payload for {} events; pass one with --payload",
+                    event
+                )
+            }),
+    }
+}
+
+// formatted like GitHub's delivery GUIDs
+pub fn random_delivery_id() -> String {
+    let bytes: [u8; 16] = rand::thread_rng().r#gen();
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,975 @@
+use axum::{
+    Router,
+    extract::{ConnectInfo, Path, Query, Request, State},
+    middleware::{self, Next},
+    response::{
+        Html, Json, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{MethodRouter, get, post, post_service},
+    serve::ListenerExt,
+};
+use futures_util::{Stream, stream};
+use http::{HeaderMap, StatusCode, header};
+use nexus::{
+    AppState, Config, WebhookService,
+    acme::{Acme, TlsListener},
+    admin::{self, DeliverySummary, ReloadRequests, Reloader},
+    anomaly, archive,
+    automations::{self, exec::ExecRun},
+    clickhouse,
+    client_ip::{self, ClientIp},
+    config::RouteGroup,
+    correlation::Timeline,
+    error::NexusError,
+    export,
+    feed::FeedEvent,
+    github::{GithubClient, auth::AppAuth},
+    influxdb, metrics, openapi,
+    outbound::{self, Outbound},
+    proxy,
+    queue::{self, DeliveryQueue, Enqueued, Processing, ProcessingStatus},
+    redact::Redactor,
+    scheduler::Scheduler,
+    schema::Schemas,
+    stats::{self, RepoStats},
+    store::Store,
+    systemd,
+};
+use serde::Deserialize;
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::TcpListener,
+    sync::broadcast::error::RecvError,
+    task::{JoinHandle, JoinSet},
+};
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tracing::{error, info, warn};
+
+use super::Args;
+
+#[derive(Deserialize)]
+struct HealthQuery {
+    token: Option<String>,
+}
+
+async fn health_check(Query(params): Query<HealthQuery>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "status": "healthy",
+        "service": "github-webhook-service",
+        "version": env!("CARGO_PKG_VERSION"),
+        "authenticated": params.token.is_some()
+    }))
+}
+
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> String {
+    metrics::render(&state).await
+}
+
+async fn outbound_status(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "pending": state.outbound.pending().await,
+        "circuits": state.outbound.circuits(),
+    }))
+}
+
+async fn admin_config(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    Ok(Json(serde_json::json!({
+        "config": state.config,
+        "webhook_secret": state.webhook_secret.as_ref().map(|_| "[redacted]"),
+    })))
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    repo: Option<String>,
+    event: Option<String>,
+}
+
+impl EventsQuery {
+    fn matches(&self, event: &FeedEvent) -> bool {
+        let repo = self.repo.as_deref().is_none_or(|repo| {
+            event
+                .repository
+                .as_deref()
+                .is_some_and(|r| r.eq_ignore_ascii_case(repo))
+        });
+        let event_type = self.event.as_deref().is_none_or(|e| e == event.event_type);
+        repo && event_type
+    }
+}
+
+// received deliveries as server-sent `delivery` events; a `lagged` event carries the
+// number of deliveries a slow reader missed
+async fn admin_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(filter): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    let receiver = state
+        .feed
+        .subscribe()
+        .ok_or(NexusError::Unavailable("the live feed is not running"))?;
+    let events = stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) if filter.matches(&event) => {
+                    Event::default().event("delivery").json_data(&event)
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    Ok(Event::default().event("lagged").data(missed.to_string()))
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((event, (receiver, filter)));
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+struct ExecQuery {
+    delivery: Option<String>,
+}
+
+async fn admin_exec(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ExecQuery>,
+) -> Result<Json<Vec<ExecRun>>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    Ok(Json(state.exec.recent(query.delivery.as_deref())))
+}
+
+#[derive(Deserialize)]
+struct TimelineQuery {
+    repo: String,
+    number: Option<u64>,
+    sha: Option<String>,
+}
+
+// a commit resolves to the pull request that claimed it
+async fn admin_timeline(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<TimelineQuery>,
+) -> Result<Json<Timeline>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    let key = match (query.number, &query.sha) {
+        (Some(number), _) => format!("{}#{}", query.repo, number),
+        (None, Some(sha)) => state
+            .correlation
+            .resolve(&format!("{}@{}", query.repo, sha)),
+        (None, None) => {
+            return Err(NexusError::InvalidRequest(
+                "either number or sha is required".to_string(),
+            ));
+        }
+    };
+    state
+        .correlation
+        .timeline(&key)
+        .map(Json)
+        .ok_or_else(|| NexusError::NotFound(format!("nothing is known about {}", key)))
+}
+
+async fn admin_openapi(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    Ok(Json(openapi::document()))
+}
+
+async fn admin_docs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Html<String>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    Ok(Html(openapi::swagger_ui()))
+}
+
+#[derive(Deserialize)]
+struct PauseQuery {
+    reason: Option<String>,
+}
+
+async fn admin_processing(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ProcessingStatus>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    admin::processing(&state)
+        .map(Json)
+        .map_err(NexusError::from)
+}
+
+async fn admin_pause(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<PauseQuery>,
+) -> Result<Json<ProcessingStatus>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    admin::pause(&state, query.reason)
+        .map(Json)
+        .map_err(NexusError::from)
+}
+
+async fn admin_resume(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ProcessingStatus>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    admin::resume(&state).map(Json).map_err(NexusError::from)
+}
+
+#[derive(Deserialize)]
+struct DeliveriesQuery {
+    event: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn admin_deliveries(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<Vec<DeliverySummary>>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    admin::deliveries(&state, query.event.as_deref(), query.limit.unwrap_or(100))
+        .await
+        .map(Json)
+        .map_err(NexusError::from)
+}
+
+async fn admin_redeliver(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(delivery_id): Path<String>,
+) -> Result<Json<serde_json::Value>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    let enqueued = admin::redeliver(&state, &delivery_id).await?;
+    info!("Redelivering {} on request", delivery_id);
+    Ok(Json(serde_json::json!({
+        "delivery_id": delivery_id,
+        "shed": enqueued == Enqueued::Shed,
+    })))
+}
+
+async fn admin_reload(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, NexusError> {
+    authorize_admin(&state, &headers)?;
+    state.reloader.reload().await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+struct StatsQuery {
+    days: Option<u32>,
+}
+
+async fn repo_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<RepoStats>, NexusError> {
+    authorize_admin(&state, &headers)?;
+    if !state.config.stats.enabled {
+        return Err(NexusError::NotFound("stats are not enabled".to_string()));
+    }
+    let repo = format!("{}/{}", owner, repo);
+    stats::repo(&state, &repo, query.days)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to read stats for {}: {}", repo, e);
+            NexusError::from(e)
+        })
+}
+
+// the admin token is compared in constant time
+fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), NexusError> {
+    let Some(expected) = &state.config.server.admin_token else {
+        return Err(NexusError::AdminDisabled);
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(NexusError::Unauthorized)?;
+
+    if admin::token_matches(expected, provided) {
+        Ok(())
+    } else {
+        Err(NexusError::Unauthorized)
+    }
+}
+
+async fn webhook_info() -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "service": "GitHub Webhook Service",
+        "endpoints": {
+            "webhook": "/webhook",
+            "health": "/health",
+            "metrics": "/metrics",
+            "outbound": "/outbound",
+            "stats": "/stats/repos/{owner}/{repo}",
+            "config": "/admin/config",
+            "events": "/admin/events",
+            "exec": "/admin/exec",
+            "timeline": "/admin/timeline",
+            "processing": "/admin/processing",
+            "deliveries": "/admin/deliveries",
+            "reload": "/admin/reload",
+            "pause": "/admin/pause",
+            "resume": "/admin/resume",
+            "openapi": "/admin/openapi.json",
+            "docs": "/admin/docs",
+            "info": "/"
+        },
+        "supported_events": [
+            "push",
+            "pull_request",
+            "issues",
+            "issue_comment",
+            "check_suite",
+            "workflow_run",
+            "deployment",
+            "release",
+            "create",
+            "ping"
+        ]
+    }))
+}
+
+// runs until `shutdown` completes or a listener fails, then drains. A reload requested
+// through the admin API drains the same way and starts again with the new configuration;
+// the certificate, the `[state]` store, outbound deliveries, sockets from systemd and the
+// webhook secret are kept
+pub async fn serve(args: &Args, mut config: Config, shutdown: impl Future<Output = ()>) {
+    let store = Arc::new(Store::from_config(&config.state).unwrap_or_else(|e| {
+        error!("Invalid state backend: {}", e);
+        std::process::exit(1);
+    }));
+
+    let tls_config = if config.server.acme.enabled {
+        let http_client = proxy::client(&config.proxy).unwrap_or_else(|e| {
+            error!("Invalid proxy settings: {}", e);
+            std::process::exit(1);
+        });
+        let acme = Acme::new(&config.server.acme, http_client).unwrap_or_else(|e| {
+            error!("Invalid ACME configuration: {}", e);
+            std::process::exit(1);
+        });
+        let acme = Arc::new(acme);
+        if let Err(e) = acme.clone().start().await {
+            error!("Failed to obtain a certificate: {}", e);
+            std::process::exit(1);
+        }
+        Some(acme.tls_config())
+    } else {
+        None
+    };
+
+    // taken once: the descriptors belong to the process, and each generation listens on
+    // clones of them, so the sockets stay open across a reload
+    let activated = systemd::listeners_from_env();
+    // outbound deliveries outlive a generation that hit its drain deadline, and are not
+    // resumed a second time by the next one
+    let outbound = Outbound::default();
+
+    let (reloader, mut reloads) = Reloader::channel();
+    let mut shutdown = std::pin::pin!(shutdown);
+    let mut processing = None;
+    systemd::spawn_watchdog();
+    loop {
+        let generation = Generation {
+            args,
+            store: store.clone(),
+            outbound: outbound.clone(),
+            activated: &activated,
+            tls_config: tls_config.clone(),
+            reloader: reloader.clone(),
+            processing,
+        };
+        match generation
+            .run(config, &mut reloads, shutdown.as_mut())
+            .await
+        {
+            Some((next, status)) => {
+                info!("Starting again with the reloaded configuration");
+                config = next;
+                processing = status;
+            }
+            None => return,
+        }
+    }
+}
+
+// what a configuration needs that can turn out invalid, built before a reload is
+// accepted so that a bad file leaves the server running as it was
+struct Parts {
+    github_app: Option<AppAuth>,
+    http_client: reqwest::Client,
+    github: GithubClient,
+    redactor: Redactor,
+    schemas: Schemas,
+    scheduler: Scheduler,
+}
+
+impl Parts {
+    fn from_config(config: &Config) -> Result<Self, String> {
+        let github_app = AppAuth::from_config(&config.github)
+            .map_err(|e| format!("Failed to load GitHub App credentials: {}", e))?;
+        let http_client =
+            proxy::client(&config.proxy).map_err(|e| format!("Invalid proxy settings: {}", e))?;
+        let github = GithubClient::from_config(config)
+            .map_err(|e| format!("Invalid GitHub settings: {}", e))?;
+        let redactor = Redactor::from_config(&config.redaction)
+            .map_err(|e| format!("Invalid redaction settings: {}", e))?;
+        let schemas = Schemas::from_config(&config.schema)
+            .map_err(|e| format!("Invalid schema settings: {}", e))?;
+
+        let mut scheduler = Scheduler::new();
+        automations::stale::schedule(&mut scheduler, &config.stale)
+            .map_err(|e| format!("Invalid stale schedule: {}", e))?;
+        automations::lock::schedule(&mut scheduler, &config.lock)
+            .map_err(|e| format!("Invalid lock schedule: {}", e))?;
+        automations::reports::schedule(&mut scheduler, &config.reports)
+            .map_err(|e| format!("Invalid report schedule: {}", e))?;
+        automations::label_sync::schedule(&mut scheduler, &config.label_sync)
+            .map_err(|e| format!("Invalid label sync schedule: {}", e))?;
+        export::schedule(&mut scheduler, &config.export)
+            .map_err(|e| format!("Invalid export schedule: {}", e))?;
+
+        Ok(Self {
+            github_app,
+            http_client,
+            github,
+            redactor,
+            schemas,
+            scheduler,
+        })
+    }
+}
+
+// one configuration's worth of the server, from start to drained
+struct Generation<'a> {
+    args: &'a Args,
+    store: Arc<Store>,
+    outbound: Outbound,
+    activated: &'a [std::net::TcpListener],
+    tls_config: Option<Arc<rustls::ServerConfig>>,
+    reloader: Reloader,
+    // carried over from before a reload, so paused processing stays paused
+    processing: Option<ProcessingStatus>,
+}
+
+impl Generation<'_> {
+    // the configuration to start again with when a reload was requested
+    async fn run(
+        self,
+        config: Config,
+        reloads: &mut ReloadRequests,
+        mut shutdown: std::pin::Pin<&mut impl Future<Output = ()>>,
+    ) -> Option<(Config, Option<ProcessingStatus>)> {
+        let Generation {
+            args,
+            store,
+            outbound,
+            activated,
+            tls_config,
+            reloader,
+            processing,
+        } = self;
+
+        let Parts {
+            github_app,
+            http_client,
+            github,
+            redactor,
+            schemas,
+            scheduler,
+        } = Parts::from_config(&config).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+
+        let (queue, receiver) = DeliveryQueue::new(&config.queue);
+        if let Some(status) = processing.filter(|s| s.state == Processing::Paused) {
+            queue.pause(status.reason);
+        }
+        let workers = config.queue.workers;
+
+        let mut state = AppState::new(args.secret.clone())
+            .with_config(config)
+            .with_http_client(http_client)
+            .with_github(github)
+            .with_queue(queue)
+            .with_redactor(redactor)
+            .with_schemas(schemas)
+            .with_reloader(reloader);
+        state.store = store;
+        state.outbound = outbound;
+        if let Some(app) = github_app {
+            info!("Authenticating as GitHub App {}", app.app_id());
+            state = state.with_github_app(app);
+        }
+        let state = Arc::new(state);
+        queue::spawn_workers(state.clone(), receiver, workers);
+        if let Some(queue) = state.queue.clone() {
+            tokio::spawn(async move { queue.replay().await });
+        }
+        outbound::resume(state.clone()).await;
+        // background work that would otherwise outlive a reload
+        let mut tasks: Vec<JoinHandle<()>> = [
+            archive::spawn_pruning(state.config.archive.clone()),
+            anomaly::spawn(state.clone()),
+            clickhouse::spawn(state.clone()),
+            influxdb::spawn(state.clone()),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        tasks.extend(scheduler.start(state.clone()));
+
+        if args.secret.is_some() {
+            info!("Webhook signature verification enabled");
+        } else {
+            warn!("No webhook secret configured - signatures will not be verified");
+        }
+
+        let (stop, on_shutdown) = tokio::sync::watch::channel(false);
+        let mut servers = JoinSet::new();
+        for (listener, routes, tls) in bind_listeners(&state.config, activated, args.port).await {
+            info!(
+                "GitHub Webhook Service starting on {} ({:?}{})",
+                listener.local_addr().unwrap(),
+                routes,
+                if tls { ", TLS" } else { "" }
+            );
+            let mut on_shutdown = on_shutdown.clone();
+            let stopped = async move {
+                let _ = on_shutdown.wait_for(|stop| *stop).await;
+            };
+            let app = router(&state, &routes).into_make_service_with_connect_info::<SocketAddr>();
+            match (&tls_config, tls) {
+                (Some(config), true) => {
+                    let listener = TlsListener::new(listener, config.clone())
+                        .unwrap_or_else(|e| {
+                            error!("Failed to start the TLS listener: {}", e);
+                            std::process::exit(1);
+                        })
+                        .tap_io(|_| {});
+                    let server = axum::serve(listener, app).with_graceful_shutdown(stopped);
+                    servers.spawn(server.into_future());
+                }
+                (None, true) => {
+                    error!("Listeners with `tls = true` need [server.acme] enabled");
+                    std::process::exit(1);
+                }
+                (_, false) => {
+                    let server = axum::serve(listener, app).with_graceful_shutdown(stopped);
+                    servers.spawn(server.into_future());
+                }
+            }
+        }
+        if state.config.server.grpc.enabled {
+            spawn_grpc(&state, &mut servers, &on_shutdown).await;
+        }
+        systemd::notify("READY=1");
+
+        let reload = loop {
+            tokio::select! {
+                _ = shutdown.as_mut() => break None,
+                Some(result) = servers.join_next() => {
+                    error!("A listener stopped unexpectedly: {:?}", result);
+                    break None;
+                }
+                request = reloads.next() => match super::try_load_config(args)
+                    .and_then(|config| Parts::from_config(&config).map(|_| config))
+                {
+                    Ok(config) => {
+                        info!("Reloading the configuration");
+                        request.accept();
+                        break Some(config);
+                    }
+                    Err(e) => {
+                        warn!("Not reloading: {}", e);
+                        request.reject(e);
+                    }
+                },
+            }
+        };
+
+        let timeout = Duration::from_secs(state.config.server.shutdown_timeout_secs);
+        match reload {
+            Some(_) => {
+                info!("Draining for up to {}s before reloading", timeout.as_secs());
+                systemd::notify("RELOADING=1");
+            }
+            None => {
+                info!("Shutting down, draining for up to {}s", timeout.as_secs());
+                systemd::notify("STOPPING=1");
+            }
+        }
+        let _ = stop.send(true);
+        if tokio::time::timeout(timeout, drain(&state, servers))
+            .await
+            .is_err()
+        {
+            warn!("Drain deadline reached, stopping with work unfinished");
+            let pending = state.outbound.pending().await.len();
+            if pending > 0 && state.config.outbound.state_path.is_some() {
+                info!(
+                    "{} outbound deliveries will resume on the next start",
+                    pending
+                );
+            }
+        }
+        for task in tasks {
+            task.abort();
+        }
+        let status = state.queue.as_ref().map(|queue| queue.status());
+        reload.map(|config| (config, status))
+    }
+}
+
+// the paths are kept so a test can hold them against the OpenAPI document
+#[derive(Default)]
+struct Routes {
+    router: Router,
+    paths: Vec<&'static str>,
+}
+
+impl Routes {
+    fn route(mut self, path: &'static str, method_router: MethodRouter) -> Self {
+        self.paths.push(path);
+        self.router = self.router.route(path, method_router);
+        self
+    }
+}
+
+fn router(state: &Arc<AppState>, routes: &[RouteGroup]) -> Router {
+    routes_for(state, routes)
+        .router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            resolve_client_ip,
+        ))
+        .layer(CorsLayer::permissive())
+        .layer(TraceLayer::new_for_http())
+}
+
+fn routes_for(state: &Arc<AppState>, routes: &[RouteGroup]) -> Routes {
+    let mut app = Routes::default();
+    for group in routes {
+        app = match group {
+            RouteGroup::Webhook => app
+                .route("/webhook", post_service(WebhookService::new(state.clone())))
+                .route(
+                    "/linear",
+                    post(automations::linear::webhook).with_state(state.clone()),
+                ),
+            RouteGroup::Health => app
+                .route("/", get(webhook_info))
+                .route("/health", get(health_check)),
+            RouteGroup::Admin => app
+                .route("/metrics", get(metrics_handler).with_state(state.clone()))
+                .route("/outbound", get(outbound_status).with_state(state.clone()))
+                .route(
+                    "/stats/repos/{owner}/{repo}",
+                    get(repo_stats).with_state(state.clone()),
+                )
+                .route("/admin/config", get(admin_config).with_state(state.clone()))
+                .route("/admin/events", get(admin_events).with_state(state.clone()))
+                .route("/admin/exec", get(admin_exec).with_state(state.clone()))
+                .route(
+                    "/admin/timeline",
+                    get(admin_timeline).with_state(state.clone()),
+                )
+                .route(
+                    "/admin/processing",
+                    get(admin_processing).with_state(state.clone()),
+                )
+                .route(
+                    "/admin/deliveries",
+                    get(admin_deliveries).with_state(state.clone()),
+                )
+                .route(
+                    "/admin/deliveries/{id}/redeliver",
+                    post(admin_redeliver).with_state(state.clone()),
+                )
+                .route(
+                    "/admin/reload",
+                    post(admin_reload).with_state(state.clone()),
+                )
+                .route("/admin/pause", post(admin_pause).with_state(state.clone()))
+                .route(
+                    "/admin/resume",
+                    post(admin_resume).with_state(state.clone()),
+                )
+                .route(
+                    "/admin/openapi.json",
+                    get(admin_openapi).with_state(state.clone()),
+                )
+                .route("/admin/docs", get(admin_docs).with_state(state.clone())),
+        };
+    }
+    app
+}
+
+async fn resolve_client_ip(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip::resolve(
+        peer.ip(),
+        request.headers(),
+        &state.config.server.trusted_proxies,
+    );
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
+
+#[cfg(feature = "grpc")]
+async fn spawn_grpc(
+    state: &AppState,
+    servers: &mut JoinSet<std::io::Result<()>>,
+    on_shutdown: &tokio::sync::watch::Receiver<bool>,
+) {
+    let address = state.config.server.grpc.address;
+    let listener = TcpListener::bind(address).await.unwrap_or_else(|e| {
+        error!("Failed to bind the gRPC admin API to {}: {}", address, e);
+        std::process::exit(1);
+    });
+    info!("gRPC admin API on {}", address);
+    let mut on_shutdown = on_shutdown.clone();
+    let stopped = async move {
+        let _ = on_shutdown.wait_for(|stop| *stop).await;
+    };
+    let server = nexus::grpc::serve(state.clone(), listener, stopped);
+    servers.spawn(async move { server.await.map_err(std::io::Error::other) });
+}
+
+#[cfg(not(feature = "grpc"))]
+async fn spawn_grpc(
+    _: &AppState,
+    _: &mut JoinSet<std::io::Result<()>>,
+    _: &tokio::sync::watch::Receiver<bool>,
+) {
+    warn!("server.grpc is enabled, but nexus was built without the `grpc` feature");
+}
+
+// sockets from systemd take the place of the configured listeners, in order; without
+// configured listeners the default one uses TLS when ACME is enabled
+async fn bind_listeners(
+    config: &Config,
+    activated: &[std::net::TcpListener],
+    port: u16,
+) -> Vec<(TcpListener, Vec<RouteGroup>, bool)> {
+    let configured = &config.server.listeners;
+    let routes = |index: usize| {
+        configured
+            .get(index)
+            .map(|l| l.routes.clone())
+            .unwrap_or_else(|| RouteGroup::ALL.to_vec())
+    };
+    let tls = |index: usize| {
+        configured
+            .get(index)
+            .map(|l| l.tls)
+            .unwrap_or(config.server.acme.enabled)
+    };
+
+    if !activated.is_empty() {
+        return activated
+            .iter()
+            .enumerate()
+            .map(|(index, listener)| {
+                let listener = listener
+                    .try_clone()
+                    .and_then(TcpListener::from_std)
+                    .unwrap_or_else(|e| {
+                        error!("Failed to listen on socket {} from systemd: {}", index, e);
+                        std::process::exit(1);
+                    });
+                (listener, routes(index), tls(index))
+            })
+            .collect();
+    }
+
+    let addresses: Vec<SocketAddr> = if configured.is_empty() {
+        vec![SocketAddr::from(([0, 0, 0, 0], port))]
+    } else {
+        configured.iter().map(|l| l.address).collect()
+    };
+    let mut listeners = Vec::new();
+    for (index, address) in addresses.into_iter().enumerate() {
+        match TcpListener::bind(address).await {
+            Ok(listener) => listeners.push((listener, routes(index), tls(index))),
+            Err(e) => {
+                error!("Failed to listen on {}: {}", address, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    listeners
+}
+
+// open connections finish, queued deliveries are processed, digests are sent and
+// outbound retries get a chance to complete
+async fn drain(state: &AppState, mut servers: JoinSet<std::io::Result<()>>) {
+    if let Some(queue) = &state.queue {
+        queue.close();
+    }
+    state.feed.close();
+    while let Some(result) = servers.join_next().await {
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Server error while draining connections: {}", e),
+            Err(e) => error!("Listener task failed: {}", e),
+        }
+    }
+    if let Some(queue) = &state.queue {
+        let status = queue.status();
+        if status.state == Processing::Paused {
+            match &state.config.queue.journal_dir {
+                Some(_) => info!("Processing is paused; held deliveries stay in the journal"),
+                None => warn!(
+                    "Processing is paused; {} held deliveries are dropped without a queue journal",
+                    status.held + queue.depth()
+                ),
+            }
+        }
+        queue.drained().await;
+    }
+    automations::debounce::flush_all(state).await;
+    automations::digest::flush_all(state).await;
+    clickhouse::flush(state).await;
+    influxdb::flush(state).await;
+    state.outbound.drained().await;
+    info!("Drained");
+}
+
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn stats(admin_token: Option<&str>, authorization: Option<&str>) -> StatusCode {
+        let mut config = Config::default();
+        config.server.admin_token = admin_token.map(String::from);
+        let state = Arc::new(AppState::new(None).with_config(config));
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization {
+            headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+        }
+        let path = Path(("org".to_string(), "repo".to_string()));
+        match repo_stats(
+            State(state),
+            headers,
+            path,
+            Query(StatsQuery { days: None }),
+        )
+        .await
+        {
+            Ok(_) => StatusCode::OK,
+            Err(e) => e.status(),
+        }
+    }
+
+    #[tokio::test]
+    async fn repo_stats_need_the_admin_token() {
+        assert_eq!(stats(None, None).await, StatusCode::FORBIDDEN);
+        assert_eq!(stats(Some("token"), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            stats(Some("token"), Some("Bearer other")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        // past the token check; statistics are off by default
+        assert_eq!(
+            stats(Some("token"), Some("Bearer token")).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn reloads_answer_with_the_servers_verdict() {
+        let mut config = Config::default();
+        config.server.admin_token = Some("token".to_string());
+        let (reloader, mut requests) = Reloader::channel();
+        let state = Arc::new(
+            AppState::new(None)
+                .with_config(config)
+                .with_reloader(reloader),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer token".parse().unwrap());
+        tokio::spawn(async move {
+            requests.next().await.reject("invalid config".to_string());
+            requests.next().await.accept();
+        });
+
+        let rejected = admin_reload(State(state.clone()), headers.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(rejected.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(rejected.code(), "reload_failed");
+        assert_eq!(rejected.to_string(), "invalid config");
+        let accepted = admin_reload(State(state), headers).await;
+        assert_eq!(accepted.unwrap(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let state = Arc::new(AppState::new(None));
+        let mut routed = routes_for(&state, &RouteGroup::ALL).paths;
+        routed.sort_unstable();
+        let document = nexus::openapi::document();
+        let mut documented: Vec<&str> = document["paths"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        documented.sort_unstable();
+        assert_eq!(routed, documented);
+    }
+}
@@ -0,0 +1,62 @@
+use clap::Subcommand;
+use nexus::winservice::{self, EventLog};
+use std::time::Duration;
+
+use super::{Args, Command, load_config, serve::serve};
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum ServiceCommand {
+    /// Register nexus with the service control manager, starting with the current --config and --port
+    Install,
+    /// Stop and remove the service
+    Uninstall,
+    /// Entry point used by the service control manager; logs go to the Application event log
+    Run,
+}
+
+pub fn run(args: Args) -> i32 {
+    let command = match &args.command {
+        Some(Command::Service(command)) => *command,
+        _ => return 1,
+    };
+    let result = match command {
+        ServiceCommand::Install => {
+            let mut arguments: Vec<std::ffi::OsString> = vec!["service".into(), "run".into()];
+            arguments.extend(["--port".into(), args.port.to_string().into()]);
+            if let Some(path) = &args.config {
+                let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+                arguments.extend(["--config".into(), path.into_os_string()]);
+            }
+            winservice::install(arguments)
+        }
+        ServiceCommand::Uninstall => winservice::uninstall(),
+        ServiceCommand::Run => {
+            match EventLog::register() {
+                Ok(log) => tracing_subscriber::fmt()
+                    .with_writer(log)
+                    .with_ansi(false)
+                    .without_time()
+                    .init(),
+                Err(_) => tracing_subscriber::fmt::init(),
+            }
+            let config = load_config(&args);
+            let timeout = Duration::from_secs(config.server.shutdown_timeout_secs);
+            winservice::run(
+                timeout,
+                Box::new(move |stop| {
+                    let stopped = async {
+                        let _ = stop.await;
+                    };
+                    super::runtime().block_on(serve(&args, config, stopped));
+                }),
+            )
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Service command failed: {}", e);
+            1
+        }
+    }
+}
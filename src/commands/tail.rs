@@ -0,0 +1,116 @@
+use nexus::feed::FeedEvent;
+use std::{io::IsTerminal, time::Duration};
+
+pub struct Filter {
+    pub repo: Option<String>,
+    pub event: Option<String>,
+}
+
+// follows /admin/events until the server closes the stream
+pub async fn run(url: &str, admin_token: Option<&str>, filter: &Filter, json: bool) -> i32 {
+    let query: Vec<(&str, &str)> = [("repo", &filter.repo), ("event", &filter.event)]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|v| (name, v)))
+        .collect();
+    let client = match reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to create the HTTP client: {}", e);
+            return 1;
+        }
+    };
+    let mut request = client
+        .get(url)
+        .query(&query)
+        .header("accept", "text/event-stream");
+    if let Some(token) = admin_token {
+        request = request.bearer_auth(token);
+    }
+
+    let mut response = match request.send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            eprintln!("{} returned {}", url, response.status());
+            if admin_token.is_none() {
+                eprintln!("Pass the admin token with --admin-token or NEXUS_ADMIN_TOKEN");
+            }
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("{} is unreachable: {}", url, e);
+            return 1;
+        }
+    };
+
+    let color = !json && std::io::stdout().is_terminal();
+    let mut buffer = String::new();
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => {
+                eprintln!("The server closed the stream");
+                return 0;
+            }
+            Err(e) => {
+                eprintln!("Lost the connection to {}: {}", url, e);
+                return 1;
+            }
+        };
+        buffer.push_str(&String::from_utf8_lossy(&chunk).replace('\r', ""));
+        // events are separated by a blank line
+        while let Some(end) = buffer.find("\n\n") {
+            let frame: String = buffer.drain(..end + 2).collect();
+            handle_frame(&frame, json, color);
+        }
+    }
+}
+
+fn handle_frame(frame: &str, json: bool, color: bool) {
+    let mut name = "message";
+    let mut data = Vec::new();
+    for line in frame.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    let data = data.join("\n");
+
+    match name {
+        "delivery" if json => println!("{}", data),
+        "delivery" => match serde_json::from_str::<FeedEvent>(&data) {
+            Ok(event) => println!("{}", format_event(&event, color)),
+            Err(e) => eprintln!("Skipping an unreadable event: {}", e),
+        },
+        "lagged" => eprintln!("Missed {} deliveries while catching up", data),
+        // keep-alive comments have no event name or data
+        _ => {}
+    }
+}
+
+// `14:03:22  pull_request.opened  octo-org/example  octocat  <delivery id>`
+pub fn format_event(event: &FeedEvent, color: bool) -> String {
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    };
+    let name = match &event.action {
+        Some(action) => format!("{}.{}", event.event_type, action),
+        None => event.event_type.clone(),
+    };
+    format!(
+        "{}  {}  {}  {}  {}",
+        paint("2", &event.received_at.format("%H:%M:%S").to_string()),
+        paint("1;36", &format!("{:<28}", name)),
+        paint("33", event.repository.as_deref().unwrap_or("-")),
+        event.sender.as_deref().unwrap_or("-"),
+        paint("2", event.delivery_id.as_deref().unwrap_or("-")),
+    )
+}
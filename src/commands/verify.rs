@@ -0,0 +1,84 @@
+use nexus::webhook::{SignatureError, SignatureVerifier};
+use std::path::Path;
+
+// exits 0 when `signature` matches the payload, 1 otherwise
+pub fn run(payload: &Path, signature: &str, secret: Option<&str>) -> i32 {
+    let Some(secret) = secret else {
+        eprintln!("Pass the webhook secret with --secret or GITHUB_WEBHOOK_SECRET");
+        return 1;
+    };
+    let body = match std::fs::read(payload) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", payload.display(), e);
+            return 1;
+        }
+    };
+    let Some(mut verifier) = SignatureVerifier::new(secret) else {
+        eprintln!("The secret cannot be used as an HMAC key");
+        return 1;
+    };
+    verifier.update(&body);
+    let expected = verifier.clone().signature();
+
+    // header values are often copied with surrounding whitespace
+    let signature = signature.trim();
+    match verifier.check(signature) {
+        Ok(()) => {
+            println!("OK: the signature matches {} bytes of payload", body.len());
+            0
+        }
+        Err(e) => {
+            println!("FAIL: {}", e);
+            match e {
+                SignatureError::WrongPrefix if signature.starts_with("sha1=") => {
+                    println!("Use the X-Hub-Signature-256 header, not X-Hub-Signature")
+                }
+                SignatureError::WrongPrefix | SignatureError::InvalidHex => {}
+                SignatureError::Mismatch => {
+                    println!("expected {}", expected);
+                    println!(
+                        "The payload ({} bytes) must be the exact bytes GitHub sent; proxies that \
+                         re-encode JSON, change line endings or decompress the body break the \
+                         signature, as does a different secret",
+                        body.len()
+                    );
+                }
+            }
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exits_zero_only_for_a_matching_signature() {
+        let path = std::env::temp_dir().join(format!("nexus-verify-{}.json", std::process::id()));
+        std::fs::write(&path, "Hello, World!").unwrap();
+        let secret = Some("It's a Secret to Everybody");
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+        assert_eq!(run(&path, signature, secret), 0);
+        // as copied out of a proxy log
+        assert_eq!(run(&path, &format!(" {}\n", signature), secret), 0);
+        assert_eq!(run(&path, signature, Some("another secret")), 1);
+        assert_eq!(
+            run(
+                &path,
+                "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59",
+                secret
+            ),
+            1
+        );
+        assert_eq!(run(&path, "sha256=zz", secret), 1);
+        assert_eq!(run(&path, signature, None), 1);
+
+        std::fs::write(&path, "Hello, World!\r\n").unwrap();
+        assert_eq!(run(&path, signature, secret), 1);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(run(&path, signature, secret), 1);
+    }
+}
@@ -0,0 +1,65 @@
+// Conventional Commits headers, `type(scope)!: description`, as used in pull request
+// titles and commit messages.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+static HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?<type>[A-Za-z]+)(?:\((?<scope>[^)]*)\))?(?<breaking>!)?:\s*(?<description>.+)$")
+        .expect("valid regex")
+});
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header<'a> {
+    // lowercased
+    pub kind: String,
+    // None when absent or empty
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub description: &'a str,
+}
+
+// the first line only; None when it does not follow the convention
+pub fn parse(message: &str) -> Option<Header<'_>> {
+    let caps = HEADER.captures(message.lines().next()?.trim_end())?;
+    Some(Header {
+        kind: caps.name("type")?.as_str().to_lowercase(),
+        scope: caps
+            .name("scope")
+            .map(|m| m.as_str())
+            .filter(|s| !s.is_empty()),
+        breaking: caps.name("breaking").is_some(),
+        description: caps.name("description")?.as_str(),
+    })
+}
+
+// why `message` breaks the rules, if it does; empty `types` or `scopes` allow any
+pub fn violation(
+    message: &str,
+    types: &[String],
+    scopes: &[String],
+    require_scope: bool,
+) -> Option<String> {
+    let Some(header) = parse(message) else {
+        return Some("does not look like `type(scope): description`".to_string());
+    };
+    if !types.is_empty() && !types.contains(&header.kind) {
+        return Some(format!(
+            "`{}` is not one of the types: {}",
+            header.kind,
+            types.join(", ")
+        ));
+    }
+    match header.scope {
+        None if require_scope => Some(format!(
+            "A scope is required, e.g. `{}(api): ...`",
+            header.kind
+        )),
+        Some(scope) if !scopes.is_empty() && !scopes.iter().any(|s| s == scope) => Some(format!(
+            "`{}` is not one of the scopes: {}",
+            scope,
+            scopes.join(", ")
+        )),
+        _ => None,
+    }
+}
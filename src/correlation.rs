@@ -0,0 +1,135 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use crate::{feed::FeedEvent, webhook::WebhookPayload};
+
+// the oldest timeline is dropped when another one starts
+const MAX_TIMELINES: usize = 1024;
+const MAX_EVENTS: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Timeline {
+    pub key: String,
+    pub events: Vec<FeedEvent>,
+}
+
+/// Related deliveries grouped under a correlation key: `owner/repo#12` for a pull request
+/// or issue, `owner/repo@<sha>` for a commit no pull request has claimed yet. Check suites,
+/// workflow runs and deployments for a pull request's head commit join its timeline, and
+/// a commit's timeline moves into the pull request once one points at it.
+#[derive(Clone, Default)]
+pub struct Correlations {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    timelines: HashMap<String, VecDeque<FeedEvent>>,
+    // keys, oldest first
+    order: VecDeque<String>,
+    // `owner/repo@<sha>` to the pull request whose head it is
+    heads: HashMap<String, String>,
+}
+
+impl Correlations {
+    // the key a delivery belongs under, or None for events that relate to nothing in particular
+    pub fn key(&self, payload: &WebhookPayload) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        key(&inner, payload)
+    }
+
+    // records the delivery and returns its key
+    pub fn record(&self, event: &FeedEvent, payload: &WebhookPayload) -> Option<String> {
+        let mut inner = self.inner.lock().unwrap();
+        if let (Some(repo), Some(pr)) = (&payload.repository, &payload.pull_request)
+            && let Some(head) = &pr.head
+        {
+            let pr_key = format!("{}#{}", repo.full_name, pr.number);
+            let commit_key = format!("{}@{}", repo.full_name, head.sha);
+            inner.heads.insert(commit_key.clone(), pr_key.clone());
+            if let Some(earlier) = inner.timelines.remove(&commit_key) {
+                inner.order.retain(|k| *k != commit_key);
+                let events = inner.entry(&pr_key);
+                events.extend(earlier);
+                events.make_contiguous().sort_by_key(|e| e.received_at);
+                trim(events);
+            }
+        }
+
+        let key = key(&inner, payload)?;
+        let events = inner.entry(&key);
+        events.push_back(event.clone());
+        trim(events);
+        Some(key)
+    }
+
+    pub fn timeline(&self, key: &str) -> Option<Timeline> {
+        let inner = self.inner.lock().unwrap();
+        let events = inner.timelines.get(key)?;
+        Some(Timeline {
+            key: key.to_string(),
+            events: events.iter().cloned().collect(),
+        })
+    }
+
+    // what a pull request or commit key currently resolves to
+    pub fn resolve(&self, key: &str) -> String {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .heads
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+impl Inner {
+    fn entry(&mut self, key: &str) -> &mut VecDeque<FeedEvent> {
+        if !self.timelines.contains_key(key) {
+            if self.order.len() == MAX_TIMELINES
+                && let Some(oldest) = self.order.pop_front()
+            {
+                self.timelines.remove(&oldest);
+                self.heads.retain(|_, pr| *pr != oldest);
+            }
+            self.order.push_back(key.to_string());
+        }
+        self.timelines.entry(key.to_string()).or_default()
+    }
+}
+
+fn trim(events: &mut VecDeque<FeedEvent>) {
+    while events.len() > MAX_EVENTS {
+        events.pop_front();
+    }
+}
+
+fn key(inner: &Inner, payload: &WebhookPayload) -> Option<String> {
+    let repo = &payload.repository.as_ref()?.full_name;
+    if let Some(pr) = &payload.pull_request {
+        return Some(format!("{}#{}", repo, pr.number));
+    }
+    if let Some(issue) = &payload.issue {
+        return Some(format!("{}#{}", repo, issue.number));
+    }
+
+    let (sha, pull_requests) = if let Some(suite) = &payload.check_suite {
+        (suite.head_sha.as_str(), suite.pull_requests.as_slice())
+    } else if let Some(run) = &payload.workflow_run {
+        (run.head_sha.as_str(), run.pull_requests.as_slice())
+    } else if let Some(deployment) = &payload.deployment {
+        (deployment.sha.as_str(), [].as_slice())
+    } else if let Some(after) = payload.after.as_deref().filter(|_| !payload.deleted) {
+        (after, [].as_slice())
+    } else {
+        return None;
+    };
+    if let Some(pr) = pull_requests.first() {
+        return Some(format!("{}#{}", repo, pr.number));
+    }
+    let commit_key = format!("{}@{}", repo, sha);
+    Some(inner.heads.get(&commit_key).cloned().unwrap_or(commit_key))
+}
@@ -0,0 +1,150 @@
+// Content-Encoding of request bodies. GitHub never compresses deliveries, but some
+// proxies and other providers do; signatures cover the compressed bytes, so bodies are
+// decoded only after verification.
+
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use http::{HeaderMap, header};
+use std::{fmt, io::Read};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    // decoding would produce more than the limit
+    TooLarge(usize),
+    Invalid(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::TooLarge(limit) => write!(f, "decompresses to more than {} bytes", limit),
+            DecodeError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl ContentEncoding {
+    // Err with the encoding's name when it is not supported
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, String> {
+        let Some(value) = headers.get(header::CONTENT_ENCODING) else {
+            return Ok(ContentEncoding::Identity);
+        };
+        let value = value.to_str().unwrap_or_default().trim();
+        match value.to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(ContentEncoding::Identity),
+            "gzip" | "x-gzip" => Ok(ContentEncoding::Gzip),
+            "deflate" => Ok(ContentEncoding::Deflate),
+            _ => Err(value.to_string()),
+        }
+    }
+
+    pub fn decode(self, body: Vec<u8>, limit: usize) -> Result<Vec<u8>, DecodeError> {
+        match self {
+            ContentEncoding::Identity => Ok(body),
+            // a body may hold several gzip members, as `cat a.gz b.gz` does
+            ContentEncoding::Gzip => read_to_limit(MultiGzDecoder::new(&body[..]), limit),
+            // HTTP's deflate is zlib-wrapped, though some senders use raw deflate
+            ContentEncoding::Deflate => {
+                read_to_limit(ZlibDecoder::new(&body[..]), limit).or_else(|e| match e {
+                    DecodeError::Invalid(_) => read_to_limit(DeflateDecoder::new(&body[..]), limit),
+                    e => Err(e),
+                })
+            }
+        }
+    }
+}
+
+// reads one byte past the limit, so a body that is exactly the limit still fits
+fn read_to_limit(decoder: impl Read, limit: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut decoded = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| DecodeError::Invalid(format!("invalid compressed body ({})", e)))?;
+    if decoded.len() > limit {
+        return Err(DecodeError::TooLarge(limit));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        Compression,
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+    };
+    use std::io::Write;
+
+    const BODY: &[u8] = br#"{"action":"opened","number":1}"#;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decodes_gzip_zlib_and_raw_deflate() {
+        assert_eq!(
+            ContentEncoding::Gzip.decode(gzip(BODY), 1024).unwrap(),
+            BODY
+        );
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(BODY).unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert_eq!(ContentEncoding::Deflate.decode(zlib, 1024).unwrap(), BODY);
+
+        let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+        raw.write_all(BODY).unwrap();
+        let raw = raw.finish().unwrap();
+        assert_eq!(ContentEncoding::Deflate.decode(raw, 1024).unwrap(), BODY);
+    }
+
+    #[test]
+    fn decodes_every_gzip_member() {
+        let mut body = gzip(&BODY[..10]);
+        body.extend(gzip(&BODY[10..]));
+        assert_eq!(ContentEncoding::Gzip.decode(body, 1024).unwrap(), BODY);
+    }
+
+    #[test]
+    fn stops_at_the_limit() {
+        let bomb = gzip(&vec![b'a'; 1 << 20]);
+        assert!(matches!(
+            ContentEncoding::Gzip.decode(bomb, 4096),
+            Err(DecodeError::TooLarge(4096))
+        ));
+        let exact = ContentEncoding::Gzip.decode(gzip(BODY), BODY.len());
+        assert_eq!(exact.unwrap(), BODY);
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        let not_gzip = ContentEncoding::Gzip.decode(BODY.to_vec(), 1024);
+        assert!(matches!(not_gzip, Err(DecodeError::Invalid(_))));
+
+        let mut truncated = gzip(BODY);
+        truncated.truncate(truncated.len() / 2);
+        let truncated = ContentEncoding::Gzip.decode(truncated, 1024);
+        assert!(matches!(truncated, Err(DecodeError::Invalid(_))));
+
+        let mut corrupt = gzip(BODY);
+        let crc = corrupt.len() - 8;
+        corrupt[crc] ^= 0xff;
+        let corrupt = ContentEncoding::Gzip.decode(corrupt, 1024);
+        assert!(matches!(corrupt, Err(DecodeError::Invalid(_))));
+
+        let garbage = ContentEncoding::Deflate.decode(vec![0xff; 64], 1024);
+        assert!(matches!(garbage, Err(DecodeError::Invalid(_))));
+    }
+}
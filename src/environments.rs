@@ -0,0 +1,71 @@
+// Which deployment environment an event concerns, from `[[environments]]`: pushes and
+// branch or tag creation by their ref, releases by their tag, workflow runs and check
+// suites by their head branch, pull requests by their base branch, and deployments by
+// their own environment.
+
+use globset::Glob;
+
+use crate::{config::EnvironmentMapping, webhook::WebhookPayload};
+
+enum Ref<'a> {
+    Branch(&'a str),
+    Tag(&'a str),
+}
+
+pub fn for_payload(mappings: &[EnvironmentMapping], payload: &WebhookPayload) -> Option<String> {
+    if let Some(deployment) = &payload.deployment {
+        return Some(deployment.environment.clone());
+    }
+    let git_ref = if let Some(name) = &payload.ref_name {
+        // create and delete events send the short name with a separate ref_type
+        match (payload.ref_type.as_deref(), name.strip_prefix("refs/tags/")) {
+            (Some("tag"), _) => Ref::Tag(name),
+            (_, Some(tag)) => Ref::Tag(tag),
+            _ => Ref::Branch(name.strip_prefix("refs/heads/").unwrap_or(name)),
+        }
+    } else if let Some(release) = &payload.release {
+        Ref::Tag(&release.tag_name)
+    } else if let Some(branch) = payload
+        .workflow_run
+        .as_ref()
+        .and_then(|run| run.head_branch.as_deref())
+        .or(payload
+            .check_suite
+            .as_ref()
+            .and_then(|suite| suite.head_branch.as_deref()))
+    {
+        Ref::Branch(branch)
+    } else if let Some(base) = payload
+        .pull_request
+        .as_ref()
+        .and_then(|pr| pr.base.as_ref())
+    {
+        Ref::Branch(&base.ref_name)
+    } else {
+        return None;
+    };
+    match git_ref {
+        Ref::Branch(branch) => for_branch(mappings, branch),
+        Ref::Tag(tag) => lookup(mappings, |m| m.tag.as_deref(), tag),
+    }
+}
+
+// the first mapping whose `branch` pattern matches, e.g. `release/*`
+pub fn for_branch(mappings: &[EnvironmentMapping], branch: &str) -> Option<String> {
+    lookup(mappings, |m| m.branch.as_deref(), branch)
+}
+
+fn lookup(
+    mappings: &[EnvironmentMapping],
+    pattern: impl Fn(&EnvironmentMapping) -> Option<&str>,
+    name: &str,
+) -> Option<String> {
+    mappings
+        .iter()
+        .find(|mapping| {
+            pattern(mapping)
+                .and_then(|p| Glob::new(p).ok())
+                .is_some_and(|glob| glob.compile_matcher().is_match(name))
+        })
+        .map(|mapping| mapping.environment.clone())
+}
@@ -0,0 +1,231 @@
+// What a delivery can fail with, from reading and verifying the request to the handlers
+// and the stores and sinks behind them. Each error keeps its source, maps to a status
+// code, and is answered with a stable code:
+//
+//     {"error": {"code": "invalid_signature", "message": "invalid signature: ..."}}
+
+use axum::response::{IntoResponse, Response};
+use http::StatusCode;
+use serde::Serialize;
+use tower::BoxError;
+
+use crate::{
+    admin::AdminError, encoding::DecodeError, github::GithubError, queue::EnqueueError,
+    signature::SignatureError, store::StoreError,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum NexusError {
+    #[error("no x-hub-signature-256 header")]
+    MissingSignature,
+    #[error("invalid signature: {0}")]
+    Signature(#[from] SignatureError),
+    // the configured secret cannot key an HMAC
+    #[error("signatures cannot be computed with the configured secret")]
+    Verifier,
+    // a sender that signs differently, e.g. Linear
+    #[error("{0}")]
+    Unverified(&'static str),
+    #[error("the {0} header is not valid")]
+    InvalidHeader(&'static str),
+    #[error("unsupported Content-Encoding {0}")]
+    UnsupportedEncoding(String),
+    #[error("the body is larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("failed to read the body: {0}")]
+    Body(#[source] BoxError),
+    #[error("failed to decode the body: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("the form-encoded body has no payload field")]
+    MissingFormPayload,
+    #[error("invalid payload: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("the payload is not UTF-8: {0}")]
+    NotUtf8(#[source] std::str::Utf8Error),
+    // a payload that was parsed but cannot be written back out, e.g. for a script
+    #[error("failed to serialize the payload: {0}")]
+    Serialize(#[source] serde_json::Error),
+    // the spool that holds large bodies on disk
+    #[error("failed to spool the body: {0}")]
+    Spool(#[source] std::io::Error),
+    #[error("the delivery was not queued: {0}")]
+    Queue(#[from] EnqueueError),
+    // a handler `dispatch` ran; `source` is what it failed with
+    #[error("the {handler} handler failed: {source}")]
+    Handler {
+        handler: String,
+        #[source]
+        source: Box<NexusError>,
+    },
+    // more than one handler of a delivery failed; all of them ran
+    #[error("{} handlers failed: {}", .0.len(), list(.0))]
+    Handlers(Vec<NexusError>),
+    #[error(transparent)]
+    Github(#[from] GithubError),
+    #[error("storage failed: {0}")]
+    Storage(#[from] StoreError),
+    #[error("{sink} is unreachable: {source}")]
+    SinkUnreachable {
+        sink: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("{sink} returned {status}: {message}")]
+    SinkRejected {
+        sink: &'static str,
+        status: StatusCode,
+        message: String,
+    },
+    // the admin API answers nothing without `server.admin_token`
+    #[error("no admin token is configured")]
+    AdminDisabled,
+    #[error("missing or invalid admin token")]
+    Unauthorized,
+    #[error(transparent)]
+    Admin(#[from] AdminError),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    NotFound(String),
+    // a part of the server that is not running, e.g. the live feed
+    #[error("{0}")]
+    Unavailable(&'static str),
+    // sinks with their own clients and errors, e.g. Jira and Linear
+    #[error("{sink} failed: {source}")]
+    SinkFailed {
+        sink: &'static str,
+        #[source]
+        source: BoxError,
+    },
+}
+
+fn list(errors: &[NexusError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl NexusError {
+    // Ok when every result is, the one failure, or all of them
+    pub fn collect(results: impl IntoIterator<Item = Result<(), NexusError>>) -> Result<(), Self> {
+        let mut errors: Vec<NexusError> = results.into_iter().filter_map(Result::err).collect();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(NexusError::Handlers(errors)),
+        }
+    }
+
+    pub fn sink(sink: &'static str, source: impl Into<BoxError>) -> Self {
+        NexusError::SinkFailed {
+            sink,
+            source: source.into(),
+        }
+    }
+
+    pub fn handler(handler: &str, source: impl Into<NexusError>) -> Self {
+        NexusError::Handler {
+            handler: handler.to_string(),
+            source: Box::new(source.into()),
+        }
+    }
+
+    // stable across releases, unlike the messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            NexusError::MissingSignature => "missing_signature",
+            NexusError::Signature(_) | NexusError::Unverified(_) => "invalid_signature",
+            NexusError::Verifier => "verifier_unavailable",
+            NexusError::InvalidHeader(_) => "invalid_header",
+            NexusError::UnsupportedEncoding(_) => "unsupported_encoding",
+            NexusError::TooLarge(_) | NexusError::Decode(DecodeError::TooLarge(_)) => {
+                "body_too_large"
+            }
+            NexusError::Body(_) => "unreadable_body",
+            NexusError::Decode(DecodeError::Invalid(_)) => "invalid_encoding",
+            NexusError::MissingFormPayload => "missing_form_payload",
+            NexusError::Parse(_) | NexusError::NotUtf8(_) => "invalid_payload",
+            NexusError::Serialize(_) => "serialize_failed",
+            NexusError::Spool(_) => "spool_failed",
+            NexusError::Queue(_) => "queue_unavailable",
+            NexusError::Handler { .. } | NexusError::Handlers(_) => "handler_failed",
+            NexusError::Github(_) => "github_failed",
+            NexusError::Storage(_) => "storage_failed",
+            NexusError::AdminDisabled => "admin_disabled",
+            NexusError::Unauthorized => "unauthorized",
+            NexusError::Admin(e) => e.code(),
+            NexusError::InvalidRequest(_) => "invalid_request",
+            NexusError::NotFound(_) => "not_found",
+            NexusError::Unavailable(_) => "unavailable",
+            NexusError::SinkUnreachable { .. }
+            | NexusError::SinkRejected { .. }
+            | NexusError::SinkFailed { .. } => "sink_failed",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            NexusError::MissingSignature | NexusError::Signature(_) | NexusError::Unverified(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            NexusError::InvalidHeader(_)
+            | NexusError::Body(_)
+            | NexusError::Decode(DecodeError::Invalid(_))
+            | NexusError::MissingFormPayload
+            | NexusError::Parse(_)
+            | NexusError::NotUtf8(_) => StatusCode::BAD_REQUEST,
+            NexusError::UnsupportedEncoding(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            NexusError::TooLarge(_) | NexusError::Decode(DecodeError::TooLarge(_)) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            NexusError::Verifier
+            | NexusError::Spool(_)
+            | NexusError::Serialize(_)
+            | NexusError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            NexusError::Queue(_) | NexusError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            NexusError::AdminDisabled => StatusCode::FORBIDDEN,
+            NexusError::Unauthorized => StatusCode::UNAUTHORIZED,
+            NexusError::Admin(e) => e.status(),
+            NexusError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            NexusError::NotFound(_) => StatusCode::NOT_FOUND,
+            NexusError::Handler { source, .. } => source.status(),
+            // the first failure decides, as it would have before the others ran
+            NexusError::Handlers(errors) => errors
+                .first()
+                .map_or(StatusCode::INTERNAL_SERVER_ERROR, NexusError::status),
+            NexusError::Github(_)
+            | NexusError::SinkUnreachable { .. }
+            | NexusError::SinkRejected { .. }
+            | NexusError::SinkFailed { .. } => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.to_string(),
+            },
+        }
+    }
+}
+
+/// The JSON body of an error response.
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorDetail {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl IntoResponse for NexusError {
+    fn into_response(self) -> Response {
+        (self.status(), axum::Json(self.body())).into_response()
+    }
+}
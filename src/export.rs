@@ -0,0 +1,616 @@
+// Archived deliveries as Parquet files for data lakes, from `nexus export` or on the
+// `[export]` schedule. Files are partitioned Hive-style, e.g.
+//
+//     {dir}/date=2026-10-16/event_type=push/part-1792108800000000.parquet
+//
+// so engines that read such layouts (Spark, Trino, DuckDB, Athena) prune by date and event
+// without opening the files. The partition values are kept in the files too. The scheduled
+// export only writes what arrived since its last run, remembered in `{dir}/.nexus-export`;
+// the leading dot keeps query engines from reading it as data. Writing Parquet needs a
+// build with the `parquet` feature.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tracing::{error, info};
+
+use crate::{archive::Archived, config::ExportConfig, scheduler::Scheduler, service::AppState};
+
+// the last exported `received_at`, RFC 3339
+const WATERMARK: &str = ".nexus-export";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Parquet,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!("unknown format {}; expected parquet", s)),
+        }
+    }
+}
+
+/// A column of the exported files, in the order they are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    DeliveryId,
+    EventType,
+    Action,
+    // `owner/name`
+    Repository,
+    // the sender's login
+    Sender,
+    ReceivedAt,
+    // the JSON document as GitHub sent it
+    Payload,
+    // GitHub's headers as a JSON object
+    Headers,
+}
+
+impl Column {
+    pub const ALL: [Column; 8] = [
+        Column::DeliveryId,
+        Column::EventType,
+        Column::Action,
+        Column::Repository,
+        Column::Sender,
+        Column::ReceivedAt,
+        Column::Payload,
+        Column::Headers,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::DeliveryId => "delivery_id",
+            Column::EventType => "event_type",
+            Column::Action => "action",
+            Column::Repository => "repository",
+            Column::Sender => "sender",
+            Column::ReceivedAt => "received_at",
+            Column::Payload => "payload",
+            Column::Headers => "headers",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .into_iter()
+            .find(|c| c.name() == s)
+            .ok_or_else(|| format!("unknown column {}", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Partition {
+    // the UTC day the delivery was received, `date=2026-10-16`
+    Date,
+    EventType,
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date" => Ok(Partition::Date),
+            "event_type" => Ok(Partition::EventType),
+            _ => Err(format!(
+                "unknown partition {}; expected date or event_type",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Parquet(String),
+    // built without the `parquet` feature
+    Unsupported(ExportFormat),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Parquet(e) => write!(f, "failed to write Parquet: {}", e),
+            ExportError::Unsupported(format) => write!(
+                f,
+                "nexus was built without {:?} support; rebuild with `--features parquet`",
+                format
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+/// How deliveries are written.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub format: ExportFormat,
+    pub partition_by: Vec<Partition>,
+    // never empty; see `columns`
+    pub columns: Vec<Column>,
+}
+
+impl Options {
+    pub fn from_config(config: &ExportConfig) -> Self {
+        Self {
+            format: config.format,
+            partition_by: config.partition_by.clone(),
+            columns: columns(&config.columns, &config.exclude_columns),
+        }
+    }
+}
+
+// `include` in their usual order, or every column when it is empty, without `exclude`;
+// the event type is kept when nothing else would be left
+pub fn columns(include: &[Column], exclude: &[Column]) -> Vec<Column> {
+    let columns: Vec<Column> = Column::ALL
+        .into_iter()
+        .filter(|c| include.is_empty() || include.contains(c))
+        .filter(|c| !exclude.contains(c))
+        .collect();
+    if columns.is_empty() {
+        vec![Column::EventType]
+    } else {
+        columns
+    }
+}
+
+// the directory under `out` a delivery is written to
+fn partition(out: &Path, partition_by: &[Partition], delivery: &Archived) -> PathBuf {
+    partition_by
+        .iter()
+        .fold(out.to_path_buf(), |dir, partition| match partition {
+            Partition::Date => dir.join(format!(
+                "date={}",
+                delivery.job.received_at.format("%Y-%m-%d")
+            )),
+            Partition::EventType => {
+                dir.join(format!("event_type={}", sanitize(&delivery.job.event_type)))
+            }
+        })
+}
+
+// event types are GitHub's, but a path segment should not be able to climb out of `out`
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Writes `deliveries` under `out`, one file per partition, and returns the files written.
+pub async fn export(
+    deliveries: Vec<Archived>,
+    out: &Path,
+    options: &Options,
+) -> Result<Vec<PathBuf>, ExportError> {
+    if !parquet_writer::SUPPORTED {
+        return Err(ExportError::Unsupported(options.format));
+    }
+    let mut partitions: BTreeMap<PathBuf, Vec<Archived>> = BTreeMap::new();
+    for delivery in deliveries {
+        let dir = partition(out, &options.partition_by, &delivery);
+        partitions.entry(dir).or_default().push(delivery);
+    }
+    // files of different runs sit side by side in a partition
+    let name = format!("part-{}.parquet", Utc::now().timestamp_micros());
+    let options = options.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut written = Vec::with_capacity(partitions.len());
+        for (dir, deliveries) in partitions {
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join(&name);
+            write(&path, &deliveries, &options)?;
+            written.push(path);
+        }
+        Ok(written)
+    })
+    .await
+    .map_err(|e| ExportError::Io(io::Error::other(e)))?
+}
+
+// to a temporary file first, so readers never see a partial one
+fn write(path: &Path, deliveries: &[Archived], options: &Options) -> Result<(), ExportError> {
+    let tmp = path.with_extension("parquet.tmp");
+    let result = match options.format {
+        ExportFormat::Parquet => parquet_writer::write(&tmp, deliveries, &options.columns),
+    };
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampMicrosecondArray};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+    use std::{fs::File, path::Path, sync::Arc};
+
+    use super::{Column, ExportError};
+    use crate::archive::Archived;
+
+    pub(super) const SUPPORTED: bool = true;
+
+    fn field(column: Column) -> Field {
+        let nullable = !matches!(
+            column,
+            Column::EventType | Column::ReceivedAt | Column::Payload | Column::Headers
+        );
+        let data_type = match column {
+            Column::ReceivedAt => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            _ => DataType::Utf8,
+        };
+        Field::new(column.name(), data_type, nullable)
+    }
+
+    fn strings<'a>(
+        deliveries: &'a [Archived],
+        value: impl Fn(&'a Archived) -> Option<String>,
+    ) -> ArrayRef {
+        Arc::new(deliveries.iter().map(value).collect::<StringArray>())
+    }
+
+    fn array(column: Column, deliveries: &[Archived]) -> ArrayRef {
+        match column {
+            Column::DeliveryId => strings(deliveries, |d| d.job.delivery_id.clone()),
+            Column::EventType => strings(deliveries, |d| Some(d.job.event_type.clone())),
+            Column::Action => strings(deliveries, |d| d.job.payload.action.clone()),
+            Column::Repository => strings(deliveries, |d| {
+                d.job
+                    .payload
+                    .repository
+                    .as_ref()
+                    .map(|r| r.full_name.clone())
+            }),
+            Column::Sender => strings(deliveries, |d| {
+                d.job.payload.sender.as_ref().map(|s| s.login.clone())
+            }),
+            Column::ReceivedAt => Arc::new(
+                deliveries
+                    .iter()
+                    .map(|d| Some(d.job.received_at.timestamp_micros()))
+                    .collect::<TimestampMicrosecondArray>()
+                    .with_timezone("UTC"),
+            ),
+            Column::Payload => strings(deliveries, |d| {
+                Some(String::from_utf8_lossy(&d.job.json).into_owned())
+            }),
+            Column::Headers => strings(deliveries, |d| serde_json::to_string(&d.headers).ok()),
+        }
+    }
+
+    pub(super) fn write(
+        path: &Path,
+        deliveries: &[Archived],
+        columns: &[Column],
+    ) -> Result<(), ExportError> {
+        let schema = Arc::new(Schema::new(
+            columns.iter().map(|c| field(*c)).collect::<Vec<_>>(),
+        ));
+        let arrays = columns.iter().map(|c| array(*c, deliveries)).collect();
+        let batch = RecordBatch::try_new(schema.clone(), arrays)
+            .map_err(|e| ExportError::Parquet(e.to_string()))?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(path)?, schema, Some(properties))
+            .map_err(|e| ExportError::Parquet(e.to_string()))?;
+        writer
+            .write(&batch)
+            .and_then(|_| writer.close().map(|_| ()))
+            .map_err(|e| ExportError::Parquet(e.to_string()))
+    }
+}
+
+#[cfg(not(feature = "parquet"))]
+mod parquet_writer {
+    use std::path::Path;
+
+    use super::{Column, ExportError, ExportFormat};
+    use crate::archive::Archived;
+
+    pub(super) const SUPPORTED: bool = false;
+
+    pub(super) fn write(_: &Path, _: &[Archived], _: &[Column]) -> Result<(), ExportError> {
+        Err(ExportError::Unsupported(ExportFormat::Parquet))
+    }
+}
+
+async fn watermark(out: &Path) -> io::Result<Option<DateTime<Utc>>> {
+    match tokio::fs::read_to_string(out.join(WATERMARK)).await {
+        Ok(text) => DateTime::parse_from_rfc3339(text.trim())
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Exports what was archived since the last run and returns the files written.
+pub async fn since_last_run(
+    archive: &Path,
+    out: &Path,
+    options: &Options,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let since = watermark(out).await?;
+    let deliveries: Vec<Archived> = crate::archive::load(archive)
+        .await?
+        .into_iter()
+        .map(|(_, delivery)| delivery)
+        .filter(|delivery| since.is_none_or(|since| delivery.job.received_at > since))
+        .collect();
+    let Some(last) = deliveries.iter().map(|d| d.job.received_at).max() else {
+        return Ok(Vec::new());
+    };
+    let written = export(deliveries, out, options).await?;
+    tokio::fs::write(out.join(WATERMARK), last.to_rfc3339()).await?;
+    Ok(written)
+}
+
+pub fn schedule(
+    scheduler: &mut Scheduler,
+    config: &ExportConfig,
+) -> Result<(), cron::error::Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    scheduler.add(
+        "export",
+        &config.schedule,
+        |state: Arc<AppState>| async move {
+            run(&state).await;
+        },
+    )
+}
+
+async fn run(state: &AppState) {
+    let config = &state.config.export;
+    let (Some(archive), Some(out)) = (&state.config.archive.dir, &config.dir) else {
+        error!("The scheduled export needs archive.dir and export.dir");
+        return;
+    };
+    match since_last_run(archive, out, &Options::from_config(config)).await {
+        Ok(written) if written.is_empty() => info!("Nothing new to export"),
+        Ok(written) => info!("Exported {} files to {}", written.len(), out.display()),
+        Err(e) => error!("Failed to export archived deliveries: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{queue::Job, webhook::WebhookPayload};
+    use bytes::Bytes;
+    use chrono::TimeZone;
+
+    fn delivery(id: &str, event_type: &str, json: &'static str, day: u32) -> Archived {
+        Archived {
+            job: Job {
+                delivery_id: Some(id.to_string()),
+                event_type: event_type.to_string(),
+                payload: serde_json::from_str::<WebhookPayload>(json).unwrap(),
+                json: Bytes::from_static(json.as_bytes()),
+                received_at: Utc.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap(),
+                journal: None,
+            },
+            headers: BTreeMap::from([("x-github-event".to_string(), event_type.to_string())]),
+            body: Bytes::from_static(json.as_bytes()),
+        }
+    }
+
+    const ISSUE: &str = r#"{"action":"opened","sender":{"login":"octocat","html_url":"https://github.com/octocat"}}"#;
+    const PUSH: &str = r#"{"ref":"refs/heads/main"}"#;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nexus-export-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn prunes_columns() {
+        assert_eq!(columns(&[], &[]), Column::ALL);
+        assert_eq!(
+            columns(&[Column::Payload, Column::DeliveryId], &[]),
+            [Column::DeliveryId, Column::Payload]
+        );
+        assert_eq!(
+            columns(&[], &[Column::Payload, Column::Headers]),
+            Column::ALL[..6]
+        );
+        assert_eq!(
+            columns(&[Column::Payload], &[Column::Payload]),
+            [Column::EventType]
+        );
+        assert_eq!("received_at".parse::<Column>(), Ok(Column::ReceivedAt));
+        assert!("body".parse::<Column>().is_err());
+    }
+
+    #[test]
+    fn partitions_hive_style() {
+        let out = Path::new("/out");
+        let issue = delivery("1", "issues", ISSUE, 15);
+        assert_eq!(
+            partition(out, &[Partition::Date, Partition::EventType], &issue),
+            Path::new("/out/date=2026-10-15/event_type=issues")
+        );
+        assert_eq!(partition(out, &[], &issue), out);
+        let odd = delivery("2", "../x", PUSH, 15);
+        assert_eq!(
+            partition(out, &[Partition::EventType], &odd),
+            Path::new("/out/event_type=___x")
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    fn read(path: &Path) -> Vec<arrow_array::RecordBatch> {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn writes_one_file_per_partition_with_the_chosen_columns() {
+        use arrow_array::{Array, StringArray};
+
+        let out = temp_dir("parquet");
+        let options = Options {
+            format: ExportFormat::Parquet,
+            partition_by: vec![Partition::Date, Partition::EventType],
+            columns: columns(&[], &[Column::Headers]),
+        };
+        let deliveries = vec![
+            delivery("1", "issues", ISSUE, 15),
+            delivery("2", "push", PUSH, 15),
+            delivery("3", "issues", ISSUE, 16),
+            delivery("4", "issues", ISSUE, 16),
+        ];
+        let written = export(deliveries, &out, &options).await.unwrap();
+        assert_eq!(written.len(), 3);
+        assert!(written[2].starts_with(out.join("date=2026-10-16/event_type=issues")));
+
+        let batches = read(&written[2]);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "delivery_id",
+                "event_type",
+                "action",
+                "repository",
+                "sender",
+                "received_at",
+                "payload"
+            ]
+        );
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(column("delivery_id").value(0), "3");
+        assert_eq!(column("sender").value(1), "octocat");
+        assert!(column("repository").is_null(0));
+        assert_eq!(column("payload").value(0), ISSUE);
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn scheduled_exports_only_what_is_new() {
+        use crate::{redact::Redactor, webhook::RawBody};
+
+        let archive = temp_dir("archive");
+        let out = temp_dir("incremental");
+        let record = |id: &'static str| {
+            let archive = archive.clone();
+            async move {
+                let body = RawBody {
+                    received: Bytes::from_static(PUSH.as_bytes()),
+                    json: Bytes::from_static(PUSH.as_bytes()),
+                };
+                crate::archive::record(
+                    &archive,
+                    &Redactor::default(),
+                    &http::HeaderMap::new(),
+                    Some(id),
+                    "push",
+                    &body,
+                )
+                .await
+                .unwrap();
+            }
+        };
+        let options = Options {
+            format: ExportFormat::Parquet,
+            partition_by: Vec::new(),
+            columns: vec![Column::DeliveryId],
+        };
+
+        record("1").await;
+        let first = since_last_run(&archive, &out, &options).await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(
+            since_last_run(&archive, &out, &options)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        record("2").await;
+        let second = since_last_run(&archive, &out, &options).await.unwrap();
+        assert_eq!(second.len(), 1);
+        let batches = read(&second[0]);
+        assert_eq!(batches[0].num_rows(), 1);
+        std::fs::remove_dir_all(&archive).unwrap();
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+
+    #[cfg(not(feature = "parquet"))]
+    #[tokio::test]
+    async fn needs_the_parquet_feature() {
+        let out = temp_dir("unsupported");
+        let options = Options {
+            format: ExportFormat::Parquet,
+            partition_by: Vec::new(),
+            columns: Column::ALL.to_vec(),
+        };
+        let result = export(vec![delivery("1", "push", PUSH, 15)], &out, &options).await;
+        assert!(matches!(result, Err(ExportError::Unsupported(_))));
+        assert!(!out.exists());
+    }
+}
@@ -0,0 +1,69 @@
+// a live feed of received deliveries, served as server-sent events on /admin/events
+// for `nexus tail`
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::webhook::WebhookPayload;
+
+// subscribers that fall further behind than this miss events
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FeedEvent {
+    pub delivery_id: Option<String>,
+    pub event_type: String,
+    pub action: Option<String>,
+    pub repository: Option<String>,
+    pub sender: Option<String>,
+    pub received_at: DateTime<Utc>,
+}
+
+impl FeedEvent {
+    pub fn new(event_type: &str, delivery_id: Option<&str>, payload: &WebhookPayload) -> Self {
+        FeedEvent {
+            delivery_id: delivery_id.map(String::from),
+            event_type: event_type.to_string(),
+            action: payload.action.clone(),
+            repository: payload.repository.as_ref().map(|r| r.full_name.clone()),
+            sender: payload.sender.as_ref().map(|s| s.login.clone()),
+            received_at: Utc::now(),
+        }
+    }
+}
+
+/// Fans received deliveries out to whoever is watching; publishing without
+/// subscribers is free.
+#[derive(Clone)]
+pub struct Feed {
+    // taken on shutdown so open streams end instead of holding connections open
+    sender: Arc<Mutex<Option<broadcast::Sender<FeedEvent>>>>,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Feed {
+            sender: Arc::new(Mutex::new(Some(broadcast::channel(CAPACITY).0))),
+        }
+    }
+}
+
+impl Feed {
+    pub fn publish(&self, event: FeedEvent) {
+        if let Some(sender) = &*self.sender.lock().unwrap() {
+            // fails only when nobody is subscribed
+            let _ = sender.send(event);
+        }
+    }
+
+    // None once the feed is closed
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<FeedEvent>> {
+        self.sender.lock().unwrap().as_ref().map(|s| s.subscribe())
+    }
+
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
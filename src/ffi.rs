@@ -0,0 +1,242 @@
+// A C ABI over the blocking API, so C and C++ services verify and parse deliveries
+// exactly as nexus does. Built as a shared or static library with the `ffi` feature;
+// include/nexus.h is generated from this file by cbindgen (see cbindgen.toml).
+//
+// Strings are NUL-terminated UTF-8. Events are opaque and owned by the caller until
+// `nexus_event_free`; the strings they return live as long as the event.
+
+use std::{
+    ffi::{CStr, CString, c_char},
+    ptr, slice,
+};
+
+use crate::{
+    blocking::{self, Delivery},
+    signature::SignatureError,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NexusStatus {
+    Ok = 0,
+    // a NULL pointer, or a string that is not UTF-8
+    InvalidArgument = 1,
+    // the signature does not start with `sha256=`
+    WrongPrefix = 2,
+    InvalidHex = 3,
+    // well formed, but computed over a different body or with a different secret
+    Mismatch = 4,
+    InvalidPayload = 5,
+}
+
+impl From<blocking::Error> for NexusStatus {
+    fn from(e: blocking::Error) -> Self {
+        match e {
+            blocking::Error::Signature(SignatureError::WrongPrefix) => NexusStatus::WrongPrefix,
+            blocking::Error::Signature(SignatureError::InvalidHex) => NexusStatus::InvalidHex,
+            blocking::Error::Signature(SignatureError::Mismatch) => NexusStatus::Mismatch,
+            blocking::Error::Parse(_) => NexusStatus::InvalidPayload,
+            blocking::Error::MissingEvent
+            | blocking::Error::MissingSignature
+            | blocking::Error::Io(_) => NexusStatus::InvalidArgument,
+        }
+    }
+}
+
+/// A parsed delivery, created by `nexus_parse_event`.
+pub struct NexusEvent {
+    delivery: Delivery,
+    event_type: CString,
+    action: Option<CString>,
+    repository: Option<CString>,
+    sender: Option<CString>,
+    payload_json: CString,
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+unsafe fn bytes_arg<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(unsafe { slice::from_raw_parts(data, len) }),
+    }
+}
+
+// strings with an interior NUL cannot cross the ABI and come back as NULL
+fn c_string(s: Option<&str>) -> Option<CString> {
+    s.and_then(|s| CString::new(s).ok())
+}
+
+fn c_ptr(s: &Option<CString>) -> *const c_char {
+    s.as_ref().map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// A static description of `status`, e.g. for logs.
+#[unsafe(no_mangle)]
+pub extern "C" fn nexus_status_message(status: NexusStatus) -> *const c_char {
+    let message: &'static CStr = match status {
+        NexusStatus::Ok => c"ok",
+        NexusStatus::InvalidArgument => c"a NULL or non-UTF-8 argument",
+        NexusStatus::WrongPrefix => c"the signature does not start with sha256=",
+        NexusStatus::InvalidHex => c"the signature is not valid hex",
+        NexusStatus::Mismatch => c"the signature does not match the payload",
+        NexusStatus::InvalidPayload => c"the body is not a webhook payload",
+    };
+    message.as_ptr()
+}
+
+/// Checks an `x-hub-signature-256` value against `body_len` bytes of `body`.
+///
+/// # Safety
+///
+/// `secret` and `signature` must be NUL-terminated strings and `body` must point to at
+/// least `body_len` readable bytes (it may be NULL when `body_len` is 0).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_verify_signature(
+    secret: *const c_char,
+    body: *const u8,
+    body_len: usize,
+    signature: *const c_char,
+) -> NexusStatus {
+    let (secret, body, signature) = unsafe {
+        (
+            str_arg(secret),
+            bytes_arg(body, body_len),
+            str_arg(signature),
+        )
+    };
+    let (Some(secret), Some(body), Some(signature)) = (secret, body, signature) else {
+        return NexusStatus::InvalidArgument;
+    };
+    match blocking::verify(secret, body, signature) {
+        Ok(()) => NexusStatus::Ok,
+        Err(e) => e.into(),
+    }
+}
+
+/// Parses a delivery's body; on success `*out` holds an event to release with
+/// `nexus_event_free`. The signature is not checked, see `nexus_verify_signature`.
+///
+/// # Safety
+///
+/// `event_type` (the `x-github-event` header) must be a NUL-terminated string, `body`
+/// must point to at least `body_len` readable bytes and `out` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_parse_event(
+    event_type: *const c_char,
+    body: *const u8,
+    body_len: usize,
+    out: *mut *mut NexusEvent,
+) -> NexusStatus {
+    if out.is_null() {
+        return NexusStatus::InvalidArgument;
+    }
+    let (event_type, body) = unsafe { (str_arg(event_type), bytes_arg(body, body_len)) };
+    let (Some(event_type), Some(body)) = (event_type, body) else {
+        return NexusStatus::InvalidArgument;
+    };
+    let delivery = match Delivery::from_parts(None, event_type, None, None, body) {
+        Ok(delivery) => delivery,
+        Err(e) => return e.into(),
+    };
+    let Some(event_type) = c_string(Some(event_type)) else {
+        return NexusStatus::InvalidArgument;
+    };
+    // serialized JSON escapes NUL, so this cannot fail
+    let payload_json =
+        c_string(serde_json::to_string(&delivery.payload).ok().as_deref()).unwrap_or_default();
+    let event = NexusEvent {
+        event_type,
+        action: c_string(delivery.action()),
+        repository: c_string(delivery.repository()),
+        sender: c_string(delivery.sender()),
+        payload_json,
+        delivery,
+    };
+    unsafe { *out = Box::into_raw(Box::new(event)) };
+    NexusStatus::Ok
+}
+
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_type(event: *const NexusEvent) -> *const c_char {
+    unsafe { &*event }.event_type.as_ptr()
+}
+
+/// NULL when the event has no action.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_action(event: *const NexusEvent) -> *const c_char {
+    c_ptr(&unsafe { &*event }.action)
+}
+
+/// The repository's `owner/name`, NULL when the event has none.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_repository(event: *const NexusEvent) -> *const c_char {
+    c_ptr(&unsafe { &*event }.repository)
+}
+
+/// The sender's login, NULL when the event has none.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_sender(event: *const NexusEvent) -> *const c_char {
+    c_ptr(&unsafe { &*event }.sender)
+}
+
+/// The payload as JSON, holding the fields nexus models: unknown fields are dropped and
+/// missing ones are null.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_payload_json(event: *const NexusEvent) -> *const c_char {
+    unsafe { &*event }.payload_json.as_ptr()
+}
+
+/// Whether the event matches `event` or `event.action`, e.g. `pull_request.opened`.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed, and `pattern` must
+/// be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_matches(
+    event: *const NexusEvent,
+    pattern: *const c_char,
+) -> bool {
+    let Some(pattern) = (unsafe { str_arg(pattern) }) else {
+        return false;
+    };
+    unsafe { &*event }.delivery.matches(pattern)
+}
+
+/// Releases an event; NULL is ignored.
+///
+/// # Safety
+///
+/// `event` must come from `nexus_parse_event` and not have been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn nexus_event_free(event: *mut NexusEvent) {
+    if !event.is_null() {
+        drop(unsafe { Box::from_raw(event) });
+    }
+}
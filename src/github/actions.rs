@@ -0,0 +1,93 @@
+use serde::Deserialize;
+
+use super::{GithubClient, GithubError};
+use crate::webhook::WorkflowRun;
+
+#[derive(Debug, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub name: String,
+    pub status: String,
+    pub conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobList {
+    jobs: Vec<Job>,
+}
+
+// only the jobs from the run's latest attempt
+pub async fn list_run_jobs(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    run_id: u64,
+) -> Result<Vec<Job>, GithubError> {
+    let list: JobList = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!(
+                "/repos/{}/actions/runs/{}/jobs?filter=latest&per_page=100",
+                repo, run_id
+            ),
+        ))
+        .await?;
+    Ok(list.jobs)
+}
+
+// GitHub answers with a redirect to a short-lived download URL, which reqwest follows
+pub async fn get_job_logs(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    job_id: u64,
+) -> Result<String, GithubError> {
+    Ok(client
+        .send(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/actions/jobs/{}/logs", repo, job_id),
+        ))
+        .await?
+        .text()
+        .await?)
+}
+
+pub async fn rerun_failed_jobs(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    run_id: u64,
+) -> Result<(), GithubError> {
+    client
+        .send(client.request(
+            reqwest::Method::POST,
+            token,
+            &format!("/repos/{}/actions/runs/{}/rerun-failed-jobs", repo, run_id),
+        ))
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RunList {
+    workflow_runs: Vec<WorkflowRun>,
+}
+
+// the latest attempt of every workflow run for a commit
+pub async fn list_runs_for_commit(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    sha: &str,
+) -> Result<Vec<WorkflowRun>, GithubError> {
+    let list: RunList = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/actions/runs?head_sha={}&per_page=100", repo, sha),
+        ))
+        .await?;
+    Ok(list.workflow_runs)
+}
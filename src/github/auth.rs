@@ -0,0 +1,167 @@
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, io, path::PathBuf};
+use tokio::sync::Mutex;
+
+use super::{GithubClient, GithubError};
+use crate::config::GitHubConfig;
+
+// GitHub rejects app JWTs that are valid for more than ten minutes
+const JWT_LIFETIME_SECS: i64 = 9 * 60;
+// installation tokens live for an hour; refresh them a little early
+const REFRESH_MARGIN_SECS: i64 = 5 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    // the private key file named in the config
+    #[error("cannot read {}: {source}", path.display())]
+    KeyFile { path: PathBuf, source: io::Error },
+    #[error("invalid app private key: {0}")]
+    Key(#[from] jsonwebtoken::errors::Error),
+    #[error("{0}")]
+    Github(#[from] GithubError),
+}
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+/// Credentials for acting as a GitHub App: signs app JWTs and exchanges them for
+/// per-installation access tokens, which are cached until shortly before they expire.
+pub struct AppAuth {
+    app_id: u64,
+    key: EncodingKey,
+    tokens: Mutex<HashMap<u64, InstallationToken>>,
+    installations: Mutex<HashMap<String, u64>>,
+}
+
+impl fmt::Debug for AppAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppAuth")
+            .field("app_id", &self.app_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AppAuth {
+    pub fn new(app_id: u64, private_key_pem: &[u8]) -> Result<Self, AuthError> {
+        Ok(Self {
+            app_id,
+            key: EncodingKey::from_rsa_pem(private_key_pem)?,
+            tokens: Mutex::default(),
+            installations: Mutex::default(),
+        })
+    }
+
+    // `None` unless both the app id and the private key path are configured
+    pub fn from_config(config: &GitHubConfig) -> Result<Option<Self>, AuthError> {
+        let (Some(app_id), Some(path)) = (config.app_id, &config.private_key_path) else {
+            return Ok(None);
+        };
+        let pem = std::fs::read(path).map_err(|e| AuthError::KeyFile {
+            path: path.clone(),
+            source: e,
+        })?;
+        Ok(Some(Self::new(app_id, &pem)?))
+    }
+
+    pub fn app_id(&self) -> u64 {
+        self.app_id
+    }
+
+    pub fn jwt(&self) -> Result<String, AuthError> {
+        let now = Utc::now().timestamp();
+        // backdated to tolerate clock drift between us and GitHub
+        let claims = Claims {
+            iat: now - 60,
+            exp: now + JWT_LIFETIME_SECS,
+            iss: self.app_id.to_string(),
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &claims,
+            &self.key,
+        )?)
+    }
+
+    // the lock is held across the exchange so concurrent deliveries share one refresh
+    pub async fn installation_token(
+        &self,
+        client: &GithubClient,
+        installation_id: u64,
+    ) -> Result<String, AuthError> {
+        let mut tokens = self.tokens.lock().await;
+        if let Some(cached) = tokens.get(&installation_id)
+            && cached.expires_at - Utc::now() > Duration::seconds(REFRESH_MARGIN_SECS)
+        {
+            return Ok(cached.token.clone());
+        }
+
+        let fresh: InstallationToken = client
+            .send_json(client.request(
+                reqwest::Method::POST,
+                &self.jwt()?,
+                &format!("/app/installations/{}/access_tokens", installation_id),
+            ))
+            .await?;
+
+        let token = fresh.token.clone();
+        tokens.insert(installation_id, fresh);
+        Ok(token)
+    }
+
+    // webhook deliveries carry the installation id, which saves a lookup later
+    pub async fn remember_installation(&self, repo: &str, installation_id: u64) {
+        self.installations
+            .lock()
+            .await
+            .insert(repo.to_string(), installation_id);
+    }
+
+    pub async fn repo_installation_id(
+        &self,
+        client: &GithubClient,
+        repo: &str,
+    ) -> Result<u64, AuthError> {
+        if let Some(id) = self.installations.lock().await.get(repo) {
+            return Ok(*id);
+        }
+
+        // an owner without a repository name asks for the org's installation
+        let path = if repo.contains('/') {
+            format!("/repos/{}/installation", repo)
+        } else {
+            format!("/orgs/{}/installation", repo)
+        };
+        let installation: Installation = client
+            .send_json(client.request(reqwest::Method::GET, &self.jwt()?, &path))
+            .await?;
+
+        self.remember_installation(repo, installation.id).await;
+        Ok(installation.id)
+    }
+
+    pub async fn token_for_repo(
+        &self,
+        client: &GithubClient,
+        repo: &str,
+    ) -> Result<String, AuthError> {
+        let installation_id = self.repo_installation_id(client, repo).await?;
+        self.installation_token(client, installation_id).await
+    }
+}
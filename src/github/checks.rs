@@ -0,0 +1,411 @@
+use serde::{Deserialize, Serialize};
+
+use super::{GithubClient, GithubError};
+use crate::config::ReportKind;
+
+// GitHub rejects more than 50 annotations in a single request
+const MAX_ANNOTATIONS_PER_REQUEST: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusState {
+    Error,
+    Failure,
+    Pending,
+    Success,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitStatus {
+    pub state: StatusState,
+    pub context: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+}
+
+impl CommitStatus {
+    pub fn new(context: impl Into<String>, state: StatusState) -> Self {
+        Self {
+            state,
+            context: context.into(),
+            description: None,
+            target_url: None,
+        }
+    }
+
+    // GitHub rejects descriptions longer than 140 characters
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        let description: String = description.into();
+        self.description = Some(description.chars().take(140).collect());
+        self
+    }
+
+    pub fn target_url(mut self, url: impl Into<String>) -> Self {
+        self.target_url = Some(url.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Queued,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckConclusion {
+    ActionRequired,
+    Cancelled,
+    Failure,
+    Neutral,
+    Success,
+    Skipped,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Annotation {
+    pub path: String,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub annotation_level: AnnotationLevel,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl Annotation {
+    pub fn new(
+        path: impl Into<String>,
+        line: u32,
+        level: AnnotationLevel,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            start_line: line,
+            end_line: line,
+            annotation_level: level,
+            message: message.into(),
+            title: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckOutput {
+    pub title: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckRun {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub head_sha: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<CheckStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<CheckConclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckOutput>,
+}
+
+impl CheckRun {
+    pub fn in_progress(name: impl Into<String>, head_sha: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            head_sha: head_sha.into(),
+            status: Some(CheckStatus::InProgress),
+            ..Default::default()
+        }
+    }
+
+    pub fn completed(
+        name: impl Into<String>,
+        head_sha: impl Into<String>,
+        conclusion: CheckConclusion,
+        output: CheckOutput,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            head_sha: head_sha.into(),
+            status: Some(CheckStatus::Completed),
+            conclusion: Some(conclusion),
+            output: Some(output),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckRunCreated {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckRunSummary {
+    pub id: u64,
+    pub name: String,
+    pub status: CheckStatus,
+    pub conclusion: Option<CheckConclusion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckRunList {
+    total_count: usize,
+    check_runs: Vec<CheckRunSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusSummary {
+    pub context: String,
+    pub state: StatusState,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CombinedStatus {
+    pub state: StatusState,
+    pub statuses: Vec<StatusSummary>,
+}
+
+pub async fn create_commit_status(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    sha: &str,
+    status: &CommitStatus,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/statuses/{}", repo, sha),
+                )
+                .json(status),
+        )
+        .await?;
+    Ok(())
+}
+
+// check runs can only be created with GitHub App credentials, not personal access tokens
+pub async fn create_check_run(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    check_run: &CheckRun,
+) -> Result<u64, GithubError> {
+    let (first, rest) = split_annotations(check_run);
+
+    let created: CheckRunCreated = client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/check-runs", repo),
+                )
+                .json(&first),
+        )
+        .await?;
+
+    append_annotations(client, token, repo, created.id, check_run, rest).await?;
+    Ok(created.id)
+}
+
+pub async fn update_check_run(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    check_run_id: u64,
+    check_run: &CheckRun,
+) -> Result<(), GithubError> {
+    let (first, rest) = split_annotations(check_run);
+
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/check-runs/{}", repo, check_run_id),
+                )
+                .json(&first),
+        )
+        .await?;
+
+    append_annotations(client, token, repo, check_run_id, check_run, rest).await
+}
+
+fn split_annotations(check_run: &CheckRun) -> (CheckRun, Vec<Annotation>) {
+    let mut first = check_run.clone();
+    let mut rest = Vec::new();
+    if let Some(output) = &mut first.output
+        && output.annotations.len() > MAX_ANNOTATIONS_PER_REQUEST
+    {
+        rest = output.annotations.split_off(MAX_ANNOTATIONS_PER_REQUEST);
+    }
+    (first, rest)
+}
+
+// annotations beyond the first batch are appended by re-sending the output
+async fn append_annotations(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    check_run_id: u64,
+    check_run: &CheckRun,
+    rest: Vec<Annotation>,
+) -> Result<(), GithubError> {
+    let Some(output) = &check_run.output else {
+        return Ok(());
+    };
+
+    for chunk in rest.chunks(MAX_ANNOTATIONS_PER_REQUEST) {
+        let update = CheckRun {
+            output: Some(CheckOutput {
+                title: output.title.clone(),
+                summary: output.summary.clone(),
+                text: output.text.clone(),
+                annotations: chunk.to_vec(),
+            }),
+            ..Default::default()
+        };
+
+        client
+            .send(
+                client
+                    .request(
+                        reqwest::Method::PATCH,
+                        token,
+                        &format!("/repos/{}/check-runs/{}", repo, check_run_id),
+                    )
+                    .json(&update),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn list_check_runs(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    git_ref: &str,
+) -> Result<Vec<CheckRunSummary>, GithubError> {
+    // the runs are wrapped in an object, so `get_paginated` does not apply
+    let mut runs = Vec::new();
+    for page in 1.. {
+        let list: CheckRunList = client
+            .send_json(client.request(
+                reqwest::Method::GET,
+                token,
+                &format!(
+                    "/repos/{}/commits/{}/check-runs?per_page=100&page={}",
+                    repo, git_ref, page
+                ),
+            ))
+            .await?;
+        let done = list.check_runs.len() < 100;
+        runs.extend(list.check_runs);
+        if done || runs.len() >= list.total_count {
+            break;
+        }
+    }
+    Ok(runs)
+}
+
+pub async fn get_combined_status(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    git_ref: &str,
+) -> Result<CombinedStatus, GithubError> {
+    client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/commits/{}/status", repo, git_ref),
+        ))
+        .await
+}
+
+/// A pass or fail result for a commit, for checks that can report either way.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    pub passed: bool,
+    // the status description, and the check run title
+    pub title: String,
+    // check runs only, Markdown
+    pub summary: String,
+    pub text: Option<String>,
+    pub details_url: Option<String>,
+}
+
+pub async fn report(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    sha: &str,
+    name: &str,
+    kind: ReportKind,
+    verdict: Verdict,
+) -> Result<(), GithubError> {
+    match kind {
+        ReportKind::Status => {
+            let state = if verdict.passed {
+                StatusState::Success
+            } else {
+                StatusState::Failure
+            };
+            let mut status = CommitStatus::new(name, state).description(verdict.title);
+            if let Some(url) = verdict.details_url {
+                status = status.target_url(url);
+            }
+            create_commit_status(client, token, repo, sha, &status).await
+        }
+        ReportKind::CheckRun => {
+            let conclusion = if verdict.passed {
+                CheckConclusion::Success
+            } else {
+                CheckConclusion::Failure
+            };
+            let output = CheckOutput {
+                title: verdict.title,
+                summary: verdict.summary,
+                text: verdict.text,
+                ..Default::default()
+            };
+            let mut run = CheckRun::completed(name, sha, conclusion, output);
+            run.details_url = verdict.details_url;
+            create_check_run(client, token, repo, &run)
+                .await
+                .map(|_| ())
+        }
+    }
+}
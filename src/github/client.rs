@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, StatusCode};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::{Config, proxy};
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+const USER_AGENT: &str = concat!("nexus/", env!("CARGO_PKG_VERSION"));
+
+const MAX_RETRIES: u32 = 3;
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+// rate limit waits longer than this are surfaced as errors instead of stalling the caller
+const MAX_WAIT: Duration = Duration::from_secs(60);
+const LOW_REMAINING_WARNING: u64 = 100;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GithubError {
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    #[error("GitHub API returned {status}: {message}")]
+    Api { status: StatusCode, message: String },
+    #[error("GitHub rate limit exceeded{}", until(.reset))]
+    RateLimited { reset: Option<DateTime<Utc>> },
+    #[error("{}", graphql(.messages))]
+    GraphQl { messages: Vec<String> },
+}
+
+fn until(reset: &Option<DateTime<Utc>>) -> String {
+    reset
+        .map(|reset| format!(" until {}", reset))
+        .unwrap_or_default()
+}
+
+fn graphql(messages: &[String]) -> String {
+    match messages.is_empty() {
+        true => "GitHub GraphQL query returned no data".to_string(),
+        false => format!("GitHub GraphQL errors: {}", messages.join("; ")),
+    }
+}
+
+impl GithubError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            GithubError::Http(e) => e.status(),
+            GithubError::Api { status, .. } => Some(*status),
+            GithubError::RateLimited { .. } | GithubError::GraphQl { .. } => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+}
+
+// a plain client with the proxy and extra trust roots, for requests outside the REST helpers
+pub fn http_client(config: &Config) -> Result<reqwest::Client, String> {
+    let mut builder = proxy::builder(&config.proxy)?;
+    for path in &config.github.ca_certs {
+        let pem =
+            std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("invalid certificate in {}: {}", path.display(), e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| format!("failed to build the GitHub client: {}", e))
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
+
+/// A `reqwest::Client` that speaks the GitHub REST API: adds the auth and version
+/// headers, waits out rate limits (retrying secondary limits with backoff), and turns
+/// error responses into `GithubError`s.
+#[derive(Debug, Clone)]
+pub struct GithubClient {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Default for GithubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GithubClient {
+    pub fn new() -> Self {
+        Self::with_http_client(reqwest::Client::new())
+    }
+
+    pub fn with_http_client(http: reqwest::Client) -> Self {
+        Self {
+            http,
+            base_url: GITHUB_API_URL.to_string(),
+        }
+    }
+
+    // e.g. `https://github.example.com/api/v3` for GitHub Enterprise Server
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    // `github.api_url`, over a client that also trusts `github.ca_certs`
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        Ok(Self::with_http_client(http_client(config)?).base_url(config.github.api_url.as_str()))
+    }
+
+    // REST lives under `/api/v3` on GitHub Enterprise Server, GraphQL at `/api/graphql`
+    fn url(&self, path: &str) -> String {
+        match self.base_url.strip_suffix("/v3") {
+            Some(api) if path == "/graphql" && api.ends_with("/api") => format!("{}{}", api, path),
+            _ => format!("{}{}", self.base_url, path),
+        }
+    }
+
+    pub(crate) fn request(
+        &self,
+        method: reqwest::Method,
+        token: &str,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        self.request_with_accept(method, token, path, "application/vnd.github+json")
+    }
+
+    pub(crate) fn request_with_accept(
+        &self,
+        method: reqwest::Method,
+        token: &str,
+        path: &str,
+        accept: &str,
+    ) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, self.url(path))
+            .bearer_auth(token)
+            .header("accept", accept)
+            .header("x-github-api-version", "2022-11-28")
+            .header("user-agent", USER_AGENT)
+    }
+
+    pub(crate) async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, GithubError> {
+        let mut attempt = 0;
+
+        loop {
+            // every request body in this crate is buffered JSON, so cloning cannot fail
+            let response = request
+                .try_clone()
+                .expect("GitHub requests have cloneable bodies")
+                .send()
+                .await?;
+
+            let status = response.status();
+            let headers = response.headers().clone();
+            log_remaining(&headers);
+            if status.is_success() {
+                return Ok(response);
+            }
+
+            let message = error_message(response).await;
+            if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+                return Err(GithubError::Api { status, message });
+            }
+
+            let retry_after = header_u64(&headers, "retry-after").map(Duration::from_secs);
+            let reset = header_u64(&headers, "x-ratelimit-reset")
+                .and_then(|secs| DateTime::from_timestamp(secs as i64, 0));
+            let secondary =
+                retry_after.is_some() || message.to_lowercase().contains("secondary rate limit");
+            let exhausted = header_u64(&headers, "x-ratelimit-remaining") == Some(0);
+
+            let wait = if secondary {
+                retry_after.unwrap_or(BASE_BACKOFF * 2u32.pow(attempt))
+            } else if exhausted {
+                reset
+                    .and_then(|reset| (reset - Utc::now()).to_std().ok())
+                    .unwrap_or_default()
+            } else {
+                // a plain permission error
+                return Err(GithubError::Api { status, message });
+            };
+
+            if attempt >= MAX_RETRIES || wait > MAX_WAIT {
+                return Err(GithubError::RateLimited {
+                    reset: reset.or_else(|| {
+                        chrono::Duration::from_std(wait)
+                            .ok()
+                            .map(|wait| Utc::now() + wait)
+                    }),
+                });
+            }
+
+            warn!(
+                "GitHub rate limit hit ({}), retrying in {}s",
+                if secondary { "secondary" } else { "primary" },
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    pub(crate) async fn send_json<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, GithubError> {
+        Ok(self.send(request).await?.json().await?)
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn log_remaining(headers: &HeaderMap) {
+    if let Some(remaining) = header_u64(headers, "x-ratelimit-remaining") {
+        if remaining < LOW_REMAINING_WARNING {
+            warn!("GitHub rate limit running low: {} requests left", remaining);
+        } else {
+            debug!("GitHub rate limit: {} requests left", remaining);
+        }
+    }
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    let text = response.text().await.unwrap_or_default();
+    serde_json::from_str::<ErrorBody>(&text)
+        .map(|body| body.message)
+        .unwrap_or(text)
+}
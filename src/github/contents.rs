@@ -0,0 +1,195 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{GithubClient, GithubError};
+
+// a file as the contents API returns it; `sha` is needed to update it
+#[derive(Debug, Clone)]
+pub struct File {
+    pub sha: String,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+struct ContentsResponse {
+    sha: String,
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct RefResponse {
+    object: RefObject,
+}
+
+#[derive(Deserialize)]
+struct RefObject {
+    sha: String,
+}
+
+pub async fn get_file(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    path: &str,
+    branch: &str,
+) -> Result<Option<File>, GithubError> {
+    let response: Result<ContentsResponse, _> = client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::GET,
+                    token,
+                    &format!("/repos/{}/contents/{}", repo, path),
+                )
+                .query(&[("ref", branch)]),
+        )
+        .await;
+    match response {
+        Ok(file) => {
+            // base64 with a line break every 60 characters
+            let encoded: String = file.content.split_whitespace().collect();
+            let content = STANDARD
+                .decode(encoded)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .unwrap_or_default();
+            Ok(Some(File {
+                sha: file.sha,
+                content,
+            }))
+        }
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub struct FileUpdate<'a> {
+    pub branch: &'a str,
+    pub message: &'a str,
+    pub content: &'a str,
+    // the blob being replaced; None creates the file
+    pub sha: Option<&'a str>,
+}
+
+pub async fn put_file(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    path: &str,
+    update: &FileUpdate<'_>,
+) -> Result<(), GithubError> {
+    let mut body = json!({
+        "message": update.message,
+        "content": STANDARD.encode(update.content),
+        "branch": update.branch,
+    });
+    if let Some(sha) = update.sha {
+        body["sha"] = json!(sha);
+    }
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PUT,
+                    token,
+                    &format!("/repos/{}/contents/{}", repo, path),
+                )
+                .json(&body),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_branch_sha(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+) -> Result<String, GithubError> {
+    let response: RefResponse = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/git/ref/heads/{}", repo, branch),
+        ))
+        .await?;
+    Ok(response.object.sha)
+}
+
+pub async fn create_branch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+    sha: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/git/refs", repo),
+                )
+                .json(&json!({ "ref": format!("refs/heads/{}", branch), "sha": sha })),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Branch {
+    pub name: String,
+    #[serde(default)]
+    pub protected: bool,
+    pub commit: BranchCommit,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BranchCommit {
+    pub sha: String,
+}
+
+pub async fn get_branch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+) -> Result<Option<Branch>, GithubError> {
+    let response = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/branches/{}", repo, branch),
+        ))
+        .await;
+    match response {
+        Ok(branch) => Ok(Some(branch)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// a branch that is already gone counts as deleted
+pub async fn delete_branch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+) -> Result<(), GithubError> {
+    let response = client
+        .send(client.request(
+            reqwest::Method::DELETE,
+            token,
+            &format!("/repos/{}/git/refs/heads/{}", repo, branch),
+        ))
+        .await;
+    match response {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_not_found() => Ok(()),
+        Err(GithubError::Api { status, .. }) if status.as_u16() == 422 => Ok(()),
+        Err(e) => Err(e),
+    }
+}
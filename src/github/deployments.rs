@@ -0,0 +1,24 @@
+use super::{GithubClient, GithubError};
+
+pub use crate::models::{DeploymentState, DeploymentStatus};
+
+pub async fn create_deployment_status(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    deployment_id: u64,
+    status: &DeploymentStatus,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/deployments/{}/statuses", repo, deployment_id),
+                )
+                .json(status),
+        )
+        .await?;
+    Ok(())
+}
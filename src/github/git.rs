@@ -0,0 +1,177 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{GithubClient, GithubError};
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub message: String,
+    pub tree: Object,
+    pub parents: Vec<Object>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Verification {
+    pub verified: bool,
+    // `valid`, `unsigned`, `unknown_key`, `bad_email`, ...
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Object {
+    pub sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Created {
+    sha: String,
+}
+
+// the outcome of applying one commit on top of a branch
+#[derive(Debug)]
+pub enum Picked {
+    // the branch's new head
+    Applied(String),
+    // nothing left to apply, e.g. the change is already on the branch
+    Empty,
+    Conflict,
+}
+
+pub async fn get_commit(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    sha: &str,
+) -> Result<Commit, GithubError> {
+    client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/git/commits/{}", repo, sha),
+        ))
+        .await
+}
+
+pub async fn create_commit(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    message: &str,
+    tree: &str,
+    parents: &[&str],
+) -> Result<String, GithubError> {
+    let created: Created = client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/git/commits", repo),
+                )
+                .json(&json!({ "message": message, "tree": tree, "parents": parents })),
+        )
+        .await?;
+    Ok(created.sha)
+}
+
+pub async fn update_branch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+    sha: &str,
+    force: bool,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/git/refs/heads/{}", repo, branch),
+                )
+                .json(&json!({ "sha": sha, "force": force })),
+        )
+        .await?;
+    Ok(())
+}
+
+// the merges API on a branch; None when there was nothing to merge, and a 409 on conflict
+async fn merge(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+    head: &str,
+) -> Result<Option<String>, GithubError> {
+    let response = client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/merges", repo),
+                )
+                .json(&json!({ "base": branch, "head": head })),
+        )
+        .await?;
+    if response.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+    let created: Created = response.json().await?;
+    Ok(Some(created.sha))
+}
+
+// Applies `sha` on top of `branch` without a checkout: the branch is moved to a commit
+// with its own tree whose parent is the picked commit's parent, the picked commit is
+// merged into that (a three-way merge against the parent, so only its own change comes
+// along), and the merged tree is committed again on the original head. On a conflict
+// the branch is moved back.
+pub async fn cherry_pick(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    branch: &str,
+    head: &str,
+    sha: &str,
+) -> Result<Picked, GithubError> {
+    let commit = get_commit(client, token, repo, sha).await?;
+    let head_commit = get_commit(client, token, repo, head).await?;
+    let Some(parent) = commit.parents.first() else {
+        // a root commit has nothing to diff against
+        return Ok(Picked::Conflict);
+    };
+
+    let sibling = create_commit(
+        client,
+        token,
+        repo,
+        "sibling of the picked commit",
+        &head_commit.tree.sha,
+        &[&parent.sha],
+    )
+    .await?;
+    update_branch(client, token, repo, branch, &sibling, true).await?;
+
+    let merged = match merge(client, token, repo, branch, sha).await {
+        Ok(merged) => merged,
+        Err(GithubError::Api { status, .. }) if status.as_u16() == 409 => {
+            update_branch(client, token, repo, branch, head, true).await?;
+            return Ok(Picked::Conflict);
+        }
+        Err(e) => return Err(e),
+    };
+    let tree = match merged {
+        Some(merged) => get_commit(client, token, repo, &merged).await?.tree.sha,
+        None => head_commit.tree.sha.clone(),
+    };
+    if tree == head_commit.tree.sha {
+        update_branch(client, token, repo, branch, head, true).await?;
+        return Ok(Picked::Empty);
+    }
+
+    let picked = create_commit(client, token, repo, &commit.message, &tree, &[head]).await?;
+    update_branch(client, token, repo, branch, &picked, true).await?;
+    Ok(Picked::Applied(picked))
+}
@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use super::{GithubClient, GithubError};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Query {
+    query: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    variables: Map<String, Value>,
+}
+
+impl Query {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            variables: Map::new(),
+        }
+    }
+
+    pub fn variable(mut self, name: impl Into<String>, value: impl Serialize) -> Self {
+        self.variables.insert(
+            name.into(),
+            serde_json::to_value(value).unwrap_or(Value::Null),
+        );
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+// GraphQL reports most failures with a 200 and an `errors` array
+pub async fn query<T: DeserializeOwned>(
+    client: &GithubClient,
+    token: &str,
+    query: &Query,
+) -> Result<T, GithubError> {
+    let response: Response<T> = client
+        .send_json(
+            client
+                .request(reqwest::Method::POST, token, "/graphql")
+                .json(query),
+        )
+        .await?;
+
+    match response.data {
+        Some(data) if response.errors.is_empty() => Ok(data),
+        _ => Err(GithubError::GraphQl {
+            messages: response.errors.into_iter().map(|e| e.message).collect(),
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewDecision {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Actor {
+    pub login: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LatestReview {
+    pub state: String,
+    pub author: Option<Actor>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestReviewState {
+    pub id: String,
+    pub review_decision: Option<ReviewDecision>,
+    // MERGEABLE, CONFLICTING or UNKNOWN while GitHub computes it
+    pub mergeable: String,
+    pub latest_opinionated_reviews: Nodes<LatestReview>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Nodes<T> {
+    pub nodes: Vec<T>,
+}
+
+const REVIEW_STATE_QUERY: &str = r#"
+query($owner: String!, $name: String!, $number: Int!) {
+  repository(owner: $owner, name: $name) {
+    pullRequest(number: $number) {
+      id
+      reviewDecision
+      mergeable
+      latestOpinionatedReviews(first: 100) {
+        nodes { state author { login } }
+      }
+    }
+  }
+}
+"#;
+
+pub async fn pull_request_review_state(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<PullRequestReviewState, GithubError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Repository {
+        pull_request: PullRequestReviewState,
+    }
+    #[derive(Deserialize)]
+    struct Data {
+        repository: Repository,
+    }
+
+    let (owner, name) = repo.split_once('/').unwrap_or((repo, ""));
+    let data: Data = query(
+        client,
+        token,
+        &Query::new(REVIEW_STATE_QUERY)
+            .variable("owner", owner)
+            .variable("name", name)
+            .variable("number", number),
+    )
+    .await?;
+    Ok(data.repository.pull_request)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectItemContent {
+    pub number: Option<u64>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProjectItem {
+    pub id: String,
+    // ISSUE, PULL_REQUEST, DRAFT_ISSUE or REDACTED
+    #[serde(rename = "type")]
+    pub item_type: String,
+    pub content: Option<ProjectItemContent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+const PROJECT_ITEMS_QUERY: &str = r#"
+query($project: ID!, $cursor: String) {
+  node(id: $project) {
+    ... on ProjectV2 {
+      items(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          id
+          type
+          content {
+            ... on Issue { number title url }
+            ... on PullRequest { number title url }
+            ... on DraftIssue { title }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+// `project_id` is the ProjectV2 node id (`PVT_...`), not the number shown in the URL
+pub async fn list_project_items(
+    client: &GithubClient,
+    token: &str,
+    project_id: &str,
+) -> Result<Vec<ProjectItem>, GithubError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Items {
+        page_info: PageInfo,
+        nodes: Vec<ProjectItem>,
+    }
+    #[derive(Deserialize)]
+    struct Project {
+        items: Items,
+    }
+    #[derive(Deserialize)]
+    struct Data {
+        node: Option<Project>,
+    }
+
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let data: Data = query(
+            client,
+            token,
+            &Query::new(PROJECT_ITEMS_QUERY)
+                .variable("project", project_id)
+                .variable("cursor", &cursor),
+        )
+        .await?;
+        let Some(project) = data.node else {
+            return Err(GithubError::GraphQl {
+                messages: vec![format!("{} is not a ProjectV2", project_id)],
+            });
+        };
+
+        items.extend(project.items.nodes);
+        match project.items.page_info {
+            PageInfo {
+                has_next_page: true,
+                end_cursor: Some(end_cursor),
+            } => cursor = Some(end_cursor),
+            _ => break,
+        }
+    }
+
+    Ok(items)
+}
+
+const ADD_PROJECT_ITEM_MUTATION: &str = r#"
+mutation($project: ID!, $content: ID!) {
+  addProjectV2ItemById(input: { projectId: $project, contentId: $content }) {
+    item { id }
+  }
+}
+"#;
+
+// adding content that is already on the project returns the existing item
+pub async fn add_project_item(
+    client: &GithubClient,
+    token: &str,
+    project_id: &str,
+    content_id: &str,
+) -> Result<String, GithubError> {
+    #[derive(Deserialize)]
+    struct Item {
+        id: String,
+    }
+    #[derive(Deserialize)]
+    struct Payload {
+        item: Item,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Data {
+        add_project_v2_item_by_id: Payload,
+    }
+
+    let data: Data = query(
+        client,
+        token,
+        &Query::new(ADD_PROJECT_ITEM_MUTATION)
+            .variable("project", project_id)
+            .variable("content", content_id),
+    )
+    .await?;
+    Ok(data.add_project_v2_item_by_id.item.id)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Name {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MilestoneRef {
+    pub number: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepositoryRef {
+    pub name_with_owner: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClosingIssue {
+    pub number: u64,
+    pub author: Option<Actor>,
+    pub labels: Nodes<Name>,
+    pub milestone: Option<MilestoneRef>,
+    pub repository: RepositoryRef,
+}
+
+const CLOSING_ISSUES_QUERY: &str = r#"
+query($owner: String!, $name: String!, $number: Int!) {
+  repository(owner: $owner, name: $name) {
+    pullRequest(number: $number) {
+      closingIssuesReferences(first: 50) {
+        nodes {
+          number
+          author { login }
+          labels(first: 100) { nodes { name } }
+          milestone { number }
+          repository { nameWithOwner }
+        }
+      }
+    }
+  }
+}
+"#;
+
+// issues the pull request closes through `Fixes #N` and similar keywords, or linked by hand;
+// they may live in other repositories
+pub async fn closing_issues(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<ClosingIssue>, GithubError> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PullRequest {
+        closing_issues_references: Nodes<ClosingIssue>,
+    }
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Repository {
+        pull_request: PullRequest,
+    }
+    #[derive(Deserialize)]
+    struct Data {
+        repository: Repository,
+    }
+
+    let (owner, name) = repo.split_once('/').unwrap_or((repo, ""));
+    let data: Data = query(
+        client,
+        token,
+        &Query::new(CLOSING_ISSUES_QUERY)
+            .variable("owner", owner)
+            .variable("name", name)
+            .variable("number", number),
+    )
+    .await?;
+    Ok(data.repository.pull_request.closing_issues_references.nodes)
+}
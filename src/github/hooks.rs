@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::{GithubClient, GithubError, get_paginated};
+
+// where a webhook is installed: `owner/repo` or an organization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOwner {
+    Repo(String),
+    Org(String),
+}
+
+impl HookOwner {
+    fn path(&self) -> String {
+        match self {
+            HookOwner::Repo(repo) => format!("/repos/{}/hooks", repo),
+            HookOwner::Org(org) => format!("/orgs/{}/hooks", org),
+        }
+    }
+}
+
+impl fmt::Display for HookOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookOwner::Repo(repo) => write!(f, "{}", repo),
+            HookOwner::Org(org) => write!(f, "org {}", org),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hook {
+    pub id: u64,
+    pub active: bool,
+    pub events: Vec<String>,
+    pub config: HookConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HookConfig {
+    pub url: Option<String>,
+    pub content_type: Option<String>,
+    // GitHub never returns the secret, only `********` when one is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    // "0" verifies the receiver's certificate, "1" does not
+    pub insecure_ssl: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookSettings {
+    pub config: HookConfig,
+    pub events: Vec<String>,
+    pub active: bool,
+}
+
+pub async fn list_hooks(
+    client: &GithubClient,
+    token: &str,
+    owner: &HookOwner,
+) -> Result<Vec<Hook>, GithubError> {
+    get_paginated(client, token, &owner.path()).await
+}
+
+pub async fn create_hook(
+    client: &GithubClient,
+    token: &str,
+    owner: &HookOwner,
+    settings: &HookSettings,
+) -> Result<Hook, GithubError> {
+    #[derive(Serialize)]
+    struct NewHook<'a> {
+        name: &'static str,
+        #[serde(flatten)]
+        settings: &'a HookSettings,
+    }
+
+    client
+        .send_json(
+            client
+                .request(reqwest::Method::POST, token, &owner.path())
+                .json(&NewHook {
+                    name: "web",
+                    settings,
+                }),
+        )
+        .await
+}
+
+pub async fn update_hook(
+    client: &GithubClient,
+    token: &str,
+    owner: &HookOwner,
+    id: u64,
+    settings: &HookSettings,
+) -> Result<Hook, GithubError> {
+    client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("{}/{}", owner.path(), id),
+                )
+                .json(settings),
+        )
+        .await
+}
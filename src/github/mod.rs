@@ -0,0 +1,674 @@
+pub mod actions;
+pub mod auth;
+pub mod checks;
+pub mod client;
+pub mod contents;
+pub mod deployments;
+pub mod git;
+pub mod graphql;
+pub mod hooks;
+pub mod releases;
+
+use serde::Deserialize;
+use serde_json::json;
+
+pub use client::{GithubClient, GithubError};
+
+use crate::{
+    cloud::uri_encode,
+    config::MergeMethod,
+    webhook::{Comment, Issue, Label, Milestone, PullRequest},
+};
+
+pub async fn create_issue_comment(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    body: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/issues/{}/comments", repo, number),
+                )
+                .json(&json!({ "body": body })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn list_issue_comments(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<Comment>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/issues/{}/comments", repo, number),
+    )
+    .await
+}
+
+pub async fn update_issue_comment(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    comment_id: u64,
+    body: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/issues/comments/{}", repo, comment_id),
+                )
+                .json(&json!({ "body": body })),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequestFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+pub async fn list_pull_request_files(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<PullRequestFile>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/pulls/{}/files", repo, number),
+    )
+    .await
+}
+
+// a commit as the commits, compare and pull request APIs list it
+#[derive(Debug, Deserialize)]
+pub struct RepoCommit {
+    pub sha: String,
+    pub commit: CommitDetails,
+    #[serde(default)]
+    pub parents: Vec<git::Object>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitDetails {
+    pub message: String,
+    pub author: Option<GitPerson>,
+    pub committer: Option<GitPerson>,
+    pub verification: Option<git::Verification>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GitPerson {
+    pub name: String,
+    pub email: String,
+}
+
+// oldest first, as they were pushed
+pub async fn list_pull_request_commits(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<RepoCommit>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/pulls/{}/commits", repo, number),
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct Comparison {
+    commits: Vec<RepoCommit>,
+}
+
+// commits reachable from `head` but not `base`, oldest first; GitHub lists at most 250
+pub async fn compare_commits(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    base: &str,
+    head: &str,
+) -> Result<Vec<RepoCommit>, GithubError> {
+    let comparison: Comparison = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/compare/{}...{}", repo, base, head),
+        ))
+        .await?;
+    Ok(comparison.commits)
+}
+
+// follows `page=N` until a short page comes back
+pub(crate) async fn get_paginated<T: serde::de::DeserializeOwned>(
+    client: &GithubClient,
+    token: &str,
+    path: &str,
+) -> Result<Vec<T>, GithubError> {
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let mut items = Vec::new();
+    let mut page = 1;
+
+    loop {
+        let batch: Vec<T> = client
+            .send_json(client.request(
+                reqwest::Method::GET,
+                token,
+                &format!("{}{}per_page=100&page={}", path, separator, page),
+            ))
+            .await?;
+
+        let done = batch.len() < 100;
+        items.extend(batch);
+        if done {
+            break;
+        }
+        page += 1;
+    }
+
+    Ok(items)
+}
+
+pub async fn add_labels(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    labels: &[String],
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/issues/{}/labels", repo, number),
+                )
+                .json(&json!({ "labels": labels })),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SearchResults {
+    pub(crate) items: Vec<Issue>,
+}
+
+// the first `per_page` issues and pull requests matching a search query, e.g.
+// `repo:my-org/api author:octocat`
+pub async fn search_issues(
+    client: &GithubClient,
+    token: &str,
+    query: &str,
+    per_page: u8,
+) -> Result<Vec<Issue>, GithubError> {
+    let results: SearchResults = client
+        .send_json(
+            client
+                .request(reqwest::Method::GET, token, "/search/issues")
+                .query(&[("q", query), ("per_page", &per_page.to_string())]),
+        )
+        .await?;
+    Ok(results.items)
+}
+
+pub async fn add_assignees(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    assignees: &[String],
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/issues/{}/assignees", repo, number),
+                )
+                .json(&json!({ "assignees": assignees })),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct PermissionResponse {
+    // admin, write, read or none
+    permission: String,
+    // also maintain, triage and custom roles
+    role_name: String,
+}
+
+const ROLES: &[&str] = &["admin", "maintain", "write", "triage", "read"];
+
+// `admin`, `maintain`, `write`, `triage`, `read` or `none`; custom roles come back as the
+// permission they are based on
+pub async fn collaborator_permission(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    user: &str,
+) -> Result<String, GithubError> {
+    let response = client
+        .send_json::<PermissionResponse>(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/collaborators/{}/permission", repo, user),
+        ))
+        .await;
+    match response {
+        Ok(response) if ROLES.contains(&response.role_name.as_str()) => Ok(response.role_name),
+        Ok(response) => Ok(response.permission),
+        Err(e) if e.is_not_found() => Ok("none".to_string()),
+        Err(e) => Err(e),
+    }
+}
+
+// `content` is one of `+1`, `-1`, `laugh`, `confused`, `heart`, `hooray`, `rocket`, `eyes`
+pub async fn add_comment_reaction(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    comment_id: u64,
+    content: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/issues/comments/{}/reactions", repo, comment_id),
+                )
+                .json(&json!({ "content": content })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn create_repository_dispatch(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    event_type: &str,
+    client_payload: &serde_json::Value,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/dispatches", repo),
+                )
+                .json(&json!({ "event_type": event_type, "client_payload": client_payload })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_file_contents(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    path: &str,
+    git_ref: Option<&str>,
+) -> Result<Option<String>, GithubError> {
+    let mut url = format!("/repos/{}/contents/{}", repo, path);
+    if let Some(git_ref) = git_ref {
+        url.push_str(&format!("?ref={}", git_ref));
+    }
+
+    let response = client
+        .send(client.request_with_accept(
+            reqwest::Method::GET,
+            token,
+            &url,
+            "application/vnd.github.raw+json",
+        ))
+        .await;
+    match response {
+        Ok(response) => Ok(Some(response.text().await?)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn request_reviewers(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    reviewers: &[String],
+    team_reviewers: &[String],
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/pulls/{}/requested_reviewers", repo, number),
+                )
+                .json(&json!({ "reviewers": reviewers, "team_reviewers": team_reviewers })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_pull_request(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<PullRequest, GithubError> {
+    client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/pulls/{}", repo, number),
+        ))
+        .await
+}
+
+// open pull requests that merge into `base`
+pub async fn list_pull_requests_into(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    base: &str,
+) -> Result<Vec<PullRequest>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/pulls?state=open&base={}", repo, uri_encode(base)),
+    )
+    .await
+}
+
+pub async fn create_pull_request(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    head: &str,
+    base: &str,
+    title: &str,
+    body: &str,
+) -> Result<PullRequest, GithubError> {
+    client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/pulls", repo),
+                )
+                .json(&json!({ "title": title, "head": head, "base": base, "body": body })),
+        )
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Review {
+    pub user: Option<crate::webhook::User>,
+    pub state: String,
+}
+
+pub async fn list_reviews(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+) -> Result<Vec<Review>, GithubError> {
+    // oldest first, so the latest decisions are on the last page
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/pulls/{}/reviews", repo, number),
+    )
+    .await
+}
+
+pub async fn merge_pull_request(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    sha: &str,
+    method: MergeMethod,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PUT,
+                    token,
+                    &format!("/repos/{}/pulls/{}/merge", repo, number),
+                )
+                .json(&json!({ "sha": sha, "merge_method": method })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn list_open_issues(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+) -> Result<Vec<Issue>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!(
+            "/repos/{}/issues?state=open&sort=updated&direction=asc",
+            repo
+        ),
+    )
+    .await
+}
+
+pub async fn close_issue(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    state_reason: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/issues/{}", repo, number),
+                )
+                .json(&json!({ "state": "closed", "state_reason": state_reason })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn list_open_milestones(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+) -> Result<Vec<Milestone>, GithubError> {
+    get_paginated(
+        client,
+        token,
+        &format!("/repos/{}/milestones?state=open", repo),
+    )
+    .await
+}
+
+// works for pull requests too, which are issues underneath
+pub async fn set_milestone(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    milestone: u64,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/issues/{}", repo, number),
+                )
+                .json(&json!({ "milestone": milestone })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn lock_issue(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    reason: Option<&str>,
+) -> Result<(), GithubError> {
+    let body = match reason {
+        Some(reason) => json!({ "lock_reason": reason }),
+        None => json!({}),
+    };
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PUT,
+                    token,
+                    &format!("/repos/{}/issues/{}/lock", repo, number),
+                )
+                .json(&body),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn remove_label(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    number: u64,
+    label: &str,
+) -> Result<(), GithubError> {
+    let response = client
+        .send(client.request(
+            reqwest::Method::DELETE,
+            token,
+            &format!(
+                "/repos/{}/issues/{}/labels/{}",
+                repo,
+                number,
+                uri_encode(label)
+            ),
+        ))
+        .await;
+    match response {
+        Ok(_) => Ok(()),
+        // the label was already gone
+        Err(e) if e.is_not_found() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_labels(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+) -> Result<Vec<Label>, GithubError> {
+    get_paginated(client, token, &format!("/repos/{}/labels", repo)).await
+}
+
+// colors are six hex digits without the leading `#`
+pub async fn create_label(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    label: &Label,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/labels", repo),
+                )
+                .json(label),
+        )
+        .await?;
+    Ok(())
+}
+
+// `name` is the label's current name, which may differ from `label.name` in case
+pub async fn update_label(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    name: &str,
+    label: &Label,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/labels/{}", repo, uri_encode(name)),
+                )
+                .json(&json!({
+                    "new_name": label.name,
+                    "color": label.color,
+                    "description": label.description,
+                })),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_label(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    name: &str,
+) -> Result<(), GithubError> {
+    let response = client
+        .send(client.request(
+            reqwest::Method::DELETE,
+            token,
+            &format!("/repos/{}/labels/{}", repo, uri_encode(name)),
+        ))
+        .await;
+    match response {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_not_found() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use super::{GithubClient, GithubError, SearchResults};
+use crate::webhook::Issue;
+
+pub use crate::models::Release;
+
+pub async fn get_release_by_tag(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    tag: &str,
+) -> Result<Option<Release>, GithubError> {
+    let response = client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/releases/tags/{}", repo, tag),
+        ))
+        .await;
+    match response {
+        Ok(release) => Ok(Some(release)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// newest first
+pub async fn list_releases(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+) -> Result<Vec<Release>, GithubError> {
+    client
+        .send_json(client.request(
+            reqwest::Method::GET,
+            token,
+            &format!("/repos/{}/releases?per_page=100", repo),
+        ))
+        .await
+}
+
+pub async fn create_release(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    tag: &str,
+    body: &str,
+    draft: bool,
+) -> Result<Release, GithubError> {
+    client
+        .send_json(
+            client
+                .request(
+                    reqwest::Method::POST,
+                    token,
+                    &format!("/repos/{}/releases", repo),
+                )
+                .json(&json!({ "tag_name": tag, "name": tag, "body": body, "draft": draft })),
+        )
+        .await
+}
+
+pub async fn update_release_body(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    release_id: u64,
+    body: &str,
+) -> Result<(), GithubError> {
+    client
+        .send(
+            client
+                .request(
+                    reqwest::Method::PATCH,
+                    token,
+                    &format!("/repos/{}/releases/{}", repo, release_id),
+                )
+                .json(&json!({ "body": body })),
+        )
+        .await?;
+    Ok(())
+}
+
+// the search API caps results at 1000 and pages at 100
+pub async fn list_merged_pull_requests(
+    client: &GithubClient,
+    token: &str,
+    repo: &str,
+    since: Option<DateTime<Utc>>,
+    until: DateTime<Utc>,
+) -> Result<Vec<Issue>, GithubError> {
+    let range = match since {
+        Some(since) => format!("{}..{}", since.to_rfc3339(), until.to_rfc3339()),
+        None => format!("<={}", until.to_rfc3339()),
+    };
+    let query = format!("repo:{} is:pr is:merged merged:{}", repo, range);
+
+    let mut items = Vec::new();
+    for page in 1..=10 {
+        let results: SearchResults = client
+            .send_json(
+                client
+                    .request(reqwest::Method::GET, token, "/search/issues")
+                    .query(&[
+                        ("q", query.as_str()),
+                        ("sort", "created"),
+                        ("order", "asc"),
+                        ("per_page", "100"),
+                        ("page", &page.to_string()),
+                    ]),
+            )
+            .await?;
+
+        let done = results.items.len() < 100;
+        items.extend(results.items);
+        if done {
+            break;
+        }
+    }
+
+    Ok(items)
+}
@@ -0,0 +1,304 @@
+// The admin API over gRPC (proto/nexus/admin/v1/admin.proto), for tooling that would
+// rather not speak HTTP+JSON. It does what the /admin routes do through the same
+// functions in `admin`, and asks for the same bearer token:
+//
+//     grpcurl -plaintext -H 'authorization: Bearer <token>' -d '{"limit": 10}' \
+//         127.0.0.1:50051 nexus.admin.v1.Admin/ListDeliveries
+
+use std::future::Future;
+use tokio::net::TcpListener;
+use tonic::{Code, Request, Response, Status, transport::server::TcpIncoming};
+
+use crate::{
+    admin::{self, AdminError},
+    queue::{Enqueued, Processing, ProcessingStatus},
+    service::AppState,
+};
+
+pub mod proto {
+    tonic::include_proto!("nexus.admin.v1");
+}
+
+use proto::{
+    admin_server::{Admin, AdminServer},
+    *,
+};
+
+// as many as the HTTP route lists without a limit
+const DEFAULT_LIMIT: usize = 100;
+
+struct AdminService {
+    state: AppState,
+}
+
+impl AdminService {
+    fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status> {
+        let Some(expected) = &self.state.config.server.admin_token else {
+            return Err(Status::permission_denied("server.admin_token is not set"));
+        };
+        let provided = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("no bearer token"))?;
+        if admin::token_matches(expected, provided) {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("invalid token"))
+        }
+    }
+}
+
+fn status(e: AdminError) -> Status {
+    let code = match e {
+        AdminError::NoArchive | AdminError::NoQueue => Code::FailedPrecondition,
+        AdminError::NotFound(_) => Code::NotFound,
+        AdminError::Reload(_) => Code::InvalidArgument,
+        AdminError::Queue(_) => Code::Unavailable,
+        AdminError::Archive(_) | AdminError::Handler(_) => Code::Internal,
+    };
+    Status::new(code, e.to_string())
+}
+
+fn processing_status(status: ProcessingStatus) -> proto::ProcessingStatus {
+    let state = match status.state {
+        Processing::Running => ProcessingState::Running,
+        Processing::Paused => ProcessingState::Paused,
+        Processing::Draining => ProcessingState::Draining,
+    };
+    proto::ProcessingStatus {
+        state: state.into(),
+        held: status.held as u64,
+        paused_at: status.paused_at.map(|t| t.to_rfc3339()),
+        reason: status.reason,
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for AdminService {
+    async fn list_deliveries(
+        &self,
+        request: Request<ListDeliveriesRequest>,
+    ) -> Result<Response<ListDeliveriesResponse>, Status> {
+        self.authorize(&request)?;
+        let request = request.into_inner();
+        let limit = match request.limit {
+            0 => DEFAULT_LIMIT,
+            limit => limit as usize,
+        };
+        let deliveries = admin::deliveries(&self.state, request.event.as_deref(), limit)
+            .await
+            .map_err(status)?
+            .into_iter()
+            .map(|d| Delivery {
+                delivery_id: d.delivery_id,
+                event_type: d.event_type,
+                action: d.action,
+                repository: d.repository,
+                received_at: d.received_at.to_rfc3339(),
+                rerun: d.rerun.map(|r| Rerun {
+                    run_id: r.run_id,
+                    attempt: r.attempt,
+                    job: r.job,
+                    pattern: r.pattern,
+                    requested_at: r.requested_at.to_rfc3339(),
+                }),
+            })
+            .collect();
+        Ok(Response::new(ListDeliveriesResponse { deliveries }))
+    }
+
+    async fn redeliver(
+        &self,
+        request: Request<RedeliverRequest>,
+    ) -> Result<Response<RedeliverResponse>, Status> {
+        self.authorize(&request)?;
+        let id = request.into_inner().delivery_id;
+        let enqueued = admin::redeliver(&self.state, &id).await.map_err(status)?;
+        Ok(Response::new(RedeliverResponse {
+            shed: enqueued == Enqueued::Shed,
+        }))
+    }
+
+    async fn reload_config(
+        &self,
+        request: Request<ReloadConfigRequest>,
+    ) -> Result<Response<ReloadConfigResponse>, Status> {
+        self.authorize(&request)?;
+        self.state.reloader.reload().await.map_err(status)?;
+        Ok(Response::new(ReloadConfigResponse {}))
+    }
+
+    async fn get_processing(
+        &self,
+        request: Request<GetProcessingRequest>,
+    ) -> Result<Response<proto::ProcessingStatus>, Status> {
+        self.authorize(&request)?;
+        let processing = admin::processing(&self.state).map_err(status)?;
+        Ok(Response::new(processing_status(processing)))
+    }
+
+    async fn pause(
+        &self,
+        request: Request<PauseRequest>,
+    ) -> Result<Response<proto::ProcessingStatus>, Status> {
+        self.authorize(&request)?;
+        let reason = request.into_inner().reason;
+        let processing = admin::pause(&self.state, reason).map_err(status)?;
+        Ok(Response::new(processing_status(processing)))
+    }
+
+    async fn resume(
+        &self,
+        request: Request<ResumeRequest>,
+    ) -> Result<Response<proto::ProcessingStatus>, Status> {
+        self.authorize(&request)?;
+        let processing = admin::resume(&self.state).map_err(status)?;
+        Ok(Response::new(processing_status(processing)))
+    }
+}
+
+// serves until `shutdown` completes, finishing the calls in flight
+pub async fn serve(
+    state: AppState,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(AdminServer::new(AdminService { state }))
+        .serve_with_incoming_shutdown(TcpIncoming::from(listener), shutdown)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, redact::Redactor, webhook::RawBody};
+    use bytes::Bytes;
+    use http::HeaderMap;
+    use proto::admin_client::AdminClient;
+    use tonic::transport::Channel;
+
+    const PUSH: &str = r#"{"ref":"refs/heads/main","repository":{"name":"nexus","full_name":"octo/nexus","html_url":"https://github.com/octo/nexus"}}"#;
+
+    fn authorized<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        request
+    }
+
+    async fn client(state: AppState) -> AdminClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(state, listener, std::future::pending()));
+        AdminClient::connect(format!("http://{}", address))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_deliveries_for_the_admin_token_only() {
+        let dir = std::env::temp_dir().join(format!("nexus-grpc-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let body = RawBody {
+            received: Bytes::from_static(PUSH.as_bytes()),
+            json: Bytes::from_static(PUSH.as_bytes()),
+        };
+        crate::archive::record(
+            &dir,
+            &Redactor::default(),
+            &HeaderMap::new(),
+            Some("1"),
+            "push",
+            &body,
+        )
+        .await
+        .unwrap();
+        let mut config = Config::default();
+        config.archive.dir = Some(dir.clone());
+        config.server.admin_token = Some("token".to_string());
+        let mut client = client(AppState::new(None).with_config(config)).await;
+
+        let request = ListDeliveriesRequest {
+            event: None,
+            limit: 0,
+        };
+        let denied = client
+            .list_deliveries(authorized(request.clone(), "wrong"))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.code(), Code::Unauthenticated);
+        let denied = client
+            .list_deliveries(Request::new(request.clone()))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.code(), Code::Unauthenticated);
+
+        let deliveries = client
+            .list_deliveries(authorized(request, "token"))
+            .await
+            .unwrap()
+            .into_inner()
+            .deliveries;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].delivery_id.as_deref(), Some("1"));
+        assert_eq!(deliveries[0].event_type, "push");
+        assert_eq!(deliveries[0].repository.as_deref(), Some("octo/nexus"));
+
+        let missing = client
+            .redeliver(authorized(
+                RedeliverRequest {
+                    delivery_id: "2".to_string(),
+                },
+                "token",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn is_disabled_without_an_admin_token() {
+        let mut client = client(AppState::new(None)).await;
+        let denied = client
+            .get_processing(authorized(GetProcessingRequest {}, "token"))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.code(), Code::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn pauses_and_resumes() {
+        let (queue, _receiver) = crate::queue::DeliveryQueue::new(&Default::default());
+        let mut config = Config::default();
+        config.server.admin_token = Some("token".to_string());
+        let state = AppState::new(None).with_config(config).with_queue(queue);
+        let mut client = client(state).await;
+
+        let paused = client
+            .pause(authorized(
+                PauseRequest {
+                    reason: Some("maintenance".to_string()),
+                },
+                "token",
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(paused.state(), ProcessingState::Paused);
+        assert_eq!(paused.reason.as_deref(), Some("maintenance"));
+        assert!(paused.paused_at.is_some());
+
+        let resumed = client
+            .resume(authorized(ResumeRequest {}, "token"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_ne!(resumed.state(), ProcessingState::Paused);
+    }
+}
@@ -0,0 +1,169 @@
+// Processed deliveries as points in InfluxDB, for trends over longer than the in-memory
+// metrics keep. Points are buffered and written in batches as line protocol to the v2
+// write API, which InfluxDB 3 and Telegraf's influxdb_v2_listener accept as well.
+
+use chrono::Utc;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{clickhouse::Processed, config::InfluxDbConfig, error::NexusError, service::AppState};
+
+/// Points waiting for the next write.
+#[derive(Clone, Default)]
+pub struct InfluxDb {
+    lines: Arc<Mutex<Vec<String>>>,
+    // one write at a time keeps batches in order
+    writing: Arc<tokio::sync::Mutex<()>>,
+    // wakes the flusher when a full batch is waiting
+    full: Arc<Notify>,
+}
+
+pub fn record(state: &AppState, processed: Processed<'_>) {
+    let config = &state.config.influxdb;
+    if !config.enabled {
+        return;
+    }
+    let payload = processed.payload;
+    let now = Utc::now();
+    let status = processed.status.map(|s| s.to_string());
+    let tags = [
+        ("event_type", processed.event_type),
+        ("action", payload.action.as_deref().unwrap_or_default()),
+        (
+            "repository",
+            payload
+                .repository
+                .as_ref()
+                .map_or("", |r| r.full_name.as_str()),
+        ),
+        (
+            "outcome",
+            match processed.status {
+                None => "success",
+                Some(_) => "failure",
+            },
+        ),
+        ("status", status.as_deref().unwrap_or_default()),
+    ];
+    let mut line = escape(&config.measurement, ", ");
+    // line protocol has no empty tag values, so those tags are left out
+    for (key, value) in tags.iter().filter(|(_, value)| !value.is_empty()) {
+        line.push_str(&format!(",{}={}", key, escape(value, ",= ")));
+    }
+    line.push_str(&format!(
+        " count=1i,duration_ms={}i,latency_ms={}i {}",
+        processed.duration_ms,
+        (now - processed.received_at).num_milliseconds().max(0),
+        now.timestamp_millis()
+    ));
+
+    let buffered = {
+        let mut lines = state.influxdb.lines.lock().unwrap();
+        lines.push(line);
+        lines.len()
+    };
+    if buffered >= config.batch_size {
+        state.influxdb.full.notify_one();
+    }
+}
+
+pub fn spawn(state: Arc<AppState>) -> Option<JoinHandle<()>> {
+    if !state.config.influxdb.enabled {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let config = &state.config.influxdb;
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.flush_interval_secs.max(1)));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = state.influxdb.full.notified() => {}
+            }
+            flush(&state).await;
+        }
+    }))
+}
+
+// sends what is buffered; on failure the points go back to the front of the buffer
+pub async fn flush(state: &AppState) {
+    let config = &state.config.influxdb;
+    if !config.enabled {
+        return;
+    }
+    let _writing = state.influxdb.writing.lock().await;
+    let lines = std::mem::take(&mut *state.influxdb.lines.lock().unwrap());
+    if lines.is_empty() {
+        return;
+    }
+
+    match write(state, config, lines.join("\n")).await {
+        Ok(()) => info!("Wrote {} points to InfluxDB", lines.len()),
+        Err(e) => {
+            let mut buffered = state.influxdb.lines.lock().unwrap();
+            let newer = std::mem::replace(&mut *buffered, lines);
+            buffered.extend(newer);
+            let excess = buffered.len().saturating_sub(config.max_buffered_points);
+            buffered.drain(..excess);
+            warn!(
+                "Failed to write to InfluxDB, keeping {} points for the next attempt{}: {}",
+                buffered.len(),
+                match excess {
+                    0 => String::new(),
+                    n => format!(" and dropping the {} oldest", n),
+                },
+                e
+            );
+        }
+    }
+}
+
+// backslash-escapes `special` characters, as line protocol wants in names and tag values
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+async fn write(state: &AppState, config: &InfluxDbConfig, body: String) -> Result<(), NexusError> {
+    let mut request = state
+        .http_client
+        .post(format!("{}/api/v2/write", config.url.trim_end_matches('/')))
+        .query(&[
+            ("org", config.org.as_str()),
+            ("bucket", config.bucket.as_str()),
+            ("precision", "ms"),
+        ])
+        .timeout(Duration::from_secs(30))
+        .body(body);
+    if let Some(token) = &config.token {
+        request = request.header("Authorization", format!("Token {}", token));
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|source| NexusError::SinkUnreachable {
+            sink: "InfluxDB",
+            source,
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    // InfluxDB explains the problem in the body, e.g. a missing bucket
+    let message = response.text().await.unwrap_or_default();
+    Err(NexusError::SinkRejected {
+        sink: "InfluxDB",
+        status,
+        message: message.trim().to_string(),
+    })
+}
@@ -1,21 +1,104 @@
-use chrono;
+#[cfg(feature = "server")]
+pub mod acme;
+#[cfg(feature = "server")]
+pub mod admin;
+#[cfg(feature = "server")]
+pub mod anomaly;
+#[cfg(feature = "server")]
+pub mod archive;
+#[cfg(feature = "server")]
+pub mod automations;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "server")]
+pub mod clickhouse;
+#[cfg(feature = "server")]
+pub mod client_ip;
+#[cfg(feature = "server")]
+pub mod cloud;
+#[cfg(feature = "server")]
+pub mod codeowners;
+#[cfg(feature = "server")]
+pub mod command;
+#[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "server")]
+pub mod conventional;
+#[cfg(feature = "server")]
+pub mod correlation;
+#[cfg(feature = "server")]
+pub mod encoding;
+#[cfg(feature = "server")]
+pub mod environments;
+#[cfg(feature = "server")]
+pub mod error;
+#[cfg(feature = "server")]
+pub mod export;
+#[cfg(feature = "server")]
+pub mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "server")]
+pub mod github;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "server")]
+pub mod influxdb;
+#[cfg(feature = "server")]
+pub mod metrics;
+pub mod models;
+#[cfg(feature = "octocrab")]
+pub mod octocrab;
+#[cfg(feature = "server")]
+pub mod openapi;
+#[cfg(feature = "server")]
+pub mod outbound;
+#[cfg(feature = "server")]
+pub mod policy;
+#[cfg(feature = "server")]
+pub mod proxy;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "server")]
+pub mod queue;
+#[cfg(feature = "server")]
+pub mod redact;
+#[cfg(feature = "server")]
+pub mod repos;
+#[cfg(feature = "server")]
+pub mod scheduler;
+#[cfg(feature = "server")]
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod service;
+#[cfg(feature = "blocking")]
+pub mod signature;
+#[cfg(feature = "server")]
+pub mod spool;
+#[cfg(feature = "server")]
+pub mod stats;
+#[cfg(feature = "server")]
+pub mod store;
+#[cfg(feature = "server")]
+pub mod systemd;
+#[cfg(feature = "server")]
+pub mod template;
+#[cfg(feature = "server")]
+pub mod testing;
+#[cfg(feature = "server")]
+pub mod timeouts;
+#[cfg(feature = "server")]
+pub mod transform;
+#[cfg(feature = "server")]
+pub mod webhook;
+#[cfg(all(windows, feature = "server"))]
+pub mod winservice;
 
-pub mod webhook {
-    use serde::{Deserialize, Serialize};
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct GitHubEvent {
-        pub event_type: String,
-        pub repository: String,
-        pub sender: String,
-        pub timestamp: chrono::DateTime<chrono::Utc>,
-    }
-
-    pub fn process_event(event: GitHubEvent) -> Result<String, Box<dyn std::error::Error>> {
-        // you can add more complex processing logic here
-        Ok(format!(
-            "Processed {} event from {}",
-            event.event_type, event.repository
-        ))
-    }
-}
+#[cfg(feature = "server")]
+pub use config::Config;
+#[cfg(feature = "server")]
+pub use error::NexusError;
+#[cfg(feature = "server")]
+pub use service::{AppState, WebhookService};
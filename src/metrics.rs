@@ -0,0 +1,220 @@
+use std::{fmt::Write, sync::atomic::Ordering};
+
+use crate::{
+    outbound::CircuitState,
+    queue::{Priority, Processing},
+    service::AppState,
+};
+
+// Prometheus text exposition format
+pub async fn render(state: &AppState) -> String {
+    let mut out = String::new();
+
+    if let Some(queue) = &state.queue {
+        let metrics = queue.metrics();
+        let gauges = [
+            (
+                "nexus_queue_depth",
+                "Deliveries waiting for a worker.",
+                queue.depth() as u64,
+            ),
+            (
+                "nexus_queue_capacity",
+                "Maximum number of queued deliveries per priority class.",
+                queue.capacity() as u64,
+            ),
+            (
+                "nexus_queue_workers",
+                "Number of delivery workers.",
+                metrics.workers.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                "nexus_queue_busy_workers",
+                "Workers currently processing a delivery.",
+                metrics.busy_workers.load(Ordering::Relaxed) as u64,
+            ),
+            (
+                "nexus_queue_held",
+                "Deliveries held while processing is paused.",
+                queue.status().held as u64,
+            ),
+            (
+                "nexus_queue_paused",
+                "1 while processing is paused.",
+                u64::from(queue.status().state == Processing::Paused),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            metric(&mut out, name, "gauge", help, &[("", value)]);
+        }
+
+        let samples: Vec<_> = Priority::ALL
+            .iter()
+            .map(|p| {
+                (
+                    format!("priority=\"{}\"", p.as_str()),
+                    queue.depth_of(*p) as u64,
+                )
+            })
+            .collect();
+        let samples: Vec<_> = samples.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        metric(
+            &mut out,
+            "nexus_queue_priority_depth",
+            "gauge",
+            "Deliveries waiting for a worker per priority class.",
+            &samples,
+        );
+
+        let counters = [
+            (
+                "nexus_deliveries_enqueued_total",
+                "Deliveries accepted into the queue.",
+                &metrics.enqueued,
+            ),
+            (
+                "nexus_deliveries_rejected_total",
+                "Deliveries refused with 503 because the queue was full.",
+                &metrics.rejected,
+            ),
+            (
+                "nexus_deliveries_shed_total",
+                "Low-priority deliveries acknowledged without processing.",
+                &metrics.shed,
+            ),
+            (
+                "nexus_deliveries_processed_total",
+                "Deliveries handled successfully.",
+                &metrics.processed,
+            ),
+            (
+                "nexus_deliveries_failed_total",
+                "Deliveries whose handler returned an error.",
+                &metrics.failed,
+            ),
+        ];
+        for (name, help, value) in counters {
+            metric(
+                &mut out,
+                name,
+                "counter",
+                help,
+                &[("", value.load(Ordering::Relaxed))],
+            );
+        }
+
+        let mut shed: Vec<_> = metrics
+            .shed_by_event
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(event, count)| (format!("event=\"{}\"", escape(event)), *count))
+            .collect();
+        shed.sort();
+        let samples: Vec<_> = shed.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        metric(
+            &mut out,
+            "nexus_deliveries_shed_by_event_total",
+            "counter",
+            "Deliveries acknowledged without processing per event type.",
+            &samples,
+        );
+    }
+
+    metric(
+        &mut out,
+        "nexus_outbound_pending",
+        "gauge",
+        "Outbound deliveries in flight or waiting to retry.",
+        &[("", state.outbound.pending().await.len() as u64)],
+    );
+
+    let mut circuits: Vec<_> = state.outbound.circuits().into_iter().collect();
+    circuits.sort_by(|a, b| a.0.cmp(&b.0));
+    let samples: Vec<_> = circuits
+        .iter()
+        .map(|(target, circuit)| {
+            let value = match circuit.state {
+                CircuitState::Closed => 0,
+                CircuitState::HalfOpen => 1,
+                CircuitState::Open => 2,
+            };
+            (format!("target=\"{}\"", escape(target)), value)
+        })
+        .collect();
+    let samples: Vec<_> = samples.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+    metric(
+        &mut out,
+        "nexus_outbound_circuit_state",
+        "gauge",
+        "Circuit breaker state per target: 0 closed, 1 half-open, 2 open.",
+        &samples,
+    );
+
+    let timeouts = [
+        (
+            "nexus_handler_timeouts_total",
+            "Handlers cancelled for running past their timeout.",
+            "handler",
+            state.timeouts.handler_counts(),
+        ),
+        (
+            "nexus_outbound_timeouts_total",
+            "Outbound attempts that ran past their target's timeout.",
+            "target",
+            state.timeouts.target_counts(),
+        ),
+    ];
+    for (name, help, label, counts) in timeouts {
+        let samples: Vec<_> = counts
+            .iter()
+            .map(|(key, count)| (format!("{}=\"{}\"", label, escape(key)), *count))
+            .collect();
+        let samples: Vec<_> = samples.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        metric(&mut out, name, "counter", help, &samples);
+    }
+
+    let drift: Vec<_> = state
+        .drift
+        .counts()
+        .into_iter()
+        .map(|(event, field, kind, count)| {
+            let labels = format!(
+                "event=\"{}\",field=\"{}\",kind=\"{}\"",
+                escape(&event),
+                escape(&field),
+                kind
+            );
+            (labels, count)
+        })
+        .collect();
+    let samples: Vec<_> = drift.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+    metric(
+        &mut out,
+        "nexus_payload_drift_total",
+        "counter",
+        "Payload fields that differ from the model or GitHub's schemas (strict schema mode).",
+        &samples,
+    );
+
+    out
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, u64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
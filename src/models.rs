@@ -0,0 +1,295 @@
+// The serde models of GitHub's webhook payloads. They need only serde, serde_json and
+// chrono, so with `default-features = false, features = ["models"]` other crates can
+// parse deliveries without the server's runtime and HTTP stack.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WebhookPayload {
+    pub action: Option<String>,
+    // set on push and create/delete events
+    #[serde(rename = "ref")]
+    pub ref_name: Option<String>,
+    pub ref_type: Option<String>,
+    pub repository: Option<Repository>,
+    pub sender: Option<User>,
+    pub pull_request: Option<PullRequest>,
+    pub issue: Option<Issue>,
+    pub check_suite: Option<CheckSuite>,
+    pub workflow_run: Option<WorkflowRun>,
+    // pull_request_review events only
+    pub review: Option<Review>,
+    pub comment: Option<Comment>,
+    pub installation: Option<InstallationRef>,
+    pub deployment: Option<Deployment>,
+    // deployment_status events only
+    pub deployment_status: Option<DeploymentStatus>,
+    pub release: Option<Release>,
+    // security_advisory and repository_advisory events
+    pub security_advisory: Option<SecurityAdvisory>,
+    pub repository_advisory: Option<SecurityAdvisory>,
+    // label events, and labeled/unlabeled actions
+    pub label: Option<Label>,
+    // push events only
+    #[serde(default)]
+    pub commits: Vec<Commit>,
+    pub compare: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Repository {
+    pub name: String,
+    pub full_name: String,
+    pub html_url: String,
+    pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct User {
+    pub login: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PullRequest {
+    pub number: u64,
+    // GraphQL node id
+    pub node_id: Option<String>,
+    pub title: String,
+    pub body: Option<String>,
+    pub html_url: String,
+    pub state: String,
+    pub user: User,
+    pub author_association: Option<String>,
+    pub head: Option<GitRef>,
+    pub base: Option<GitRef>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub draft: bool,
+    pub merged: Option<bool>,
+    pub milestone: Option<Milestone>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub merged_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl PullRequest {
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels
+            .iter()
+            .any(|l| l.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Label {
+    pub name: String,
+    pub color: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Milestone {
+    pub number: u64,
+    pub title: String,
+    pub state: String,
+    pub due_on: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GitRef {
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub sha: String,
+    pub repo: Option<Repository>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Issue {
+    pub number: u64,
+    pub node_id: Option<String>,
+    pub title: String,
+    pub body: Option<String>,
+    pub html_url: String,
+    pub state: String,
+    pub user: User,
+    pub author_association: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    pub milestone: Option<Milestone>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    // present when the issue is actually a pull request
+    pub pull_request: Option<serde_json::Value>,
+}
+
+impl Issue {
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels
+            .iter()
+            .any(|l| l.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Comment {
+    pub id: u64,
+    pub body: String,
+    pub user: User,
+    pub html_url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Review {
+    pub id: u64,
+    pub user: User,
+    // approved, changes_requested or commented
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Commit {
+    pub id: String,
+    pub message: String,
+    pub url: String,
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Deployment {
+    pub id: u64,
+    pub sha: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub environment: String,
+    pub task: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SecurityAdvisory {
+    pub ghsa_id: String,
+    pub summary: String,
+    pub severity: Option<String>,
+    // repository advisories only
+    pub html_url: Option<String>,
+}
+
+// present on deliveries to GitHub App webhooks
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InstallationRef {
+    pub id: u64,
+}
+
+// pull requests as embedded in check suite and workflow run payloads
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PullRequestRef {
+    pub number: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CheckSuite {
+    pub id: u64,
+    pub head_sha: String,
+    pub head_branch: Option<String>,
+    pub status: Option<String>,
+    pub conclusion: Option<String>,
+    #[serde(default)]
+    pub pull_requests: Vec<PullRequestRef>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub name: Option<String>,
+    pub head_sha: String,
+    pub head_branch: Option<String>,
+    pub status: Option<String>,
+    pub conclusion: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub run_attempt: u32,
+    #[serde(default)]
+    pub pull_requests: Vec<PullRequestRef>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Release {
+    pub id: u64,
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    // the date of the tagged commit, not of the release itself
+    pub created_at: DateTime<Utc>,
+    pub html_url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    Error,
+    Failure,
+    Inactive,
+    InProgress,
+    Queued,
+    Pending,
+    Success,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeploymentStatus {
+    pub state: DeploymentState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment_url: Option<String>,
+}
+
+impl DeploymentStatus {
+    pub fn new(state: DeploymentState) -> Self {
+        Self {
+            state,
+            description: None,
+            log_url: None,
+            environment_url: None,
+        }
+    }
+
+    // GitHub rejects descriptions longer than 140 characters
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        let description: String = description.into();
+        self.description = Some(description.chars().take(140).collect());
+        self
+    }
+
+    pub fn log_url(mut self, url: impl Into<String>) -> Self {
+        self.log_url = Some(url.into());
+        self
+    }
+
+    pub fn environment_url(mut self, url: impl Into<String>) -> Self {
+        self.environment_url = Some(url.into());
+        self
+    }
+}
@@ -0,0 +1,301 @@
+// Conversions from octocrab's webhook and Events API models into `nexus::models`, so a
+// crate that already parses deliveries with octocrab can hand them to nexus's pipeline
+// and automations without a second set of models:
+//
+//     let event = WebhookEvent::try_from_header_and_body(event_type, &body)?;
+//     let payload = WebhookPayload::try_from(&event)?;
+//
+// nexus keeps only the fields its automations read, so the way back to octocrab starts
+// from the JSON as GitHub sent it (`webhook_event`, or a queued `Job`), not from a model.
+// Conversions that can meet a payload without a field nexus requires return
+// `serde_json::Error`, as parsing the delivery would have.
+
+use ::octocrab::models::{
+    self as octo,
+    events::{Event, EventType},
+    webhook_events::{WebhookEvent, WebhookEventType},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use crate::models::{
+    Comment, Issue, Label, Milestone, PullRequest, Repository, User, WebhookPayload,
+};
+
+// through GitHub's JSON shape, which both sides serialize to
+fn convert<T: Serialize, U: DeserializeOwned>(value: &T) -> Result<U, serde_json::Error> {
+    serde_json::from_value(serde_json::to_value(value)?)
+}
+
+macro_rules! via_json {
+    ($($from:ty => $to:ty),+ $(,)?) => {
+        $(
+            impl TryFrom<&$from> for $to {
+                type Error = serde_json::Error;
+
+                fn try_from(value: &$from) -> Result<Self, Self::Error> {
+                    convert(value)
+                }
+            }
+        )+
+    };
+}
+
+via_json! {
+    octo::Repository => Repository,
+    octo::Milestone => Milestone,
+    octo::pulls::PullRequest => PullRequest,
+    octo::issues::Issue => Issue,
+    octo::issues::Comment => Comment,
+}
+
+impl From<&octo::Author> for User {
+    fn from(author: &octo::Author) -> Self {
+        Self {
+            login: author.login.clone(),
+            html_url: author.html_url.to_string(),
+        }
+    }
+}
+
+impl From<&octo::Label> for Label {
+    fn from(label: &octo::Label) -> Self {
+        Self {
+            name: label.name.clone(),
+            color: Some(label.color.clone()),
+            description: label.description.clone(),
+        }
+    }
+}
+
+impl TryFrom<&WebhookEvent> for WebhookPayload {
+    type Error = serde_json::Error;
+
+    fn try_from(event: &WebhookEvent) -> Result<Self, Self::Error> {
+        // `specific` is an externally tagged enum, `{"PullRequest": {...}}`, around the
+        // event's own fields as GitHub names them
+        let mut fields = match serde_json::to_value(&event.specific)? {
+            Value::Object(tagged) => match tagged.into_iter().next() {
+                Some((_, Value::Object(fields))) => fields,
+                _ => Map::new(),
+            },
+            _ => Map::new(),
+        };
+        insert(&mut fields, "repository", &event.repository)?;
+        insert(&mut fields, "sender", &event.sender)?;
+        insert(&mut fields, "installation", &event.installation)?;
+        serde_json::from_value(Value::Object(fields))
+    }
+}
+
+impl TryFrom<&Event> for WebhookPayload {
+    type Error = serde_json::Error;
+
+    // the Events API nests the payload and abbreviates the repository and actor, which are
+    // filled in from `repo` and `actor` when the payload has no full ones
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let mut fields = match &event.payload {
+            Some(payload) => match serde_json::to_value(payload)? {
+                Value::Object(fields) => fields,
+                _ => Map::new(),
+            },
+            None => Map::new(),
+        };
+        fields.retain(|_, value| !value.is_null());
+        let web = web_url(event.repo.url.as_str());
+        if !fields.contains_key("repository") {
+            let name = event.repo.name.rsplit('/').next().unwrap_or_default();
+            fields.insert(
+                "repository".to_string(),
+                serde_json::json!({
+                    "name": name,
+                    "full_name": event.repo.name,
+                    "html_url": format!("{}/{}", web, event.repo.name),
+                }),
+            );
+        }
+        if !fields.contains_key("sender") {
+            fields.insert(
+                "sender".to_string(),
+                serde_json::json!({
+                    "login": event.actor.login,
+                    "html_url": format!("{}/{}", web, event.actor.login),
+                }),
+            );
+        }
+        serde_json::from_value(Value::Object(fields))
+    }
+}
+
+fn insert<T: Serialize>(
+    fields: &mut Map<String, Value>,
+    key: &str,
+    value: &Option<T>,
+) -> Result<(), serde_json::Error> {
+    if let Some(value) = value {
+        fields.insert(key.to_string(), serde_json::to_value(value)?);
+    }
+    Ok(())
+}
+
+// `https://api.github.com/repos/o/r` is on `https://github.com`, and
+// `https://ghe.example.com/api/v3/repos/o/r` on `https://ghe.example.com`
+fn web_url(api: &str) -> String {
+    let (scheme, rest) = api.split_once("://").unwrap_or(("https", api));
+    match rest.split('/').next() {
+        Some("api.github.com" | "") | None => "https://github.com".to_string(),
+        Some(host) => format!("{}://{}", scheme, host),
+    }
+}
+
+/// The `X-GitHub-Event` name of a webhook event, e.g. `pull_request`.
+pub fn webhook_event_type(kind: &WebhookEventType) -> Option<String> {
+    match serde_json::to_value(kind).ok()? {
+        Value::String(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// The `X-GitHub-Event` name an Events API event would be delivered under, e.g.
+/// `pull_request` for a `PullRequestEvent`.
+pub fn event_type(kind: &EventType) -> Option<String> {
+    let name = match serde_json::to_value(kind).ok()? {
+        Value::String(name) => name,
+        _ => return None,
+    };
+    let name = name.strip_suffix("Event")?;
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    Some(snake)
+}
+
+/// An octocrab webhook event from a delivery's JSON, as nexus passes it to handlers next to
+/// the parsed payload.
+pub fn webhook_event(event_type: &str, json: &[u8]) -> Result<WebhookEvent, serde_json::Error> {
+    WebhookEvent::try_from_header_and_body(event_type, json)
+}
+
+#[cfg(feature = "server")]
+impl TryFrom<&crate::queue::Job> for WebhookEvent {
+    type Error = serde_json::Error;
+
+    fn try_from(job: &crate::queue::Job) -> Result<Self, Self::Error> {
+        webhook_event(&job.event_type, &job.json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PULL_REQUEST: &str = r#"{
+        "action": "opened",
+        "number": 7,
+        "pull_request": {
+            "url": "https://api.github.com/repos/octo/nexus/pulls/7",
+            "id": 1,
+            "number": 7,
+            "state": "open",
+            "title": "Add a thing",
+            "body": "Adds it",
+            "html_url": "https://github.com/octo/nexus/pull/7",
+            "user": {"login": "octocat", "id": 1, "node_id": "U_1", "avatar_url": "https://github.com/images/octocat.gif", "gravatar_id": "", "url": "https://api.github.com/users/octocat", "html_url": "https://github.com/octocat", "followers_url": "https://api.github.com/users/octocat/followers", "following_url": "https://api.github.com/users/octocat/following", "gists_url": "https://api.github.com/users/octocat/gists", "starred_url": "https://api.github.com/users/octocat/starred", "subscriptions_url": "https://api.github.com/users/octocat/subscriptions", "organizations_url": "https://api.github.com/users/octocat/orgs", "repos_url": "https://api.github.com/users/octocat/repos", "events_url": "https://api.github.com/users/octocat/events", "received_events_url": "https://api.github.com/users/octocat/received_events", "type": "User", "site_admin": false},
+            "labels": [{"id": 1, "node_id": "L_1", "url": "https://api.github.com/repos/octo/nexus/labels/bug", "name": "bug", "color": "d73a4a", "default": true, "description": null}],
+            "draft": false,
+            "head": {"ref": "feature", "sha": "abc", "label": "octo:feature"},
+            "base": {"ref": "main", "sha": "def", "label": "octo:main"}
+        },
+        "repository": {"id": 2, "name": "nexus", "full_name": "octo/nexus", "url": "https://api.github.com/repos/octo/nexus", "html_url": "https://github.com/octo/nexus", "default_branch": "main"},
+        "sender": {"login": "octocat", "id": 1, "node_id": "U_1", "avatar_url": "https://github.com/images/octocat.gif", "gravatar_id": "", "url": "https://api.github.com/users/octocat", "html_url": "https://github.com/octocat", "followers_url": "https://api.github.com/users/octocat/followers", "following_url": "https://api.github.com/users/octocat/following", "gists_url": "https://api.github.com/users/octocat/gists", "starred_url": "https://api.github.com/users/octocat/starred", "subscriptions_url": "https://api.github.com/users/octocat/subscriptions", "organizations_url": "https://api.github.com/users/octocat/orgs", "repos_url": "https://api.github.com/users/octocat/repos", "events_url": "https://api.github.com/users/octocat/events", "received_events_url": "https://api.github.com/users/octocat/received_events", "type": "User", "site_admin": false},
+        "installation": {"id": 42, "node_id": "I_42"}
+    }"#;
+
+    #[test]
+    fn webhook_events_become_payloads() {
+        let event = webhook_event("pull_request", PULL_REQUEST.as_bytes()).unwrap();
+        assert_eq!(
+            webhook_event_type(&event.kind).as_deref(),
+            Some("pull_request")
+        );
+
+        let payload = WebhookPayload::try_from(&event).unwrap();
+        assert_eq!(payload.action.as_deref(), Some("opened"));
+        let pr = payload.pull_request.unwrap();
+        assert_eq!(pr.number, 7);
+        assert_eq!(pr.title, "Add a thing");
+        assert_eq!(pr.user.login, "octocat");
+        assert!(pr.has_label("bug"));
+        assert_eq!(pr.head.unwrap().ref_name, "feature");
+        let repository = payload.repository.unwrap();
+        assert_eq!(repository.full_name, "octo/nexus");
+        assert_eq!(repository.default_branch.as_deref(), Some("main"));
+        assert_eq!(payload.sender.unwrap().login, "octocat");
+        assert_eq!(payload.installation.unwrap().id, 42);
+
+        let ::octocrab::models::webhook_events::WebhookEventPayload::PullRequest(specific) =
+            &event.specific
+        else {
+            panic!("not a pull request event");
+        };
+        let pr = PullRequest::try_from(&specific.pull_request).unwrap();
+        assert_eq!(pr.html_url, "https://github.com/octo/nexus/pull/7");
+        let author = User::from(event.sender.as_ref().unwrap());
+        assert_eq!(author.html_url, "https://github.com/octocat");
+    }
+
+    #[test]
+    fn events_api_events_become_payloads() {
+        let event: Event = serde_json::from_str(
+            r#"{
+                "id": "1",
+                "type": "IssuesEvent",
+                "actor": {"id": 1, "login": "octocat", "display_login": "octocat", "gravatar_id": "", "url": "https://api.github.com/users/octocat", "avatar_url": "https://github.com/images/octocat.gif"},
+                "repo": {"id": 2, "name": "octo/nexus", "url": "https://api.github.com/repos/octo/nexus"},
+                "payload": {
+                    "action": "closed",
+                    "issue": {"id": 3, "node_id": "I_3", "url": "https://api.github.com/repos/octo/nexus/issues/5", "repository_url": "https://api.github.com/repos/octo/nexus", "labels_url": "https://api.github.com/repos/octo/nexus/issues/5/labels{/name}", "comments_url": "https://api.github.com/repos/octo/nexus/issues/5/comments", "events_url": "https://api.github.com/repos/octo/nexus/issues/5/events", "html_url": "https://github.com/octo/nexus/issues/5", "number": 5, "state": "closed", "title": "Broken", "body": null, "user": {"login": "octocat", "id": 1, "node_id": "U_1", "avatar_url": "https://github.com/images/octocat.gif", "gravatar_id": "", "url": "https://api.github.com/users/octocat", "html_url": "https://github.com/octocat", "followers_url": "https://api.github.com/users/octocat/followers", "following_url": "https://api.github.com/users/octocat/following", "gists_url": "https://api.github.com/users/octocat/gists", "starred_url": "https://api.github.com/users/octocat/starred", "subscriptions_url": "https://api.github.com/users/octocat/subscriptions", "organizations_url": "https://api.github.com/users/octocat/orgs", "repos_url": "https://api.github.com/users/octocat/repos", "events_url": "https://api.github.com/users/octocat/events", "received_events_url": "https://api.github.com/users/octocat/received_events", "type": "User", "site_admin": false}, "labels": [], "assignees": [], "author_association": "OWNER", "locked": false, "comments": 0, "created_at": "2024-05-01T00:00:00Z", "updated_at": "2024-05-02T00:00:00Z"}
+                },
+                "public": true,
+                "created_at": "2024-05-02T00:00:00Z"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(event_type(&event.r#type).as_deref(), Some("issues"));
+
+        let payload = WebhookPayload::try_from(&event).unwrap();
+        assert_eq!(payload.action.as_deref(), Some("closed"));
+        let issue = payload.issue.unwrap();
+        assert_eq!(issue.number, 5);
+        assert_eq!(issue.title, "Broken");
+        let repository = payload.repository.unwrap();
+        assert_eq!(repository.name, "nexus");
+        assert_eq!(repository.full_name, "octo/nexus");
+        assert_eq!(repository.html_url, "https://github.com/octo/nexus");
+        assert_eq!(payload.sender.unwrap().login, "octocat");
+    }
+
+    #[test]
+    fn event_type_names() {
+        assert_eq!(
+            event_type(&EventType::PullRequestReviewCommentEvent).as_deref(),
+            Some("pull_request_review_comment")
+        );
+        assert_eq!(
+            event_type(&EventType::UnknownEvent("Custom".to_string())),
+            None
+        );
+        assert_eq!(
+            web_url("https://ghe.example.com/api/v3/repos/o/r"),
+            "https://ghe.example.com"
+        );
+        assert_eq!(
+            web_url("https://api.github.com/repos/o/r"),
+            "https://github.com"
+        );
+    }
+}
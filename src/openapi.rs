@@ -0,0 +1,38 @@
+// The OpenAPI document for the HTTP API, kept by hand next to the routes in
+// `commands/serve.rs`; add a path there whenever a route is added, or the test
+// beside the router fails.
+
+use serde_json::Value;
+
+const DOCUMENT: &str = include_str!("openapi.json");
+
+// Swagger UI from its CDN, with the document inlined so the page needs no second
+// authenticated request
+const SWAGGER_UI: &str = r##"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>nexus API</title>
+<link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+<script>
+SwaggerUIBundle({ spec: {spec}, dom_id: "#swagger-ui" });
+</script>
+</body>
+</html>
+"##;
+
+pub fn document() -> Value {
+    let mut document: Value = serde_json::from_str(DOCUMENT).expect("openapi.json is valid JSON");
+    document["info"]["version"] = Value::from(env!("CARGO_PKG_VERSION"));
+    document
+}
+
+pub fn swagger_ui() -> String {
+    // `</` would end the script element early
+    let spec = document().to_string().replace("</", "<\\/");
+    SWAGGER_UI.replace("{spec}", &spec)
+}
@@ -0,0 +1,982 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Mutex, OwnedSemaphorePermit, Semaphore},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    anomaly::{self, Signal},
+    cloud::{self, FunctionAuth},
+    config::OutboundConfig,
+    github::{self, GithubError},
+    service::AppState,
+};
+
+// `outbound-completed:{idempotency key}` in the shared store, set once a delivery succeeded
+const COMPLETED: &str = "outbound-completed:";
+
+/// What an outbound delivery sends. Kept as plain data so pending retries can be
+/// written to disk and picked up again after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Request {
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+        body: serde_json::Value,
+    },
+    // a forwarded delivery, sent byte for byte so the signature over it stays valid
+    Forward {
+        url: String,
+        headers: HashMap<String, String>,
+        body: String,
+    },
+    Dispatch {
+        repo: String,
+        event_type: String,
+        client_payload: serde_json::Value,
+    },
+    // a cloud function; credentials are looked up on each attempt
+    Function {
+        url: String,
+        auth: FunctionAuth,
+        headers: HashMap<String, String>,
+        body: serde_json::Value,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    // a short name for logs, e.g. `deploy:production` or `dispatch:org/repo`
+    pub target: String,
+    pub request: Request,
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    // sent as Idempotency-Key, and once delivered the same key is not sent again
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl Delivery {
+    pub fn new(target: impl Into<String>, request: Request) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let created_at = Utc::now();
+        Self {
+            id: format!(
+                "{}-{}",
+                created_at.timestamp_micros(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ),
+            target: target.into(),
+            request,
+            attempts: 0,
+            created_at,
+            idempotency_key: None,
+        }
+    }
+
+    // `scope` names what caused the delivery, usually the GitHub delivery GUID; together
+    // with the target it gives the same key when that cause is processed again
+    pub fn idempotent(mut self, scope: Option<&str>) -> Self {
+        self.idempotency_key = scope.map(|scope| {
+            let digest = Sha256::digest(format!("{}\n{}", scope, self.target));
+            hex::encode(&digest[..16])
+        });
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct DeliveryError {
+    pub attempts: u32,
+    pub message: String,
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (after {} attempts)", self.message, self.attempts)
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+struct AttemptError {
+    message: String,
+    retryable: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    #[default]
+    Closed,
+    // failing; deliveries are refused until the cooldown has passed
+    Open,
+    // one probe delivery is let through to see whether the target recovered
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Circuit {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    // when the circuit last opened or let a probe through
+    pub opened_at: Option<DateTime<Utc>>,
+}
+
+/// Deliveries that have not succeeded or given up yet, mirrored to
+/// `outbound.state_path` when one is configured, and a circuit breaker per target.
+#[derive(Clone, Default)]
+pub struct Outbound {
+    pending: Arc<Mutex<HashMap<String, Delivery>>>,
+    circuits: Arc<std::sync::Mutex<HashMap<String, Circuit>>>,
+    // created on first use, keyed by target; `None` holds the global limit
+    limits: Arc<std::sync::Mutex<HashMap<Option<String>, Arc<Semaphore>>>>,
+}
+
+impl Outbound {
+    // waits for a free slot under both the target's and the global in-flight limit; the
+    // target's comes first so deliveries queued behind a slow target hold no global slot
+    async fn acquire(&self, config: &OutboundConfig, target: &str) -> Vec<OwnedSemaphorePermit> {
+        let target_limit = config
+            .target_limits
+            .get(target)
+            .copied()
+            .unwrap_or(config.max_in_flight_per_target);
+        let limits = [(Some(target), target_limit), (None, config.max_in_flight)];
+
+        let mut permits = Vec::new();
+        for (key, limit) in limits {
+            if limit == 0 {
+                continue;
+            }
+            let semaphore = self
+                .limits
+                .lock()
+                .unwrap()
+                .entry(key.map(String::from))
+                .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                .clone();
+            // the semaphores are never closed
+            if let Ok(permit) = semaphore.acquire_owned().await {
+                permits.push(permit);
+            }
+        }
+        permits
+    }
+
+    fn allow(&self, config: &OutboundConfig, target: &str) -> bool {
+        if config.circuit_failure_threshold == 0 {
+            return true;
+        }
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(target.to_string()).or_default();
+        if circuit.state == CircuitState::Closed {
+            return true;
+        }
+
+        // a probe that never reported back (e.g. its task was cancelled) frees up again
+        // after another cooldown
+        let cooldown = chrono::Duration::seconds(config.circuit_cooldown_secs as i64);
+        let now = Utc::now();
+        if circuit.opened_at.is_some_and(|at| now < at + cooldown) {
+            return false;
+        }
+        circuit.state = CircuitState::HalfOpen;
+        circuit.opened_at = Some(now);
+        true
+    }
+
+    fn report(&self, config: &OutboundConfig, target: &str, healthy: bool) {
+        if config.circuit_failure_threshold == 0 {
+            return;
+        }
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(target.to_string()).or_default();
+        if healthy {
+            if circuit.state != CircuitState::Closed {
+                info!("Circuit for {} closed", target);
+            }
+            *circuit = Circuit::default();
+            return;
+        }
+
+        circuit.consecutive_failures += 1;
+        if circuit.state == CircuitState::HalfOpen
+            || circuit.consecutive_failures >= config.circuit_failure_threshold
+        {
+            if circuit.state == CircuitState::Closed {
+                warn!(
+                    "Circuit for {} opened after {} consecutive failures",
+                    target, circuit.consecutive_failures
+                );
+            }
+            circuit.state = CircuitState::Open;
+            circuit.opened_at = Some(Utc::now());
+        }
+    }
+
+    pub fn circuits(&self) -> HashMap<String, Circuit> {
+        self.circuits.lock().unwrap().clone()
+    }
+
+    async fn track(&self, config: &OutboundConfig, delivery: &Delivery) {
+        let mut pending = self.pending.lock().await;
+        pending.insert(delivery.id.clone(), delivery.clone());
+        persist(config, &pending).await;
+    }
+
+    async fn untrack(&self, config: &OutboundConfig, id: &str) {
+        let mut pending = self.pending.lock().await;
+        if pending.remove(id).is_some() {
+            persist(config, &pending).await;
+        }
+    }
+
+    pub async fn pending(&self) -> Vec<Delivery> {
+        self.pending.lock().await.values().cloned().collect()
+    }
+
+    // resolves once no delivery is in flight or waiting to retry
+    pub async fn drained(&self) {
+        while !self.pending.lock().await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+// retries 5xx responses, rate limits and network errors with exponential backoff
+pub async fn deliver(state: &AppState, mut delivery: Delivery) -> Result<(), DeliveryError> {
+    let config = &state.config.outbound;
+    let max_attempts = config.max_attempts.max(1);
+
+    // a replayed delivery or a retry that outlived a crash may already have gone through
+    if let Some(key) = &delivery.idempotency_key {
+        match state.store.value(&format!("{}{}", COMPLETED, key)).await {
+            Ok(Some(at)) => {
+                info!(
+                    "Skipping delivery to {}, already completed at {}",
+                    delivery.target, at
+                );
+                state.outbound.untrack(config, &delivery.id).await;
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to check whether {} was delivered: {}", key, e),
+        }
+    }
+
+    loop {
+        if !state.outbound.allow(config, &delivery.target) {
+            state.outbound.untrack(config, &delivery.id).await;
+            return Err(DeliveryError {
+                attempts: delivery.attempts,
+                message: format!("circuit for {} is open", delivery.target),
+            });
+        }
+
+        delivery.attempts += 1;
+        state.outbound.track(config, &delivery).await;
+
+        let permits = state.outbound.acquire(config, &delivery.target).await;
+        let budget = timeout(config, &delivery.target);
+        let result = match tokio::time::timeout(budget, attempt(state, &delivery)).await {
+            Ok(result) => result,
+            // also covers credential lookups and the GitHub client's own retries
+            Err(_) => {
+                state.timeouts.record_target(&delivery.target);
+                Err(AttemptError {
+                    message: format!("timed out after {}s", budget.as_secs()),
+                    retryable: true,
+                })
+            }
+        };
+        drop(permits);
+        // a 4xx still proves the target is up
+        let healthy = result.as_ref().map_or_else(|e| !e.retryable, |_| true);
+        state.outbound.report(config, &delivery.target, healthy);
+        if !healthy && delivery.target != anomaly::ALERT_TARGET {
+            state.anomalies.record(Signal::SinkErrors);
+        }
+
+        let error = match result {
+            Ok(()) => {
+                complete(state, &delivery).await;
+                state.outbound.untrack(config, &delivery.id).await;
+                return Ok(());
+            }
+            Err(e) if !e.retryable || delivery.attempts >= max_attempts => {
+                state.outbound.untrack(config, &delivery.id).await;
+                return Err(DeliveryError {
+                    attempts: delivery.attempts,
+                    message: e.message,
+                });
+            }
+            Err(e) => e,
+        };
+
+        let wait = backoff(config, delivery.attempts);
+        warn!(
+            "Delivery to {} failed ({}), retrying in {}ms",
+            delivery.target,
+            error.message,
+            wait.as_millis()
+        );
+        tokio::time::sleep(wait).await;
+    }
+}
+
+// picks up deliveries that were still retrying when the process last stopped
+pub async fn resume(state: Arc<AppState>) {
+    let Some(path) = &state.config.outbound.state_path else {
+        return;
+    };
+    let deliveries: Vec<Delivery> = match tokio::fs::read(path).await {
+        Ok(data) => match serde_json::from_slice(&data) {
+            Ok(deliveries) => deliveries,
+            Err(e) => {
+                error!(
+                    "Ignoring unreadable outbound state {}: {}",
+                    path.display(),
+                    e
+                );
+                return;
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            error!("Failed to read outbound state {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    // after a reload, what the previous generation is still retrying is left to it
+    let deliveries: Vec<Delivery> = {
+        let pending = state.outbound.pending.lock().await;
+        deliveries
+            .into_iter()
+            .filter(|d| !pending.contains_key(&d.id))
+            .collect()
+    };
+    if !deliveries.is_empty() {
+        info!("Resuming {} pending outbound deliveries", deliveries.len());
+    }
+    for delivery in deliveries {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let target = delivery.target.clone();
+            match deliver(&state, delivery).await {
+                Ok(()) => info!("Delivered resumed request to {}", target),
+                Err(e) => error!("Resumed delivery to {} failed: {}", target, e),
+            }
+        });
+    }
+}
+
+async fn complete(state: &AppState, delivery: &Delivery) {
+    let Some(key) = &delivery.idempotency_key else {
+        return;
+    };
+    let key = format!("{}{}", COMPLETED, key);
+    let ttl = state.config.outbound.idempotency_ttl_secs;
+    let result = async {
+        state
+            .store
+            .set_if_absent(&key, &Utc::now().to_rfc3339())
+            .await?;
+        state.store.expire(&key, ttl).await
+    };
+    if let Err(e) = result.await {
+        warn!(
+            "Failed to record the delivery to {} as completed: {}",
+            delivery.target, e
+        );
+    }
+}
+
+// a JSON body to a plain HTTP endpoint; the delivery's headers may set their own content type
+async fn post(
+    state: &AppState,
+    delivery: &Delivery,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Vec<u8>,
+) -> Result<(), AttemptError> {
+    let mut builder = state.http_client.post(url);
+    if !headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("content-type"))
+    {
+        builder = builder.header("content-type", "application/json");
+    }
+    if let Some(key) = &delivery.idempotency_key {
+        builder = builder.header("idempotency-key", key);
+    }
+    for (name, value) in headers {
+        builder = builder.header(name, value);
+    }
+
+    let response = builder.body(body).send().await.map_err(|e| AttemptError {
+        retryable: e.is_timeout() || e.is_connect(),
+        message: e.to_string(),
+    })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(AttemptError {
+        message: format!("{} returned {}", url, status),
+        retryable: status.is_server_error() || status.as_u16() == 429,
+    })
+}
+
+async fn attempt(state: &AppState, delivery: &Delivery) -> Result<(), AttemptError> {
+    match &delivery.request {
+        Request::Http { url, headers, body } => {
+            let body = serde_json::to_vec(body).unwrap_or_default();
+            post(state, delivery, url, headers, body).await
+        }
+        Request::Forward { url, headers, body } => {
+            post(state, delivery, url, headers, body.clone().into_bytes()).await
+        }
+        Request::Dispatch {
+            repo,
+            event_type,
+            client_payload,
+        } => {
+            let Some(token) = state.github_token(repo).await else {
+                return Err(AttemptError {
+                    message: format!("no GitHub token is available for {}", repo),
+                    retryable: false,
+                });
+            };
+            github::create_repository_dispatch(
+                &state.github,
+                &token,
+                repo,
+                event_type,
+                client_payload,
+            )
+            .await
+            .map_err(|e| AttemptError {
+                retryable: is_retryable(&e),
+                message: e.to_string(),
+            })
+        }
+        Request::Function {
+            url,
+            auth,
+            headers,
+            body,
+        } => {
+            let parsed = reqwest::Url::parse(url).map_err(|e| AttemptError {
+                message: format!("invalid function URL {}: {}", url, e),
+                retryable: false,
+            })?;
+            // signed as sent, so the body is serialized here rather than by reqwest
+            let body = serde_json::to_vec(body).unwrap_or_default();
+            let credentials = cloud::authorize(&state.http_client, auth, &parsed, &body)
+                .await
+                .map_err(|e| AttemptError {
+                    message: e.message,
+                    retryable: e.retryable,
+                })?;
+
+            let mut builder = state.http_client.post(parsed);
+            if let Some(key) = &delivery.idempotency_key {
+                builder = builder.header("idempotency-key", key);
+            }
+            for (name, value) in function_headers(headers, credentials) {
+                builder = builder.header(name, value);
+            }
+
+            let response = builder.body(body).send().await.map_err(|e| AttemptError {
+                retryable: e.is_timeout() || e.is_connect(),
+                message: e.to_string(),
+            })?;
+            let status = response.status();
+            // a synchronous Lambda invocation answers 200 even when the function failed
+            let function_error = response
+                .headers()
+                .get("x-amz-function-error")
+                .map(|v| v.to_str().unwrap_or_default().to_string());
+            if let Some(kind) = function_error {
+                let message = response.text().await.unwrap_or_default();
+                return Err(AttemptError {
+                    message: format!(
+                        "{} failed ({}): {}",
+                        url,
+                        kind,
+                        message.chars().take(200).collect::<String>()
+                    ),
+                    retryable: true,
+                });
+            }
+            if status.is_success() {
+                return Ok(());
+            }
+            Err(AttemptError {
+                message: format!("{} returned {}", url, status),
+                retryable: status.is_server_error() || status.as_u16() == 429,
+            })
+        }
+    }
+}
+
+// each header once: the target's own, then the credentials, whose signed values must be
+// sent as they were signed, e.g. SigV4's content type
+fn function_headers(
+    headers: &HashMap<String, String>,
+    credentials: Vec<(String, String)>,
+) -> HashMap<String, String> {
+    let mut merged = HashMap::from([("content-type".to_string(), "application/json".to_string())]);
+    for (name, value) in headers {
+        merged.insert(name.to_lowercase(), value.clone());
+    }
+    for (name, value) in credentials {
+        merged.insert(name.to_lowercase(), value);
+    }
+    merged
+}
+
+// `outbound.timeout_secs` unless the target has its own entry in `target_timeouts`
+fn timeout(config: &OutboundConfig, target: &str) -> Duration {
+    let secs = config
+        .target_timeouts
+        .get(target)
+        .copied()
+        .unwrap_or(config.timeout_secs);
+    Duration::from_secs(secs)
+}
+
+fn is_retryable(e: &GithubError) -> bool {
+    match e {
+        GithubError::Http(e) => {
+            e.is_timeout() || e.is_connect() || e.status().is_some_and(|s| s.is_server_error())
+        }
+        GithubError::Api { status, .. } => status.is_server_error(),
+        GithubError::RateLimited { .. } => true,
+        GithubError::GraphQl { .. } => false,
+    }
+}
+
+// doubles from `initial_backoff_ms` up to `max_backoff_ms`; jitter picks a point in
+// the upper half so retries from a burst of failures spread out
+fn backoff(config: &OutboundConfig, attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    let ms = config
+        .initial_backoff_ms
+        .saturating_mul(1 << exponent)
+        .min(config.max_backoff_ms);
+    let ms = if config.jitter && ms > 1 {
+        rand::thread_rng().gen_range(ms / 2..=ms)
+    } else {
+        ms
+    };
+    Duration::from_millis(ms)
+}
+
+async fn persist(config: &OutboundConfig, pending: &HashMap<String, Delivery>) {
+    let Some(path) = &config.state_path else {
+        return;
+    };
+    let deliveries: Vec<&Delivery> = pending.values().collect();
+    let data = match serde_json::to_vec(&deliveries) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to serialize outbound state: {}", e);
+            return;
+        }
+    };
+
+    // written next to the target and renamed so a crash never leaves half a file
+    let tmp = path.with_extension("tmp");
+    if let Err(e) = write_atomic(&tmp, path, &data).await {
+        error!("Failed to write outbound state {}: {}", path.display(), e);
+    }
+}
+
+// readable by the owner only, since requests carry their credential headers
+async fn write_atomic(tmp: &Path, path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(tmp).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(tmp, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::{
+        Router,
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+    };
+    use std::collections::VecDeque;
+
+    type Received = (HeaderMap, Vec<u8>);
+
+    // records what it receives and answers with the queued statuses, then 200
+    #[derive(Clone, Default)]
+    struct Target {
+        statuses: Arc<std::sync::Mutex<VecDeque<u16>>>,
+        received: Arc<std::sync::Mutex<Vec<Received>>>,
+    }
+
+    impl Target {
+        async fn start(statuses: &[u16]) -> (Self, String) {
+            let target = Target::default();
+            target.statuses.lock().unwrap().extend(statuses);
+            let app = Router::new()
+                .route("/", post(receive))
+                .with_state(target.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            (target, url)
+        }
+
+        fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        State(target): State<Target>,
+        headers: HeaderMap,
+        body: bytes::Bytes,
+    ) -> StatusCode {
+        target
+            .received
+            .lock()
+            .unwrap()
+            .push((headers, body.to_vec()));
+        let status = target.statuses.lock().unwrap().pop_front().unwrap_or(200);
+        StatusCode::from_u16(status).unwrap()
+    }
+
+    fn state(outbound: OutboundConfig) -> AppState {
+        AppState::new(None).with_config(Config {
+            outbound,
+            ..Default::default()
+        })
+    }
+
+    fn fast() -> OutboundConfig {
+        OutboundConfig {
+            initial_backoff_ms: 1,
+            max_backoff_ms: 1,
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    fn http(url: &str, headers: &[(&str, &str)]) -> Request {
+        Request::Http {
+            url: url.to_string(),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: serde_json::json!({"ok": true}),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let config = OutboundConfig {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+            jitter: false,
+            ..Default::default()
+        };
+        let waits: Vec<u128> = (1..=6)
+            .map(|attempt| backoff(&config, attempt).as_millis())
+            .collect();
+        assert_eq!(waits, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(backoff(&config, u32::MAX).as_millis(), 1_000);
+    }
+
+    #[test]
+    fn jitter_stays_in_the_upper_half() {
+        let config = OutboundConfig {
+            initial_backoff_ms: 400,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let ms = backoff(&config, 2).as_millis();
+            assert!((400..=800).contains(&ms), "{}", ms);
+        }
+    }
+
+    #[tokio::test]
+    async fn server_errors_and_rate_limits_are_retried() {
+        let (target, url) = Target::start(&[503, 429]).await;
+        let state = state(fast());
+
+        deliver(&state, Delivery::new("test", http(&url, &[])))
+            .await
+            .unwrap();
+        assert_eq!(target.received().len(), 3);
+        assert!(state.outbound.pending().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (target, url) = Target::start(&[404]).await;
+        let state = state(fast());
+
+        let error = deliver(&state, Delivery::new("test", http(&url, &[])))
+            .await
+            .unwrap_err();
+        assert_eq!(error.attempts, 1);
+        assert_eq!(target.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (target, url) = Target::start(&[500; 10]).await;
+        let state = state(OutboundConfig {
+            max_attempts: 3,
+            circuit_failure_threshold: 0,
+            ..fast()
+        });
+
+        let error = deliver(&state, Delivery::new("test", http(&url, &[])))
+            .await
+            .unwrap_err();
+        assert_eq!(error.attempts, 3);
+        assert_eq!(target.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn pending_deliveries_are_persisted_privately() {
+        let dir = std::env::temp_dir().join(format!("nexus-outbound-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("outbound.json");
+        let config = OutboundConfig {
+            state_path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        let outbound = Outbound::default();
+        let delivery = Delivery::new(
+            "test",
+            http("http://localhost/", &[("authorization", "Bearer secret")]),
+        );
+        outbound.track(&config, &delivery).await;
+
+        let saved: Vec<Delivery> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].id, delivery.id);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        outbound.untrack(&config, &delivery.id).await;
+        let saved: Vec<Delivery> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(saved.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_leaves_deliveries_still_in_flight_alone() {
+        let dir = std::env::temp_dir().join(format!("nexus-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (target, url) = Target::start(&[]).await;
+        let config = OutboundConfig {
+            state_path: Some(dir.join("outbound.json")),
+            ..fast()
+        };
+        let delivery = Delivery::new("test", http(&url, &[]));
+
+        // a reload keeps the `Outbound` whose task is still retrying the delivery
+        let state = Arc::new(state(config.clone()));
+        state.outbound.track(&config, &delivery).await;
+        resume(state.clone()).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(target.received().is_empty());
+
+        // a restart starts with nothing in flight and sends it
+        let restarted = Arc::new(self::state(config));
+        resume(restarted).await;
+        for _ in 0..100 {
+            if !target.received().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(target.received().len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let config = OutboundConfig {
+            circuit_failure_threshold: 3,
+            ..Default::default()
+        };
+        let outbound = Outbound::default();
+        for _ in 0..2 {
+            assert!(outbound.allow(&config, "a"));
+            outbound.report(&config, "a", false);
+        }
+        // a success in between starts the count again
+        outbound.report(&config, "a", true);
+        for _ in 0..2 {
+            outbound.report(&config, "a", false);
+        }
+        assert!(outbound.allow(&config, "a"));
+
+        outbound.report(&config, "a", false);
+        assert_eq!(outbound.circuits()["a"].state, CircuitState::Open);
+        assert!(!outbound.allow(&config, "a"));
+        // other targets are unaffected
+        assert!(outbound.allow(&config, "b"));
+    }
+
+    #[test]
+    fn circuit_lets_one_probe_through_after_the_cooldown() {
+        let config = OutboundConfig {
+            circuit_failure_threshold: 1,
+            circuit_cooldown_secs: 0,
+            ..Default::default()
+        };
+        let outbound = Outbound::default();
+        outbound.report(&config, "a", false);
+        assert_eq!(outbound.circuits()["a"].state, CircuitState::Open);
+
+        assert!(outbound.allow(&config, "a"));
+        assert_eq!(outbound.circuits()["a"].state, CircuitState::HalfOpen);
+        // a failed probe opens it again, a successful one closes it
+        outbound.report(&config, "a", false);
+        assert_eq!(outbound.circuits()["a"].state, CircuitState::Open);
+        assert!(outbound.allow(&config, "a"));
+        outbound.report(&config, "a", true);
+        assert_eq!(outbound.circuits()["a"].state, CircuitState::Closed);
+        assert_eq!(outbound.circuits()["a"].consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn open_circuit_refuses_deliveries() {
+        let (target, url) = Target::start(&[500; 10]).await;
+        let state = state(OutboundConfig {
+            max_attempts: 10,
+            circuit_failure_threshold: 2,
+            ..fast()
+        });
+
+        let error = deliver(&state, Delivery::new("test", http(&url, &[])))
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, "circuit for test is open");
+        assert_eq!(target.received().len(), 2);
+
+        deliver(&state, Delivery::new("test", http(&url, &[])))
+            .await
+            .unwrap_err();
+        assert_eq!(target.received().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_saturated_target_does_not_hold_global_slots() {
+        let config = OutboundConfig {
+            max_in_flight: 2,
+            max_in_flight_per_target: 1,
+            ..Default::default()
+        };
+        let outbound = Outbound::default();
+        let _slow = outbound.acquire(&config, "slow").await;
+
+        // waits for the slow target's only slot
+        let queued = tokio::spawn({
+            let (outbound, config) = (outbound.clone(), config.clone());
+            async move { outbound.acquire(&config, "slow").await.len() }
+        });
+        tokio::task::yield_now().await;
+
+        let other = tokio::time::timeout(Duration::from_secs(1), outbound.acquire(&config, "fast"))
+            .await
+            .expect("another target still gets a global slot");
+        assert_eq!(other.len(), 2);
+        assert!(!queued.is_finished());
+        queued.abort();
+    }
+
+    #[test]
+    fn signed_headers_are_sent_once_as_signed() {
+        let headers = HashMap::from([
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("x-source".to_string(), "nexus".to_string()),
+        ]);
+        let credentials = vec![
+            ("content-type".to_string(), "application/json".to_string()),
+            (
+                "authorization".to_string(),
+                "AWS4-HMAC-SHA256 ...".to_string(),
+            ),
+        ];
+        let merged = function_headers(&headers, credentials);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged["content-type"], "application/json");
+        assert_eq!(merged["x-source"], "nexus");
+
+        // without credentials the target's own content type stands
+        assert_eq!(
+            function_headers(&headers, Vec::new())["content-type"],
+            "text/plain"
+        );
+    }
+
+    #[tokio::test]
+    async fn functions_get_one_content_type_and_their_credentials() {
+        let (target, url) = Target::start(&[]).await;
+        let token_file = std::env::temp_dir().join(format!("nexus-oidc-{}", std::process::id()));
+        std::fs::write(&token_file, "id-token\n").unwrap();
+        let request = Request::Function {
+            url,
+            auth: FunctionAuth::Oidc {
+                audience: "nexus".to_string(),
+                token_file: Some(token_file.clone()),
+            },
+            headers: HashMap::from([("x-github-event".to_string(), "push".to_string())]),
+            body: serde_json::json!({"event": "push"}),
+        };
+
+        let delivered = deliver(&state(fast()), Delivery::new("test", request)).await;
+        std::fs::remove_file(&token_file).unwrap();
+        delivered.unwrap();
+        let received = target.received();
+        let (headers, body) = &received[0];
+        let content_types: Vec<_> = headers.get_all("content-type").iter().collect();
+        assert_eq!(content_types, ["application/json"]);
+        assert_eq!(headers["authorization"], "Bearer id-token");
+        assert_eq!(headers["x-github-event"], "push");
+        assert_eq!(body, br#"{"event":"push"}"#);
+    }
+}
@@ -0,0 +1,164 @@
+// central constraints on what the automations may do on GitHub, checked right before
+// each merge, label, comment, assignment, review request or close
+
+use globset::{Glob, GlobSetBuilder};
+use std::fmt;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{PolicyAction, PolicyEffect, PolicyRule},
+    github, repos,
+    service::AppState,
+    webhook::{Issue, PullRequest},
+};
+
+impl fmt::Display for PolicyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PolicyAction::Merge => "merge",
+            PolicyAction::Label => "label",
+            PolicyAction::Unlabel => "unlabel",
+            PolicyAction::Comment => "comment",
+            PolicyAction::Assign => "assign",
+            PolicyAction::RequestReview => "request_review",
+            PolicyAction::Close => "close",
+            PolicyAction::DeleteBranch => "delete_branch",
+            PolicyAction::Milestone => "milestone",
+            PolicyAction::Lock => "lock",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The pull request or issue an action would touch.
+pub struct Subject<'a> {
+    pub repo: &'a str,
+    pub number: u64,
+    pub author: &'a str,
+    pub labels: Vec<&'a str>,
+    pub pull_request: bool,
+}
+
+impl<'a> Subject<'a> {
+    pub fn pull_request(repo: &'a str, pr: &'a PullRequest) -> Self {
+        Subject {
+            repo,
+            number: pr.number,
+            author: &pr.user.login,
+            labels: pr.labels.iter().map(|l| l.name.as_str()).collect(),
+            pull_request: true,
+        }
+    }
+
+    pub fn issue(repo: &'a str, issue: &'a Issue) -> Self {
+        Subject {
+            repo,
+            number: issue.number,
+            author: &issue.user.login,
+            labels: issue.labels.iter().map(|l| l.name.as_str()).collect(),
+            pull_request: issue.is_pull_request(),
+        }
+    }
+}
+
+// true when every action is allowed; refusals are logged. Changed files are only fetched
+// when a rule needs them, and a failed fetch refuses the action
+pub async fn allows(
+    state: &AppState,
+    token: &str,
+    actions: &[PolicyAction],
+    subject: &Subject<'_>,
+) -> bool {
+    let config = &state.config.policy;
+    if !config.enabled {
+        return true;
+    }
+
+    let mut files: Option<Vec<String>> = None;
+    for &action in actions {
+        let rules: Vec<&PolicyRule> = config
+            .rules
+            .iter()
+            .filter(|rule| rule.actions.is_empty() || rule.actions.contains(&action))
+            .filter(|rule| rule.repos.is_empty() || repos::matches(&rule.repos, subject.repo))
+            .collect();
+
+        let needs_files = subject.pull_request && rules.iter().any(|rule| !rule.paths.is_empty());
+        if needs_files && files.is_none() {
+            match github::list_pull_request_files(
+                &state.github,
+                token,
+                subject.repo,
+                subject.number,
+            )
+            .await
+            {
+                Ok(list) => files = Some(list.into_iter().map(|f| f.filename).collect()),
+                Err(e) => {
+                    error!(
+                        "Policy could not list files of {}#{}, refusing to {}: {}",
+                        subject.repo, subject.number, action, e
+                    );
+                    return false;
+                }
+            }
+        }
+        let files = files.as_deref().unwrap_or_default();
+
+        let refusal = if let Some(rule) = rules
+            .iter()
+            .find(|rule| rule.effect == PolicyEffect::Deny && matches(rule, subject, files))
+        {
+            Some(rule.reason.as_deref().unwrap_or("a deny rule matched"))
+        } else if rules.iter().any(|rule| rule.effect == PolicyEffect::Allow)
+            && !rules
+                .iter()
+                .any(|rule| rule.effect == PolicyEffect::Allow && matches(rule, subject, files))
+        {
+            Some("no allow rule matched")
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            info!(
+                "Policy refused to {} {}#{}: {}",
+                action, subject.repo, subject.number, reason
+            );
+            return false;
+        }
+    }
+    true
+}
+
+fn matches(rule: &PolicyRule, subject: &Subject, files: &[String]) -> bool {
+    if !rule.authors.is_empty() && !rule.authors.iter().any(|a| a == subject.author) {
+        return false;
+    }
+    if !rule
+        .labels
+        .iter()
+        .all(|l| subject.labels.contains(&l.as_str()))
+    {
+        return false;
+    }
+    if rule.paths.is_empty() {
+        return true;
+    }
+    if !subject.pull_request {
+        return false;
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &rule.paths {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Invalid policy glob {:?}: {}", pattern, e),
+        }
+    }
+    let Ok(set) = builder.build() else {
+        return false;
+    };
+    files.iter().all(|f| set.is_match(f))
+}
@@ -0,0 +1,26 @@
+// Egress through a proxy for every outbound client: the shared `http_client`, the
+// GitHub client and ACME. Without `[proxy] url`, reqwest's own handling of the
+// HTTP(S)_PROXY, ALL_PROXY and NO_PROXY environment variables applies.
+
+use reqwest::{ClientBuilder, NoProxy, Proxy};
+
+use crate::config::ProxyConfig;
+
+pub fn builder(config: &ProxyConfig) -> Result<ClientBuilder, String> {
+    let builder = reqwest::Client::builder();
+    let Some(url) = &config.url else {
+        return Ok(builder);
+    };
+    let mut proxy = Proxy::all(url).map_err(|e| format!("invalid proxy url {}: {}", url, e))?;
+    if let Some(username) = &config.username {
+        proxy = proxy.basic_auth(username, config.password.as_deref().unwrap_or_default());
+    }
+    proxy = proxy.no_proxy(NoProxy::from_string(&config.no_proxy.join(",")));
+    Ok(builder.proxy(proxy))
+}
+
+pub fn client(config: &ProxyConfig) -> Result<reqwest::Client, String> {
+    builder(config)?
+        .build()
+        .map_err(|e| format!("failed to build the HTTP client: {}", e))
+}
@@ -0,0 +1,121 @@
+// Python bindings over the blocking API, so scripts verify and parse deliveries exactly
+// as the server does. Built into a `nexus` extension module by maturin (see
+// pyproject.toml), which turns on `python` and pyo3's `extension-module`.
+//
+//     import nexus
+//     delivery = nexus.Delivery(dict(request.headers), request.get_data(), secret=secret)
+//     if delivery.matches("pull_request.opened"):
+//         print(delivery.repository, delivery.payload["pull_request"]["title"])
+
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::{
+    blocking::{self, Delivery},
+    signature,
+};
+
+create_exception!(
+    nexus,
+    DeliveryError,
+    PyValueError,
+    "A delivery that is unsigned, badly signed or not a webhook payload."
+);
+
+fn rejected(e: blocking::Error) -> PyErr {
+    DeliveryError::new_err(e.to_string())
+}
+
+// through the json module, so payloads become plain dicts and lists
+fn to_python<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let text = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (text,))
+}
+
+#[pyfunction]
+fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    signature::verify_signature(secret, body, signature)
+}
+
+// the payload as nexus models it: fields it does not know are dropped, missing ones are None
+#[pyfunction]
+fn parse_payload<'py>(py: Python<'py>, body: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+    to_python(py, &blocking::parse(body).map_err(rejected)?)
+}
+
+/// A verified and parsed delivery.
+#[pyclass(name = "Delivery", module = "nexus", frozen)]
+struct PyDelivery {
+    inner: Delivery,
+}
+
+#[pymethods]
+impl PyDelivery {
+    // header names are matched ignoring case; without a secret the signature is not checked
+    #[new]
+    #[pyo3(signature = (headers, body, secret = None))]
+    fn new(headers: HashMap<String, String>, body: &[u8], secret: Option<&str>) -> PyResult<Self> {
+        let inner = Delivery::from_headers(secret, headers, body).map_err(rejected)?;
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    fn event_type(&self) -> &str {
+        &self.inner.event_type
+    }
+
+    #[getter]
+    fn delivery_id(&self) -> Option<&str> {
+        self.inner.delivery_id.as_deref()
+    }
+
+    #[getter]
+    fn action(&self) -> Option<&str> {
+        self.inner.action()
+    }
+
+    #[getter]
+    fn repository(&self) -> Option<&str> {
+        self.inner.repository()
+    }
+
+    #[getter]
+    fn sender(&self) -> Option<&str> {
+        self.inner.sender()
+    }
+
+    #[getter]
+    fn payload<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.inner.payload)
+    }
+
+    // `event` or `event.action`, e.g. `pull_request.opened`
+    fn matches(&self, pattern: &str) -> bool {
+        self.inner.matches(pattern)
+    }
+
+    fn matches_any(&self, patterns: Vec<String>) -> bool {
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        self.inner.matches_any(&patterns)
+    }
+
+    fn __repr__(&self) -> String {
+        let quoted = |value: Option<&str>| value.map_or("None".to_string(), |v| format!("'{}'", v));
+        format!(
+            "Delivery(event_type='{}', action={}, repository={})",
+            self.inner.event_type,
+            quoted(self.inner.action()),
+            quoted(self.inner.repository())
+        )
+    }
+}
+
+#[pymodule]
+fn nexus(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(verify_signature, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_payload, m)?)?;
+    m.add_class::<PyDelivery>()?;
+    m.add("DeliveryError", m.py().get_type::<DeliveryError>())?;
+    Ok(())
+}
@@ -0,0 +1,973 @@
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{
+        mpsc::{
+            self,
+            error::{SendTimeoutError, TrySendError},
+        },
+        watch,
+    },
+    task::JoinHandle,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    clickhouse::{self, Processed},
+    config::{FullQueuePolicy, QueueConfig, ShedRule},
+    influxdb,
+    service::AppState,
+    webhook::{self, WebhookPayload},
+};
+
+// how many finished deliveries are kept for inspection
+const RECENT_OUTCOMES: usize = 100;
+
+pub struct Job {
+    pub delivery_id: Option<String>,
+    pub event_type: String,
+    pub payload: WebhookPayload,
+    // the JSON `payload` was parsed from, as GitHub sent it
+    pub json: Bytes,
+    pub received_at: DateTime<Utc>,
+    // the job's file in `queue.journal_dir`, removed once it has been processed
+    pub journal: Option<PathBuf>,
+}
+
+impl Job {
+    // a queue journal or archive entry
+    pub fn from_entry(data: &[u8]) -> serde_json::Result<Self> {
+        let entry: Entry = serde_json::from_slice(data)?;
+        Ok(Self {
+            delivery_id: entry.delivery_id,
+            event_type: entry.event_type,
+            payload: serde_json::from_str(entry.payload.get())?,
+            json: Bytes::copy_from_slice(entry.payload.get().as_bytes()),
+            received_at: entry.received_at,
+            journal: None,
+        })
+    }
+}
+
+// a journaled job; `payload` holds the job's JSON itself rather than the parsed model, so
+// fields nexus does not model survive a restart
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    delivery_id: Option<String>,
+    event_type: String,
+    payload: Box<RawValue>,
+    received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryOutcome {
+    pub delivery_id: Option<String>,
+    pub event_type: String,
+    pub received_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub success: bool,
+    // the status the handler failed with
+    pub status: Option<u16>,
+}
+
+// workers always take the highest class that has something queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Processing {
+    #[default]
+    Running,
+    // deliveries are accepted and held; no handler runs
+    Paused,
+    // handlers run again while the held deliveries are queued behind what was waiting
+    Draining,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingStatus {
+    pub state: Processing,
+    // deliveries accepted while paused that are not queued yet
+    pub held: usize,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+}
+
+#[derive(Default)]
+struct Held {
+    state: Processing,
+    jobs: VecDeque<Job>,
+    paused_at: Option<DateTime<Utc>>,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enqueued {
+    Queued,
+    // dropped on purpose to keep room for more important events
+    Shed,
+}
+
+#[derive(Debug)]
+pub enum EnqueueError {
+    Full,
+    Closed,
+    Journal(std::io::Error),
+}
+
+impl std::fmt::Display for EnqueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnqueueError::Full => write!(f, "delivery queue is full"),
+            EnqueueError::Closed => write!(f, "delivery queue is closed"),
+            EnqueueError::Journal(e) => write!(f, "could not persist delivery: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EnqueueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EnqueueError::Journal(e) => Some(e),
+            EnqueueError::Full | EnqueueError::Closed => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct QueueMetrics {
+    pub enqueued: AtomicU64,
+    pub rejected: AtomicU64,
+    pub shed: AtomicU64,
+    pub shed_by_event: Mutex<HashMap<String, u64>>,
+    pub processed: AtomicU64,
+    pub failed: AtomicU64,
+    pub busy_workers: AtomicUsize,
+    pub workers: AtomicUsize,
+}
+
+/// The sending half of the delivery queue; handed to `AppState` so requests can
+/// enqueue verified deliveries and return before any handler runs.
+#[derive(Clone)]
+pub struct DeliveryQueue {
+    // one channel per priority class, in `Priority::ALL` order
+    senders: [mpsc::Sender<Job>; 3],
+    config: QueueConfig,
+    metrics: Arc<QueueMetrics>,
+    recent: Arc<Mutex<VecDeque<DeliveryOutcome>>>,
+    closed: Arc<AtomicBool>,
+    held: Arc<Mutex<Held>>,
+    // true while paused; workers wait on it before taking the next job
+    paused: Arc<watch::Sender<bool>>,
+}
+
+pub struct Receiver([mpsc::Receiver<Job>; 3]);
+
+impl Receiver {
+    pub(crate) async fn recv(&mut self) -> Option<Job> {
+        let [high, normal, low] = &mut self.0;
+        tokio::select! {
+            biased;
+            Some(job) = high.recv() => Some(job),
+            Some(job) = normal.recv() => Some(job),
+            Some(job) = low.recv() => Some(job),
+            else => None,
+        }
+    }
+}
+
+impl DeliveryQueue {
+    pub fn new(config: &QueueConfig) -> (Self, Receiver) {
+        let [high, normal, low] = Priority::ALL.map(|_| mpsc::channel(config.capacity.max(1)));
+        let queue = Self {
+            senders: [high.0, normal.0, low.0],
+            config: config.clone(),
+            metrics: Arc::default(),
+            recent: Arc::default(),
+            closed: Arc::default(),
+            held: Arc::default(),
+            paused: Arc::new(watch::channel(false).0),
+        };
+        (queue, Receiver([high.1, normal.1, low.1]))
+    }
+
+    // matches `event` or `event.action` against the configured classes
+    pub fn priority(&self, event_type: &str, action: Option<&str>) -> Priority {
+        let qualified = action.map(|a| format!("{}.{}", event_type, a));
+        let listed = |events: &[String]| {
+            events
+                .iter()
+                .any(|e| e == event_type || Some(e) == qualified.as_ref())
+        };
+        if listed(&self.config.high_priority) {
+            Priority::High
+        } else if listed(&self.config.low_priority) {
+            Priority::Low
+        } else {
+            Priority::Normal
+        }
+    }
+
+    // `shed_events` always go; otherwise the first matching rule keeps a sample
+    fn sheds(&self, job: &Job) -> bool {
+        if self.config.shed_events.contains(&job.event_type) {
+            return true;
+        }
+        let rule = self
+            .config
+            .shed_rules
+            .iter()
+            .find(|rule| shed_rule_matches(rule, job));
+        match rule {
+            Some(rule) => !rand::thread_rng().gen_bool(rule.sample_rate.clamp(0.0, 1.0)),
+            None => false,
+        }
+    }
+
+    fn sender(&self, priority: Priority) -> &mpsc::Sender<Job> {
+        &self.senders[priority as usize]
+    }
+
+    pub async fn enqueue(&self, job: Job) -> Result<Enqueued, EnqueueError> {
+        if self.closed.load(Ordering::Relaxed) {
+            self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(EnqueueError::Closed);
+        }
+        let priority = self.priority(&job.event_type, job.payload.action.as_deref());
+        if self.config.on_full == FullQueuePolicy::Shed {
+            let threshold = (self.capacity() as f64 * self.config.shed_threshold) as usize;
+            let depth = self.depth_of(priority);
+            if depth >= threshold && self.sheds(&job) {
+                debug!("Shedding {} event at queue depth {}", job.event_type, depth);
+                self.metrics.shed.fetch_add(1, Ordering::Relaxed);
+                *self
+                    .metrics
+                    .shed_by_event
+                    .lock()
+                    .unwrap()
+                    .entry(job.event_type.clone())
+                    .or_default() += 1;
+                return Ok(Enqueued::Shed);
+            }
+        }
+
+        let mut job = job;
+        if let Some(dir) = &self.config.journal_dir {
+            match write_journal(dir, &job).await {
+                Ok(path) => job.journal = Some(path),
+                Err(e) => {
+                    self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(EnqueueError::Journal(e));
+                }
+            }
+        }
+        let journal = job.journal.clone();
+
+        // while paused, and until the held deliveries are queued again, new ones line up behind them
+        let job = {
+            let mut held = self.held.lock().unwrap();
+            match held.state {
+                Processing::Running => Some(job),
+                _ if held.jobs.len() < self.config.max_held => {
+                    held.jobs.push_back(job);
+                    self.metrics.enqueued.fetch_add(1, Ordering::Relaxed);
+                    return Ok(Enqueued::Queued);
+                }
+                _ => None,
+            }
+        };
+
+        let result = match job {
+            Some(job) => self.send(priority, job).await,
+            None => Err(EnqueueError::Full),
+        };
+
+        match result {
+            Ok(()) => {
+                self.metrics.enqueued.fetch_add(1, Ordering::Relaxed);
+                Ok(Enqueued::Queued)
+            }
+            Err(e) => {
+                if let Some(path) = journal {
+                    let _ = tokio::fs::remove_file(path).await;
+                }
+                self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    // re-queues deliveries journaled before a crash or restart, oldest first; waits for
+    // room instead of applying the full-queue policy
+    pub async fn replay(&self) {
+        let Some(dir) = &self.config.journal_dir else {
+            return;
+        };
+        let mut paths = match read_journal_dir(dir).await {
+            Ok(paths) => paths,
+            Err(e) => {
+                error!("Failed to read queue journal {}: {}", dir.display(), e);
+                return;
+            }
+        };
+        paths.sort();
+
+        if !paths.is_empty() {
+            info!("Replaying {} journaled deliveries", paths.len());
+        }
+        for path in paths {
+            let job = tokio::fs::read(&path)
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|data| Job::from_entry(&data).map_err(|e| e.to_string()));
+            let mut job = match job {
+                Ok(job) => job,
+                Err(e) => {
+                    error!(
+                        "Skipping unreadable journal entry {}: {}",
+                        path.display(),
+                        e
+                    );
+                    let _ = tokio::fs::rename(&path, path.with_extension("failed")).await;
+                    continue;
+                }
+            };
+
+            let priority = self.priority(&job.event_type, job.payload.action.as_deref());
+            job.journal = Some(path);
+            if self.sender(priority).send(job).await.is_err() {
+                return;
+            }
+            self.metrics.enqueued.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn send(&self, priority: Priority, job: Job) -> Result<(), EnqueueError> {
+        match self.config.on_full {
+            FullQueuePolicy::Reject | FullQueuePolicy::Shed => self.try_send(priority, job),
+            FullQueuePolicy::Block => {
+                let timeout = Duration::from_millis(self.config.block_timeout_ms);
+                self.sender(priority)
+                    .send_timeout(job, timeout)
+                    .await
+                    .map_err(|e| match e {
+                        SendTimeoutError::Timeout(_) => EnqueueError::Full,
+                        SendTimeoutError::Closed(_) => EnqueueError::Closed,
+                    })
+            }
+        }
+    }
+
+    fn try_send(&self, priority: Priority, job: Job) -> Result<(), EnqueueError> {
+        self.sender(priority).try_send(job).map_err(|e| match e {
+            TrySendError::Full(_) => EnqueueError::Full,
+            TrySendError::Closed(_) => EnqueueError::Closed,
+        })
+    }
+
+    // refuses new deliveries; the workers keep going until the queue is empty
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    // stops the workers after their current delivery; new deliveries are held until resumed
+    pub fn pause(&self, reason: Option<String>) -> ProcessingStatus {
+        {
+            let mut held = self.held.lock().unwrap();
+            if held.state != Processing::Paused {
+                held.state = Processing::Paused;
+                held.paused_at = Some(Utc::now());
+                held.reason = reason;
+                self.paused.send_replace(true);
+                warn!(
+                    "Processing paused{}",
+                    held.reason
+                        .as_deref()
+                        .map(|r| format!(": {}", r))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        self.status()
+    }
+
+    // starts the workers again and queues the held deliveries in the order they arrived,
+    // after the ones that were already waiting
+    pub fn resume(&self) -> ProcessingStatus {
+        {
+            let mut held = self.held.lock().unwrap();
+            if held.state == Processing::Paused {
+                info!(
+                    "Processing resumed, {} held deliveries to queue",
+                    held.jobs.len()
+                );
+                held.state = Processing::Draining;
+                held.paused_at = None;
+                held.reason = None;
+                self.paused.send_replace(false);
+                let queue = self.clone();
+                tokio::spawn(async move { queue.release().await });
+            }
+        }
+        self.status()
+    }
+
+    // one task at a time moves held deliveries into the channels, waiting for room
+    async fn release(&self) {
+        loop {
+            let job = {
+                let mut held = self.held.lock().unwrap();
+                // paused again; what is left stays held
+                if held.state != Processing::Draining {
+                    return;
+                }
+                match held.jobs.pop_front() {
+                    Some(job) => job,
+                    None => {
+                        held.state = Processing::Running;
+                        info!("Held deliveries are all queued");
+                        return;
+                    }
+                }
+            };
+            let priority = self.priority(&job.event_type, job.payload.action.as_deref());
+            if self.sender(priority).send(job).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub fn status(&self) -> ProcessingStatus {
+        let held = self.held.lock().unwrap();
+        ProcessingStatus {
+            state: held.state,
+            held: held.jobs.len(),
+            paused_at: held.paused_at,
+            reason: held.reason.clone(),
+        }
+    }
+
+    async fn running(&self) {
+        let mut paused = self.paused.subscribe();
+        let _ = paused.wait_for(|paused| !*paused).await;
+    }
+
+    // resolves once nothing is queued or being processed, or when paused since nothing
+    // more will be processed until someone resumes
+    pub async fn drained(&self) {
+        while !*self.paused.borrow()
+            && (self.depth() > 0 || self.metrics.busy_workers.load(Ordering::Relaxed) > 0)
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        Priority::ALL.iter().map(|p| self.depth_of(*p)).sum()
+    }
+
+    pub fn depth_of(&self, priority: Priority) -> usize {
+        let sender = self.sender(priority);
+        sender.max_capacity() - sender.capacity()
+    }
+
+    // per priority class
+    pub fn capacity(&self) -> usize {
+        self.senders[0].max_capacity()
+    }
+
+    pub fn metrics(&self) -> &QueueMetrics {
+        &self.metrics
+    }
+
+    pub fn recent_outcomes(&self) -> Vec<DeliveryOutcome> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn record(&self, outcome: DeliveryOutcome) {
+        let counter = if outcome.success {
+            &self.metrics.processed
+        } else {
+            &self.metrics.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_OUTCOMES {
+            recent.pop_front();
+        }
+        recent.push_back(outcome);
+    }
+}
+
+fn shed_rule_matches(rule: &ShedRule, job: &Job) -> bool {
+    let payload = &job.payload;
+    if rule.event != job.event_type
+        || rule
+            .action
+            .as_ref()
+            .is_some_and(|a| payload.action.as_ref() != Some(a))
+    {
+        return false;
+    }
+    if !rule.non_default_branch {
+        return true;
+    }
+    // a push whose default branch is unknown is left alone
+    let default_branch = payload
+        .repository
+        .as_ref()
+        .and_then(|r| r.default_branch.as_deref());
+    match (payload.ref_name.as_deref(), default_branch) {
+        (Some(ref_name), Some(default)) => ref_name.strip_prefix("refs/heads/") != Some(default),
+        _ => false,
+    }
+}
+
+// the workers share one receiver; each takes the next job as soon as it is free
+pub fn spawn_workers(
+    state: Arc<AppState>,
+    receiver: Receiver,
+    workers: usize,
+) -> Vec<JoinHandle<()>> {
+    let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+    let workers = workers.max(1);
+    if let Some(queue) = &state.queue {
+        queue.metrics.workers.store(workers, Ordering::Relaxed);
+    }
+
+    (0..workers)
+        .map(|worker| {
+            let state = state.clone();
+            let receiver = receiver.clone();
+            tokio::spawn(async move {
+                loop {
+                    if let Some(queue) = &state.queue {
+                        queue.running().await;
+                    }
+                    let job = {
+                        let mut receiver = receiver.lock().await;
+                        let job = receiver.recv().await;
+                        // counted before the lock is released so a drain never sees an
+                        // empty queue with the job in nobody's hands
+                        if let (Some(_), Some(queue)) = (&job, &state.queue) {
+                            queue.metrics.busy_workers.fetch_add(1, Ordering::Relaxed);
+                        }
+                        job
+                    };
+                    let Some(job) = job else {
+                        info!("Delivery worker {} stopping", worker);
+                        return;
+                    };
+                    // paused while this worker waited for the job: it keeps it until resumed
+                    if let Some(queue) = &state.queue {
+                        queue.running().await;
+                    }
+                    process(&state, job).await;
+                }
+            })
+        })
+        .collect()
+}
+
+// written to a temporary name, synced and renamed so a crash never leaves half an entry;
+// the payload is not redacted, so that a replayed delivery runs as GitHub sent it
+async fn write_journal(dir: &Path, job: &Job) -> std::io::Result<PathBuf> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let payload = RawValue::from_string(String::from_utf8_lossy(&job.json).into_owned())?;
+    let data = serde_json::to_vec(&Entry {
+        delivery_id: job.delivery_id.clone(),
+        event_type: job.event_type.clone(),
+        payload,
+        received_at: job.received_at,
+    })?;
+    tokio::fs::create_dir_all(dir).await?;
+    // zero-padded so that sorting the names sorts by arrival
+    let name = format!(
+        "{:020}-{:06}",
+        job.received_at.timestamp_micros(),
+        NEXT.fetch_add(1, Ordering::Relaxed) % 1_000_000
+    );
+    let tmp = dir.join(format!("{}.tmp", name));
+    let path = dir.join(format!("{}.json", name));
+
+    // readable by the owner only, like the payloads in it
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    file.write_all(&data).await?;
+    file.sync_data().await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    Ok(path)
+}
+
+async fn read_journal_dir(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+async fn process(state: &AppState, job: Job) {
+    let metrics = state.queue.as_ref().map(|q| q.metrics.clone());
+    let started = Instant::now();
+    let result = webhook::dispatch(
+        state,
+        &job.event_type,
+        job.delivery_id.as_deref(),
+        &job.payload,
+        &job.json,
+    )
+    .await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    if let Some(metrics) = &metrics {
+        metrics.busy_workers.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    match &result {
+        Ok(()) => info!(
+            "Processed {} delivery {} in {}ms",
+            job.event_type,
+            job.delivery_id.as_deref().unwrap_or("-"),
+            duration_ms
+        ),
+        Err(e) => error!(
+            "Failed {} delivery {} after {}ms: {}",
+            job.event_type,
+            job.delivery_id.as_deref().unwrap_or("-"),
+            duration_ms,
+            e
+        ),
+    }
+
+    // at-least-once: the entry goes only after the handlers ran, successfully or not
+    if let Some(path) = &job.journal
+        && let Err(e) = tokio::fs::remove_file(path).await
+    {
+        error!("Failed to remove journal entry {}: {}", path.display(), e);
+    }
+
+    let processed = Processed {
+        event_type: &job.event_type,
+        delivery_id: job.delivery_id.as_deref(),
+        payload: &job.payload,
+        received_at: job.received_at,
+        duration_ms,
+        status: result.as_ref().err().map(|e| e.status().as_u16()),
+    };
+    clickhouse::record(state, processed);
+    influxdb::record(state, processed);
+
+    if let Some(queue) = &state.queue {
+        queue.record(DeliveryOutcome {
+            delivery_id: job.delivery_id,
+            event_type: job.event_type,
+            received_at: job.received_at,
+            duration_ms,
+            success: result.is_ok(),
+            status: result.err().map(|e| e.status().as_u16()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUSH: &str = r#"{"ref":"refs/heads/main","pusher":{"name":"octocat","email":"octocat@github.com"},"commits":[]}"#;
+
+    fn job(event_type: &str, json: &str) -> Job {
+        Job {
+            delivery_id: Some("1".to_string()),
+            event_type: event_type.to_string(),
+            payload: serde_json::from_str(json).unwrap(),
+            json: Bytes::copy_from_slice(json.as_bytes()),
+            received_at: Utc::now(),
+            journal: None,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nexus-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    const ISSUES: &str = r#"{"action":"opened"}"#;
+
+    fn queue_config(capacity: usize, on_full: FullQueuePolicy) -> QueueConfig {
+        QueueConfig {
+            capacity,
+            on_full,
+            block_timeout_ms: 50,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_refuses_deliveries_once_their_class_is_full() {
+        let (queue, mut receiver) = DeliveryQueue::new(&queue_config(2, FullQueuePolicy::Reject));
+        assert_eq!(
+            queue.enqueue(job("issues", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        assert_eq!(
+            queue.enqueue(job("issues", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        assert!(matches!(
+            queue.enqueue(job("issues", ISSUES)).await,
+            Err(EnqueueError::Full)
+        ));
+        // each priority class has its own room, and is taken first
+        assert_eq!(
+            queue.enqueue(job("deployment", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        assert_eq!(queue.depth(), 3);
+        assert_eq!(queue.depth_of(Priority::High), 1);
+        assert_eq!(receiver.recv().await.unwrap().event_type, "deployment");
+        assert_eq!(receiver.recv().await.unwrap().event_type, "issues");
+
+        let metrics = queue.metrics();
+        assert_eq!(metrics.enqueued.load(Ordering::Relaxed), 3);
+        assert_eq!(metrics.rejected.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.shed.load(Ordering::Relaxed), 0);
+
+        queue.close();
+        assert!(matches!(
+            queue.enqueue(job("issues", ISSUES)).await,
+            Err(EnqueueError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_a_free_slot() {
+        let (queue, mut receiver) = DeliveryQueue::new(&queue_config(1, FullQueuePolicy::Block));
+        queue.enqueue(job("issues", ISSUES)).await.unwrap();
+        let started = Instant::now();
+        assert!(matches!(
+            queue.enqueue(job("issues", ISSUES)).await,
+            Err(EnqueueError::Full)
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // a worker taking a job within the timeout makes room
+        let worker = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            receiver.recv().await.unwrap();
+            receiver
+        });
+        assert_eq!(
+            queue.enqueue(job("issues", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        let _receiver = worker.await.unwrap();
+        assert_eq!(queue.metrics().rejected.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn shed_drops_listed_events_above_the_threshold() {
+        let config = QueueConfig {
+            shed_threshold: 0.5,
+            shed_events: vec!["star".to_string()],
+            low_priority: vec!["star".to_string(), "watch".to_string()],
+            ..queue_config(4, FullQueuePolicy::Shed)
+        };
+        let (queue, _receiver) = DeliveryQueue::new(&config);
+        assert_eq!(
+            queue.enqueue(job("star", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        assert_eq!(
+            queue.enqueue(job("watch", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        // the low class is at the threshold: stars go, anything else still queues
+        assert_eq!(
+            queue.enqueue(job("star", ISSUES)).await.unwrap(),
+            Enqueued::Shed
+        );
+        assert_eq!(
+            queue.enqueue(job("watch", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        // other classes are measured against their own depth
+        assert_eq!(
+            queue.enqueue(job("issues", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+
+        assert_eq!(queue.depth_of(Priority::Low), 3);
+        let metrics = queue.metrics();
+        assert_eq!(metrics.shed.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.enqueued.load(Ordering::Relaxed), 4);
+        assert_eq!(
+            metrics.shed_by_event.lock().unwrap().get("star").copied(),
+            Some(1)
+        );
+    }
+
+    fn push(branch: &str) -> Job {
+        job(
+            "push",
+            &format!(
+                r#"{{"ref":"refs/heads/{}","repository":{{"name":"nexus","full_name":"octo/nexus","html_url":"https://github.com/octo/nexus","default_branch":"main"}}}}"#,
+                branch
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn shed_rules_sample_while_shedding() {
+        let rule = |event: &str, action: Option<&str>, non_default_branch, sample_rate| ShedRule {
+            event: event.to_string(),
+            action: action.map(str::to_string),
+            non_default_branch,
+            sample_rate,
+        };
+        let config = QueueConfig {
+            shed_threshold: 0.0,
+            shed_events: Vec::new(),
+            shed_rules: vec![
+                rule("push", None, true, 0.0),
+                rule("issues", Some("labeled"), false, 0.0),
+                rule("issues", None, false, 1.0),
+            ],
+            ..queue_config(16, FullQueuePolicy::Shed)
+        };
+        let (queue, _receiver) = DeliveryQueue::new(&config);
+
+        // pushes to the default branch keep flowing
+        assert_eq!(
+            queue.enqueue(push("feature")).await.unwrap(),
+            Enqueued::Shed
+        );
+        assert_eq!(queue.enqueue(push("main")).await.unwrap(), Enqueued::Queued);
+        // the first matching rule applies
+        let labeled = job("issues", r#"{"action":"labeled"}"#);
+        assert_eq!(queue.enqueue(labeled).await.unwrap(), Enqueued::Shed);
+        assert_eq!(
+            queue.enqueue(job("issues", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+        // no rule, nothing shed
+        assert_eq!(
+            queue.enqueue(job("star", ISSUES)).await.unwrap(),
+            Enqueued::Queued
+        );
+
+        let shed = queue.metrics().shed_by_event.lock().unwrap().clone();
+        assert_eq!(
+            shed,
+            HashMap::from([("push".to_string(), 1), ("issues".to_string(), 1)])
+        );
+    }
+
+    #[tokio::test]
+    async fn shed_rules_apply_only_above_the_threshold() {
+        let config = QueueConfig {
+            shed_threshold: 0.5,
+            shed_rules: vec![ShedRule {
+                event: "push".to_string(),
+                sample_rate: 0.0,
+                ..Default::default()
+            }],
+            ..queue_config(2, FullQueuePolicy::Shed)
+        };
+        let (queue, _receiver) = DeliveryQueue::new(&config);
+        assert_eq!(queue.enqueue(push("main")).await.unwrap(), Enqueued::Queued);
+        assert_eq!(queue.enqueue(push("main")).await.unwrap(), Enqueued::Shed);
+        assert_eq!(queue.metrics().shed.load(Ordering::Relaxed), 1);
+
+        // the reject policy never sheds, whatever the rules say
+        let config = QueueConfig {
+            on_full: FullQueuePolicy::Reject,
+            ..config
+        };
+        let (queue, _receiver) = DeliveryQueue::new(&config);
+        assert_eq!(queue.enqueue(push("main")).await.unwrap(), Enqueued::Queued);
+        assert_eq!(queue.enqueue(push("main")).await.unwrap(), Enqueued::Queued);
+        assert!(matches!(
+            queue.enqueue(push("main")).await,
+            Err(EnqueueError::Full)
+        ));
+    }
+
+    #[tokio::test]
+    async fn journal_keeps_the_json_as_received() {
+        let dir = temp_dir("journal");
+        let config = QueueConfig {
+            journal_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let (queue, receiver) = DeliveryQueue::new(&config);
+        queue.enqueue(job("push", PUSH)).await.unwrap();
+        drop(receiver);
+
+        let paths = read_journal_dir(&dir).await.unwrap();
+        assert_eq!(paths.len(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&paths[0]).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // a restart replays it with the fields nexus does not model
+        let (queue, mut receiver) = DeliveryQueue::new(&config);
+        queue.replay().await;
+        let replayed = receiver.recv().await.unwrap();
+        assert_eq!(replayed.json, PUSH.as_bytes());
+        assert_eq!(replayed.event_type, "push");
+        assert_eq!(
+            replayed.payload.ref_name.as_deref(),
+            Some("refs/heads/main")
+        );
+        assert_eq!(replayed.journal.as_ref(), Some(&paths[0]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
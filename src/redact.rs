@@ -0,0 +1,159 @@
+// Scrubs payloads before they are written to disk (archive, queue journal) and titles
+// before they are logged. Handlers still see the payload as GitHub sent it.
+//   fields:   JSON pointers into the payload, with `*` for every array item, e.g.
+//             `/issue/body` or `/commits/*/message`; every string under one is replaced
+//   patterns: regexes matched against every string, e.g. tokens pasted into a comment
+
+use regex::Regex;
+use serde_json::Value;
+use std::{borrow::Cow, sync::Arc};
+
+use crate::config::RedactionConfig;
+
+#[derive(Clone, Default)]
+pub struct Redactor {
+    // None when nothing is configured, so the common case costs nothing
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    fields: Vec<Vec<String>>,
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl Redactor {
+    pub fn from_config(config: &RedactionConfig) -> Result<Self, String> {
+        if !config.enabled || (config.fields.is_empty() && config.patterns.is_empty()) {
+            return Ok(Self::default());
+        }
+        let mut fields = Vec::new();
+        for field in &config.fields {
+            let Some(pointer) = field.strip_prefix('/') else {
+                return Err(format!(
+                    "redaction field {} must be a JSON pointer starting with /",
+                    field
+                ));
+            };
+            fields.push(
+                pointer
+                    .split('/')
+                    .map(|s| s.replace("~1", "/").replace("~0", "~"))
+                    .collect(),
+            );
+        }
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| format!("invalid redaction pattern {}: {}", p, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            inner: Some(Arc::new(Inner {
+                fields,
+                patterns,
+                replacement: config.replacement.clone(),
+            })),
+        })
+    }
+
+    // a payload as JSON; the structure stays intact so redacted entries still deserialize
+    pub fn value(&self, value: &mut Value) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        for field in &inner.fields {
+            walk(value, field, &mut |v| {
+                replace_strings(v, &inner.replacement)
+            });
+        }
+        if !inner.patterns.is_empty() {
+            scrub(value, inner);
+        }
+    }
+
+    // a JSON document, untouched when nothing is configured
+    pub fn json<'a>(&self, json: &'a [u8]) -> serde_json::Result<Cow<'a, [u8]>> {
+        if self.inner.is_none() {
+            return Ok(Cow::Borrowed(json));
+        }
+        let mut value = serde_json::from_slice(json)?;
+        self.value(&mut value);
+        Ok(Cow::Owned(serde_json::to_vec(&value)?))
+    }
+
+    // free text such as a title on its way into a log line; only patterns apply
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.inner {
+            Some(inner) => inner.scrub_text(text),
+            None => Cow::Borrowed(text),
+        }
+    }
+}
+
+impl Inner {
+    fn scrub_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, self.replacement.as_str()) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+fn walk(value: &mut Value, segments: &[String], f: &mut dyn FnMut(&mut Value)) {
+    let Some((segment, rest)) = segments.split_first() else {
+        f(value);
+        return;
+    };
+    match value {
+        Value::Array(items) if segment == "*" => {
+            for item in items {
+                walk(item, rest, f);
+            }
+        }
+        Value::Object(map) if segment == "*" => {
+            for item in map.values_mut() {
+                walk(item, rest, f);
+            }
+        }
+        Value::Object(map) => {
+            if let Some(item) = map.get_mut(segment) {
+                walk(item, rest, f);
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item) = segment.parse().ok().and_then(|i: usize| items.get_mut(i)) {
+                walk(item, rest, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn replace_strings(value: &mut Value, replacement: &str) {
+    match value {
+        Value::String(s) => *s = replacement.to_string(),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|v| replace_strings(v, replacement)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|v| replace_strings(v, replacement)),
+        _ => {}
+    }
+}
+
+fn scrub(value: &mut Value, inner: &Inner) {
+    match value {
+        Value::String(s) => {
+            if let Cow::Owned(replaced) = inner.scrub_text(s) {
+                *s = replaced;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| scrub(v, inner)),
+        Value::Object(map) => map.values_mut().for_each(|v| scrub(v, inner)),
+        _ => {}
+    }
+}
@@ -0,0 +1,124 @@
+// Which repositories a rule, target or section applies to. Entries in a `repos` list
+// are `owner/name`, `owner/*` for every repository of an org (including ones created
+// later), or `!owner/name` to leave a repository out of an org-wide entry so it can be
+// given its own rule instead.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{github, service::AppState};
+
+// an empty list is handled by the caller, as it means all repositories in some
+// places and none in others; a list of exclusions alone matches everything else
+pub fn matches(patterns: &[String], repo: &str) -> bool {
+    let mut included = patterns.iter().all(|p| p.starts_with('!'));
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(excluded) if matches_one(excluded, repo) => return false,
+            Some(_) => {}
+            None => included |= matches_one(pattern, repo),
+        }
+    }
+    included
+}
+
+fn matches_one(pattern: &str, repo: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(org) => repo
+            .split_once('/')
+            .is_some_and(|(owner, _)| owner.eq_ignore_ascii_case(org)),
+        None => pattern.eq_ignore_ascii_case(repo),
+    }
+}
+
+// per-repository sections keyed by `owner/name` or `owner/*`: the repository's own
+// entry first, then its org's
+pub fn lookup<'a, T>(sections: &'a HashMap<String, T>, repo: &str) -> impl Iterator<Item = &'a T> {
+    let org = repo
+        .split_once('/')
+        .and_then(|(owner, _)| sections.get(&format!("{}/*", owner)));
+    sections.get(repo).into_iter().chain(org)
+}
+
+#[derive(Deserialize)]
+struct OrgRepository {
+    full_name: String,
+    #[serde(default)]
+    archived: bool,
+}
+
+// the repositories a scheduled job visits: `owner/*` entries are listed from GitHub on
+// every run, so new repositories are picked up without a config change
+pub async fn expand(state: &AppState, patterns: &[String]) -> Vec<String> {
+    let mut repos: Vec<String> = Vec::new();
+    for pattern in patterns.iter().filter(|p| !p.starts_with('!')) {
+        let Some(org) = pattern.strip_suffix("/*") else {
+            repos.push(pattern.clone());
+            continue;
+        };
+        let Some(token) = state.github_token(org).await else {
+            error!(
+                "No GitHub token is available to list the repositories of {}",
+                org
+            );
+            continue;
+        };
+        let path = format!("/orgs/{}/repos?type=all", org);
+        match github::get_paginated::<OrgRepository>(&state.github, &token, &path).await {
+            Ok(listed) => repos.extend(
+                listed
+                    .into_iter()
+                    .filter(|r| !r.archived)
+                    .map(|r| r.full_name),
+            ),
+            Err(e) => error!("Failed to list the repositories of {}: {}", org, e),
+        }
+    }
+    let mut seen = HashSet::new();
+    repos.retain(|repo| matches(patterns, repo) && seen.insert(repo.to_lowercase()));
+    repos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn an_empty_list_matches_everything() {
+        // callers that treat it as none check for it first
+        assert!(matches(&[], "octo-org/example"));
+    }
+
+    #[test]
+    fn exclusions_alone_match_everything_else() {
+        let patterns = list(&["!octo-org/website"]);
+        assert!(matches(&patterns, "octo-org/example"));
+        assert!(matches(&patterns, "other/repo"));
+        assert!(!matches(&patterns, "octo-org/website"));
+    }
+
+    #[test]
+    fn an_org_wildcard_leaves_out_its_exclusions() {
+        let patterns = list(&["octo-org/*", "!octo-org/website", "other/tool"]);
+        assert!(matches(&patterns, "octo-org/example"));
+        assert!(!matches(&patterns, "octo-org/website"));
+        assert!(matches(&patterns, "other/tool"));
+        assert!(!matches(&patterns, "other/repo"));
+        // the org must match in full
+        assert!(!matches(&patterns, "octo-org-fork/example"));
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        let patterns = list(&["Octo-Org/*", "!octo-org/WEBSITE", "Other/Tool"]);
+        assert!(matches(&patterns, "octo-org/Example"));
+        assert!(!matches(&patterns, "Octo-Org/website"));
+        assert!(matches(&patterns, "other/tool"));
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::Utc;
+use cron::Schedule;
+use std::{future::Future, pin::Pin, str::FromStr, sync::Arc};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::service::AppState;
+
+type TaskFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Task = Arc<dyn Fn(Arc<AppState>) -> TaskFuture + Send + Sync>;
+
+struct Job {
+    name: String,
+    schedule: Schedule,
+    task: Task,
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+}
+
+// accepts standard 5-field cron expressions as well as the 6/7-field form with seconds
+pub fn parse_schedule(expression: &str) -> Result<Schedule, cron::error::Error> {
+    let fields = expression.split_whitespace().count();
+    if fields == 5 {
+        Schedule::from_str(&format!("0 {}", expression))
+    } else {
+        Schedule::from_str(expression)
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        expression: &str,
+        task: F,
+    ) -> Result<(), cron::error::Error>
+    where
+        F: Fn(Arc<AppState>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let schedule = parse_schedule(expression)?;
+        self.jobs.push(Job {
+            name: name.into(),
+            schedule,
+            task: Arc::new(move |state| Box::pin(task(state))),
+        });
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn start(self, state: Arc<AppState>) -> Vec<JoinHandle<()>> {
+        self.jobs
+            .into_iter()
+            .map(|job| {
+                let state = state.clone();
+                tokio::spawn(run_job(job, state))
+            })
+            .collect()
+    }
+}
+
+async fn run_job(job: Job, state: Arc<AppState>) {
+    info!("Scheduled job {} registered", job.name);
+
+    loop {
+        let Some(next) = job.schedule.upcoming(Utc).next() else {
+            error!("Scheduled job {} has no upcoming runs", job.name);
+            return;
+        };
+
+        let delay = (next - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(delay).await;
+
+        info!("Running scheduled job {}", job.name);
+        (job.task)(state.clone()).await;
+    }
+}
@@ -0,0 +1,368 @@
+// strict mode: compares each payload with the serde model, so a field GitHub renamed or
+// dropped shows up as a warning and a metric instead of a silently empty value. Only
+// objects present in the payload are compared, since which top-level objects appear
+// depends on the event. With `schema.file` set, payloads are also validated against
+// GitHub's published webhook schemas, which catches the fields the model does not read:
+// ones GitHub added, and values of the wrong type.
+
+use http::HeaderMap;
+use jsonschema::{Draft, Validator, error::ValidationErrorKind};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use tracing::warn;
+
+use crate::{config::SchemaConfig, service::AppState, webhook::WebhookPayload};
+
+// event, field and kind
+type DriftKey = (String, String, &'static str);
+
+/// How the payloads of each event type differed from the model and the schemas.
+#[derive(Clone, Default)]
+pub struct Drift {
+    counts: Arc<Mutex<HashMap<DriftKey, u64>>>,
+}
+
+impl Drift {
+    // (event, field, kind), sorted; the kind is `missing`, `unknown` or `invalid`
+    pub fn counts(&self) -> Vec<(String, String, &'static str, u64)> {
+        let mut counts: Vec<_> = self
+            .counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((event, field, kind), count)| (event.clone(), field.clone(), *kind, *count))
+            .collect();
+        counts.sort();
+        counts
+    }
+
+    // true the first time a field differs this way for an event
+    fn record(&self, event_type: &str, field: &str, kind: &'static str) -> bool {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts
+            .entry((event_type.to_string(), field.to_string(), kind))
+            .or_default();
+        *count += 1;
+        *count == 1
+    }
+}
+
+/// The `definitions` of the octokit/webhooks schema, compiled for an event the first time
+/// one arrives.
+#[derive(Clone, Default)]
+pub struct Schemas {
+    definitions: Option<Arc<Value>>,
+    validators: Arc<Mutex<HashMap<String, Option<Arc<Validator>>>>>,
+}
+
+impl Schemas {
+    // empty unless strict mode has a schema file
+    pub fn from_config(config: &SchemaConfig) -> Result<Self, String> {
+        let Some(path) = config.file.as_ref().filter(|_| config.strict) else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let document: Value = serde_json::from_str(&text)
+            .map_err(|e| format!("{} is not JSON: {}", path.display(), e))?;
+        let Some(definitions) = document.get("definitions").filter(|d| d.is_object()) else {
+            return Err(format!("{} has no schema definitions", path.display()));
+        };
+        Ok(Self {
+            definitions: Some(Arc::new(definitions.clone())),
+            validators: Arc::default(),
+        })
+    }
+
+    // `{event}${action}`, e.g. `pull_request$opened`, else `{event}_event`; the latter is a
+    // `oneOf` over the actions, which reports no more than that none of them matched
+    fn validator(&self, event_type: &str, action: Option<&str>) -> Option<Arc<Validator>> {
+        let definitions = self.definitions.as_ref()?;
+        let name = action
+            .map(|action| format!("{}${}", event_type, action))
+            .filter(|name| definitions.get(name).is_some())
+            .unwrap_or_else(|| format!("{}_event", event_type));
+        let mut validators = self.validators.lock().unwrap();
+        validators
+            .entry(name.clone())
+            .or_insert_with(|| {
+                definitions.get(&name)?;
+                let schema = json!({
+                    "$ref": format!("#/definitions/{}", name),
+                    "definitions": definitions.as_ref(),
+                });
+                match jsonschema::options()
+                    .with_draft(Draft::Draft7)
+                    .build(&schema)
+                {
+                    Ok(validator) => Some(Arc::new(validator)),
+                    Err(e) => {
+                        warn!("Cannot use the {} webhook schema: {}", name, e);
+                        None
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+pub fn parse(
+    state: &AppState,
+    headers: &HeaderMap,
+    raw: Value,
+) -> Result<WebhookPayload, serde_json::Error> {
+    let payload = WebhookPayload::deserialize(&raw)?;
+    let event_type = headers
+        .get("x-github-event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown");
+
+    let modelled = serde_json::to_value(&payload)?;
+    let ignored: HashSet<&str> = state
+        .config
+        .schema
+        .ignore
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut missing = Vec::new();
+    if let (Value::Object(modelled), Value::Object(raw)) = (&modelled, &raw) {
+        for (key, value) in modelled {
+            if let Some(raw_value) = raw.get(key) {
+                compare(value, raw_value, key, &mut missing);
+            }
+        }
+    }
+    // (field, kind, the schema keyword an invalid value failed)
+    let mut problems: Vec<_> = missing
+        .into_iter()
+        .map(|field| (field, "missing", ""))
+        .collect();
+    if let Some(validator) = state
+        .schemas
+        .validator(event_type, payload.action.as_deref())
+    {
+        problems.extend(validate(&validator, &raw));
+    }
+    problems.sort();
+    problems.dedup();
+
+    for (field, kind, keyword) in problems {
+        if ignored.contains(field.as_str()) {
+            continue;
+        }
+        // logged once per field; the metric keeps counting
+        if !state.drift.record(event_type, &field, kind) {
+            continue;
+        }
+        match kind {
+            "missing" => warn!(
+                "{} payload has no {}, which the model expects; GitHub may have renamed it",
+                event_type, field
+            ),
+            "unknown" => warn!(
+                "{} payload has {}, which GitHub's schema does not list",
+                event_type, field
+            ),
+            _ => warn!(
+                "{} payload has a {} that fails GitHub's schema ({})",
+                event_type, field, keyword
+            ),
+        }
+    }
+    Ok(payload)
+}
+
+// values are left out of what is reported, since they may be sensitive
+fn validate(validator: &Validator, raw: &Value) -> Vec<(String, &'static str, &'static str)> {
+    let mut problems = Vec::new();
+    for error in validator.iter_errors(raw) {
+        let path = field(error.instance_path.as_str());
+        match &error.kind {
+            ValidationErrorKind::AdditionalProperties { unexpected } => {
+                problems.extend(
+                    unexpected
+                        .iter()
+                        .map(|name| (child(&path, name), "unknown", "")),
+                );
+            }
+            ValidationErrorKind::Required { property } => {
+                let name = property.as_str().unwrap_or_default();
+                problems.push((child(&path, name), "invalid", "required"));
+            }
+            kind => problems.push((path, "invalid", keyword(kind))),
+        }
+    }
+    problems
+}
+
+// `/commits/0/added` as `commits[].added`, the way model fields are reported
+fn field(pointer: &str) -> String {
+    let mut field = String::new();
+    for segment in pointer.split('/').skip(1) {
+        if segment.parse::<usize>().is_ok() {
+            field.push_str("[]");
+            continue;
+        }
+        if !field.is_empty() {
+            field.push('.');
+        }
+        field.push_str(&segment.replace("~1", "/").replace("~0", "~"));
+    }
+    field
+}
+
+fn child(field: &str, name: &str) -> String {
+    match field.is_empty() {
+        true => name.to_string(),
+        false => format!("{}.{}", field, name),
+    }
+}
+
+fn keyword(kind: &ValidationErrorKind) -> &'static str {
+    match kind {
+        ValidationErrorKind::Type { .. } => "type",
+        ValidationErrorKind::Enum { .. } => "enum",
+        ValidationErrorKind::Constant { .. } => "const",
+        ValidationErrorKind::Format { .. } => "format",
+        ValidationErrorKind::Pattern { .. } => "pattern",
+        ValidationErrorKind::OneOfNotValid | ValidationErrorKind::OneOfMultipleValid => "oneOf",
+        ValidationErrorKind::AnyOf => "anyOf",
+        _ => "constraint",
+    }
+}
+
+// array items are compared pairwise and reported as `field[]`
+fn compare(modelled: &Value, raw: &Value, path: &str, missing: &mut Vec<String>) {
+    match (modelled, raw) {
+        (Value::Object(modelled), Value::Object(raw)) => {
+            for (key, value) in modelled {
+                let path = format!("{}.{}", path, key);
+                match raw.get(key) {
+                    Some(raw_value) => compare(value, raw_value, &path, missing),
+                    None => missing.push(path),
+                }
+            }
+        }
+        (Value::Array(modelled), Value::Array(raw)) => {
+            let path = format!("{}[]", path);
+            for (value, raw_value) in modelled.iter().zip(raw) {
+                compare(value, raw_value, &path, missing);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // shaped like octokit's: an action-specific definition next to the event's `oneOf`
+    const SCHEMA: &str = r##"{"definitions":{
+        "issues$opened":{"type":"object","required":["action","issue","sender"],"additionalProperties":false,"properties":{
+            "action":{"const":"opened"},
+            "issue":{"type":"object","additionalProperties":false,"properties":{
+                "number":{"type":"integer"},
+                "title":{"type":"string"},
+                "html_url":{"type":"string"},
+                "state":{"enum":["open","closed"]},
+                "user":{"type":"object","properties":{"login":{"type":"string"}}},
+                "labels":{"type":"array","items":{"type":"object","additionalProperties":false,"properties":{"name":{"type":"string"}}}}}},
+            "sender":{"type":"object"}}},
+        "issues_event":{"oneOf":[{"$ref":"#/definitions/issues$opened"}]}
+    }}"##;
+
+    fn state(name: &str) -> AppState {
+        let path =
+            std::env::temp_dir().join(format!("nexus-schema-{}-{}.json", name, std::process::id()));
+        std::fs::write(&path, SCHEMA).unwrap();
+        let mut config = Config::default();
+        config.schema.strict = true;
+        config.schema.file = Some(path.clone());
+        let schemas = Schemas::from_config(&config.schema).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        AppState::new(None)
+            .with_config(config)
+            .with_schemas(schemas)
+    }
+
+    // an `issues.opened` delivery with `issue` extended by `extra`
+    fn parse_issue(state: &AppState, extra: Value, sender: bool) {
+        let user = json!({"login": "octocat", "html_url": "https://github.com/octocat"});
+        let mut issue = json!({
+            "number": 1,
+            "title": "Crash",
+            "html_url": "https://github.com/octo/nexus/issues/1",
+            "state": "open",
+            "user": user,
+        });
+        issue
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        let mut payload = json!({"action": "opened", "issue": issue});
+        if sender {
+            payload["sender"] = user;
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert("x-github-event", "issues".parse().unwrap());
+        parse(state, &headers, payload).unwrap();
+    }
+
+    fn drift(state: &AppState, kind: &str) -> Vec<String> {
+        state
+            .drift
+            .counts()
+            .into_iter()
+            .filter(|(_, _, k, _)| *k == kind)
+            .map(|(_, field, _, _)| field)
+            .collect()
+    }
+
+    #[test]
+    fn reports_fields_the_schema_does_not_list() {
+        let state = state("unknown");
+        let extra = json!({"labels": [{"name": "bug", "color": "red"}], "pinned": true});
+        parse_issue(&state, extra, true);
+        assert_eq!(
+            drift(&state, "unknown"),
+            ["issue.labels[].color", "issue.pinned"]
+        );
+        assert!(drift(&state, "invalid").is_empty());
+    }
+
+    #[test]
+    fn reports_values_of_the_wrong_shape() {
+        let state = state("invalid");
+        parse_issue(&state, json!({"state": "archived"}), false);
+        assert_eq!(drift(&state, "invalid"), ["issue.state", "sender"]);
+        assert!(drift(&state, "unknown").is_empty());
+    }
+
+    #[test]
+    fn schemas_are_only_loaded_in_strict_mode() {
+        let config = SchemaConfig {
+            file: Some("/nonexistent/schema.json".into()),
+            ..Default::default()
+        };
+        assert!(Schemas::from_config(&config).is_ok());
+        let strict = SchemaConfig {
+            strict: true,
+            ..config
+        };
+        assert!(Schemas::from_config(&strict).is_err());
+    }
+
+    #[test]
+    fn pointers_read_like_model_fields() {
+        assert_eq!(field(""), "");
+        assert_eq!(field("/commits/0/added"), "commits[].added");
+        assert_eq!(field("/a~1b/c~0d"), "a/b.c~d");
+    }
+}
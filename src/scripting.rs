@@ -0,0 +1,463 @@
+// Rhai scripts referenced from rules with `script = "path.rhai"`. A script sees the event
+// as a map and decides what the rule does with it by what it evaluates to:
+//
+//     false      the rule is skipped for this event
+//     () / true  the rule goes ahead with the payload it has
+//     a map      the rule goes ahead with the map as its payload
+//
+//     // only pull requests into main, and only what the receiver needs
+//     if event.payload.pull_request.base.ref != "main" { return false; }
+//     #{ repo: event.repository, number: event.payload.number, author: event.sender }
+//
+// `event` has `type`, `action`, `delivery`, `repository`, `sender` and `payload`. Besides
+// the standard library a script can call `post(url, body)` and `dispatch(repo,
+// event_type, client_payload)`, which are sent as outbound deliveries once the script has
+// finished, and `print`/`debug`, which log. Scripts are compiled on first use and again
+// whenever the file's modification time changes; a version that fails to compile is
+// logged and the previous one keeps running.
+
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::ScriptingConfig,
+    outbound::{self, Delivery, Request},
+    service::AppState,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("failed to read the script: {0}")]
+    Read(#[source] std::io::Error),
+    #[error("failed to compile: {0}")]
+    Compile(#[source] rhai::ParseError),
+    #[error("failed: {0}")]
+    Run(#[source] Box<rhai::EvalAltResult>),
+    // the script evaluated to something other than a map, a bool or ()
+    #[error("returned {0}, expected a map, true, false or ()")]
+    Result(String),
+}
+
+struct Compiled {
+    modified: Option<SystemTime>,
+    ast: Arc<AST>,
+}
+
+/// Compiled scripts by path, recompiled when their file changes.
+#[derive(Clone, Default)]
+pub struct Scripts {
+    compiled: Arc<Mutex<HashMap<PathBuf, Compiled>>>,
+}
+
+impl Scripts {
+    fn load(&self, path: &Path) -> Result<Arc<AST>, ScriptError> {
+        let modified = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map_err(ScriptError::Read)?;
+        let previous = match self.compiled.lock().unwrap().get(path) {
+            Some(compiled) if compiled.modified == Some(modified) => {
+                return Ok(compiled.ast.clone());
+            }
+            Some(compiled) => Some(compiled.ast.clone()),
+            None => None,
+        };
+
+        let ast = std::fs::read_to_string(path)
+            .map_err(ScriptError::Read)
+            .and_then(|source| compile(&source));
+        let ast = match ast {
+            Ok(ast) => {
+                info!("Compiled script {}", path.display());
+                Arc::new(ast)
+            }
+            Err(e) => {
+                let Some(previous) = previous else {
+                    return Err(e);
+                };
+                error!(
+                    "Keeping the previous version of {}, the new one {}",
+                    path.display(),
+                    e
+                );
+                previous
+            }
+        };
+        // a broken version is not retried until the file changes again
+        self.compiled.lock().unwrap().insert(
+            path.to_path_buf(),
+            Compiled {
+                modified: Some(modified),
+                ast: ast.clone(),
+            },
+        );
+        Ok(ast)
+    }
+}
+
+/// What a rule's script decided.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Skip,
+    Keep,
+    Replace(Value),
+}
+
+// compiles `source` without running it, e.g. for `nexus doctor`
+pub fn compile(source: &str) -> Result<AST, ScriptError> {
+    Engine::new().compile(source).map_err(ScriptError::Compile)
+}
+
+// the payload a rule goes ahead with, or None when the script skips the rule or fails
+pub fn apply(
+    state: &AppState,
+    script: &Path,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &Value,
+) -> Option<Value> {
+    match run(state, script, event_type, delivery_id, payload) {
+        Ok(Outcome::Skip) => None,
+        Ok(Outcome::Keep) => Some(payload.clone()),
+        Ok(Outcome::Replace(payload)) => Some(payload),
+        Err(e) => {
+            warn!("Script {} {}", script.display(), e);
+            None
+        }
+    }
+}
+
+// runs `script` for one event, then sends what it posted or dispatched
+pub fn run(
+    state: &AppState,
+    script: &Path,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &Value,
+) -> Result<Outcome, ScriptError> {
+    let ast = state.scripts.load(script)?;
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let engine = engine(&state.config.scripting, script, requests.clone());
+
+    let mut scope = Scope::new();
+    scope.push("event", event(event_type, delivery_id, payload)?);
+    let result = engine
+        .eval_ast_with_scope::<Dynamic>(&mut scope, &ast)
+        .map_err(ScriptError::Run)?;
+    let outcome = outcome(result)?;
+
+    let requests = std::mem::take(&mut *requests.lock().unwrap());
+    for (i, (target, request)) in requests.into_iter().enumerate() {
+        // scoped by script and call, so two posts to one URL are not taken for a replay
+        let scope = delivery_id.map(|id| format!("{}\n{}\n{}", id, script.display(), i));
+        let delivery = Delivery::new(target, request).idempotent(scope.as_deref());
+        let state = state.clone();
+        let script = script.display().to_string();
+        tokio::spawn(async move {
+            let target = delivery.target.clone();
+            match outbound::deliver(&state, delivery).await {
+                Ok(()) => info!("Script {} sent {}", script, target),
+                Err(e) => error!("Script {} failed to send {}: {}", script, target, e),
+            }
+        });
+    }
+    Ok(outcome)
+}
+
+fn engine(
+    config: &ScriptingConfig,
+    script: &Path,
+    requests: Arc<Mutex<Vec<(String, Request)>>>,
+) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(config.max_operations)
+        .set_max_call_levels(32)
+        .set_max_string_size(1024 * 1024)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000);
+
+    let name = script.display().to_string();
+    let source = name.clone();
+    engine.on_print(move |text| info!("{}: {}", source, text));
+    engine.on_debug(move |text, _, position| debug!("{} {}: {}", name, position, text));
+
+    let posts = requests.clone();
+    engine.register_fn(
+        "post",
+        move |url: &str, body: Dynamic| -> Result<(), Box<rhai::EvalAltResult>> {
+            let body = to_json(body)?;
+            posts.lock().unwrap().push((
+                format!("script:{}", url),
+                Request::Http {
+                    url: url.to_string(),
+                    headers: HashMap::new(),
+                    body,
+                },
+            ));
+            Ok(())
+        },
+    );
+    engine.register_fn(
+        "dispatch",
+        move |repo: &str,
+              event_type: &str,
+              client_payload: Dynamic|
+              -> Result<(), Box<rhai::EvalAltResult>> {
+            let client_payload = to_json(client_payload)?;
+            requests.lock().unwrap().push((
+                format!("dispatch:{}", repo),
+                Request::Dispatch {
+                    repo: repo.to_string(),
+                    event_type: event_type.to_string(),
+                    client_payload,
+                },
+            ));
+            Ok(())
+        },
+    );
+    engine
+}
+
+fn event(event_type: &str, delivery_id: Option<&str>, payload: &Value) -> Result<Map, ScriptError> {
+    let text = |value: Option<&str>| value.map_or(Dynamic::UNIT, |v| v.to_string().into());
+    let mut event = Map::new();
+    event.insert("type".into(), event_type.into());
+    event.insert("action".into(), text(payload["action"].as_str()));
+    event.insert("delivery".into(), text(delivery_id));
+    event.insert(
+        "repository".into(),
+        text(payload["repository"]["full_name"].as_str()),
+    );
+    event.insert("sender".into(), text(payload["sender"]["login"].as_str()));
+    let payload = rhai::serde::to_dynamic(payload).map_err(ScriptError::Run)?;
+    event.insert("payload".into(), payload);
+    Ok(event)
+}
+
+fn outcome(result: Dynamic) -> Result<Outcome, ScriptError> {
+    if result.is_unit() {
+        return Ok(Outcome::Keep);
+    }
+    if let Ok(keep) = result.as_bool() {
+        return Ok(if keep { Outcome::Keep } else { Outcome::Skip });
+    }
+    if result.is_map() {
+        return to_json(result)
+            .map(Outcome::Replace)
+            .map_err(ScriptError::Run);
+    }
+    Err(ScriptError::Result(result.type_name().to_string()))
+}
+
+fn to_json(value: Dynamic) -> Result<Value, Box<rhai::EvalAltResult>> {
+    rhai::serde::from_dynamic(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, extract::State, routing::post};
+    use serde_json::json;
+    use std::time::Duration;
+
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "nexus-scripting-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            Dir(dir)
+        }
+
+        fn script(&self, source: &str) -> PathBuf {
+            let path = self.0.join("rule.rhai");
+            std::fs::write(&path, source).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // a rewrite within the file system's timestamp resolution would go unnoticed
+    fn touch(path: &Path, secs: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn payload() -> Value {
+        json!({
+            "action": "opened",
+            "number": 7,
+            "pull_request": {"base": {"ref": "main"}},
+            "repository": {"full_name": "org/repo"},
+            "sender": {"login": "octocat"},
+        })
+    }
+
+    fn run_source(dir: &Dir, source: &str) -> Result<Outcome, ScriptError> {
+        let state = AppState::new(None);
+        run(
+            &state,
+            &dir.script(source),
+            "pull_request",
+            Some("d1"),
+            &payload(),
+        )
+    }
+
+    #[test]
+    fn the_result_decides_what_the_rule_does() {
+        let dir = Dir::new("outcome");
+        assert_eq!(run_source(&dir, "true").unwrap(), Outcome::Keep);
+        assert_eq!(run_source(&dir, "let x = 1;").unwrap(), Outcome::Keep);
+        assert_eq!(run_source(&dir, "false").unwrap(), Outcome::Skip);
+        assert!(matches!(
+            run_source(&dir, "42"),
+            Err(ScriptError::Result(_))
+        ));
+    }
+
+    #[test]
+    fn scripts_see_the_event_and_can_replace_the_payload() {
+        let dir = Dir::new("event");
+        let source = r#"
+            if event.payload.pull_request.base.ref != "main" { return false; }
+            #{
+                type: event.type,
+                action: event.action,
+                delivery: event.delivery,
+                repo: event.repository,
+                author: event.sender,
+                number: event.payload.number,
+            }
+        "#;
+        assert_eq!(
+            run_source(&dir, source).unwrap(),
+            Outcome::Replace(json!({
+                "type": "pull_request",
+                "action": "opened",
+                "delivery": "d1",
+                "repo": "org/repo",
+                "author": "octocat",
+                "number": 7,
+            }))
+        );
+
+        let state = AppState::new(None);
+        let script = dir.script(r#"event.payload.number += 1; event.payload"#);
+        let replaced = apply(&state, &script, "pull_request", None, &payload()).unwrap();
+        assert_eq!(replaced["number"], 8);
+        assert_eq!(replaced["repository"]["full_name"], "org/repo");
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let dir = Dir::new("limit");
+        let mut state = AppState::new(None);
+        state.config.scripting.max_operations = 1_000;
+        let script = dir.script("loop { }");
+        assert!(matches!(
+            run(&state, &script, "push", None, &payload()),
+            Err(ScriptError::Run(_))
+        ));
+        // a failing script skips its rule
+        assert_eq!(apply(&state, &script, "push", None, &payload()), None);
+    }
+
+    #[test]
+    fn scripts_are_recompiled_when_the_file_changes() {
+        let dir = Dir::new("reload");
+        let state = AppState::new(None);
+        let script = dir.script("true");
+        let outcome = || run(&state, &script, "push", None, &payload()).unwrap();
+        assert_eq!(outcome(), Outcome::Keep);
+
+        std::fs::write(&script, "false").unwrap();
+        touch(&script, 10);
+        assert_eq!(outcome(), Outcome::Skip);
+
+        // a broken version leaves the last good one running
+        std::fs::write(&script, "if {").unwrap();
+        touch(&script, 20);
+        assert_eq!(outcome(), Outcome::Skip);
+
+        std::fs::write(&script, "#{ fixed: true }").unwrap();
+        touch(&script, 30);
+        assert_eq!(outcome(), Outcome::Replace(json!({"fixed": true})));
+    }
+
+    #[test]
+    fn a_script_that_never_compiled_is_an_error() {
+        let dir = Dir::new("broken");
+        assert!(matches!(
+            run_source(&dir, "if {"),
+            Err(ScriptError::Compile(_))
+        ));
+        let state = AppState::new(None);
+        assert!(matches!(
+            run(
+                &state,
+                &dir.0.join("missing.rhai"),
+                "push",
+                None,
+                &payload()
+            ),
+            Err(ScriptError::Read(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn posts_are_sent_after_the_script_finishes() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/",
+                post(
+                    |State(received): State<Arc<Mutex<Vec<Value>>>>,
+                     axum::Json(body): axum::Json<Value>| async move {
+                        received.lock().unwrap().push(body);
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = Dir::new("post");
+        let state = AppState::new(None);
+        let script = dir.script(&format!(
+            r#"post("{}", #{{ repo: event.repository, number: event.payload.number }}); false"#,
+            url
+        ));
+        let outcome = run(&state, &script, "pull_request", Some("d1"), &payload()).unwrap();
+        assert_eq!(outcome, Outcome::Skip);
+
+        for _ in 0..100 {
+            if !received.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![json!({"repo": "org/repo", "number": 7})]
+        );
+    }
+}
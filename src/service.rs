@@ -0,0 +1,408 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use http::{HeaderMap, Request, Response, StatusCode, header};
+use http_body::Body;
+use http_body_util::{BodyExt, Full};
+use tower::{BoxError, Service};
+use tracing::{Instrument, error, info_span, warn};
+
+use crate::{
+    admin::Reloader,
+    anomaly::{Anomalies, Signal},
+    automations::{exec::ExecRuns, mirror::Mirrors, push_deploy::PushDeploys, rebuild::Rebuilds},
+    clickhouse::ClickHouse,
+    client_ip::ClientIp,
+    config::Config,
+    correlation::Correlations,
+    encoding::ContentEncoding,
+    error::NexusError,
+    feed::Feed,
+    github::{GithubClient, auth::AppAuth},
+    influxdb::InfluxDb,
+    outbound::Outbound,
+    queue::DeliveryQueue,
+    redact::Redactor,
+    schema::{Drift, Schemas},
+    scripting::Scripts,
+    spool::Spool,
+    store::Store,
+    timeouts::Timeouts,
+    webhook::{self, RawBody, WebhookPayload},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub webhook_secret: Option<String>,
+    pub http_client: reqwest::Client,
+    pub github: GithubClient,
+    pub config: Config,
+    // digest windows and reviewer rotation, shared across replicas with the redis backend
+    pub store: Arc<Store>,
+    pub github_app: Option<Arc<AppAuth>>,
+    pub queue: Option<DeliveryQueue>,
+    pub outbound: Outbound,
+    pub feed: Feed,
+    pub correlation: Correlations,
+    pub anomalies: Anomalies,
+    pub clickhouse: ClickHouse,
+    pub influxdb: InfluxDb,
+    pub exec: ExecRuns,
+    pub push_deploys: PushDeploys,
+    pub mirrors: Mirrors,
+    pub rebuilds: Rebuilds,
+    pub drift: Drift,
+    pub schemas: Schemas,
+    pub redactor: Redactor,
+    pub scripts: Scripts,
+    pub reloader: Reloader,
+    pub timeouts: Timeouts,
+}
+
+impl AppState {
+    pub fn new(webhook_secret: Option<String>) -> Self {
+        let http_client = reqwest::Client::new();
+        Self {
+            webhook_secret,
+            github: GithubClient::with_http_client(http_client.clone()),
+            http_client,
+            config: Config::default(),
+            store: Arc::default(),
+            github_app: None,
+            queue: None,
+            outbound: Outbound::default(),
+            feed: Feed::default(),
+            correlation: Correlations::default(),
+            anomalies: Anomalies::default(),
+            clickhouse: ClickHouse::default(),
+            influxdb: InfluxDb::default(),
+            exec: ExecRuns::default(),
+            push_deploys: PushDeploys::default(),
+            mirrors: Mirrors::default(),
+            rebuilds: Rebuilds::default(),
+            drift: Drift::default(),
+            schemas: Schemas::default(),
+            redactor: Redactor::default(),
+            scripts: Scripts::default(),
+            reloader: Reloader::default(),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    // deliveries are acknowledged with 202 and handled by the queue's workers
+    pub fn with_queue(mut self, queue: DeliveryQueue) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    // the `[proxy]` settings; the GitHub client is set separately with `with_github`
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    // the configured API URL and trust roots, e.g. for GitHub Enterprise Server
+    pub fn with_github(mut self, github: GithubClient) -> Self {
+        self.github = github;
+        self
+    }
+
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn with_schemas(mut self, schemas: Schemas) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    pub fn with_reloader(mut self, reloader: Reloader) -> Self {
+        self.reloader = reloader;
+        self
+    }
+
+    pub fn with_store(mut self, store: Store) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    pub fn with_github_app(mut self, app: AppAuth) -> Self {
+        self.github_app = Some(Arc::new(app));
+        self
+    }
+
+    // an installation token when running as a GitHub App, otherwise the configured token
+    pub async fn github_token(&self, repo: &str) -> Option<String> {
+        if let Some(app) = &self.github_app {
+            match app.token_for_repo(&self.github, repo).await {
+                Ok(token) => return Some(token),
+                Err(e) => error!("Failed to get an installation token for {}: {}", repo, e),
+            }
+        }
+        self.config.github.token.clone()
+    }
+}
+
+/// The webhook processor (verify -> parse -> dispatch) as a plain `tower::Service`,
+/// usable from axum, a bare hyper server, lambda_http, or directly in tests.
+#[derive(Clone)]
+pub struct WebhookService {
+    state: Arc<AppState>,
+}
+
+impl WebhookService {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+}
+
+impl<B> Service<Request<B>> for WebhookService
+where
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let span = match parts.extensions.get::<ClientIp>() {
+                Some(ClientIp(ip)) => info_span!("delivery", client_ip = %ip),
+                None => info_span!("delivery"),
+            };
+
+            let result = async {
+                let (payload, body) = read_body(&state, &parts.headers, body).await?;
+                webhook::handle_payload(&state, &parts.headers, payload, body).await
+            }
+            .instrument(span)
+            .await;
+            match result {
+                Ok((status, response)) => Ok(json_response(status, &response)),
+                Err(e) => {
+                    if matches!(e, NexusError::MissingSignature | NexusError::Signature(_)) {
+                        state.anomalies.record(Signal::SignatureFailures);
+                    }
+                    Ok(json_response(e.status(), &e.body()))
+                }
+            }
+        })
+    }
+}
+
+// hashes each frame as it arrives and spools it, so the body is never buffered twice;
+// returns the payload and the body it was parsed from
+async fn read_body<B>(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: B,
+) -> Result<(WebhookPayload, RawBody), NexusError>
+where
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    let mut body = std::pin::pin!(body);
+    let config = &state.config.server;
+    let mut verification = webhook::start_verification(state, headers)?;
+    let encoding = webhook::content_encoding(headers)?;
+    let mut spool = Spool::new(config.spool_threshold_bytes);
+
+    while let Some(frame) = body.frame().await {
+        let frame = frame.map_err(|e| {
+            let e = e.into();
+            error!("Failed to read webhook body: {}", e);
+            NexusError::Body(e)
+        })?;
+        let Ok(mut data) = frame.into_data() else {
+            continue;
+        };
+        let chunk = data.copy_to_bytes(data.remaining());
+
+        if spool.len() + chunk.len() > config.max_body_bytes {
+            warn!(
+                "Rejecting webhook body over {} bytes",
+                config.max_body_bytes
+            );
+            return Err(NexusError::TooLarge(config.max_body_bytes));
+        }
+        if let Some((verifier, _)) = &mut verification {
+            verifier.update(&chunk);
+        }
+        spool.write(&chunk).map_err(|e| {
+            error!("Failed to spool webhook body: {}", e);
+            NexusError::Spool(e)
+        })?;
+    }
+
+    if let Some((verifier, signature)) = verification {
+        webhook::finish_verification(verifier, &signature)?;
+    }
+    let received = Bytes::from(spool.read_all().map_err(|e| {
+        error!("Failed to read spooled webhook body: {}", e);
+        NexusError::Spool(e)
+    })?);
+    let json = match encoding != ContentEncoding::Identity || webhook::is_form(headers) {
+        true => webhook::decode_body(state, headers, received.to_vec())?.into(),
+        false => received.clone(),
+    };
+    let payload = webhook::parse_payload(state, headers, &json)?;
+    Ok((payload, RawBody { received, json }))
+}
+
+fn json_response<T: serde::Serialize>(status: StatusCode, value: &T) -> Response<Full<Bytes>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap(),
+        Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+fn empty_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::SignatureVerifier;
+
+    const SECRET: &str = "secret";
+    const PING: &[u8] = br#"{"zen":"Keep it logically awesome.","hook_id":1}"#;
+
+    fn signature(body: &[u8]) -> String {
+        let mut verifier = SignatureVerifier::new(SECRET).unwrap();
+        verifier.update(body);
+        verifier.signature()
+    }
+
+    async fn call(
+        state: AppState,
+        signature: Option<&str>,
+        body: &'static [u8],
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::post("/webhook").header("x-github-event", "ping");
+        if let Some(signature) = signature {
+            request = request.header("x-hub-signature-256", signature);
+        }
+        let request = request.body(Full::new(Bytes::from_static(body))).unwrap();
+        let response = WebhookService::new(Arc::new(state))
+            .call(request)
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn processes_a_signed_delivery() {
+        let state = AppState::new(Some(SECRET.to_string()));
+        let (status, body) = call(state, Some(&signature(PING)), PING).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["processed"], true);
+    }
+
+    #[tokio::test]
+    async fn rejects_unsigned_and_forged_deliveries() {
+        let state = AppState::new(Some(SECRET.to_string()));
+        let (status, body) = call(state.clone(), None, PING).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "missing_signature");
+
+        let forged = signature(br#"{"zen":"Something else.","hook_id":1}"#);
+        let (status, body) = call(state.clone(), Some(&forged), PING).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "invalid_signature");
+
+        let (status, body) = call(state, Some("sha256=not-hex"), PING).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            body["error"]["message"],
+            "invalid signature: the signature is not valid hex"
+        );
+    }
+
+    #[tokio::test]
+    async fn verifies_nothing_without_a_secret() {
+        let (status, _) = call(AppState::new(None), None, PING).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(AppState::new(None), None, b"{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "invalid_payload");
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_over_the_limit_before_verifying() {
+        let mut config = Config::default();
+        config.server.max_body_bytes = 16;
+        let state = AppState::new(Some(SECRET.to_string())).with_config(config);
+        let (status, body) = call(state, Some(&signature(PING)), PING).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["error"]["code"], "body_too_large");
+    }
+
+    #[tokio::test]
+    async fn a_failing_handler_does_not_skip_the_rest() {
+        use crate::testing::{Event, PullRequestEventBuilder, github::MockGithub};
+
+        let github = MockGithub::start().await.unwrap();
+        let mut config = Config::default();
+        config.github.token = Some("token".to_string());
+        // listing the changed files is answered with a 404
+        config.labeler.enabled = true;
+        config.labeler.labels =
+            std::collections::HashMap::from([("docs".to_string(), vec!["*.md".to_string()])]);
+        config.pr_title.enabled = true;
+        let state = AppState::new(None)
+            .with_config(config)
+            .with_github(github.client());
+
+        let request = PullRequestEventBuilder::opened().request(None);
+        let response = WebhookService::new(Arc::new(state))
+            .call(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "handler_failed");
+        // the title check runs after the labeler failed
+        assert_eq!(
+            github
+                .requests_to(http::Method::POST, "/repos/*/*/statuses/*")
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,137 @@
+// `x-hub-signature-256` verification, shared by the server and the blocking API.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn verify_signature(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let Some(mut verifier) = SignatureVerifier::new(secret) else {
+        return false;
+    };
+    verifier.update(payload);
+    verifier.verify(signature)
+}
+
+// why a signature was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    // the value does not start with `sha256=`, e.g. an `x-hub-signature` sha1 value
+    WrongPrefix,
+    InvalidHex,
+    // well formed, but computed over a different body or with a different secret
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::WrongPrefix => write!(f, "the signature does not start with sha256="),
+            SignatureError::InvalidHex => write!(f, "the signature is not valid hex"),
+            SignatureError::Mismatch => write!(f, "the signature does not match the payload"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Computes the `x-hub-signature-256` HMAC incrementally, so a body can be verified
+/// while it is still being read.
+#[derive(Clone)]
+pub struct SignatureVerifier {
+    mac: HmacSha256,
+}
+
+impl SignatureVerifier {
+    pub fn new(secret: &str) -> Option<Self> {
+        HmacSha256::new_from_slice(secret.as_bytes())
+            .ok()
+            .map(|mac| Self { mac })
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.mac.update(chunk);
+    }
+
+    // the header value for everything passed to `update`, e.g. `sha256=6f1e...`
+    pub fn signature(self) -> String {
+        format!("sha256={}", hex::encode(self.mac.finalize().into_bytes()))
+    }
+
+    pub fn verify(self, signature: &str) -> bool {
+        self.check(signature).is_ok()
+    }
+
+    pub fn check(self, signature: &str) -> Result<(), SignatureError> {
+        let signature = signature
+            .strip_prefix("sha256=")
+            .ok_or(SignatureError::WrongPrefix)?;
+        let expected = hex::decode(signature).map_err(|_| SignatureError::InvalidHex)?;
+        self.mac
+            .verify_slice(&expected)
+            .map_err(|_| SignatureError::Mismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example from GitHub's "Validating webhook deliveries" guide
+    const SECRET: &str = "It's a Secret to Everybody";
+    const PAYLOAD: &[u8] = b"Hello, World!";
+    const SIGNATURE: &str =
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+
+    #[test]
+    fn matches_githubs_signature() {
+        assert!(verify_signature(SECRET, PAYLOAD, SIGNATURE));
+
+        let mut verifier = SignatureVerifier::new(SECRET).unwrap();
+        for chunk in PAYLOAD.chunks(3) {
+            verifier.update(chunk);
+        }
+        assert_eq!(verifier.signature(), SIGNATURE);
+    }
+
+    #[test]
+    fn says_why_a_signature_is_rejected() {
+        let check = |secret: &str, payload: &[u8], signature: &str| {
+            let mut verifier = SignatureVerifier::new(secret).unwrap();
+            verifier.update(payload);
+            verifier.check(signature)
+        };
+        assert_eq!(check(SECRET, PAYLOAD, SIGNATURE), Ok(()));
+        assert_eq!(
+            check(
+                SECRET,
+                PAYLOAD,
+                "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59"
+            ),
+            Err(SignatureError::WrongPrefix)
+        );
+        assert_eq!(
+            check(SECRET, PAYLOAD, &SIGNATURE["sha256=".len()..]),
+            Err(SignatureError::WrongPrefix)
+        );
+        assert_eq!(
+            check(SECRET, PAYLOAD, "sha256=not-hex"),
+            Err(SignatureError::InvalidHex)
+        );
+        assert_eq!(
+            check(SECRET, b"Hello, World", SIGNATURE),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            check("another secret", PAYLOAD, SIGNATURE),
+            Err(SignatureError::Mismatch)
+        );
+        // a truncated digest is not accepted as a prefix match
+        assert_eq!(
+            check(SECRET, PAYLOAD, &SIGNATURE[..SIGNATURE.len() - 2]),
+            Err(SignatureError::Mismatch)
+        );
+        assert!(!verify_signature(SECRET, PAYLOAD, ""));
+    }
+}
@@ -0,0 +1,153 @@
+use serde::de::DeserializeOwned;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// A request body kept in memory up to a threshold and moved to a temporary file
+/// beyond it, so multi-megabyte push payloads are not held in memory while parsed.
+pub enum Spool {
+    Memory {
+        data: Vec<u8>,
+        threshold: usize,
+    },
+    File {
+        file: File,
+        path: PathBuf,
+        len: usize,
+    },
+}
+
+impl Spool {
+    pub fn new(threshold: usize) -> Self {
+        Spool::Memory {
+            data: Vec::new(),
+            threshold,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Spool::Memory { data, .. } => data.len(),
+            Spool::File { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        match self {
+            Spool::Memory { data, threshold } if data.len() + chunk.len() > *threshold => {
+                let path = temp_path();
+                let mut options = File::options();
+                options.read(true).write(true).create_new(true);
+                // the temp directory is usually shared, and bodies carry private data
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+                let mut file = options.open(&path)?;
+                file.write_all(data)?;
+                file.write_all(chunk)?;
+                let len = data.len() + chunk.len();
+                *self = Spool::File { file, path, len };
+                Ok(())
+            }
+            Spool::Memory { data, .. } => {
+                data.extend_from_slice(chunk);
+                Ok(())
+            }
+            Spool::File { file, len, .. } => {
+                file.write_all(chunk)?;
+                *len += chunk.len();
+                Ok(())
+            }
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&mut self) -> Result<T, serde_json::Error> {
+        match self {
+            Spool::Memory { data, .. } => serde_json::from_slice(data),
+            Spool::File { file, .. } => {
+                file.rewind().map_err(serde_json::Error::io)?;
+                serde_json::from_reader(BufReader::new(&*file))
+            }
+        }
+    }
+
+    // the whole body, for formats that cannot be parsed as a stream
+    pub fn read_all(&mut self) -> io::Result<Vec<u8>> {
+        match self {
+            Spool::Memory { data, .. } => Ok(std::mem::take(data)),
+            Spool::File { file, len, .. } => {
+                let mut data = Vec::with_capacity(*len);
+                file.rewind()?;
+                file.read_to_end(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        if let Spool::File { path, .. } = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn temp_path() -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "nexus-body-{}-{}.json",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn small_bodies_stay_in_memory() {
+        let mut spool = Spool::new(64);
+        spool.write(br#"{"a":"#).unwrap();
+        spool.write(b"1}").unwrap();
+        assert!(matches!(spool, Spool::Memory { .. }));
+        assert_eq!(spool.len(), 7);
+        assert_eq!(spool.parse::<Value>().unwrap(), serde_json::json!({"a": 1}));
+    }
+
+    #[test]
+    fn large_bodies_move_to_a_private_file() {
+        let body = format!(r#"{{"a":"{}"}}"#, "x".repeat(100));
+        let mut spool = Spool::new(16);
+        for chunk in body.as_bytes().chunks(10) {
+            spool.write(chunk).unwrap();
+        }
+        let Spool::File { path, .. } = &spool else {
+            panic!("the body was not spooled to disk");
+        };
+        let path = path.clone();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert_eq!(spool.len(), body.len());
+        assert_eq!(
+            spool.parse::<Value>().unwrap()["a"].as_str().unwrap().len(),
+            100
+        );
+        assert_eq!(spool.read_all().unwrap(), body.as_bytes());
+        drop(spool);
+        assert!(!path.exists());
+    }
+}
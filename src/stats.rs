@@ -0,0 +1,215 @@
+// Activity counted per repository, author and UTC day in the shared store, so every
+// replica contributes and /stats reads the same numbers from any of them:
+//   stats:{repo}:{day}:{metric}           pull requests opened or merged, reviews
+//   stats:{repo}:{day}:{metric}:@{login}  the same per author
+//   stats:{repo}:{day}:merge_secs         seconds from opening to merge, one per merge
+
+use chrono::{Days, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::error;
+
+use crate::{service::AppState, store::StoreError, webhook::WebhookPayload};
+
+const PREFIX: &str = "stats:";
+const OPENED: &str = "opened";
+const MERGED: &str = "merged";
+const REVIEWS: &str = "reviews";
+const MERGE_SECS: &str = "merge_secs";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Counts {
+    pub prs_opened: u64,
+    pub prs_merged: u64,
+    pub reviews: u64,
+}
+
+impl Counts {
+    fn add(&mut self, metric: &str, value: u64) {
+        match metric {
+            OPENED => self.prs_opened += value,
+            MERGED => self.prs_merged += value,
+            REVIEWS => self.reviews += value,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DayStats {
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorStats {
+    pub login: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeTimes {
+    pub count: usize,
+    pub mean_secs: u64,
+    pub median_secs: u64,
+    pub p90_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoStats {
+    pub repo: String,
+    pub days: u32,
+    pub since: NaiveDate,
+    #[serde(flatten)]
+    pub totals: Counts,
+    // None until something merged in the window
+    pub time_to_merge: Option<MergeTimes>,
+    // oldest first
+    pub daily: Vec<DayStats>,
+    // most merged first, then most reviews
+    pub authors: Vec<AuthorStats>,
+}
+
+pub async fn record(state: &AppState, event_type: &str, payload: &WebhookPayload) {
+    let config = &state.config.stats;
+    if !config.enabled {
+        return;
+    }
+    let (Some(repo), Some(action)) = (&payload.repository, payload.action.as_deref()) else {
+        return;
+    };
+
+    let mut merge_secs = None;
+    let (metric, login) = match (event_type, action) {
+        ("pull_request", "opened") => match &payload.pull_request {
+            Some(pr) => (OPENED, &pr.user.login),
+            None => return,
+        },
+        ("pull_request", "closed") => match &payload.pull_request {
+            Some(pr) if pr.merged == Some(true) => {
+                if let (Some(created), Some(merged)) = (pr.created_at, pr.merged_at) {
+                    merge_secs = Some((merged - created).num_seconds().max(0));
+                }
+                (MERGED, &pr.user.login)
+            }
+            _ => return,
+        },
+        ("pull_request_review", "submitted") => match &payload.review {
+            Some(review) => (REVIEWS, &review.user.login),
+            None => return,
+        },
+        _ => return,
+    };
+
+    let day = format!("{}{}:{}:", PREFIX, repo.full_name.to_lowercase(), today());
+    let ttl = (u64::from(config.retention_days) + 1) * 86400;
+    let result = async {
+        for key in [
+            format!("{}{}", day, metric),
+            format!("{}{}:@{}", day, metric, login),
+        ] {
+            state.store.incr(&key).await?;
+            state.store.expire(&key, ttl).await?;
+        }
+        if let Some(secs) = merge_secs {
+            let key = format!("{}{}", day, MERGE_SECS);
+            state.store.push(&key, &secs.to_string()).await?;
+            state.store.expire(&key, ttl).await?;
+        }
+        Ok::<_, StoreError>(())
+    };
+    if let Err(e) = result.await {
+        error!("Failed to record stats for {}: {}", repo.full_name, e);
+    }
+}
+
+// the last `days` days including today, clamped to what is retained
+pub async fn repo(
+    state: &AppState,
+    repo: &str,
+    days: Option<u32>,
+) -> Result<RepoStats, StoreError> {
+    let config = &state.config.stats;
+    let days = days
+        .unwrap_or(config.default_days)
+        .clamp(1, config.retention_days.max(1));
+    let repo = repo.to_lowercase();
+    let today = today();
+    let since = today - Days::new(u64::from(days) - 1);
+
+    let mut stats = RepoStats {
+        repo: repo.clone(),
+        days,
+        since,
+        totals: Counts::default(),
+        time_to_merge: None,
+        daily: Vec::new(),
+        authors: Vec::new(),
+    };
+    let mut merge_secs: Vec<u64> = Vec::new();
+    for date in since.iter_days().take(days as usize) {
+        let day = format!("{}{}:{}:", PREFIX, repo, date);
+        let mut counts = Counts::default();
+        for metric in [OPENED, MERGED, REVIEWS] {
+            counts.add(
+                metric,
+                state.store.count(&format!("{}{}", day, metric)).await?,
+            );
+        }
+        stats.totals.add(OPENED, counts.prs_opened);
+        stats.totals.add(MERGED, counts.prs_merged);
+        stats.totals.add(REVIEWS, counts.reviews);
+        stats.daily.push(DayStats { date, counts });
+
+        let secs = state.store.list(&format!("{}{}", day, MERGE_SECS)).await?;
+        merge_secs.extend(secs.iter().filter_map(|s| s.parse::<u64>().ok()));
+    }
+
+    // author keys are found by listing, so only days that saw activity cost a read
+    let mut authors: BTreeMap<String, Counts> = BTreeMap::new();
+    let prefix = format!("{}{}:", PREFIX, repo);
+    for key in state.store.keys(&prefix).await? {
+        let mut parts = key[prefix.len()..].splitn(3, ':');
+        let (Some(date), Some(metric), Some(login)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let Some(login) = login.strip_prefix('@') else {
+            continue;
+        };
+        match date.parse::<NaiveDate>() {
+            Ok(date) if date >= since && date <= today => {}
+            _ => continue,
+        }
+        let count = state.store.count(&key).await?;
+        authors
+            .entry(login.to_string())
+            .or_default()
+            .add(metric, count);
+    }
+    stats.authors = authors
+        .into_iter()
+        .map(|(login, counts)| AuthorStats { login, counts })
+        .collect();
+    stats.authors.sort_by(|a, b| {
+        (b.counts.prs_merged, b.counts.reviews).cmp(&(a.counts.prs_merged, a.counts.reviews))
+    });
+
+    if !merge_secs.is_empty() {
+        merge_secs.sort_unstable();
+        let count = merge_secs.len();
+        stats.time_to_merge = Some(MergeTimes {
+            count,
+            mean_secs: merge_secs.iter().sum::<u64>() / count as u64,
+            median_secs: merge_secs[count / 2],
+            p90_secs: merge_secs[(count * 9 / 10).min(count - 1)],
+        });
+    }
+    Ok(stats)
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
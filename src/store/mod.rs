@@ -0,0 +1,255 @@
+// state the automations keep between deliveries that has to agree across replicas:
+// digest windows, reviewer rotation positions and activity statistics
+
+mod redis;
+
+pub use redis::{Redis, Reply};
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::{StateBackend, StateConfig};
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    // what the server or the client reported; RESP errors carry no more than a message
+    #[error("redis: {0}")]
+    Redis(String),
+}
+
+#[derive(Default)]
+pub struct Memory {
+    counters: HashMap<String, u64>,
+    lists: HashMap<String, Vec<String>>,
+    values: HashMap<String, String>,
+    // deadlines set by `expire`, swept at most once a minute
+    deadlines: HashMap<String, Instant>,
+    swept_at: Option<Instant>,
+}
+
+impl Memory {
+    fn sweep(&mut self) {
+        let now = Instant::now();
+        if self
+            .swept_at
+            .is_some_and(|at| now.duration_since(at) < Duration::from_secs(60))
+        {
+            return;
+        }
+        self.swept_at = Some(now);
+        let expired: Vec<String> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            self.deadlines.remove(&key);
+            self.counters.remove(&key);
+            self.lists.remove(&key);
+            self.values.remove(&key);
+        }
+    }
+}
+
+/// Counters, lists and values shared by the automations. `Memory` keeps them in this
+/// process; `Redis` shares them between every instance pointed at the same database.
+pub enum Store {
+    Memory(Mutex<Memory>),
+    Redis(Redis),
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store::Memory(Mutex::default())
+    }
+}
+
+impl Store {
+    pub fn from_config(config: &StateConfig) -> Result<Self, StoreError> {
+        match config.backend {
+            StateBackend::Memory => Ok(Store::default()),
+            StateBackend::Redis => {
+                let url = config
+                    .redis_url
+                    .as_deref()
+                    .ok_or_else(|| StoreError::Redis("redis_url is not set".to_string()))?;
+                Ok(Store::Redis(Redis::new(url, &config.key_prefix)?))
+            }
+        }
+    }
+
+    // returns the value before the increment, starting at 0
+    pub async fn incr(&self, key: &str) -> Result<u64, StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                let mut memory = memory.lock().unwrap();
+                let counter = memory.counters.entry(key.to_string()).or_default();
+                let value = *counter;
+                *counter = counter.wrapping_add(1);
+                Ok(value)
+            }
+            Store::Redis(redis) => {
+                let value = redis.command(&["INCR", &redis.key(key)]).await?.integer()?;
+                Ok(value.saturating_sub(1) as u64)
+            }
+        }
+    }
+
+    // reads a counter without changing it
+    pub async fn count(&self, key: &str) -> Result<u64, StoreError> {
+        match self {
+            Store::Memory(memory) => Ok(memory
+                .lock()
+                .unwrap()
+                .counters
+                .get(key)
+                .copied()
+                .unwrap_or_default()),
+            Store::Redis(redis) => Ok(redis
+                .command(&["GET", &redis.key(key)])
+                .await?
+                .string()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default()),
+        }
+    }
+
+    // appends to a list and returns its new length
+    pub async fn push(&self, key: &str, item: &str) -> Result<usize, StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                let mut memory = memory.lock().unwrap();
+                let list = memory.lists.entry(key.to_string()).or_default();
+                list.push(item.to_string());
+                Ok(list.len())
+            }
+            Store::Redis(redis) => {
+                let len = redis
+                    .command(&["RPUSH", &redis.key(key), item])
+                    .await?
+                    .integer()?;
+                Ok(len as usize)
+            }
+        }
+    }
+
+    // reads a list without removing it
+    pub async fn list(&self, key: &str) -> Result<Vec<String>, StoreError> {
+        match self {
+            Store::Memory(memory) => Ok(memory
+                .lock()
+                .unwrap()
+                .lists
+                .get(key)
+                .cloned()
+                .unwrap_or_default()),
+            Store::Redis(redis) => Ok(redis
+                .command(&["LRANGE", &redis.key(key), "0", "-1"])
+                .await?
+                .strings()),
+        }
+    }
+
+    // removes a list and returns what it held; concurrent takes never see the same items
+    pub async fn take(&self, key: &str) -> Result<Vec<String>, StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                Ok(memory.lock().unwrap().lists.remove(key).unwrap_or_default())
+            }
+            Store::Redis(redis) => {
+                let key = redis.key(key);
+                let reply = redis
+                    .transaction(&[&["LRANGE", &key, "0", "-1"], &["DEL", &key]])
+                    .await?;
+                Ok(reply
+                    .into_iter()
+                    .next()
+                    .map(Reply::strings)
+                    .unwrap_or_default())
+            }
+        }
+    }
+
+    // true when this call set the value
+    pub async fn set_if_absent(&self, key: &str, value: &str) -> Result<bool, StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                let mut memory = memory.lock().unwrap();
+                if memory.values.contains_key(key) {
+                    return Ok(false);
+                }
+                memory.values.insert(key.to_string(), value.to_string());
+                Ok(true)
+            }
+            Store::Redis(redis) => {
+                let reply = redis
+                    .command(&["SET", &redis.key(key), value, "NX"])
+                    .await?;
+                Ok(!matches!(reply, Reply::Nil))
+            }
+        }
+    }
+
+    pub async fn value(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self {
+            Store::Memory(memory) => Ok(memory.lock().unwrap().values.get(key).cloned()),
+            Store::Redis(redis) => Ok(redis.command(&["GET", &redis.key(key)]).await?.string()),
+        }
+    }
+
+    // removes a value and returns it
+    pub async fn take_value(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self {
+            Store::Memory(memory) => Ok(memory.lock().unwrap().values.remove(key)),
+            Store::Redis(redis) => {
+                let key = redis.key(key);
+                let reply = redis.transaction(&[&["GET", &key], &["DEL", &key]]).await?;
+                Ok(reply.into_iter().next().and_then(Reply::string))
+            }
+        }
+    }
+
+    // drops a key after `secs`; in memory it may linger for up to a minute longer
+    pub async fn expire(&self, key: &str, secs: u64) -> Result<(), StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                let mut memory = memory.lock().unwrap();
+                let deadline = Instant::now() + Duration::from_secs(secs);
+                memory.deadlines.insert(key.to_string(), deadline);
+                memory.sweep();
+                Ok(())
+            }
+            Store::Redis(redis) => {
+                redis
+                    .command(&["EXPIRE", &redis.key(key), &secs.to_string()])
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    // keys starting with `prefix`, without the configured key prefix
+    pub async fn keys(&self, prefix: &str) -> Result<Vec<String>, StoreError> {
+        match self {
+            Store::Memory(memory) => {
+                let memory = memory.lock().unwrap();
+                Ok(memory
+                    .counters
+                    .keys()
+                    .chain(memory.lists.keys())
+                    .chain(memory.values.keys())
+                    .filter(|k| k.starts_with(prefix))
+                    .cloned()
+                    .collect())
+            }
+            Store::Redis(redis) => redis.scan(prefix).await,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// socket activation and readiness notification for running under systemd; both are
+// no-ops when the service was not started by systemd
+
+use std::time::Duration;
+
+// the sockets passed by a `.socket` unit, in the order it lists them, per sd_listen_fds(3).
+// Takes ownership of the descriptors, so it is called once; the variables are unset so
+// that a second call, or a child process, finds nothing
+#[cfg(unix)]
+pub fn listeners_from_env() -> Vec<std::net::TcpListener> {
+    use std::os::fd::FromRawFd;
+
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    let var = |name| std::env::var(name).ok()?.parse::<u32>().ok();
+    let (pid, fds) = (var("LISTEN_PID"), var("LISTEN_FDS"));
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        // called once at startup, before anything else reads the environment
+        unsafe { std::env::remove_var(name) };
+    }
+    let (Some(pid), Some(fds)) = (pid, fds) else {
+        return Vec::new();
+    };
+    if pid != std::process::id() {
+        return Vec::new();
+    }
+
+    (0..fds as i32)
+        .filter_map(|offset| {
+            // systemd hands the descriptors over to this process, nothing else owns them
+            let listener =
+                unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START + offset) };
+            listener.set_nonblocking(true).ok()?;
+            Some(listener)
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn listeners_from_env() -> Vec<std::net::TcpListener> {
+    Vec::new()
+}
+
+// sends a state string such as `READY=1` to the socket in NOTIFY_SOCKET, per sd_notify(3)
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+
+    let result = match path.to_str().and_then(|p| p.strip_prefix('@')) {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        _ => socket.send_to(state.as_bytes(), &path),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+// pings the watchdog at half the interval systemd expects, per sd_watchdog_enabled(3)
+pub fn spawn_watchdog() {
+    let Some(interval) = watchdog_interval() else {
+        return;
+    };
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval / 2);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    });
+}
+
+fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Ok(pid) = std::env::var("WATCHDOG_PID")
+        && pid.parse::<u32>().ok()? != std::process::id()
+    {
+        return None;
+    }
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
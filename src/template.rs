@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+// replaces `{name}` placeholders; unknown placeholders are left untouched, and scanning
+// resumes right after an unmatched `{` so braces in JSON templates do not hide placeholders
+pub fn render(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match vars.get(name) {
+                    Some(value) => {
+                        output.push_str(value);
+                        rest = &after[end + 1..];
+                    }
+                    None => {
+                        output.push('{');
+                        rest = after;
+                    }
+                }
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,267 @@
+// A stand-in for the GitHub REST API on a local port, so automations can be tested offline.
+// The endpoints nexus calls most (comments, labels, statuses, check runs, installation
+// tokens) answer with canned responses, everything else with a 404, and every request is
+// recorded for assertions. Only built with the `test-support` feature.
+//
+//     let github = MockGithub::start().await?;
+//     let state = AppState::new(None).with_github(github.client());
+//     ...
+//     assert_eq!(github.requests_to(Method::POST, "/repos/a/b/issues/1/comments").len(), 1);
+
+use axum::{body::Bytes, extract::Request, response::IntoResponse};
+use http::{Method, StatusCode, header::AUTHORIZATION};
+use serde_json::{Value, json};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::github::GithubClient;
+
+/// A request the mock received.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<String>,
+    // e.g. `Bearer ghs_mock`
+    pub authorization: Option<String>,
+    // null when the request had no JSON body
+    pub body: Value,
+}
+
+type Responder = Arc<dyn Fn(&RecordedRequest) -> (StatusCode, Value) + Send + Sync>;
+
+// `pattern` matches a path segment by segment, `*` matching any one segment
+struct Route {
+    method: Method,
+    pattern: String,
+    respond: Responder,
+}
+
+impl Route {
+    fn new(
+        method: Method,
+        pattern: &str,
+        respond: impl Fn(&RecordedRequest) -> (StatusCode, Value) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            method,
+            pattern: pattern.to_string(),
+            respond: Arc::new(respond),
+        }
+    }
+
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        let mut pattern = self.pattern.split('/');
+        let mut path = path.split('/');
+        self.method == method
+            && loop {
+                match (pattern.next(), path.next()) {
+                    (None, None) => break true,
+                    (Some(expected), Some(actual)) if expected == "*" || expected == actual => {}
+                    _ => break false,
+                }
+            }
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    requests: Mutex<Vec<RecordedRequest>>,
+    // checked before the canned routes, the latest first
+    overrides: Mutex<Vec<Route>>,
+}
+
+/// The mock API, stopped when dropped.
+pub struct MockGithub {
+    addr: SocketAddr,
+    recorder: Arc<Recorder>,
+    server: JoinHandle<()>,
+}
+
+impl MockGithub {
+    // listens on 127.0.0.1 on a free port
+    pub async fn start() -> std::io::Result<Self> {
+        let recorder = Arc::new(Recorder::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let routes = Arc::new(canned());
+        let app = axum::Router::new().fallback({
+            let recorder = recorder.clone();
+            move |request: Request| handle(recorder.clone(), routes.clone(), request)
+        });
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self {
+            addr,
+            recorder,
+            server,
+        })
+    }
+
+    // e.g. `http://127.0.0.1:54321`
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // a client that talks to the mock, for `AppState::with_github`
+    pub fn client(&self) -> GithubClient {
+        GithubClient::new().base_url(self.url())
+    }
+
+    // answers `method` on paths matching `pattern` (`*` for any one segment) with `body`,
+    // ahead of the canned responses and earlier overrides
+    pub fn respond(&self, method: Method, pattern: &str, status: StatusCode, body: Value) {
+        let route = Route::new(method, pattern, move |_| (status, body.clone()));
+        self.recorder.overrides.lock().unwrap().push(route);
+    }
+
+    // like `respond`, computing the answer from the request, e.g. one page of a list
+    pub fn respond_with(
+        &self,
+        method: Method,
+        pattern: &str,
+        respond: impl Fn(&RecordedRequest) -> (StatusCode, Value) + Send + Sync + 'static,
+    ) {
+        let route = Route::new(method, pattern, respond);
+        self.recorder.overrides.lock().unwrap().push(route);
+    }
+
+    // everything received so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.recorder.requests.lock().unwrap().clone()
+    }
+
+    pub fn requests_to(&self, method: Method, pattern: &str) -> Vec<RecordedRequest> {
+        let route = Route::new(method, pattern, |_| (StatusCode::OK, Value::Null));
+        self.requests()
+            .into_iter()
+            .filter(|r| route.matches(&r.method, &r.path))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.recorder.requests.lock().unwrap().clear();
+    }
+}
+
+impl Drop for MockGithub {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn handle(
+    recorder: Arc<Recorder>,
+    routes: Arc<Vec<Route>>,
+    request: Request,
+) -> impl IntoResponse {
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .unwrap_or_else(|_| Bytes::new());
+    let recorded = RecordedRequest {
+        method: parts.method,
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(str::to_string),
+        authorization: parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    };
+    let respond = {
+        let overrides = recorder.overrides.lock().unwrap();
+        overrides
+            .iter()
+            .rev()
+            .chain(routes.iter())
+            .find(|route| route.matches(&recorded.method, &recorded.path))
+            .map(|route| route.respond.clone())
+    };
+    let (status, body) = match respond {
+        Some(respond) => respond(&recorded),
+        None => (
+            StatusCode::NOT_FOUND,
+            json!({ "message": "Not Found", "documentation_url": "https://docs.github.com/rest" }),
+        ),
+    };
+    recorder.requests.lock().unwrap().push(recorded);
+    (status, axum::Json(body))
+}
+
+fn user() -> Value {
+    json!({ "login": "nexus[bot]", "html_url": "https://github.com/apps/nexus" })
+}
+
+// what GitHub answers when the call succeeds, trimmed to the fields nexus reads
+fn canned() -> Vec<Route> {
+    let comment = |r: &RecordedRequest| {
+        json!({
+            "id": 1,
+            "body": r.body["body"],
+            "user": user(),
+            "html_url": "https://github.com/octo-org/example/issues/1#issuecomment-1",
+        })
+    };
+    let labels = |r: &RecordedRequest| -> Value {
+        r.body["labels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|name| json!({ "name": name, "color": "ededed", "description": null }))
+            .collect()
+    };
+    vec![
+        Route::new(Method::GET, "/repos/*/*/issues/*/comments", |_| {
+            (StatusCode::OK, json!([]))
+        }),
+        Route::new(Method::POST, "/repos/*/*/issues/*/comments", move |r| {
+            (StatusCode::CREATED, comment(r))
+        }),
+        Route::new(Method::PATCH, "/repos/*/*/issues/comments/*", move |r| {
+            (StatusCode::OK, comment(r))
+        }),
+        Route::new(Method::POST, "/repos/*/*/issues/*/labels", move |r| {
+            (StatusCode::OK, labels(r))
+        }),
+        Route::new(Method::DELETE, "/repos/*/*/issues/*/labels/*", |_| {
+            (StatusCode::OK, json!([]))
+        }),
+        Route::new(Method::GET, "/repos/*/*/labels", |_| {
+            (StatusCode::OK, json!([]))
+        }),
+        Route::new(Method::POST, "/repos/*/*/labels", |r| {
+            (StatusCode::CREATED, r.body.clone())
+        }),
+        Route::new(Method::PATCH, "/repos/*/*/labels/*", |r| {
+            (StatusCode::OK, r.body.clone())
+        }),
+        Route::new(Method::POST, "/repos/*/*/statuses/*", |r| {
+            (StatusCode::CREATED, r.body.clone())
+        }),
+        Route::new(Method::POST, "/repos/*/*/check-runs", |_| {
+            (StatusCode::CREATED, json!({ "id": 1 }))
+        }),
+        Route::new(Method::PATCH, "/repos/*/*/check-runs/*", |_| {
+            (StatusCode::OK, json!({ "id": 1 }))
+        }),
+        Route::new(Method::POST, "/app/installations/*/access_tokens", |_| {
+            let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+            (
+                StatusCode::CREATED,
+                json!({ "token": "ghs_mock", "expires_at": expires_at }),
+            )
+        }),
+        Route::new(Method::GET, "/repos/*/*/installation", |_| {
+            (StatusCode::OK, json!({ "id": 1 }))
+        }),
+        Route::new(Method::GET, "/orgs/*/installation", |_| {
+            (StatusCode::OK, json!({ "id": 1 }))
+        }),
+    ]
+}
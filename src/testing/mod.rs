@@ -0,0 +1,598 @@
+// Helpers for testing handlers without fixture files: payload builders shaped like GitHub's
+// deliveries, a signer for `x-hub-signature-256`, and an in-process server running the
+// webhook pipeline on a local port.
+//
+//     let server = TestServer::start(AppState::new(Some("secret".into()))).await?;
+//     let event = PullRequestEventBuilder::opened().repo("a/b").title("Fix login");
+//     assert_eq!(server.send(&event).await?.status(), 200);
+
+// also built for the crate's own tests
+#[cfg(any(test, feature = "test-support"))]
+pub mod github;
+
+use bytes::Bytes;
+use http::Request;
+use http_body_util::Full;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::{
+    service::{AppState, WebhookService},
+    webhook::{SignatureVerifier, WebhookPayload},
+};
+
+const HEAD_SHA: &str = "6dcb09b5b57875f334f61aebed695e2e4193db5e";
+const BASE_SHA: &str = "9049f1265b7d61be4a8904a9a27120d2064dab3b";
+const TIMESTAMP: &str = "2024-05-01T12:00:00Z";
+
+/// The `x-hub-signature-256` value GitHub would send for `body`.
+pub fn sign_request(secret: &str, body: &[u8]) -> String {
+    let mut verifier = SignatureVerifier::new(secret).expect("HMAC accepts any key length");
+    verifier.update(body);
+    verifier.signature()
+}
+
+/// A webhook delivery that can be sent to the pipeline.
+pub trait Event {
+    // the `x-github-event` header, e.g. `pull_request`
+    fn event_type(&self) -> &'static str;
+
+    fn to_json(&self) -> Value;
+
+    // the payload as handlers receive it
+    fn payload(&self) -> WebhookPayload {
+        WebhookPayload::deserialize(&self.to_json()).expect("builders produce valid payloads")
+    }
+
+    // a POST with GitHub's headers, signed when a secret is given
+    fn request(&self, secret: Option<&str>) -> Request<Full<Bytes>> {
+        static NEXT_DELIVERY: AtomicU64 = AtomicU64::new(1);
+        let body = serde_json::to_vec(&self.to_json()).unwrap_or_default();
+        let delivery = format!(
+            "00000000-0000-0000-0000-{:012}",
+            NEXT_DELIVERY.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut request = Request::post("/webhook")
+            .header("content-type", "application/json")
+            .header("user-agent", "GitHub-Hookshot/nexus-testing")
+            .header("x-github-event", self.event_type())
+            .header("x-github-delivery", delivery);
+        if let Some(secret) = secret {
+            request = request.header("x-hub-signature-256", sign_request(secret, &body));
+        }
+        request.body(Full::new(Bytes::from(body))).unwrap()
+    }
+}
+
+// what every event carries: the repository and the user who triggered it
+#[derive(Debug, Clone)]
+struct Common {
+    repo: String,
+    sender: String,
+}
+
+impl Default for Common {
+    fn default() -> Self {
+        Self {
+            repo: "octo-org/example".to_string(),
+            sender: "octocat".to_string(),
+        }
+    }
+}
+
+impl Common {
+    fn repository(&self) -> Value {
+        let (owner, name) = self
+            .repo
+            .split_once('/')
+            .unwrap_or((&self.repo, &self.repo));
+        json!({
+            "id": 1296269,
+            "name": name,
+            "full_name": self.repo,
+            "private": false,
+            "owner": user(owner),
+            "html_url": format!("https://github.com/{}", self.repo),
+            "default_branch": "main",
+        })
+    }
+}
+
+fn user(login: &str) -> Value {
+    json!({
+        "login": login,
+        "id": 583231,
+        "type": "User",
+        "html_url": format!("https://github.com/{}", login),
+    })
+}
+
+fn labels(names: &[String]) -> Value {
+    names
+        .iter()
+        .map(|name| json!({ "name": name, "color": "ededed", "description": null }))
+        .collect()
+}
+
+/// A `pull_request` delivery; the author defaults to the sender.
+#[derive(Debug, Clone)]
+pub struct PullRequestEventBuilder {
+    common: Common,
+    action: String,
+    number: u64,
+    title: String,
+    body: Option<String>,
+    author: Option<String>,
+    head: String,
+    base: String,
+    labels: Vec<String>,
+    draft: bool,
+    merged: bool,
+}
+
+impl PullRequestEventBuilder {
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            common: Common::default(),
+            action: action.into(),
+            number: 1,
+            title: "Update the README with new information".to_string(),
+            body: None,
+            author: None,
+            head: "update-readme".to_string(),
+            base: "main".to_string(),
+            labels: Vec::new(),
+            draft: false,
+            merged: false,
+        }
+    }
+
+    pub fn opened() -> Self {
+        Self::new("opened")
+    }
+
+    pub fn reopened() -> Self {
+        Self::new("reopened")
+    }
+
+    pub fn edited() -> Self {
+        Self::new("edited")
+    }
+
+    pub fn synchronize() -> Self {
+        Self::new("synchronize")
+    }
+
+    // closed without merging
+    pub fn closed() -> Self {
+        Self::new("closed")
+    }
+
+    pub fn merged() -> Self {
+        Self {
+            merged: true,
+            ..Self::new("closed")
+        }
+    }
+
+    pub fn repo(mut self, repo: impl Into<String>) -> Self {
+        self.common.repo = repo.into();
+        self
+    }
+
+    pub fn sender(mut self, login: impl Into<String>) -> Self {
+        self.common.sender = login.into();
+        self
+    }
+
+    pub fn number(mut self, number: u64) -> Self {
+        self.number = number;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn author(mut self, login: impl Into<String>) -> Self {
+        self.author = Some(login.into());
+        self
+    }
+
+    pub fn head(mut self, branch: impl Into<String>) -> Self {
+        self.head = branch.into();
+        self
+    }
+
+    pub fn base(mut self, branch: impl Into<String>) -> Self {
+        self.base = branch.into();
+        self
+    }
+
+    pub fn label(mut self, name: impl Into<String>) -> Self {
+        self.labels.push(name.into());
+        self
+    }
+
+    pub fn draft(mut self, draft: bool) -> Self {
+        self.draft = draft;
+        self
+    }
+}
+
+impl Event for PullRequestEventBuilder {
+    fn event_type(&self) -> &'static str {
+        "pull_request"
+    }
+
+    fn to_json(&self) -> Value {
+        let repository = self.common.repository();
+        let author = self.author.as_deref().unwrap_or(&self.common.sender);
+        json!({
+            "action": self.action,
+            "number": self.number,
+            "repository": repository,
+            "sender": user(&self.common.sender),
+            "pull_request": {
+                "id": 279147437,
+                "node_id": "MDExOlB1bGxSZXF1ZXN0Mjc5MTQ3NDM3",
+                "number": self.number,
+                "title": self.title,
+                "body": self.body,
+                "html_url": format!("https://github.com/{}/pull/{}", self.common.repo, self.number),
+                "state": if self.action == "closed" { "closed" } else { "open" },
+                "user": user(author),
+                "author_association": "CONTRIBUTOR",
+                "head": { "ref": self.head, "sha": HEAD_SHA, "repo": repository },
+                "base": { "ref": self.base, "sha": BASE_SHA, "repo": repository },
+                "labels": labels(&self.labels),
+                "draft": self.draft,
+                "merged": self.merged,
+                "created_at": TIMESTAMP,
+                "merged_at": if self.merged { Some(TIMESTAMP) } else { None },
+            },
+        })
+    }
+}
+
+/// An `issues` delivery; the author defaults to the sender.
+#[derive(Debug, Clone)]
+pub struct IssueEventBuilder {
+    common: Common,
+    action: String,
+    number: u64,
+    title: String,
+    body: Option<String>,
+    author: Option<String>,
+    labels: Vec<String>,
+}
+
+impl IssueEventBuilder {
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            common: Common::default(),
+            action: action.into(),
+            number: 1,
+            title: "Spelling error in the README file".to_string(),
+            body: None,
+            author: None,
+            labels: Vec::new(),
+        }
+    }
+
+    pub fn opened() -> Self {
+        Self::new("opened")
+    }
+
+    pub fn closed() -> Self {
+        Self::new("closed")
+    }
+
+    pub fn repo(mut self, repo: impl Into<String>) -> Self {
+        self.common.repo = repo.into();
+        self
+    }
+
+    pub fn sender(mut self, login: impl Into<String>) -> Self {
+        self.common.sender = login.into();
+        self
+    }
+
+    pub fn number(mut self, number: u64) -> Self {
+        self.number = number;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    pub fn author(mut self, login: impl Into<String>) -> Self {
+        self.author = Some(login.into());
+        self
+    }
+
+    pub fn label(mut self, name: impl Into<String>) -> Self {
+        self.labels.push(name.into());
+        self
+    }
+}
+
+impl Event for IssueEventBuilder {
+    fn event_type(&self) -> &'static str {
+        "issues"
+    }
+
+    fn to_json(&self) -> Value {
+        let author = self.author.as_deref().unwrap_or(&self.common.sender);
+        json!({
+            "action": self.action,
+            "repository": self.common.repository(),
+            "sender": user(&self.common.sender),
+            "issue": {
+                "number": self.number,
+                "node_id": "MDU6SXNzdWUx",
+                "title": self.title,
+                "body": self.body,
+                "html_url": format!("https://github.com/{}/issues/{}", self.common.repo, self.number),
+                "state": if self.action == "closed" { "closed" } else { "open" },
+                "user": user(author),
+                "author_association": "CONTRIBUTOR",
+                "labels": labels(&self.labels),
+                "updated_at": TIMESTAMP,
+            },
+        })
+    }
+}
+
+/// An `issue_comment` delivery, on an issue or with `on_pull_request` on a pull request.
+#[derive(Debug, Clone)]
+pub struct IssueCommentEventBuilder {
+    common: Common,
+    action: String,
+    number: u64,
+    body: String,
+    pull_request: bool,
+}
+
+impl IssueCommentEventBuilder {
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            common: Common::default(),
+            action: action.into(),
+            number: 1,
+            body: "Me too".to_string(),
+            pull_request: false,
+        }
+    }
+
+    pub fn created() -> Self {
+        Self::new("created")
+    }
+
+    pub fn repo(mut self, repo: impl Into<String>) -> Self {
+        self.common.repo = repo.into();
+        self
+    }
+
+    // the comment's author
+    pub fn sender(mut self, login: impl Into<String>) -> Self {
+        self.common.sender = login.into();
+        self
+    }
+
+    pub fn number(mut self, number: u64) -> Self {
+        self.number = number;
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn on_pull_request(mut self) -> Self {
+        self.pull_request = true;
+        self
+    }
+}
+
+impl Event for IssueCommentEventBuilder {
+    fn event_type(&self) -> &'static str {
+        "issue_comment"
+    }
+
+    fn to_json(&self) -> Value {
+        let sender = user(&self.common.sender);
+        let issue_url = format!(
+            "https://github.com/{}/issues/{}",
+            self.common.repo, self.number
+        );
+        json!({
+            "action": self.action,
+            "repository": self.common.repository(),
+            "sender": sender,
+            "issue": {
+                "number": self.number,
+                "title": "Spelling error in the README file",
+                "html_url": issue_url,
+                "state": "open",
+                "user": sender,
+                "labels": [],
+                "pull_request": self.pull_request.then(|| json!({
+                    "html_url": format!("https://github.com/{}/pull/{}", self.common.repo, self.number),
+                })),
+            },
+            "comment": {
+                "id": 1,
+                "body": self.body,
+                "user": sender,
+                "html_url": format!("{}#issuecomment-1", issue_url),
+            },
+        })
+    }
+}
+
+/// A `push` delivery to a branch, by default with one commit.
+#[derive(Debug, Clone)]
+pub struct PushEventBuilder {
+    common: Common,
+    branch: String,
+    commits: Vec<String>,
+}
+
+impl PushEventBuilder {
+    pub fn branch(branch: impl Into<String>) -> Self {
+        Self {
+            common: Common::default(),
+            branch: branch.into(),
+            commits: Vec::new(),
+        }
+    }
+
+    pub fn repo(mut self, repo: impl Into<String>) -> Self {
+        self.common.repo = repo.into();
+        self
+    }
+
+    pub fn sender(mut self, login: impl Into<String>) -> Self {
+        self.common.sender = login.into();
+        self
+    }
+
+    // adds a commit with this message; the last one is the head commit
+    pub fn commit(mut self, message: impl Into<String>) -> Self {
+        self.commits.push(message.into());
+        self
+    }
+}
+
+impl Event for PushEventBuilder {
+    fn event_type(&self) -> &'static str {
+        "push"
+    }
+
+    fn to_json(&self) -> Value {
+        let messages = match self.commits.is_empty() {
+            true => vec!["Fix all the bugs".to_string()],
+            false => self.commits.clone(),
+        };
+        let count = messages.len();
+        let commits: Vec<Value> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                // the head commit gets the well-known sha, earlier ones a made-up one
+                let id = match i + 1 == count {
+                    true => HEAD_SHA.to_string(),
+                    false => format!("{:040x}", i + 1),
+                };
+                json!({
+                    "id": id,
+                    "message": message,
+                    "timestamp": TIMESTAMP,
+                    "url": format!("https://github.com/{}/commit/{}", self.common.repo, id),
+                    "author": { "name": "Monalisa Octocat", "email": "octocat@github.com", "username": self.common.sender },
+                    "added": [],
+                    "removed": [],
+                    "modified": ["README.md"],
+                })
+            })
+            .collect();
+        json!({
+            "ref": format!("refs/heads/{}", self.branch),
+            "before": BASE_SHA,
+            "after": HEAD_SHA,
+            "deleted": false,
+            "repository": self.common.repository(),
+            "sender": user(&self.common.sender),
+            "commits": commits,
+            "compare": format!("https://github.com/{}/compare/{}...{}", self.common.repo, &BASE_SHA[..12], &HEAD_SHA[..12]),
+        })
+    }
+}
+
+/// The webhook pipeline served on a local port, stopped when dropped.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<AppState>,
+    client: reqwest::Client,
+    server: JoinHandle<()>,
+}
+
+impl TestServer {
+    // listens on 127.0.0.1 on a free port; deliveries are signed with the state's secret
+    pub async fn start(state: AppState) -> std::io::Result<Self> {
+        let state = Arc::new(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = axum::Router::new().route(
+            "/webhook",
+            axum::routing::post_service(WebhookService::new(state.clone())),
+        );
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        Ok(Self {
+            addr,
+            state,
+            client: reqwest::Client::new(),
+            server,
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // e.g. `http://127.0.0.1:54321/webhook`
+    pub fn url(&self) -> String {
+        format!("http://{}/webhook", self.addr)
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    pub async fn send(&self, event: &impl Event) -> reqwest::Result<reqwest::Response> {
+        let (parts, body) = event
+            .request(self.state.webhook_secret.as_deref())
+            .into_parts();
+        let body = http_body_util::BodyExt::collect(body)
+            .await
+            .map(|collected| collected.to_bytes())
+            .unwrap_or_default();
+        self.client
+            .post(self.url())
+            .headers(parts.headers)
+            .body(body)
+            .send()
+            .await
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
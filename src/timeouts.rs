@@ -0,0 +1,89 @@
+// Budgets for the work a delivery causes. Each handler `dispatch` runs is cancelled
+// once it overruns `timeouts.handler_secs` (or its entry in `timeouts.handlers`), so one
+// hung downstream call no longer holds up the delivery or a queue worker; the
+// remaining handlers still run. Outbound attempts have their own budget per target.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::error;
+
+use crate::{error::NexusError, service::AppState};
+
+/// How often each handler and outbound target ran out of time.
+#[derive(Clone, Default)]
+pub struct Timeouts {
+    handlers: Arc<Mutex<HashMap<String, u64>>>,
+    targets: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Timeouts {
+    pub fn handler_counts(&self) -> Vec<(String, u64)> {
+        sorted(&self.handlers)
+    }
+
+    pub fn target_counts(&self) -> Vec<(String, u64)> {
+        sorted(&self.targets)
+    }
+
+    pub(crate) fn record_target(&self, target: &str) {
+        *self
+            .targets
+            .lock()
+            .unwrap()
+            .entry(target.to_string())
+            .or_default() += 1;
+    }
+
+    fn record_handler(&self, handler: &str) {
+        *self
+            .handlers
+            .lock()
+            .unwrap()
+            .entry(handler.to_string())
+            .or_default() += 1;
+    }
+}
+
+fn sorted(counts: &Mutex<HashMap<String, u64>>) -> Vec<(String, u64)> {
+    let mut counts: Vec<_> = counts
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, count)| (name.clone(), *count))
+        .collect();
+    counts.sort();
+    counts
+}
+
+// runs one handler within its budget; 0 means no limit. A failure names the handler.
+pub async fn handler<E: Into<NexusError>>(
+    state: &AppState,
+    name: &str,
+    event_type: &str,
+    work: impl Future<Output = Result<(), E>>,
+) -> Result<(), NexusError> {
+    let config = &state.config.timeouts;
+    let secs = config
+        .handlers
+        .get(name)
+        .copied()
+        .unwrap_or(config.handler_secs);
+    if secs == 0 {
+        return work.await.map_err(|e| NexusError::handler(name, e));
+    }
+    match tokio::time::timeout(Duration::from_secs(secs), work).await {
+        Ok(result) => result.map_err(|e| NexusError::handler(name, e)),
+        Err(_) => {
+            state.timeouts.record_handler(name);
+            error!(
+                "Handler {} for {} event timed out after {}s and was cancelled",
+                name, event_type, secs
+            );
+            Ok(())
+        }
+    }
+}
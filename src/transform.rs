@@ -0,0 +1,68 @@
+// reshapes a payload before it is forwarded, for receivers that only want a few fields:
+// every output key names a JSON pointer into the payload, e.g. `author = "/sender/login"`.
+// Dotted keys nest (`pr.number`), and a `*` segment maps over an array, so
+// `"/pull_request/labels/*/name"` gives a list of label names. Missing fields become null.
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+pub fn apply(mapping: &HashMap<String, String>, payload: &Value) -> Result<Value, String> {
+    // sorted so the output does not depend on the map's iteration order
+    let mut keys: Vec<&String> = mapping.keys().collect();
+    keys.sort();
+
+    let mut output = Map::new();
+    for key in keys {
+        let pointer = &mapping[key];
+        if !pointer.is_empty() && !pointer.starts_with('/') {
+            return Err(format!(
+                "`{}` must be a JSON pointer starting with /, got {}",
+                key, pointer
+            ));
+        }
+        let segments: Vec<String> = pointer
+            .split('/')
+            .skip(1)
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        insert(&mut output, key, lookup(payload, &segments))?;
+    }
+    Ok(Value::Object(output))
+}
+
+fn lookup(value: &Value, segments: &[String]) -> Value {
+    let Some((segment, rest)) = segments.split_first() else {
+        return value.clone();
+    };
+    match (value, segment.as_str()) {
+        (Value::Array(items), "*") => {
+            Value::Array(items.iter().map(|item| lookup(item, rest)).collect())
+        }
+        (Value::Object(fields), name) => fields
+            .get(name)
+            .map_or(Value::Null, |field| lookup(field, rest)),
+        (Value::Array(items), index) => index
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| items.get(i))
+            .map_or(Value::Null, |item| lookup(item, rest)),
+        _ => Value::Null,
+    }
+}
+
+fn insert(output: &mut Map<String, Value>, key: &str, value: Value) -> Result<(), String> {
+    let (parent, rest) = match key.split_once('.') {
+        Some(split) => split,
+        None => {
+            output.insert(key.to_string(), value);
+            return Ok(());
+        }
+    };
+    let nested = output
+        .entry(parent)
+        .or_insert_with(|| Value::Object(Map::new()));
+    match nested {
+        Value::Object(nested) => insert(nested, rest, value),
+        _ => Err(format!("`{}` is both a value and an object", parent)),
+    }
+}
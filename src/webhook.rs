@@ -0,0 +1,736 @@
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    anomaly::Signal,
+    archive, automations,
+    clickhouse::{self, Processed},
+    encoding::ContentEncoding,
+    error::NexusError,
+    feed::FeedEvent,
+    influxdb,
+    queue::{Enqueued, Job},
+    schema,
+    service::AppState,
+    stats, timeouts,
+};
+
+pub use crate::models::{
+    CheckSuite, Comment, Commit, Deployment, GitRef, InstallationRef, Issue, Label, Milestone,
+    PullRequest, PullRequestRef, Repository, Review, SecurityAdvisory, User, WebhookPayload,
+    WorkflowRun,
+};
+pub use crate::signature::{SignatureError, SignatureVerifier, verify_signature};
+
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub message: String,
+    pub processed: bool,
+}
+
+// the verifier and the signature it has to match, or None when no secret is configured
+pub(crate) fn start_verification(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<(SignatureVerifier, String)>, NexusError> {
+    let Some(secret) = &state.webhook_secret else {
+        return Ok(None);
+    };
+    let Some(signature) = headers.get("x-hub-signature-256") else {
+        warn!("Missing webhook signature");
+        return Err(NexusError::MissingSignature);
+    };
+    let signature = signature
+        .to_str()
+        .map_err(|_| NexusError::InvalidHeader("x-hub-signature-256"))?;
+    let verifier = SignatureVerifier::new(secret).ok_or(NexusError::Verifier)?;
+    Ok(Some((verifier, signature.to_string())))
+}
+
+pub(crate) fn finish_verification(
+    verifier: SignatureVerifier,
+    signature: &str,
+) -> Result<(), NexusError> {
+    verifier.check(signature).map_err(|e| {
+        warn!("Invalid webhook signature: {}", e);
+        NexusError::Signature(e)
+    })
+}
+
+pub(crate) fn parse_error(e: serde_json::Error) -> NexusError {
+    error!("Failed to parse webhook payload: {}", e);
+    NexusError::Parse(e)
+}
+
+// hooks created with the form content type send `payload=<json>`; the signature covers
+// the form body, so this runs after verification
+pub(crate) fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|v| {
+            v.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+pub(crate) fn form_payload(body: &[u8]) -> Result<Vec<u8>, NexusError> {
+    form_urlencoded::parse(body)
+        .find(|(name, _)| name == "payload")
+        .map(|(_, payload)| payload.into_owned().into_bytes())
+        .ok_or_else(|| {
+            error!("Form-encoded webhook body has no payload field");
+            NexusError::MissingFormPayload
+        })
+}
+
+pub(crate) fn content_encoding(headers: &HeaderMap) -> Result<ContentEncoding, NexusError> {
+    ContentEncoding::from_headers(headers).map_err(|encoding| {
+        warn!("Unsupported Content-Encoding {:?}", encoding);
+        NexusError::UnsupportedEncoding(encoding)
+    })
+}
+
+// the JSON document in a verified body that is compressed, form-encoded or both
+pub(crate) fn decode_body(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: Vec<u8>,
+) -> Result<Vec<u8>, NexusError> {
+    let limit = state.config.server.max_decompressed_bytes;
+    let body = content_encoding(headers)?
+        .decode(body, limit)
+        .map_err(|e| {
+            warn!("Rejecting webhook body: {}", e);
+            NexusError::Decode(e)
+        })?;
+    match is_form(headers) {
+        true => form_payload(&body),
+        false => Ok(body),
+    }
+}
+
+pub(crate) fn parse_payload(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<WebhookPayload, NexusError> {
+    if state.config.schema.strict {
+        let raw = serde_json::from_slice(body).map_err(parse_error)?;
+        return schema::parse(state, headers, raw).map_err(parse_error);
+    }
+    serde_json::from_slice(body).map_err(parse_error)
+}
+
+// verify -> parse -> enqueue (or dispatch inline without a queue) for a buffered body;
+// `WebhookService` streams the body instead and calls `handle_payload` itself
+pub async fn process_delivery(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(StatusCode, WebhookResponse), NexusError> {
+    if let Some((mut verifier, signature)) = start_verification(state, headers)? {
+        verifier.update(body);
+        finish_verification(verifier, &signature)?;
+    }
+
+    let json = decode_body(state, headers, body.to_vec())?;
+    let payload = parse_payload(state, headers, &json)?;
+    let body = RawBody {
+        received: Bytes::copy_from_slice(body),
+        json: json.into(),
+    };
+    handle_payload(state, headers, payload, body).await
+}
+
+/// A verified delivery's body as it was received, and the JSON document in it: the same
+/// bytes unless the body was compressed or form-encoded.
+#[derive(Debug, Clone, Default)]
+pub struct RawBody {
+    pub received: Bytes,
+    pub json: Bytes,
+}
+
+// `body` is what `payload` was parsed from, kept for what is persisted or passed on
+pub async fn handle_payload(
+    state: &AppState,
+    headers: &HeaderMap,
+    payload: WebhookPayload,
+    body: RawBody,
+) -> Result<(StatusCode, WebhookResponse), NexusError> {
+    let event_type = headers
+        .get("x-github-event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown");
+
+    let delivery_id = headers
+        .get("x-github-delivery")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    info!(
+        "Received {} event (delivery {})",
+        event_type,
+        delivery_id.as_deref().unwrap_or("-")
+    );
+    state.anomalies.record(Signal::Deliveries);
+    let event = FeedEvent::new(event_type, delivery_id.as_deref(), &payload);
+    state.correlation.record(&event, &payload);
+    state.feed.publish(event);
+    // best effort: a full disk should not turn into failed deliveries
+    if let Some(dir) = &state.config.archive.dir
+        && let Err(e) = archive::record(
+            dir,
+            &state.redactor,
+            headers,
+            delivery_id.as_deref(),
+            event_type,
+            &body,
+        )
+        .await
+    {
+        error!("Failed to archive {} delivery: {}", event_type, e);
+    }
+
+    if let (Some(app), Some(installation), Some(repo)) = (
+        &state.github_app,
+        &payload.installation,
+        &payload.repository,
+    ) {
+        app.remember_installation(&repo.full_name, installation.id)
+            .await;
+    }
+
+    let Some(queue) = &state.queue else {
+        let received_at = chrono::Utc::now();
+        let started = std::time::Instant::now();
+        let result = dispatch(
+            state,
+            event_type,
+            delivery_id.as_deref(),
+            &payload,
+            &body.json,
+        )
+        .await;
+        let processed = Processed {
+            event_type,
+            delivery_id: delivery_id.as_deref(),
+            payload: &payload,
+            received_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+            status: result.as_ref().err().map(|e| e.status().as_u16()),
+        };
+        clickhouse::record(state, processed);
+        influxdb::record(state, processed);
+        result?;
+        return Ok((
+            StatusCode::OK,
+            WebhookResponse {
+                message: format!("Successfully processed {} event", event_type),
+                processed: true,
+            },
+        ));
+    };
+
+    let job = Job {
+        delivery_id,
+        event_type: event_type.to_string(),
+        payload,
+        json: body.json,
+        received_at: chrono::Utc::now(),
+        journal: None,
+    };
+    let enqueued = queue.enqueue(job).await.map_err(|e| {
+        warn!("Rejecting {} event: {}", event_type, e);
+        NexusError::Queue(e)
+    })?;
+
+    let message = match enqueued {
+        Enqueued::Queued => format!("Queued {} event", event_type),
+        Enqueued::Shed => format!("Skipped {} event, queue is under pressure", event_type),
+    };
+    Ok((
+        StatusCode::ACCEPTED,
+        WebhookResponse {
+            message,
+            processed: false,
+        },
+    ))
+}
+
+// `json` is the payload as received, for handlers that pass it on unchanged
+pub async fn dispatch(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+    json: &[u8],
+) -> Result<(), NexusError> {
+    let stats = async {
+        stats::record(state, event_type, payload).await;
+        Ok::<_, NexusError>(())
+    };
+    let stats = timeouts::handler(state, "stats", event_type, stats).await;
+    let handled =
+        match automations::debounce::hold(state, event_type, delivery_id, payload, json).await {
+            true => Ok(()),
+            false => handle(state, event_type, delivery_id, payload, json).await,
+        };
+    NexusError::collect([stats, handled])
+}
+
+// every handler, once per delivery or per debounce window
+pub(crate) async fn handle(
+    state: &AppState,
+    event_type: &str,
+    delivery_id: Option<&str>,
+    payload: &WebhookPayload,
+    json: &[u8],
+) -> Result<(), NexusError> {
+    // a failing handler does not stop the ones after it; the delivery fails at the end
+    let mut results = Vec::new();
+    results.push(
+        timeouts::handler(
+            state,
+            "fanout",
+            event_type,
+            automations::fanout::on_event(state, event_type, delivery_id, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "digest",
+            event_type,
+            automations::digest::on_event(state, event_type, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "reports",
+            event_type,
+            automations::reports::on_event(state, event_type, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "exec",
+            event_type,
+            automations::exec::on_event(state, event_type, delivery_id, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "forward",
+            event_type,
+            automations::forward::on_event(state, event_type, delivery_id, payload, json),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "functions",
+            event_type,
+            automations::functions::on_event(state, event_type, delivery_id, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "scripts",
+            event_type,
+            automations::scripts::on_event(state, event_type, delivery_id, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "statuspage",
+            event_type,
+            automations::statuspage::on_event(state, event_type, payload),
+        )
+        .await,
+    );
+    results.push(
+        timeouts::handler(
+            state,
+            "grafana",
+            event_type,
+            automations::grafana::on_event(state, event_type, delivery_id, payload),
+        )
+        .await,
+    );
+
+    match event_type {
+        "push" => {
+            info!(
+                "Processing push event for repository: {:?}",
+                payload.repository.as_ref().map(|r| &r.full_name)
+            );
+            // your push event logic here
+            results.push(
+                timeouts::handler(
+                    state,
+                    "push_deploy",
+                    event_type,
+                    automations::push_deploy::on_push(state, payload),
+                )
+                .await,
+            );
+            results.push(
+                timeouts::handler(
+                    state,
+                    "mirror",
+                    event_type,
+                    automations::mirror::on_push(state, payload),
+                )
+                .await,
+            );
+            results.push(
+                timeouts::handler(
+                    state,
+                    "rebuild",
+                    event_type,
+                    automations::rebuild::on_push(state, payload),
+                )
+                .await,
+            );
+            results.push(
+                timeouts::handler(
+                    state,
+                    "signatures",
+                    event_type,
+                    automations::signatures::on_push(state, delivery_id, payload),
+                )
+                .await,
+            );
+            results.push(
+                timeouts::handler(
+                    state,
+                    "commit_messages",
+                    event_type,
+                    automations::commit_messages::on_push(state, payload),
+                )
+                .await,
+            );
+        }
+        "pull_request" => {
+            if let Some(pr) = &payload.pull_request {
+                info!(
+                    "Processing pull request #{}: {} ({})",
+                    pr.number,
+                    state.redactor.text(&pr.title),
+                    pr.state
+                );
+                // your PR event logic here
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "pull_request",
+                        event_type,
+                        handle_pull_request_event(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "notify",
+                        event_type,
+                        automations::notify::on_event(state, event_type, delivery_id, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "pr_title",
+                        event_type,
+                        automations::pr_title::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "pr_size",
+                        event_type,
+                        automations::pr_size::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "dco",
+                        event_type,
+                        automations::dco::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "commit_messages",
+                        event_type,
+                        automations::commit_messages::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "jira",
+                        event_type,
+                        automations::jira::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "linear",
+                        event_type,
+                        automations::linear::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "branch_cleanup",
+                        event_type,
+                        automations::branch_cleanup::on_pull_request_closed(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "backport",
+                        event_type,
+                        automations::backport::on_pull_request(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "milestone",
+                        event_type,
+                        automations::milestone::on_pull_request_closed(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "pull_request_review" => {
+            results.push(
+                timeouts::handler(
+                    state,
+                    "notify",
+                    event_type,
+                    automations::notify::on_event(state, event_type, delivery_id, payload),
+                )
+                .await,
+            );
+        }
+        "issues" => {
+            if let Some(issue) = &payload.issue {
+                info!(
+                    "Processing issue #{}: {} ({})",
+                    issue.number,
+                    state.redactor.text(&issue.title),
+                    issue.state
+                );
+                // your issue event logic here
+                if payload.action.as_deref() == Some("opened") {
+                    results.push(
+                        timeouts::handler(
+                            state,
+                            "triage",
+                            event_type,
+                            automations::triage::on_issue_opened(state, payload),
+                        )
+                        .await,
+                    );
+                    results.push(
+                        timeouts::handler(
+                            state,
+                            "welcome",
+                            event_type,
+                            automations::welcome::on_issue_opened(state, payload),
+                        )
+                        .await,
+                    );
+                }
+            }
+        }
+        "label" => {
+            results.push(
+                timeouts::handler(
+                    state,
+                    "label_sync",
+                    event_type,
+                    automations::label_sync::on_label(state, payload),
+                )
+                .await,
+            );
+        }
+        "issue_comment" => {
+            if payload.action.as_deref() == Some("created") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "stale",
+                        event_type,
+                        automations::stale::on_issue_comment(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "chatops",
+                        event_type,
+                        automations::chatops::on_issue_comment(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "release" => {
+            if payload.action.as_deref() == Some("created") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "release_notes",
+                        event_type,
+                        automations::release_notes::on_release_created(state, payload),
+                    )
+                    .await,
+                );
+            }
+            if payload.action.as_deref() == Some("published") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "changelog",
+                        event_type,
+                        automations::changelog::on_release_published(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "create" => {
+            if payload.ref_type.as_deref() == Some("tag") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "release_notes",
+                        event_type,
+                        automations::release_notes::on_tag_created(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "deployment" => {
+            if payload.action.as_deref() == Some("created") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "deploy",
+                        event_type,
+                        automations::deploy::on_deployment_created(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "check_suite" | "workflow_run" => {
+            if payload.action.as_deref() == Some("completed") {
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "automerge",
+                        event_type,
+                        automations::automerge::on_checks_completed(state, payload),
+                    )
+                    .await,
+                );
+                results.push(
+                    timeouts::handler(
+                        state,
+                        "rerun",
+                        event_type,
+                        automations::rerun::on_workflow_run_completed(state, payload),
+                    )
+                    .await,
+                );
+            }
+        }
+        "ping" => {
+            info!("Received ping event - webhook is configured correctly!");
+        }
+        _ => {
+            info!("Unhandled event type: {}", event_type);
+        }
+    }
+
+    NexusError::collect(results)
+}
+
+async fn handle_pull_request_event(
+    state: &AppState,
+    payload: &WebhookPayload,
+) -> Result<(), NexusError> {
+    let mut results = Vec::new();
+    if let (Some(action), Some(pr), Some(repo)) =
+        (&payload.action, &payload.pull_request, &payload.repository)
+    {
+        match action.as_str() {
+            "opened" => {
+                info!(
+                    "New PR opened: {} in {}",
+                    state.redactor.text(&pr.title),
+                    repo.full_name
+                );
+                results.push(automations::welcome::on_pull_request_opened(state, pr, repo).await);
+                results.push(automations::labeler::on_pull_request_changed(state, pr, repo).await);
+                results.push(automations::reviewers::on_pull_request_opened(state, pr, repo).await);
+            }
+            "closed" => {
+                info!(
+                    "PR closed: {} in {}",
+                    state.redactor.text(&pr.title),
+                    repo.full_name
+                );
+            }
+            "synchronize" => {
+                info!(
+                    "PR updated: {} in {}",
+                    state.redactor.text(&pr.title),
+                    repo.full_name
+                );
+                results.push(automations::labeler::on_pull_request_changed(state, pr, repo).await);
+            }
+            _ => {}
+        }
+    }
+    NexusError::collect(results)
+}
@@ -0,0 +1,246 @@
+// running as a Windows service: registration with the service control manager, the
+// dispatcher entry point and an event log writer for tracing, since a service has no console
+
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+use windows_sys::Win32::{
+    Foundation::HANDLE,
+    System::EventLog::{
+        DeregisterEventSource, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+        EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE, RegisterEventSourceW, ReportEventW,
+    },
+};
+
+pub const SERVICE_NAME: &str = "nexus";
+
+// runs the server until the receiver fires, which happens when the service is stopped
+pub type Serve = Box<dyn FnOnce(oneshot::Receiver<()>) + Send>;
+
+static SERVE: Mutex<Option<Serve>> = Mutex::new(None);
+static STOP_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+static STATUS: OnceLock<ServiceStatusHandle> = OnceLock::new();
+
+// `arguments` are passed to the executable when the service starts, e.g. `service run`
+pub fn install(arguments: Vec<OsString>) -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: "Nexus GitHub webhook service".into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().map_err(windows_service::Error::Winapi)?,
+        launch_arguments: arguments,
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description("Receives GitHub webhooks and runs repository automations")
+}
+
+pub fn uninstall() -> windows_service::Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    service.delete()
+}
+
+// blocks until the service control manager stops the service; fails when the process was
+// not started by the service control manager
+pub fn run(stop_timeout: Duration, serve: Serve) -> windows_service::Result<()> {
+    *SERVE.lock().unwrap() = Some(serve);
+    let _ = STOP_TIMEOUT.set(stop_timeout);
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Windows service failed: {}", e);
+    }
+}
+
+fn run_service() -> windows_service::Result<()> {
+    let Some(serve) = SERVE.lock().unwrap().take() else {
+        return Ok(());
+    };
+    let stop_timeout = STOP_TIMEOUT.get().copied().unwrap_or_default();
+
+    let (stop, on_stop) = oneshot::channel();
+    let stop = Mutex::new(Some(stop));
+
+    // the wait hint covers the drain so the service control manager does not give up on it
+    let handler = move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            if let Some(stop) = stop.lock().unwrap().take() {
+                if let Some(handle) = STATUS.get() {
+                    let _ = handle.set_service_status(status(
+                        ServiceState::StopPending,
+                        ServiceControlAccept::empty(),
+                        stop_timeout,
+                    ));
+                }
+                let _ = stop.send(());
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let status_handle = service_control_handler::register(SERVICE_NAME, handler)?;
+    let _ = STATUS.set(status_handle);
+
+    status_handle.set_service_status(status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        Duration::default(),
+    ))?;
+    serve(on_stop);
+    status_handle.set_service_status(status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        Duration::default(),
+    ))
+}
+
+fn status(state: ServiceState, accept: ServiceControlAccept, wait_hint: Duration) -> ServiceStatus {
+    ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: state,
+        controls_accepted: accept,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint,
+        process_id: None,
+    }
+}
+
+/// Writes each tracing event as an entry in the Windows Application event log, with
+/// errors and warnings mapped to the matching event types.
+pub struct EventLog {
+    source: HANDLE,
+}
+
+// the event source handle may be used from any thread
+unsafe impl Send for EventLog {}
+unsafe impl Sync for EventLog {}
+
+impl EventLog {
+    pub fn register() -> io::Result<Self> {
+        let name = wide(SERVICE_NAME);
+        let source = unsafe { RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+        if source.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(EventLog { source })
+    }
+
+    fn report(&self, kind: REPORT_EVENT_TYPE, message: &str) {
+        let message = wide(message.trim_end());
+        let strings = [message.as_ptr()];
+        unsafe {
+            ReportEventW(
+                self.source,
+                kind,
+                0,
+                0,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            );
+        }
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        unsafe { DeregisterEventSource(self.source) };
+    }
+}
+
+impl<'a> MakeWriter<'a> for EventLog {
+    type Writer = EventWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        EventWriter::new(self, EVENTLOG_INFORMATION_TYPE)
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        let kind = match *meta.level() {
+            Level::ERROR => EVENTLOG_ERROR_TYPE,
+            Level::WARN => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+        EventWriter::new(self, kind)
+    }
+}
+
+// buffers one formatted event and reports it when dropped
+pub struct EventWriter<'a> {
+    log: &'a EventLog,
+    kind: REPORT_EVENT_TYPE,
+    buffer: Vec<u8>,
+}
+
+impl<'a> EventWriter<'a> {
+    fn new(log: &'a EventLog, kind: REPORT_EVENT_TYPE) -> Self {
+        EventWriter {
+            log,
+            kind,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for EventWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for EventWriter<'_> {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            self.log
+                .report(self.kind, &String::from_utf8_lossy(&self.buffer));
+        }
+    }
+}
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}